@@ -0,0 +1,331 @@
+/*
+Copyright 2025 Ethan Thummel
+
+Permission is hereby granted, free of charge, to any person obtaining a copy of this software and
+associated documentation files (the "Software"), to deal in the Software without restriction,
+including without limitation the rights to use, copy, modify, merge, publish, distribute,
+sublicense, and/or sell copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all copies or substantial
+portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT
+NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT
+OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+*/
+use std::sync::OnceLock;
+
+use crate::board::{BoardState, PieceType};
+use crate::eval::distance;
+use crate::tables::Tables;
+use crate::types::{Bitboard, Color, Square};
+
+// Bits for every position with the pawn on the a-d files, both kings anywhere and either side to move
+const PAWN_SQUARES: usize = 24;
+const SIZE: usize = PAWN_SQUARES * 64 * 64 * 2;
+
+static KPK: OnceLock<Vec<u64>> = OnceLock::new();
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    Invalid,
+    Unknown,
+    Draw,
+    Win,
+}
+
+// The pawn is white, and is kept on the a-d files
+fn index(white_to_move: bool, white_king: usize, black_king: usize, pawn: usize) -> usize {
+    let pawn_index = (pawn / 8 - 1) * 4 + pawn % 8 - 4;
+    ((pawn_index * 64 + white_king) * 64 + black_king) * 2 + !white_to_move as usize
+}
+
+fn king_moves(square: usize) -> Bitboard {
+    (0..64)
+        .filter(|s| *s != square && distance(Square::new(*s), Square::new(square)) == 1)
+        .fold(Bitboard::EMPTY, |moves, s| moves | Square::new(s).bit())
+}
+
+fn pawn_attacks(pawn: usize) -> Bitboard {
+    let pawn = Square::new(pawn).bit();
+    ((pawn & !Tables::FILE_A) << 9) | ((pawn & !Tables::FILE_H) << 7)
+}
+
+// What can be known about a position without looking at the positions after it
+fn classify(white_to_move: bool, white_king: usize, black_king: usize, pawn: usize) -> Outcome {
+    if white_king == black_king
+        || white_king == pawn
+        || black_king == pawn
+        || distance(Square::new(white_king), Square::new(black_king)) <= 1
+        || (white_to_move && pawn_attacks(pawn).contains(Square::new(black_king)))
+    {
+        return Outcome::Invalid;
+    }
+    if white_to_move {
+        // The pawn queens, and the new queen can not be taken
+        let queening = pawn + 8;
+        if pawn / 8 == 6
+            && white_king != queening
+            && black_king != queening
+            && (distance(Square::new(black_king), Square::new(queening)) > 1
+                || distance(Square::new(white_king), Square::new(queening)) == 1)
+        {
+            return Outcome::Win;
+        }
+    } else {
+        let moves = king_moves(black_king) & !king_moves(white_king) & !pawn_attacks(pawn);
+        // Stalemate, or the pawn can be taken
+        if moves.is_empty() || moves.contains(Square::new(pawn)) {
+            return Outcome::Draw;
+        }
+    }
+    Outcome::Unknown
+}
+
+/// Builds the bitbase by classifying the positions which are decided right away, and then
+/// working out the rest from their successors until nothing changes
+fn generate() -> Vec<u64> {
+    let mut outcomes = vec![Outcome::Invalid; SIZE];
+    let pawns = || (8..56).filter(|p| p % 8 >= 4);
+    for pawn in pawns() {
+        for white_king in 0..64 {
+            for black_king in 0..64 {
+                for white_to_move in [true, false] {
+                    outcomes[index(white_to_move, white_king, black_king, pawn)] =
+                        classify(white_to_move, white_king, black_king, pawn);
+                }
+            }
+        }
+    }
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for pawn in pawns() {
+            for white_king in 0..64 {
+                for black_king in 0..64 {
+                    for white_to_move in [true, false] {
+                        let i = index(white_to_move, white_king, black_king, pawn);
+                        if outcomes[i] != Outcome::Unknown {
+                            continue;
+                        }
+                        let outcome = match white_to_move {
+                            true => white_outcome(&outcomes, white_king, black_king, pawn),
+                            false => black_outcome(&outcomes, white_king, black_king, pawn),
+                        };
+                        if outcome != Outcome::Unknown {
+                            outcomes[i] = outcome;
+                            changed = true;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // Whatever is left can never be forced, so it is a draw
+    let mut bits = vec![0u64; SIZE / 64];
+    for (i, outcome) in outcomes.iter().enumerate() {
+        if *outcome == Outcome::Win {
+            bits[i / 64] |= 1 << (i % 64);
+        }
+    }
+    bits
+}
+
+// White wins with any winning move, and draws when every move draws
+fn white_outcome(
+    outcomes: &[Outcome],
+    white_king: usize,
+    black_king: usize,
+    pawn: usize,
+) -> Outcome {
+    let mut successors = Vec::with_capacity(10);
+    for square in king_moves(white_king) & !Square::new(pawn).bit() {
+        successors.push(outcomes[index(false, square.index(), black_king, pawn)]);
+    }
+    let push = pawn + 8;
+    if push != white_king && push != black_king {
+        if pawn / 8 == 6 {
+            // Queening where the king takes the queen
+            successors.push(Outcome::Draw);
+        } else {
+            successors.push(outcomes[index(false, white_king, black_king, push)]);
+            let double = push + 8;
+            if pawn / 8 == 1 && double != white_king && double != black_king {
+                successors.push(outcomes[index(false, white_king, black_king, double)]);
+            }
+        }
+    }
+    let mut successors = successors.into_iter().filter(|o| *o != Outcome::Invalid);
+    if successors.clone().any(|o| o == Outcome::Win) {
+        Outcome::Win
+    } else if successors.all(|o| o == Outcome::Draw) {
+        Outcome::Draw
+    } else {
+        Outcome::Unknown
+    }
+}
+
+// Black draws with any drawing move, and loses when every move loses
+fn black_outcome(
+    outcomes: &[Outcome],
+    white_king: usize,
+    black_king: usize,
+    pawn: usize,
+) -> Outcome {
+    let king = king_moves(black_king) & !king_moves(white_king) & !pawn_attacks(pawn);
+    let mut all_win = true;
+    for square in king {
+        match outcomes[index(true, white_king, square.index(), pawn)] {
+            Outcome::Draw => return Outcome::Draw,
+            Outcome::Win => {}
+            _ => all_win = false,
+        }
+    }
+    match all_win {
+        true => Outcome::Win,
+        false => Outcome::Unknown,
+    }
+}
+
+/// Builds the bitbase now instead of on first use
+pub fn init() {
+    KPK.get_or_init(generate);
+}
+
+/// If the side with the pawn wins, with the pawn side as white. Squares are flipped to put the pawn
+/// on the a-d files
+pub fn kpk_win(white_to_move: bool, white_king: usize, black_king: usize, pawn: usize) -> bool {
+    let (white_king, black_king, pawn) = match pawn % 8 >= 4 {
+        true => (white_king, black_king, pawn),
+        false => (white_king ^ 7, black_king ^ 7, pawn ^ 7),
+    };
+    let bits = KPK.get_or_init(generate);
+    let i = index(white_to_move, white_king, black_king, pawn);
+    bits[i / 64] & 1 << (i % 64) != 0
+}
+
+/// For a king and pawn against king position, gets if the side with the pawn wins
+pub fn probe(board: &BoardState) -> Option<bool> {
+    let pieces = board.occupancy();
+    let pawns =
+        board.pieces(Color::White, PieceType::Pawn) | board.pieces(Color::Black, PieceType::Pawn);
+    if pieces.count() != 3
+        || pawns.count() != 1
+        || !(pawns & (Tables::RANK_1 | Tables::RANK_8)).is_empty()
+        || (board.pieces(Color::White, PieceType::King)
+            | board.pieces(Color::Black, PieceType::King))
+        .count()
+            != 2
+    {
+        return None;
+    }
+    let white_king = Square::from_bit(board.pieces(Color::White, PieceType::King)).index();
+    let black_king = Square::from_bit(board.pieces(Color::Black, PieceType::King)).index();
+    let pawn = Square::from_bit(pawns).index();
+    // Look at the position from the side with the pawn, as if it were white
+    Some(
+        match !board.pieces(Color::White, PieceType::Pawn).is_empty() {
+            true => kpk_win(board.white_to_move, white_king, black_king, pawn),
+            false => kpk_win(
+                !board.white_to_move,
+                black_king ^ 56,
+                white_king ^ 56,
+                pawn ^ 56,
+            ),
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tablebase::{generate_table, Signature, Tablebases, TbValue};
+    use crate::tt::ZobKeys;
+    use crate::types::Piece;
+
+    fn probe_fen(fen: &str) -> Option<bool> {
+        probe(&BoardState::state_from_string_fen(fen.to_string()))
+    }
+
+    #[test]
+    fn rook_pawn() {
+        // The defending king in the corner can not be shut out
+        assert_eq!(probe_fen("k7/8/8/8/P7/8/8/K7 w - - 0 1"), Some(false));
+        assert_eq!(probe_fen("8/8/8/8/8/7k/8/7K b - - 0 1"), None);
+        assert_eq!(probe_fen("7k/8/5K2/7P/8/8/8/8 w - - 0 1"), Some(false));
+        // Unless the attacking king controls the queening square first
+        assert_eq!(probe_fen("8/1K6/8/P7/8/8/8/7k w - - 0 1"), Some(true));
+        // Or the pawn outruns the defending king
+        assert_eq!(probe_fen("5k2/8/8/8/P7/8/8/K7 w - - 0 1"), Some(true));
+        assert_eq!(probe_fen("5k2/8/8/8/P7/8/8/K7 b - - 0 1"), Some(false));
+    }
+
+    #[test]
+    fn key_squares() {
+        // With the king on a key square the pawn queens, whoever is to move
+        assert_eq!(probe_fen("4k3/8/4K3/8/4P3/8/8/8 w - - 0 1"), Some(true));
+        assert_eq!(probe_fen("4k3/8/4K3/8/4P3/8/8/8 b - - 0 1"), Some(true));
+        assert_eq!(probe_fen("8/5k2/3K4/8/4P3/8/8/8 b - - 0 1"), Some(true));
+        // Otherwise it comes down to the opposition
+        assert_eq!(probe_fen("8/4k3/8/4K3/4P3/8/8/8 w - - 0 1"), Some(false));
+        assert_eq!(probe_fen("8/4k3/8/4K3/4P3/8/8/8 b - - 0 1"), Some(true));
+        // Stalemate saves the defender
+        assert_eq!(probe_fen("4k3/4P3/4K3/8/8/8/8/8 b - - 0 1"), Some(false));
+        // The same with colors reversed
+        assert_eq!(probe_fen("8/8/8/4p3/4k3/8/4K3/8 b - - 0 1"), Some(false));
+        assert_eq!(probe_fen("8/8/8/4p3/4k3/8/4K3/8 w - - 0 1"), Some(true));
+    }
+
+    #[test]
+    fn matches_tablebase() {
+        let tables = Tables::new();
+        let zob_keys = ZobKeys::new();
+        let mut tablebases = Tablebases::default();
+        for name in ["KQK", "KRK", "KPK"] {
+            let signature = Signature::parse(name).unwrap();
+            let table = generate_table(&signature, &tablebases, &tables, &zob_keys).unwrap();
+            tablebases.insert(table);
+        }
+
+        let mut board = BoardState::state_from_string_fen("8/8/8/8/8/8/8/8 w - - 0 1".to_string());
+        let mut checked = 0;
+        for pawn in 8..56 {
+            for white_king in 0..64 {
+                for black_king in 0..64 {
+                    for white_to_move in [true, false] {
+                        if classify(white_to_move, white_king, black_king, pawn) == Outcome::Invalid
+                        {
+                            continue;
+                        }
+                        board.clear_pieces();
+                        let white = |kind| Piece::new(Color::White, kind);
+                        board.put_piece(white(PieceType::King), Square::new(white_king));
+                        board.put_piece(
+                            Piece::new(Color::Black, PieceType::King),
+                            Square::new(black_king),
+                        );
+                        board.put_piece(white(PieceType::Pawn), Square::new(pawn));
+                        board.white_to_move = white_to_move;
+                        let expected = match tablebases.probe(&board) {
+                            Some(TbValue::Win(_)) => white_to_move,
+                            Some(TbValue::Loss(_)) => !white_to_move,
+                            _ => false,
+                        };
+                        assert_eq!(
+                            probe(&board),
+                            Some(expected),
+                            "{white_to_move} {white_king} {black_king} {pawn}"
+                        );
+                        checked += 1;
+                    }
+                }
+            }
+        }
+        assert!(checked > 300000);
+    }
+}
@@ -16,35 +16,40 @@ NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FO
 DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT
 OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
 */
-use crate::eval::{delta_ps_score, piece_square_score};
+use crate::eval::{delta_ps_score, piece_square_score, EvalParams};
+use crate::nnue::{Accumulator, Network};
+use crate::tablebase::Tablebases;
 use crate::tt::ZobKeys;
+use crate::types::{Bitboard, Color, Piece, Square};
 use crate::{generate::*, tables::Tables};
 use std::io::{self, Write};
+use std::sync::{Arc, OnceLock};
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct BoardState {
-    pub white_pawns: u64,
-    pub white_knights: u64,
-    pub white_rooks: u64,
-    pub white_bishops: u64,
-    pub white_queens: u64,
-    pub white_king: u64,
+    // Bitboards indexed by Color::index and then PieceType. These, the occupancy of each color and
+    // the mailbox are kept in step by put_piece and remove_piece, so they are private
+    pieces: [[Bitboard; 6]; 2],
+    color_occupancy: [Bitboard; 2],
+    mailbox: [Option<Piece>; 64],
     pub white_queenside_castle_rights: bool,
     pub white_kingside_castle_rights: bool,
-
-    pub black_pawns: u64,
-    pub black_knights: u64,
-    pub black_rooks: u64,
-    pub black_bishops: u64,
-    pub black_queens: u64,
-    pub black_king: u64,
     pub black_queenside_castle_rights: bool,
     pub black_kingside_castle_rights: bool,
+    // Where the castling rooks start, in the order of ZobKeys::castle_keys. They are only off the
+    // corners in Chess960
+    pub castle_rooks: [Square; 4],
+    // Castles are written as the king taking its own rook in Chess960
+    pub chess960: bool,
 
     pub white_to_move: bool,
-    pub en_passant_target: u64,
+    pub en_passant_target: Option<Square>,
     pub reversable_move_counter: u8,
     pub full_move_counter: u16,
     pub piece_square_score: isize,
+    pub eval_params: Arc<EvalParams>,
+    pub network: Option<Arc<Network>>,
+    pub tablebases: Option<Arc<Tablebases>>,
+    pub accumulator: Accumulator,
     pub hash: u64,
     pub move_stack: Vec<MoveStackFrame>,
     pub move_stack_pointer: usize,
@@ -52,8 +57,8 @@ pub struct BoardState {
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub struct MoveRep {
-    pub starting_square: u64,
-    pub ending_square: u64,
+    pub starting_square: Square,
+    pub ending_square: Square,
     pub promotion: Option<Promotion>,
     pub moved_type: PieceType,
     pub attacked_type: Option<PieceType>,
@@ -61,8 +66,8 @@ pub struct MoveRep {
 
 impl MoveRep {
     pub fn new(
-        starting_square: u64,
-        ending_square: u64,
+        starting_square: Square,
+        ending_square: Square,
         promotion: Option<Promotion>,
         piece_hint: PieceType,
         attacked_type: Option<PieceType>,
@@ -77,6 +82,63 @@ impl MoveRep {
     }
 }
 
+/// A move packed into 16 bits: the starting square index in the low six bits, the ending square
+/// index in the next six and a flag saying what kind of move it is in the top four. Castles end on
+/// the king's square, as with `MoveRep`. Lists of moves that are only compared, such as the root
+/// moves a search leaves out, keep them packed
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Move(u16);
+
+impl Move {
+    pub const QUIET: u16 = 0;
+    pub const DOUBLE_PUSH: u16 = 1;
+    pub const KING_CASTLE: u16 = 2;
+    pub const QUEEN_CASTLE: u16 = 3;
+    // Set in the flag of every move that takes a piece
+    pub const CAPTURE: u16 = 4;
+    pub const EN_PASSANT: u16 = 5;
+    // Promotions have the top bit set, along with the capture bit if they take a piece, and the
+    // piece in the low two bits
+    pub const KNIGHT_PROMOTION: u16 = 8;
+    pub const BISHOP_PROMOTION: u16 = 9;
+    pub const ROOK_PROMOTION: u16 = 10;
+    pub const QUEEN_PROMOTION: u16 = 11;
+
+    pub fn new(from: Square, to: Square, flag: u16) -> Move {
+        debug_assert!(flag < 16);
+        Move(from.index() as u16 | (to.index() as u16) << 6 | flag << 12)
+    }
+
+    /// Packs a move to be played on the board
+    pub fn from_rep(mv: &MoveRep, board: &BoardState) -> Move {
+        let (from, to) = (mv.starting_square, mv.ending_square);
+        let capture = match mv.attacked_type {
+            Some(_) => Move::CAPTURE,
+            None => Move::QUIET,
+        };
+        let flag = match mv.promotion {
+            Some(Promotion::Castle) => match BoardState::castle_index(mv) {
+                Some(index) if index % 2 == 0 => Move::KING_CASTLE,
+                _ => Move::QUEEN_CASTLE,
+            },
+            Some(Promotion::Knight) => Move::KNIGHT_PROMOTION | capture,
+            Some(Promotion::Bishop) => Move::BISHOP_PROMOTION | capture,
+            Some(Promotion::Rook) => Move::ROOK_PROMOTION | capture,
+            Some(Promotion::Queen) => Move::QUEEN_PROMOTION | capture,
+            None if mv.moved_type == PieceType::Pawn
+                && Some(mv.ending_square) == board.en_passant_target =>
+            {
+                Move::EN_PASSANT
+            }
+            None if mv.moved_type == PieceType::Pawn && from.index().abs_diff(to.index()) == 16 => {
+                Move::DOUBLE_PUSH
+            }
+            None => capture,
+        };
+        Move::new(from, to, flag)
+    }
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Promotion {
     Queen,
@@ -86,8 +148,32 @@ pub enum Promotion {
     Castle,
 }
 
+impl Promotion {
+    /// The letter for the promoted piece in algebraic notation, in upper case
+    pub fn letter(self) -> Option<char> {
+        match self {
+            Promotion::Queen => Some('Q'),
+            Promotion::Bishop => Some('B'),
+            Promotion::Rook => Some('R'),
+            Promotion::Knight => Some('N'),
+            Promotion::Castle => None,
+        }
+    }
+
+    /// The promotion written by a letter in either case
+    pub fn from_letter(letter: char) -> Option<Promotion> {
+        match letter.to_ascii_uppercase() {
+            'Q' => Some(Promotion::Queen),
+            'B' => Some(Promotion::Bishop),
+            'R' => Some(Promotion::Rook),
+            'N' => Some(Promotion::Knight),
+            _ => None,
+        }
+    }
+}
+
 // Helps the move maker know what bitboard to manipulate
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum PieceType {
     Pawn,
     Knight,
@@ -97,62 +183,94 @@ pub enum PieceType {
     King,
 }
 
+impl PieceType {
+    pub const ALL: [PieceType; 6] = [
+        PieceType::Pawn,
+        PieceType::Knight,
+        PieceType::Bishop,
+        PieceType::Rook,
+        PieceType::Queen,
+        PieceType::King,
+    ];
+
+    /// The letter for the piece in fen and algebraic notation, in upper case
+    pub fn letter(self) -> char {
+        match self {
+            PieceType::Pawn => 'P',
+            PieceType::Knight => 'N',
+            PieceType::Bishop => 'B',
+            PieceType::Rook => 'R',
+            PieceType::Queen => 'Q',
+            PieceType::King => 'K',
+        }
+    }
+}
+
 // Stores state of the board which can not be recovered when unmaking a move
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct MoveStackFrame {
-    en_passant_target: u64,
+    en_passant_target: Option<Square>,
     reversable_move_counter: u8,
     fullmove_counter: u16,
     white_queenside_castle_rights: bool,
     white_kingside_castle_rights: bool,
     black_queenside_castle_rights: bool,
     black_kingside_castle_rights: bool,
+    hash: u64,
 }
 
 impl MoveStackFrame {
     fn new() -> MoveStackFrame {
         MoveStackFrame {
-            en_passant_target: 0,
+            en_passant_target: None,
             reversable_move_counter: 0,
             fullmove_counter: 0,
             white_queenside_castle_rights: true,
             white_kingside_castle_rights: true,
             black_queenside_castle_rights: true,
             black_kingside_castle_rights: true,
+            hash: 0,
         }
     }
 }
 
 impl BoardState {
-    pub fn starting_state() -> BoardState {
-        let mut state = BoardState {
-            white_pawns: 0xff00,
-            white_knights: 0x42,
-            white_rooks: 0x81,
-            white_bishops: 0x24,
-            white_queens: 0x10,
-            white_king: 0x8,
-            white_queenside_castle_rights: true,
-            white_kingside_castle_rights: true,
+    /// Castling rooks of the standard starting position
+    pub const STANDARD_CASTLE_ROOKS: [Square; 4] = [Square::H1, Square::A1, Square::H8, Square::A8];
+    /// Where the king and rook land for each castle, wherever they started
+    pub const CASTLE_TARGETS: [(Square, Square); 4] = [
+        (Square::G1, Square::F1),
+        (Square::C1, Square::D1),
+        (Square::G8, Square::F8),
+        (Square::C8, Square::D8),
+    ];
 
-            black_pawns: 0xff000000000000,
-            black_knights: 0x4200000000000000,
-            black_rooks: 0x8100000000000000,
-            black_bishops: 0x2400000000000000,
-            black_queens: 0x1000000000000000,
-            black_king: 0x800000000000000,
-            black_queenside_castle_rights: true,
-            black_kingside_castle_rights: true,
-
-            white_to_move: true,
-            en_passant_target: 0x0,
-            reversable_move_counter: 0,
-            full_move_counter: 1,
-            piece_square_score: 0,
-            hash: 0,
-            move_stack: vec![MoveStackFrame::new(); 0],
-            move_stack_pointer: 0,
-        };
+    pub fn starting_state() -> BoardState {
+        let mut state = BoardState::empty_state();
+        // Pawns, knights, bishops, rooks, queens and king, for white and then black
+        let layout = [
+            [0xff00, 0x42, 0x24, 0x81, 0x10, 0x8],
+            [
+                0xff000000000000,
+                0x4200000000000000,
+                0x2400000000000000,
+                0x8100000000000000,
+                0x1000000000000000,
+                0x800000000000000,
+            ],
+        ];
+        for (color, masks) in [Color::White, Color::Black].into_iter().zip(layout) {
+            for (kind, mask) in PieceType::ALL.into_iter().zip(masks) {
+                for square in Bitboard(mask) {
+                    state.put_piece(Piece::new(color, kind), square);
+                }
+            }
+        }
+        state.white_queenside_castle_rights = true;
+        state.white_kingside_castle_rights = true;
+        state.black_queenside_castle_rights = true;
+        state.black_kingside_castle_rights = true;
+        state.full_move_counter = 1;
         // NOTE It might be better to pass a zobrist table to this function as a ref, but since it is not called
         // often, it should not decrease preformance to do it this way, which is easier :)
         let zob_keys = ZobKeys::new();
@@ -161,31 +279,28 @@ impl BoardState {
         state
     }
 
-    fn empty_state() -> BoardState {
+    /// A board with no pieces and white to move, for setting up positions piece by piece
+    pub fn empty_state() -> BoardState {
         let mut board = BoardState {
-            white_pawns: 0,
-            white_knights: 0,
-            white_rooks: 0,
-            white_bishops: 0,
-            white_queens: 0,
-            white_king: 0,
+            pieces: [[Bitboard::EMPTY; 6]; 2],
+            color_occupancy: [Bitboard::EMPTY; 2],
+            mailbox: [None; 64],
             white_queenside_castle_rights: false,
             white_kingside_castle_rights: false,
-
-            black_pawns: 0,
-            black_knights: 0,
-            black_rooks: 0,
-            black_bishops: 0,
-            black_queens: 0,
-            black_king: 0,
             black_queenside_castle_rights: false,
             black_kingside_castle_rights: false,
+            castle_rooks: BoardState::STANDARD_CASTLE_ROOKS,
+            chess960: false,
 
             white_to_move: true,
-            en_passant_target: 0,
+            en_passant_target: None,
             reversable_move_counter: 0,
             full_move_counter: 0,
             piece_square_score: 0,
+            eval_params: Arc::new(EvalParams::default()),
+            network: None,
+            tablebases: None,
+            accumulator: Accumulator::default(),
             hash: 0,
             move_stack: vec![MoveStackFrame::new(); 0],
             move_stack_pointer: 0,
@@ -199,9 +314,18 @@ impl BoardState {
         board
     }
 
+    /// Reads a position from the fields of a fen string, refusing positions without one king a side
+    /// or with the side not to move in check
     pub fn state_from_fen<'a>(
-        mut fen_tokens: impl Iterator<Item = &'a str>,
+        fen_tokens: impl Iterator<Item = &'a str>,
     ) -> Result<BoardState, String> {
+        let state = BoardState::parse_fen(fen_tokens)?;
+        state.check_kings()?;
+        Ok(state)
+    }
+
+    // Reads a fen without checking the kings, so tests can set up positions with pieces missing
+    fn parse_fen<'a>(mut fen_tokens: impl Iterator<Item = &'a str>) -> Result<BoardState, String> {
         // Split the fen string at every / and space
         // let mut fen_tokens = fen.split(|c| c == '/' || c == ' ');
 
@@ -221,31 +345,13 @@ impl BoardState {
                 } else {
                     // If the character is not a digit, match it to the piece
                     // type and set the relevent bit in the board state
-                    match character {
-                        // First match  black pieces (lowercase)
-                        // p -> pawn
-                        // r -> rook
-                        // n -> knight
-                        // b -> bishop
-                        // q -> queen
-                        // k -> king
-                        'p' => state.black_pawns |= shift_value,
-                        'r' => state.black_rooks |= shift_value,
-                        'n' => state.black_knights |= shift_value,
-                        'b' => state.black_bishops |= shift_value,
-                        'q' => state.black_queens |= shift_value,
-                        'k' => state.black_king |= shift_value,
-
-                        // Now try the white pieces (uppercase)
-                        'P' => state.white_pawns |= shift_value,
-                        'R' => state.white_rooks |= shift_value,
-                        'N' => state.white_knights |= shift_value,
-                        'B' => state.white_bishops |= shift_value,
-                        'Q' => state.white_queens |= shift_value,
-                        'K' => state.white_king |= shift_value,
-
-                        _ => return Err(format!("Unexpected character found in {character}")),
+                    let Some(piece) = Piece::from_letter(character) else {
+                        return Err(format!("Unexpected character found in {character}"));
+                    };
+                    if shift_value == 0 {
+                        return Err("Too many squares in the fen placement data".to_string());
                     }
+                    state.put_piece(piece, Square::from_bit(Bitboard(shift_value)));
                     shift_value >>= 1;
                 }
                 // shift_value >>= 1;
@@ -274,22 +380,8 @@ impl BoardState {
         // castling rights
         if let Some(castle_rights) = fen_tokens.next() {
             for character in castle_rights.chars() {
-                match character {
-                    // K -> white kingside
-                    // Q -> white queen side
-                    // k -> black kingside
-                    // q -> black queenside
-                    'K' => state.white_kingside_castle_rights = true,
-                    'Q' => state.white_queenside_castle_rights = true,
-                    'k' => state.black_kingside_castle_rights = true,
-                    'q' => state.black_queenside_castle_rights = true,
-                    // No castle rights
-                    '-' => continue,
-                    _ => {
-                        return Err(format!(
-                            "Unknown character \"{character}\" found in castle rights field"
-                        ))
-                    }
+                if character != '-' {
+                    state.parse_castle_right(character)?;
                 }
             }
         } else {
@@ -300,35 +392,21 @@ impl BoardState {
         if let Some(en_passant_target) = fen_tokens.next() {
             if en_passant_target == "-" {
                 // No en passant target
-                state.en_passant_target = 0;
+                state.en_passant_target = None;
             } else {
+                // The target is behind a pawn that just moved two squares, so it is on the sixth
+                // rank when white is to move and the third when black is
+                let expected_rank = if state.white_to_move { '6' } else { '3' };
                 let mut target_chars = en_passant_target.chars();
-                if let (Some(file), Some(rank)) = (target_chars.next(), target_chars.next()) {
-                    // Match the rank and file to get the correct mask
-                    let file_shift = match file {
-                        'h' => 0,
-                        'g' => 1,
-                        'f' => 2,
-                        'e' => 3,
-                        'd' => 4,
-                        'c' => 5,
-                        'b' => 6,
-                        'a' => 7,
-                        _ => return Err(format!("Unrecognized value \"{file}\" found in file")),
-                    };
-                    let rank_shift: i32;
-                    if let Some(rank_value) = rank.to_digit(10) {
-                        rank_shift = rank_value as i32;
-                    } else {
-                        return Err(format!("Unrecognized value \"{rank}\" found in rank"));
+                match (
+                    target_chars.next(),
+                    target_chars.next(),
+                    target_chars.next(),
+                ) {
+                    (Some(file), Some(rank), None) if rank == expected_rank => {
+                        state.en_passant_target = Some(position_to_square(file, rank)?);
                     }
-
-                    state.en_passant_target = (1 << file_shift) << ((rank_shift - 1) * 8);
-                } else {
-                    return Err(
-                        "En passant target does not have the expected number of characters"
-                            .to_string(),
-                    );
+                    _ => return Err(format!("Invalid en passant target \"{en_passant_target}\"")),
                 }
             }
         } else {
@@ -365,84 +443,346 @@ impl BoardState {
         Ok(state)
     }
 
-    #[cfg(test)]
-    pub fn state_from_string_fen(fen_string: String) -> BoardState {
-        let tokens = fen_string.split(" ");
-        BoardState::state_from_fen(tokens).unwrap()
+    // Refuses positions that could not come up in a game: each side needs exactly one king, and the
+    // side that just moved can not have left its king in check. The search and evaluation rely on both
+    fn check_kings(&self) -> Result<(), String> {
+        for color in [Color::White, Color::Black] {
+            let kings = self.pieces(color, PieceType::King).count();
+            if kings != 1 {
+                return Err(format!("Expected one {color:?} king, found {kings}"));
+            }
+        }
+        // Building the tables is slow, so they are made once and kept for every fen after
+        static TABLES: OnceLock<Tables> = OnceLock::new();
+        let tables = TABLES.get_or_init(Tables::new);
+        let in_check = match self.white_to_move {
+            true => self.white_attack_mask(tables) & self.pieces(Color::Black, PieceType::King),
+            false => self.black_attack_mask(tables) & self.pieces(Color::White, PieceType::King),
+        };
+        if !in_check.is_empty() {
+            return Err("The side not to move is in check".to_string());
+        }
+        Ok(())
+    }
+
+    // Reads one castle right from a fen. KQkq take the outermost rook on that side of the king, as in
+    // X-FEN, and the files A-H and a-h name the rook, as in Shredder-FEN. A right with no friendly
+    // rook on the back rank to castle with is dropped
+    fn parse_castle_right(&mut self, character: char) -> Result<(), String> {
+        let white = character.is_ascii_uppercase();
+        let offset = if white { 0 } else { 2 };
+        let (index, rook) = match character.to_ascii_lowercase() {
+            'k' => (offset, self.outermost_castle_rook(offset)),
+            'q' => (offset + 1, self.outermost_castle_rook(offset + 1)),
+            file @ 'a'..='h' => {
+                let (color, rank, back_rank) = match white {
+                    true => (Color::White, '1', Tables::RANK_1),
+                    false => (Color::Black, '8', Tables::RANK_8),
+                };
+                let square = position_to_square(file, rank)?;
+                let king = (self.pieces(color, PieceType::King) & back_rank).lsb();
+                // Squares towards the h file have lower indices
+                match king.filter(|_| self.pieces(color, PieceType::Rook).contains(square)) {
+                    Some(king) if square < king => (offset, Some(square)),
+                    Some(_) => (offset + 1, Some(square)),
+                    None => (offset, None),
+                }
+            }
+            _ => {
+                return Err(format!(
+                    "Unknown character \"{character}\" found in castle rights field"
+                ))
+            }
+        };
+        let Some(rook) = rook else {
+            return Ok(());
+        };
+        *self.castle_right_mut(index) = true;
+        self.castle_rooks[index] = rook;
+        Ok(())
+    }
+
+    // The rook KQkq stand for: the outermost rook on that side of the king, or None if there is none
+    // or the king is not on its back rank
+    fn outermost_castle_rook(&self, index: usize) -> Option<Square> {
+        let (king, rooks, back_rank) = match index {
+            ZobKeys::WHITE_KINGSIDE_INDEX | ZobKeys::WHITE_QUEENSIDE_INDEX => (
+                self.pieces(Color::White, PieceType::King),
+                self.pieces(Color::White, PieceType::Rook),
+                Tables::RANK_1,
+            ),
+            _ => (
+                self.pieces(Color::Black, PieceType::King),
+                self.pieces(Color::Black, PieceType::Rook),
+                Tables::RANK_8,
+            ),
+        };
+        let king = (king & back_rank).lsb()?;
+        let mut rooks = rooks & back_rank;
+        // Squares towards the h file have lower indices
+        match index {
+            ZobKeys::WHITE_KINGSIDE_INDEX | ZobKeys::BLACK_KINGSIDE_INDEX => {
+                rooks.find(|rook| *rook < king)
+            }
+            _ => rooks.filter(|rook| *rook > king).last(),
+        }
+    }
+
+    /// Writes the position as a fen string. Castle rights use KQkq where they can, and the rook's
+    /// file otherwise, as in X-FEN
+    pub fn to_fen(&self) -> String {
+        let mut placement = String::new();
+        for rank in (0..8).rev() {
+            let mut empty = 0;
+            // The a file has the highest index on each rank
+            for file in (0..8).rev() {
+                match self.get_piece_and_color(Square::new(rank * 8 + file)) {
+                    Some(piece) => {
+                        if empty > 0 {
+                            placement.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        placement.push(piece.letter());
+                    }
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                placement.push_str(&empty.to_string());
+            }
+            if rank > 0 {
+                placement.push('/');
+            }
+        }
+
+        let side_to_move = if self.white_to_move { 'w' } else { 'b' };
+
+        let mut castle_rights = String::new();
+        for (index, standard) in ['K', 'Q', 'k', 'q'].into_iter().enumerate() {
+            if !self.castle_right(index) {
+                continue;
+            }
+            let rook = self.castle_rooks[index];
+            if Some(rook) == self.outermost_castle_rook(index) {
+                castle_rights.push(standard);
+            } else {
+                let file = rook.to_string().remove(0);
+                castle_rights.push(match standard.is_ascii_uppercase() {
+                    true => file.to_ascii_uppercase(),
+                    false => file,
+                });
+            }
+        }
+        if castle_rights.is_empty() {
+            castle_rights.push('-');
+        }
+
+        let en_passant = match self.en_passant_target {
+            Some(target) => target.to_string(),
+            None => "-".to_string(),
+        };
+
+        format!(
+            "{placement} {side_to_move} {castle_rights} {en_passant} {} {}",
+            self.reversable_move_counter, self.full_move_counter
+        )
     }
 
-    fn move_rep_from_masks(&self, start: u64, end: u64) -> MoveRep {
-        let moved_piece = self.get_piece_type(start);
-        let attacked_piece = self.get_piece_type(end);
-        MoveRep {
-            starting_square: start,
-            ending_square: end,
-            promotion: None,
-            moved_type: moved_piece.unwrap(),
-            attacked_type: attacked_piece,
+    /// Whether the castle with the given index, ordered as in `castle_rooks`, is still allowed
+    pub fn castle_right(&self, index: usize) -> bool {
+        match index {
+            ZobKeys::WHITE_KINGSIDE_INDEX => self.white_kingside_castle_rights,
+            ZobKeys::WHITE_QUEENSIDE_INDEX => self.white_queenside_castle_rights,
+            ZobKeys::BLACK_KINGSIDE_INDEX => self.black_kingside_castle_rights,
+            _ => self.black_queenside_castle_rights,
         }
     }
 
-    pub fn apply_string_move(&mut self, play: String, zob_keys: &ZobKeys) {
-        let char1 = play.chars().nth(0).unwrap();
-        let char2 = play.chars().nth(1).unwrap();
-        let char3 = play.chars().nth(2).unwrap();
-        let char4 = play.chars().nth(3).unwrap();
-        let char5 = play.chars().nth(4);
+    fn castle_right_mut(&mut self, index: usize) -> &mut bool {
+        match index {
+            ZobKeys::WHITE_KINGSIDE_INDEX => &mut self.white_kingside_castle_rights,
+            ZobKeys::WHITE_QUEENSIDE_INDEX => &mut self.white_queenside_castle_rights,
+            ZobKeys::BLACK_KINGSIDE_INDEX => &mut self.black_kingside_castle_rights,
+            _ => &mut self.black_queenside_castle_rights,
+        }
+    }
 
-        let start = position_to_mask(char1, char2).unwrap();
-        let end = position_to_mask(char3, char4).unwrap();
-        let mut move_rep = self.move_rep_from_masks(start, end);
-        if let Some(promotion) = char5 {
-            match promotion {
-                'q' => move_rep.promotion = Some(Promotion::Queen),
-                'r' => move_rep.promotion = Some(Promotion::Rook),
-                'b' => move_rep.promotion = Some(Promotion::Bishop),
-                'n' => move_rep.promotion = Some(Promotion::Knight),
-                _ => {}
-            }
+    // Takes away a castle right, keeping the hash up to date
+    fn remove_castle_right(&mut self, index: usize, zob_keys: &ZobKeys) {
+        if self.castle_right(index) {
+            *self.castle_right_mut(index) = false;
+            self.hash ^= zob_keys.castle_keys[index];
         }
-        // Handle castling logic
-        // Is the move even a castle?
+    }
 
-        // White kingside
-        if start == 1 << Tables::E1
-            && end == 1 << Tables::G1
-            && self.white_kingside_castle_rights
-            && self.get_piece_type(start) == Some(PieceType::King)
-        {
-            move_rep.promotion = Some(Promotion::Castle);
+    // Takes away the castle rights of a rook leaving, or being taken on, the square
+    fn remove_castle_rights_on(&mut self, square: Square, zob_keys: &ZobKeys) {
+        for index in 0..4 {
+            if self.castle_rooks[index] == square {
+                self.remove_castle_right(index, zob_keys);
+            }
         }
-        // White kingside
-        if start == 1 << Tables::E1
-            && end == 1 << Tables::C1
-            && self.white_queenside_castle_rights
-            && self.get_piece_type(start) == Some(PieceType::King)
-        {
-            move_rep.promotion = Some(Promotion::Castle);
+    }
+
+    /// Index of the castle a move makes, found from where the king lands
+    pub fn castle_index(mv: &MoveRep) -> Option<usize> {
+        BoardState::CASTLE_TARGETS
+            .iter()
+            .position(|(king, _)| *king == mv.ending_square)
+    }
+
+    /// The starting and ending squares of the rook moved by a castle
+    pub fn castle_rook_squares(&self, mv: &MoveRep) -> Option<(Square, Square)> {
+        let index = BoardState::castle_index(mv)?;
+        Some((
+            self.castle_rooks[index],
+            BoardState::CASTLE_TARGETS[index].1,
+        ))
+    }
+
+    /// The move in uci notation, which writes castles differently in Chess960
+    pub fn move_to_uci(&self, mv: &MoveRep) -> String {
+        mv.to_uci(self.chess960.then_some(&self.castle_rooks))
+    }
+
+    /// Turns Chess960 castle notation on or off
+    pub fn set_chess960(&mut self, chess960: bool) {
+        self.chess960 = chess960;
+    }
+
+    /// Changes the evaluation parameters used by the board, and rescores the board with them
+    pub fn set_eval_params(&mut self, params: Arc<EvalParams>) {
+        self.eval_params = params;
+        self.piece_square_score = piece_square_score(self);
+    }
+
+    /// Changes the network used for evaluation (None uses the hand written eval), and rebuilds the accumulator
+    pub fn set_network(&mut self, network: Option<Arc<Network>>) {
+        self.accumulator = match &network {
+            Some(n) => n.refresh(self),
+            None => Accumulator::default(),
+        };
+        self.network = network;
+    }
+
+    /// Changes the endgame tables probed by the search
+    pub fn set_tablebases(&mut self, tablebases: Option<Arc<Tablebases>>) {
+        self.tablebases = tablebases;
+    }
+
+    #[cfg(test)]
+    pub fn state_from_string_fen(fen_string: String) -> BoardState {
+        let tokens = fen_string.split(" ");
+        BoardState::parse_fen(tokens).unwrap()
+    }
+
+    /// Plays a move written in uci notation if it is legal, returning the move. Castles may be
+    /// written as the king moving two squares or as the king taking its own rook, which is the only
+    /// way to write them in Chess960
+    pub fn apply_string_move(
+        &mut self,
+        play: String,
+        tables: &Tables,
+        zob_keys: &ZobKeys,
+    ) -> Result<MoveRep, String> {
+        let play = play.to_ascii_lowercase();
+        let moves = generate(self, tables);
+        // In Chess960 a plain king move can be written the same as a castle in standard notation,
+        // in which case it is the plain move
+        let found = moves
+            .iter()
+            .find(|mv| mv.promotion != Some(Promotion::Castle) && mv.to_string() == play)
+            .or_else(|| {
+                moves.iter().find(|mv| {
+                    mv.promotion == Some(Promotion::Castle)
+                        && (mv.to_string() == play || mv.to_uci(Some(&self.castle_rooks)) == play)
+                })
+            });
+        match found {
+            Some(&mv) => {
+                self.make(&mv, zob_keys);
+                Ok(mv)
+            }
+            None => Err(format!("Illegal move \"{play}\"")),
         }
-        // Black kingside
-        if start == 1 << Tables::E8
-            && end == 1 << Tables::G8
-            && self.black_kingside_castle_rights
-            && self.get_piece_type(start) == Some(PieceType::King)
-        {
-            move_rep.promotion = Some(Promotion::Castle);
+    }
+
+    /// Finds the legal move written in standard algebraic notation. Check marks, annotations,
+    /// `0-0` castles, a missing `=` before a promotion and a trailing `e.p.` are all accepted
+    pub fn parse_san(&self, san: &str, tables: &Tables) -> Result<MoveRep, String> {
+        let trimmed = san.trim().trim_end_matches(['+', '#', '!', '?']);
+        let trimmed = trimmed
+            .strip_suffix("e.p.")
+            .or_else(|| trimmed.strip_suffix("ep"))
+            .unwrap_or(trimmed)
+            .trim_end();
+        let moves = generate(self, tables);
+
+        let castle = match trimmed {
+            "O-O" | "0-0" => Some(0),
+            "O-O-O" | "0-0-0" => Some(1),
+            _ => None,
+        };
+        if let Some(side) = castle {
+            return moves
+                .into_iter()
+                .find(|mv| {
+                    mv.promotion == Some(Promotion::Castle)
+                        && BoardState::castle_index(mv).map(|index| index % 2) == Some(side)
+                })
+                .ok_or_else(|| format!("Illegal castle \"{san}\""));
+        }
+
+        let mut chars: Vec<char> = trimmed
+            .chars()
+            .filter(|c| !matches!(c, 'x' | ':' | '-'))
+            .collect();
+        let mut promotion = None;
+        if chars.len() > 2 && !chars[chars.len() - 1].is_ascii_digit() {
+            promotion = Promotion::from_letter(chars.pop().unwrap());
+            if promotion.is_none() {
+                return Err(format!("Unrecognized promotion in \"{san}\""));
+            }
+            if chars.last() == Some(&'=') {
+                chars.pop();
+            }
         }
-        // Black queenside
-        if start == 1 << Tables::E8
-            && end == 1 << Tables::C8
-            && self.black_queenside_castle_rights
-            && self.get_piece_type(start) == Some(PieceType::King)
-        {
-            move_rep.promotion = Some(Promotion::Castle);
+        let moved_type = match chars.first() {
+            Some('N') => PieceType::Knight,
+            Some('B') => PieceType::Bishop,
+            Some('R') => PieceType::Rook,
+            Some('Q') => PieceType::Queen,
+            Some('K') => PieceType::King,
+            _ => PieceType::Pawn,
+        };
+        let from = match moved_type {
+            PieceType::Pawn => 0,
+            _ => 1,
+        };
+        if chars.len() < from + 2 {
+            return Err(format!("Unrecognized move \"{san}\""));
         }
+        let end = position_to_square(chars[chars.len() - 2], chars[chars.len() - 1])?;
+        let hints = &chars[from..chars.len() - 2];
 
-        self.make(&move_rep, zob_keys);
+        let mut matching = moves.into_iter().filter(|mv| {
+            let start = mv.starting_square.to_string();
+            mv.moved_type == moved_type
+                && mv.ending_square == end
+                && mv.promotion != Some(Promotion::Castle)
+                && mv.promotion == promotion
+                && hints.iter().all(|hint| start.contains(*hint))
+        });
+        match (matching.next(), matching.next()) {
+            (Some(mv), None) => Ok(mv),
+            (Some(_), Some(_)) => Err(format!("Ambiguous move \"{san}\"")),
+            (None, _) => Err(format!("Illegal move \"{san}\"")),
+        }
     }
 
     /// Prints the board in an easy to understand way
-    pub fn pretty_print_board(&self) {
+    /// Draws the board with colored squares for printing to a terminal
+    pub fn pretty_board(&self) -> String {
         // TODO This could be improved alot
         fn white_square(piece: &str) -> String {
             format!("\x1b[48;2;196;187;90m{piece}\x1b[0m")
@@ -451,11 +791,14 @@ impl BoardState {
             format!("\x1b[48;2;142;79;15m{piece}\x1b[0m")
         }
 
-        println!();
+        let mut text = String::from("\n");
         for index in 1..=64 {
             let index = 64 - index;
             // NOTE On my machine, the white chess piece characters look black, so this reflects that
-            let piece: &str = match self.get_piece_and_color(1 << index) {
+            let piece: &str = match self
+                .get_piece_and_color(Square::new(index))
+                .map(|p| (p.kind, p.color.is_white()))
+            {
                 Some((PieceType::Pawn, false)) => "♙ ",
                 Some((PieceType::Knight, false)) => "♘ ",
                 Some((PieceType::Bishop, false)) => "♗ ",
@@ -473,26 +816,26 @@ impl BoardState {
             };
             match (index % 2, (index / 8) % 2) {
                 (0, 0) => {
-                    print!("{}", white_square(piece));
+                    text.push_str(&white_square(piece));
                 }
                 (0, 1) => {
-                    print!("{}", black_square(piece));
+                    text.push_str(&black_square(piece));
                 }
                 (1, 0) => {
-                    print!("{}", black_square(piece));
+                    text.push_str(&black_square(piece));
                 }
                 (1, 1) => {
-                    print!("{}", white_square(piece));
+                    text.push_str(&white_square(piece));
                 }
 
                 _ => {}
             }
             if index % 8 == 0 {
-                println!();
+                text.push('\n');
             }
-            let _ = io::stdout().flush();
         }
-        println!();
+        text.push('\n');
+        text
     }
 
     /// Pushes the current non reversible state to the stack
@@ -505,6 +848,7 @@ impl BoardState {
         frame.white_kingside_castle_rights = self.white_kingside_castle_rights;
         frame.black_queenside_castle_rights = self.black_queenside_castle_rights;
         frame.black_kingside_castle_rights = self.black_kingside_castle_rights;
+        frame.hash = self.hash;
         self.move_stack.push(frame);
         self.move_stack_pointer += 1;
     }
@@ -522,186 +866,109 @@ impl BoardState {
         self.black_kingside_castle_rights = frame.black_kingside_castle_rights;
     }
 
+    /// Checks if the position already came up since the last capture or pawn move
+    pub fn is_repetition(&self) -> bool {
+        // Every other frame has the same side to move
+        self.move_stack
+            .iter()
+            .rev()
+            .take(self.reversable_move_counter as usize)
+            .skip(1)
+            .step_by(2)
+            .any(|frame| frame.hash == self.hash)
+    }
+
     /// Changes the board state to reflect the move. Also pushes to the move stack
     pub fn make(&mut self, play: &MoveRep, zob_keys: &ZobKeys) {
         // Update piece square score
         self.piece_square_score += delta_ps_score(self, play);
         self.piece_square_score *= -1;
 
+        self.push_state();
+        // Only pawn moves and captures reset the halfmove clock
+        if play.moved_type == PieceType::Pawn || play.attacked_type.is_some() {
+            self.reversable_move_counter = 0;
+        } else {
+            self.reversable_move_counter = self.reversable_move_counter.saturating_add(1);
+        }
+        if !self.white_to_move {
+            self.full_move_counter += 1;
+        }
+
         // If the move is castling, do the move logic here, and return (dont do the normal path)
         if play.promotion == Some(Promotion::Castle) {
-            self.push_state();
             // If there was an enpassant target, clear it from the hash
-            if self.en_passant_target != 0 {
-                self.hash ^=
-                    zob_keys.enpassant_keys[self.en_passant_target.trailing_zeros() as usize];
+            if let Some(target) = self.en_passant_target.take() {
+                self.hash ^= zob_keys.enpassant_keys[target];
             }
 
-            self.en_passant_target = 0;
-            match play.ending_square {
-                e if e == 1 << Tables::G1 => {
-                    // White kingside
-                    self.clear(play.starting_square, Some(PieceType::King));
-                    self.hash ^= zob_keys.piece_keys[ZobKeys::WHITE_KING_INDEX]
-                        [play.starting_square.trailing_zeros() as usize];
-
-                    self.clear(1 << Tables::H1, Some(PieceType::Rook));
-                    self.hash ^=
-                        zob_keys.piece_keys[ZobKeys::WHITE_ROOK_INDEX][Tables::H1 as usize];
-
-                    self.set(play.ending_square, Some(PieceType::King));
-                    self.hash ^= zob_keys.piece_keys[ZobKeys::WHITE_KING_INDEX]
-                        [play.ending_square.trailing_zeros() as usize];
-
-                    self.set(1 << Tables::F1, Some(PieceType::Rook));
-                    self.hash ^=
-                        zob_keys.piece_keys[ZobKeys::WHITE_ROOK_INDEX][Tables::F1 as usize];
-
-                    if self.white_queenside_castle_rights {
-                        self.hash ^= zob_keys.castle_keys[ZobKeys::WHITE_QUEENSIDE_INDEX];
-                    }
-                    if self.white_kingside_castle_rights {
-                        self.hash ^= zob_keys.castle_keys[ZobKeys::WHITE_KINGSIDE_INDEX];
-                    }
-                    self.white_queenside_castle_rights = false;
-                    self.white_kingside_castle_rights = false;
-                }
-                e if e == 1 << Tables::C1 => {
-                    // White queenside
-                    self.clear(play.starting_square, Some(PieceType::King));
-                    self.hash ^= zob_keys.piece_keys[ZobKeys::WHITE_KING_INDEX]
-                        [play.starting_square.trailing_zeros() as usize];
-
-                    self.clear(1 << Tables::A1, Some(PieceType::Rook));
-                    self.hash ^=
-                        zob_keys.piece_keys[ZobKeys::WHITE_ROOK_INDEX][Tables::A1 as usize];
-
-                    self.set(play.ending_square, Some(PieceType::King));
-                    self.hash ^= zob_keys.piece_keys[ZobKeys::WHITE_KING_INDEX]
-                        [play.ending_square.trailing_zeros() as usize];
-
-                    self.set(1 << Tables::D1, Some(PieceType::Rook));
-                    self.hash ^=
-                        zob_keys.piece_keys[ZobKeys::WHITE_ROOK_INDEX][Tables::D1 as usize];
-
-                    if self.white_queenside_castle_rights {
-                        self.hash ^= zob_keys.castle_keys[ZobKeys::WHITE_QUEENSIDE_INDEX];
-                    }
-                    if self.white_kingside_castle_rights {
-                        self.hash ^= zob_keys.castle_keys[ZobKeys::WHITE_KINGSIDE_INDEX];
-                    }
-                    self.white_queenside_castle_rights = false;
-                    self.white_kingside_castle_rights = false;
-                }
-                e if e == 1 << Tables::G8 => {
-                    // Black kingside
-                    self.clear(play.starting_square, Some(PieceType::King));
-                    self.hash ^= zob_keys.piece_keys[ZobKeys::BLACK_KING_INDEX]
-                        [play.starting_square.trailing_zeros() as usize];
-
-                    self.clear(1 << Tables::H8, Some(PieceType::Rook));
-                    self.hash ^=
-                        zob_keys.piece_keys[ZobKeys::BLACK_ROOK_INDEX][Tables::H8 as usize];
-
-                    self.set(play.ending_square, Some(PieceType::King));
-                    self.hash ^= zob_keys.piece_keys[ZobKeys::BLACK_KING_INDEX]
-                        [play.ending_square.trailing_zeros() as usize];
-
-                    self.set(1 << Tables::F8, Some(PieceType::Rook));
-                    self.hash ^=
-                        zob_keys.piece_keys[ZobKeys::BLACK_ROOK_INDEX][Tables::F8 as usize];
-
-                    if self.black_queenside_castle_rights {
-                        self.hash ^= zob_keys.castle_keys[ZobKeys::BLACK_QUEENSIDE_INDEX];
-                    }
-                    if self.black_kingside_castle_rights {
-                        self.hash ^= zob_keys.castle_keys[ZobKeys::BLACK_KINGSIDE_INDEX];
-                    }
-                    self.black_queenside_castle_rights = false;
-                    self.black_kingside_castle_rights = false;
-                }
-                e if e == 1 << Tables::C8 => {
-                    // Black queenside
-                    self.clear(play.starting_square, Some(PieceType::King));
-                    self.hash ^= zob_keys.piece_keys[ZobKeys::BLACK_KING_INDEX]
-                        [play.starting_square.trailing_zeros() as usize];
-
-                    self.clear(1 << Tables::A8, Some(PieceType::Rook));
-                    self.hash ^=
-                        zob_keys.piece_keys[ZobKeys::BLACK_ROOK_INDEX][Tables::A8 as usize];
-
-                    self.set(play.ending_square, Some(PieceType::King));
-                    self.hash ^= zob_keys.piece_keys[ZobKeys::BLACK_KING_INDEX]
-                        [play.ending_square.trailing_zeros() as usize];
-
-                    self.set(1 << Tables::D8, Some(PieceType::Rook));
-                    self.hash ^=
-                        zob_keys.piece_keys[ZobKeys::BLACK_ROOK_INDEX][Tables::D8 as usize];
-
-                    if self.black_queenside_castle_rights {
-                        self.hash ^= zob_keys.castle_keys[ZobKeys::BLACK_QUEENSIDE_INDEX];
-                    }
-                    if self.black_kingside_castle_rights {
-                        self.hash ^= zob_keys.castle_keys[ZobKeys::BLACK_KINGSIDE_INDEX];
-                    }
-                    self.black_queenside_castle_rights = false;
-                    self.black_kingside_castle_rights = false;
-                }
-                _ => return,
+            let Some((rook_start, rook_end)) = self.castle_rook_squares(play) else {
+                return;
+            };
+            let king_index = ZobKeys::match_to_index(PieceType::King, self.white_to_move);
+            let rook_index = ZobKeys::match_to_index(PieceType::Rook, self.white_to_move);
+            // Both pieces come off before either is put back, as in Chess960 they can land on
+            // each others squares
+            self.clear(play.starting_square, Some(PieceType::King));
+            self.hash ^= zob_keys.piece_keys[king_index][play.starting_square];
+            self.clear(rook_start, Some(PieceType::Rook));
+            self.hash ^= zob_keys.piece_keys[rook_index][rook_start];
+
+            self.set(play.ending_square, Some(PieceType::King));
+            self.hash ^= zob_keys.piece_keys[king_index][play.ending_square];
+            self.set(rook_end, Some(PieceType::Rook));
+            self.hash ^= zob_keys.piece_keys[rook_index][rook_end];
+
+            let side_rights = match self.white_to_move {
+                true => [
+                    ZobKeys::WHITE_KINGSIDE_INDEX,
+                    ZobKeys::WHITE_QUEENSIDE_INDEX,
+                ],
+                false => [
+                    ZobKeys::BLACK_KINGSIDE_INDEX,
+                    ZobKeys::BLACK_QUEENSIDE_INDEX,
+                ],
+            };
+            for index in side_rights {
+                self.remove_castle_right(index, zob_keys);
             }
             self.white_to_move = !self.white_to_move;
             self.hash ^= zob_keys.side_key;
             return;
         }
-        self.push_state();
         self.clear(play.starting_square, Some(play.moved_type));
         self.hash ^= zob_keys.piece_keys
-            [ZobKeys::match_to_index(play.moved_type, self.white_to_move)]
-            [play.starting_square.trailing_zeros() as usize];
-        if play.ending_square == self.en_passant_target && play.moved_type == PieceType::Pawn {
+            [ZobKeys::match_to_index(play.moved_type, self.white_to_move)][play.starting_square];
+        if Some(play.ending_square) == self.en_passant_target && play.moved_type == PieceType::Pawn
+        {
             // Special en passant attack logic
             match self.white_to_move {
                 true => {
-                    // TODO clear_all is the ugly result of a architectural mistake. Fix this!
-                    self.clear_all(play.ending_square >> 8);
-                    self.hash ^= zob_keys.piece_keys[ZobKeys::BLACK_PAWN_INDEX]
-                        [(play.ending_square >> 8).trailing_zeros() as usize];
+                    self.clear_square(play.ending_square.south());
+                    self.hash ^= zob_keys.piece_keys
+                        [ZobKeys::match_to_index(PieceType::Pawn, false)]
+                        [play.ending_square.south()];
                 }
                 false => {
-                    // TODO clear_all is the ugly result of a architectural mistake. Fix this!
-                    self.clear_all(play.ending_square << 8);
-                    self.hash ^= zob_keys.piece_keys[ZobKeys::WHITE_PAWN_INDEX]
-                        [(play.ending_square << 8).trailing_zeros() as usize];
+                    self.clear_square(play.ending_square.north());
+                    self.hash ^= zob_keys.piece_keys
+                        [ZobKeys::match_to_index(PieceType::Pawn, true)]
+                        [play.ending_square.north()];
                 }
             }
         } else {
             // Normal attack clear
-            // TODO clear_all is the ugly result of a architectural mistake. Fix this!
-            self.clear_all(play.ending_square);
+            self.clear_square(play.ending_square);
             if let Some(attacked) = play.attacked_type {
                 self.hash ^= zob_keys.piece_keys
-                    [ZobKeys::match_to_index(attacked, !self.white_to_move)]
-                    [play.ending_square.trailing_zeros() as usize];
+                    [ZobKeys::match_to_index(attacked, !self.white_to_move)][play.ending_square];
             }
             // If the attacked piece was a rook, remove the relevent castling rights
-            if play.ending_square == 1 << Tables::A1 && self.white_queenside_castle_rights {
-                self.white_queenside_castle_rights = false;
-                self.hash ^= zob_keys.castle_keys[ZobKeys::WHITE_QUEENSIDE_INDEX];
-            } else if play.ending_square == 1 << Tables::H1 && self.white_kingside_castle_rights {
-                self.white_kingside_castle_rights = false;
-                self.hash ^= zob_keys.castle_keys[ZobKeys::WHITE_KINGSIDE_INDEX];
-            } else if play.ending_square == 1 << Tables::A8 && self.black_queenside_castle_rights {
-                self.black_queenside_castle_rights = false;
-                self.hash ^= zob_keys.castle_keys[ZobKeys::BLACK_QUEENSIDE_INDEX];
-            } else if play.ending_square == 1 << Tables::H8 && self.black_kingside_castle_rights {
-                self.black_kingside_castle_rights = false;
-                self.hash ^= zob_keys.castle_keys[ZobKeys::BLACK_KINGSIDE_INDEX];
-            }
+            self.remove_castle_rights_on(play.ending_square, zob_keys);
         }
         // Now that the en passant target has been handled, clear the en passant hash
-        if self.en_passant_target != 0 {
-            self.hash ^= zob_keys.enpassant_keys[self.en_passant_target.trailing_zeros() as usize];
-            self.en_passant_target = 0;
+        if let Some(target) = self.en_passant_target.take() {
+            self.hash ^= zob_keys.enpassant_keys[target];
         }
         // Promotion logic
         if let Some(promotion) = play.promotion {
@@ -710,53 +977,37 @@ impl BoardState {
                     self.set(play.ending_square, Some(PieceType::Queen));
                     self.hash ^= zob_keys.piece_keys
                         [ZobKeys::match_to_index(PieceType::Queen, self.white_to_move)]
-                        [play.ending_square.trailing_zeros() as usize];
+                        [play.ending_square];
                 }
                 Promotion::Rook => {
                     self.set(play.ending_square, Some(PieceType::Rook));
                     self.hash ^= zob_keys.piece_keys
                         [ZobKeys::match_to_index(PieceType::Rook, self.white_to_move)]
-                        [play.ending_square.trailing_zeros() as usize];
+                        [play.ending_square];
                 }
                 Promotion::Bishop => {
                     self.set(play.ending_square, Some(PieceType::Bishop));
                     self.hash ^= zob_keys.piece_keys
                         [ZobKeys::match_to_index(PieceType::Bishop, self.white_to_move)]
-                        [play.ending_square.trailing_zeros() as usize];
+                        [play.ending_square];
                 }
                 Promotion::Knight => {
                     self.set(play.ending_square, Some(PieceType::Knight));
                     self.hash ^= zob_keys.piece_keys
                         [ZobKeys::match_to_index(PieceType::Knight, self.white_to_move)]
-                        [play.ending_square.trailing_zeros() as usize];
+                        [play.ending_square];
                 }
                 _ => {}
             }
         } else {
             self.set(play.ending_square, Some(play.moved_type));
             self.hash ^= zob_keys.piece_keys
-                [ZobKeys::match_to_index(play.moved_type, self.white_to_move)]
-                [play.ending_square.trailing_zeros() as usize];
+                [ZobKeys::match_to_index(play.moved_type, self.white_to_move)][play.ending_square];
         }
         // Do special logic here
         // If the move is not castling, but can effect castling rights, change the rights here
         if play.moved_type == PieceType::Rook {
-            if self.white_queenside_castle_rights && play.starting_square == 1 << Tables::A1 {
-                self.white_queenside_castle_rights = false;
-                self.hash ^= zob_keys.castle_keys[ZobKeys::WHITE_QUEENSIDE_INDEX];
-            }
-            if self.white_kingside_castle_rights && play.starting_square == 1 << Tables::H1 {
-                self.white_kingside_castle_rights = false;
-                self.hash ^= zob_keys.castle_keys[ZobKeys::WHITE_KINGSIDE_INDEX];
-            }
-            if self.black_queenside_castle_rights && play.starting_square == 1 << Tables::A8 {
-                self.black_queenside_castle_rights = false;
-                self.hash ^= zob_keys.castle_keys[ZobKeys::BLACK_QUEENSIDE_INDEX];
-            }
-            if self.black_kingside_castle_rights && play.starting_square == 1 << Tables::H8 {
-                self.black_kingside_castle_rights = false;
-                self.hash ^= zob_keys.castle_keys[ZobKeys::BLACK_KINGSIDE_INDEX];
-            }
+            self.remove_castle_rights_on(play.starting_square, zob_keys);
         }
         if play.moved_type == PieceType::King && play.promotion.is_none() {
             if self.white_to_move {
@@ -781,25 +1032,17 @@ impl BoardState {
         }
         // Set en passant target
         if play.moved_type == PieceType::Pawn
-            && (play.starting_square & Tables::RANK_2 != 0
-                && play.ending_square & Tables::RANK_4 != 0
-                || play.starting_square & Tables::RANK_7 != 0
-                    && play.ending_square & Tables::RANK_5 != 0)
+            && (Tables::RANK_2.contains(play.starting_square)
+                && Tables::RANK_4.contains(play.ending_square)
+                || Tables::RANK_7.contains(play.starting_square)
+                    && Tables::RANK_5.contains(play.ending_square))
         {
-            self.en_passant_target = match self.white_to_move {
-                true => {
-                    self.hash ^= zob_keys.enpassant_keys
-                        [(play.starting_square << 8).trailing_zeros() as usize];
-                    play.starting_square << 8
-                }
-                false => {
-                    self.hash ^= zob_keys.enpassant_keys
-                        [(play.starting_square >> 8).trailing_zeros() as usize];
-                    play.starting_square >> 8
-                }
-            }
-        } else if self.en_passant_target != 0 {
-            self.hash ^= zob_keys.enpassant_keys[self.en_passant_target.trailing_zeros() as usize];
+            let target = match self.white_to_move {
+                true => play.starting_square.north(),
+                false => play.starting_square.south(),
+            };
+            self.hash ^= zob_keys.enpassant_keys[target];
+            self.en_passant_target = Some(target);
         }
         self.hash ^= zob_keys.side_key;
         self.white_to_move = !self.white_to_move;
@@ -828,12 +1071,12 @@ impl BoardState {
             self.hash ^= zob_keys.castle_keys[ZobKeys::BLACK_KINGSIDE_INDEX];
         }
 
-        if self.en_passant_target != 0 {
-            self.hash ^= zob_keys.enpassant_keys[self.en_passant_target.trailing_zeros() as usize];
+        if let Some(target) = self.en_passant_target {
+            self.hash ^= zob_keys.enpassant_keys[target];
         }
 
-        if previous_en_passant != 0 {
-            self.hash ^= zob_keys.enpassant_keys[previous_en_passant.trailing_zeros() as usize];
+        if let Some(target) = previous_en_passant {
+            self.hash ^= zob_keys.enpassant_keys[target];
         }
 
         self.pop_state();
@@ -842,117 +1085,26 @@ impl BoardState {
             // Swap side to play first
             self.white_to_move = !self.white_to_move;
             self.hash ^= zob_keys.side_key;
-            match play.ending_square {
-                e if e == 1 << Tables::G1 => {
-                    // White kingside
-                    self.set(play.starting_square, Some(PieceType::King));
-                    self.hash ^= zob_keys.piece_keys[ZobKeys::WHITE_KING_INDEX]
-                        [play.starting_square.trailing_zeros() as usize];
-
-                    self.set(1 << Tables::H1, Some(PieceType::Rook));
-                    self.hash ^=
-                        zob_keys.piece_keys[ZobKeys::WHITE_ROOK_INDEX][Tables::H1 as usize];
-
-                    self.clear(play.ending_square, Some(PieceType::King));
-                    self.hash ^= zob_keys.piece_keys[ZobKeys::WHITE_KING_INDEX]
-                        [play.ending_square.trailing_zeros() as usize];
-
-                    self.clear(1 << Tables::F1, Some(PieceType::Rook));
-                    self.hash ^=
-                        zob_keys.piece_keys[ZobKeys::WHITE_ROOK_INDEX][Tables::F1 as usize];
-                }
-                e if e == 1 << Tables::C1 => {
-                    // White queenside
-                    self.set(play.starting_square, Some(PieceType::King));
-                    self.hash ^= zob_keys.piece_keys[ZobKeys::WHITE_KING_INDEX]
-                        [play.starting_square.trailing_zeros() as usize];
-
-                    self.set(1 << Tables::A1, Some(PieceType::Rook));
-                    self.hash ^=
-                        zob_keys.piece_keys[ZobKeys::WHITE_ROOK_INDEX][Tables::A1 as usize];
-
-                    self.clear(play.ending_square, Some(PieceType::King));
-                    self.hash ^= zob_keys.piece_keys[ZobKeys::WHITE_KING_INDEX]
-                        [play.ending_square.trailing_zeros() as usize];
-
-                    self.clear(1 << Tables::D1, Some(PieceType::Rook));
-                    self.hash ^=
-                        zob_keys.piece_keys[ZobKeys::WHITE_ROOK_INDEX][Tables::D1 as usize];
-                }
-                e if e == 1 << Tables::G8 => {
-                    // Black kingside
-                    self.set(play.starting_square, Some(PieceType::King));
-                    self.hash ^= zob_keys.piece_keys[ZobKeys::BLACK_KING_INDEX]
-                        [play.starting_square.trailing_zeros() as usize];
-
-                    self.set(1 << Tables::H8, Some(PieceType::Rook));
-                    self.hash ^=
-                        zob_keys.piece_keys[ZobKeys::BLACK_ROOK_INDEX][Tables::H8 as usize];
-
-                    self.clear(play.ending_square, Some(PieceType::King));
-                    self.hash ^= zob_keys.piece_keys[ZobKeys::BLACK_KING_INDEX]
-                        [play.ending_square.trailing_zeros() as usize];
-
-                    self.clear(1 << Tables::F8, Some(PieceType::Rook));
-                    self.hash ^=
-                        zob_keys.piece_keys[ZobKeys::BLACK_ROOK_INDEX][Tables::F8 as usize];
-                }
-                e if e == 1 << Tables::C8 => {
-                    // Black queenside
-                    self.set(play.starting_square, Some(PieceType::King));
-                    self.hash ^= zob_keys.piece_keys[ZobKeys::BLACK_KING_INDEX]
-                        [play.starting_square.trailing_zeros() as usize];
-
-                    self.set(1 << Tables::A8, Some(PieceType::Rook));
-                    self.hash ^=
-                        zob_keys.piece_keys[ZobKeys::BLACK_ROOK_INDEX][Tables::A8 as usize];
-
-                    self.clear(play.ending_square, Some(PieceType::King));
-                    self.hash ^= zob_keys.piece_keys[ZobKeys::BLACK_KING_INDEX]
-                        [play.ending_square.trailing_zeros() as usize];
-
-                    self.clear(1 << Tables::D8, Some(PieceType::Rook));
-                    self.hash ^=
-                        zob_keys.piece_keys[ZobKeys::BLACK_ROOK_INDEX][Tables::D8 as usize];
-                }
-                _ => return,
-            }
+            let Some((rook_start, rook_end)) = self.castle_rook_squares(play) else {
+                return;
+            };
+            let king_index = ZobKeys::match_to_index(PieceType::King, self.white_to_move);
+            let rook_index = ZobKeys::match_to_index(PieceType::Rook, self.white_to_move);
+            self.clear(play.ending_square, Some(PieceType::King));
+            self.hash ^= zob_keys.piece_keys[king_index][play.ending_square];
+            self.clear(rook_end, Some(PieceType::Rook));
+            self.hash ^= zob_keys.piece_keys[rook_index][rook_end];
+
+            self.set(play.starting_square, Some(PieceType::King));
+            self.hash ^= zob_keys.piece_keys[king_index][play.starting_square];
+            self.set(rook_start, Some(PieceType::Rook));
+            self.hash ^= zob_keys.piece_keys[rook_index][rook_start];
             // Update the piece square score
             self.piece_square_score *= -1;
             self.piece_square_score -= delta_ps_score(self, play);
             return;
         }
-        if play.ending_square == self.en_passant_target && play.moved_type == PieceType::Pawn {
-            // Special en passant attack logic
-            // Remember, we have not switched the side to move back yet
-            match !self.white_to_move {
-                true => {
-                    self.set(play.ending_square >> 8, play.attacked_type);
-                    if let Some(attacked) = play.attacked_type {
-                        self.hash ^= zob_keys.piece_keys
-                            [ZobKeys::match_to_index(attacked, self.white_to_move)]
-                            [(play.ending_square >> 8).trailing_zeros() as usize];
-                    }
-                }
-                false => {
-                    self.set(play.ending_square << 8, play.attacked_type);
-                    if let Some(attacked) = play.attacked_type {
-                        self.hash ^= zob_keys.piece_keys
-                            [ZobKeys::match_to_index(attacked, self.white_to_move)]
-                            [(play.ending_square << 8).trailing_zeros() as usize];
-                    }
-                }
-            }
-        } else {
-            self.set(play.ending_square, play.attacked_type);
-            if let Some(attacked) = play.attacked_type {
-                // NOTE, we dont invert the side to move because we have not toggled it back yet
-                self.hash ^= zob_keys.piece_keys
-                    [ZobKeys::match_to_index(attacked, self.white_to_move)]
-                    [play.ending_square.trailing_zeros() as usize];
-            }
-        }
-        // Put this after the first set because we want to replace the opponents piece
+        // The moved piece comes off before the taken piece goes back on its square
         self.white_to_move = !self.white_to_move;
         self.hash ^= zob_keys.side_key;
         if let Some(promotion) = play.promotion {
@@ -961,767 +1113,317 @@ impl BoardState {
                     self.clear(play.ending_square, Some(PieceType::Queen));
                     self.hash ^= zob_keys.piece_keys
                         [ZobKeys::match_to_index(PieceType::Queen, self.white_to_move)]
-                        [play.ending_square.trailing_zeros() as usize];
+                        [play.ending_square];
                 }
                 Promotion::Rook => {
                     self.clear(play.ending_square, Some(PieceType::Rook));
                     self.hash ^= zob_keys.piece_keys
                         [ZobKeys::match_to_index(PieceType::Rook, self.white_to_move)]
-                        [play.ending_square.trailing_zeros() as usize];
+                        [play.ending_square];
                 }
                 Promotion::Bishop => {
                     self.clear(play.ending_square, Some(PieceType::Bishop));
                     self.hash ^= zob_keys.piece_keys
                         [ZobKeys::match_to_index(PieceType::Bishop, self.white_to_move)]
-                        [play.ending_square.trailing_zeros() as usize];
+                        [play.ending_square];
                 }
                 Promotion::Knight => {
                     self.clear(play.ending_square, Some(PieceType::Knight));
                     self.hash ^= zob_keys.piece_keys
                         [ZobKeys::match_to_index(PieceType::Knight, self.white_to_move)]
-                        [play.ending_square.trailing_zeros() as usize];
+                        [play.ending_square];
                 }
                 _ => {}
             }
         } else {
             self.clear(play.ending_square, Some(play.moved_type));
             self.hash ^= zob_keys.piece_keys
-                [ZobKeys::match_to_index(play.moved_type, self.white_to_move)]
-                [play.ending_square.trailing_zeros() as usize];
+                [ZobKeys::match_to_index(play.moved_type, self.white_to_move)][play.ending_square];
         }
         self.set(play.starting_square, Some(play.moved_type));
         self.hash ^= zob_keys.piece_keys
-            [ZobKeys::match_to_index(play.moved_type, self.white_to_move)]
-            [play.starting_square.trailing_zeros() as usize];
+            [ZobKeys::match_to_index(play.moved_type, self.white_to_move)][play.starting_square];
+        // The taken piece belongs to the side not to move
+        let taken_square = if Some(play.ending_square) == self.en_passant_target
+            && play.moved_type == PieceType::Pawn
+        {
+            match self.white_to_move {
+                true => play.ending_square.south(),
+                false => play.ending_square.north(),
+            }
+        } else {
+            play.ending_square
+        };
+        if let Some(attacked) = play.attacked_type {
+            let color = !Color::from_white(self.white_to_move);
+            self.put(taken_square, attacked, color);
+            self.hash ^= zob_keys.piece_keys
+                [ZobKeys::match_to_index(attacked, !self.white_to_move)][taken_square];
+        }
         // Update the piece square score
         self.piece_square_score *= -1;
         self.piece_square_score -= delta_ps_score(self, play);
     }
 
-    // Clear all bitboards at this mask
+    /// The bitboard of one kind of piece for a color
     #[inline]
-    fn clear_all(&mut self, bb: u64) {
-        self.white_pawns &= !bb;
-        self.white_knights &= !bb;
-        self.white_bishops &= !bb;
-        self.white_rooks &= !bb;
-        self.white_queens &= !bb;
-        self.white_king &= !bb;
-
-        self.black_pawns &= !bb;
-        self.black_knights &= !bb;
-        self.black_bishops &= !bb;
-        self.black_rooks &= !bb;
-        self.black_queens &= !bb;
-        self.black_king &= !bb;
-    }
-
-    // Clear bitboards for this value
+    pub fn pieces(&self, color: Color, kind: PieceType) -> Bitboard {
+        self.pieces[color.index()][kind as usize]
+    }
+
+    /// Puts a piece on an empty square, without touching the hash, scores or accumulator
     #[inline]
-    fn clear(&mut self, bb: u64, attacked: Option<PieceType>) {
-        if let Some(piece) = attacked {
-            if self.white_to_move {
-                match piece {
-                    PieceType::Pawn => self.white_pawns &= !bb,
-                    PieceType::Knight => self.white_knights &= !bb,
-                    PieceType::Bishop => self.white_bishops &= !bb,
-                    PieceType::Rook => self.white_rooks &= !bb,
-                    PieceType::Queen => self.white_queens &= !bb,
-                    PieceType::King => self.white_king &= !bb,
-                }
-            } else {
-                match piece {
-                    PieceType::Pawn => self.black_pawns &= !bb,
-                    PieceType::Knight => self.black_knights &= !bb,
-                    PieceType::Bishop => self.black_bishops &= !bb,
-                    PieceType::Rook => self.black_rooks &= !bb,
-                    PieceType::Queen => self.black_queens &= !bb,
-                    PieceType::King => self.black_king &= !bb,
-                }
+    pub fn put_piece(&mut self, piece: Piece, square: Square) {
+        debug_assert!(self.mailbox[square].is_none());
+        self.pieces[piece.color.index()][piece.kind as usize] |= square.bit();
+        self.color_occupancy[piece.color.index()] |= square.bit();
+        self.mailbox[square] = Some(piece);
+    }
+
+    /// Takes whatever piece is on a square off the board, without touching the hash, scores or
+    /// accumulator
+    #[inline]
+    pub fn remove_piece(&mut self, square: Square) -> Option<Piece> {
+        let piece = self.mailbox[square].take()?;
+        self.pieces[piece.color.index()][piece.kind as usize] &= !square.bit();
+        self.color_occupancy[piece.color.index()] &= !square.bit();
+        Some(piece)
+    }
+
+    /// Takes every piece off the board
+    pub fn clear_pieces(&mut self) {
+        self.pieces = [[Bitboard::EMPTY; 6]; 2];
+        self.color_occupancy = [Bitboard::EMPTY; 2];
+        self.mailbox = [None; 64];
+    }
+
+    // Clear whatever piece is on this square
+    #[inline]
+    fn clear_square(&mut self, square: Square) {
+        if let Some(piece) = self.remove_piece(square) {
+            if let Some(network) = &self.network {
+                network.remove(
+                    &mut self.accumulator,
+                    piece.kind,
+                    piece.color.is_white(),
+                    square,
+                );
             }
         }
     }
 
+    // Clear the piece of the side to move on this square
     #[inline]
-    fn set(&mut self, bb: u64, present_piece: Option<PieceType>) {
-        if let Some(piece) = present_piece {
-            if self.white_to_move {
-                match piece {
-                    PieceType::Pawn => self.white_pawns |= bb,
-                    PieceType::Knight => self.white_knights |= bb,
-                    PieceType::Bishop => self.white_bishops |= bb,
-                    PieceType::Rook => self.white_rooks |= bb,
-                    PieceType::Queen => self.white_queens |= bb,
-                    PieceType::King => self.white_king |= bb,
-                }
-            } else {
-                match piece {
-                    PieceType::Pawn => self.black_pawns |= bb,
-                    PieceType::Knight => self.black_knights |= bb,
-                    PieceType::Bishop => self.black_bishops |= bb,
-                    PieceType::Rook => self.black_rooks |= bb,
-                    PieceType::Queen => self.black_queens |= bb,
-                    PieceType::King => self.black_king |= bb,
-                }
+    fn clear(&mut self, square: Square, attacked: Option<PieceType>) {
+        if let Some(piece) = attacked {
+            if let Some(network) = &self.network {
+                network.remove(&mut self.accumulator, piece, self.white_to_move, square);
             }
+            let removed = self.remove_piece(square);
+            debug_assert_eq!(
+                removed,
+                Some(Piece::new(Color::from_white(self.white_to_move), piece))
+            );
         }
     }
 
+    // Put a piece of the side to move on this square
     #[inline]
-    pub fn white_occupancy(&self) -> u64 {
-        self.white_pawns
-            | self.white_knights
-            | self.white_bishops
-            | self.white_rooks
-            | self.white_queens
-            | self.white_king
+    fn set(&mut self, square: Square, present_piece: Option<PieceType>) {
+        if let Some(piece) = present_piece {
+            self.put(square, piece, Color::from_white(self.white_to_move));
+        }
     }
 
     #[inline]
-    pub fn black_occupancy(&self) -> u64 {
-        self.black_pawns
-            | self.black_knights
-            | self.black_bishops
-            | self.black_rooks
-            | self.black_queens
-            | self.black_king
+    fn put(&mut self, square: Square, piece: PieceType, color: Color) {
+        if let Some(network) = &self.network {
+            network.add(&mut self.accumulator, piece, color.is_white(), square);
+        }
+        self.put_piece(Piece::new(color, piece), square);
     }
 
     #[inline]
-    pub fn occupancy(&self) -> u64 {
-        self.white_pawns
-            | self.white_knights
-            | self.white_bishops
-            | self.white_rooks
-            | self.white_queens
-            | self.white_king
-            | self.black_pawns
-            | self.black_knights
-            | self.black_bishops
-            | self.black_rooks
-            | self.black_queens
-            | self.black_king
+    pub fn color_occupancy(&self, color: Color) -> Bitboard {
+        self.color_occupancy[color.index()]
     }
 
     #[inline]
-    // Gets the type of piece present at the mask
-    pub fn get_piece_type(&self, mask: u64) -> Option<PieceType> {
-        if self.white_pawns & mask != 0 || self.black_pawns & mask != 0 {
-            return Some(PieceType::Pawn);
-        }
-        if self.white_knights & mask != 0 || self.black_knights & mask != 0 {
-            return Some(PieceType::Knight);
-        }
-        if self.white_bishops & mask != 0 || self.black_bishops & mask != 0 {
-            return Some(PieceType::Bishop);
-        }
-        if self.white_rooks & mask != 0 || self.black_rooks & mask != 0 {
-            return Some(PieceType::Rook);
-        }
-        if self.white_queens & mask != 0 || self.black_queens & mask != 0 {
-            return Some(PieceType::Queen);
-        }
-        if self.white_king & mask != 0 || self.black_king & mask != 0 {
-            return Some(PieceType::King);
-        }
+    pub fn occupancy(&self) -> Bitboard {
+        self.color_occupancy[0] | self.color_occupancy[1]
+    }
 
-        None
+    #[inline]
+    // Gets the type of piece on the square
+    pub fn get_piece_type(&self, square: Square) -> Option<PieceType> {
+        self.mailbox[square].map(|piece| piece.kind)
     }
 
     #[inline]
-    /// Gets the type of piece and color
-    pub fn get_piece_and_color(&self, mask: u64) -> Option<(PieceType, bool)> {
-        if self.white_pawns & mask != 0 {
-            return Some((PieceType::Pawn, true));
-        }
+    /// Gets the piece on a square, with its color
+    pub fn get_piece_and_color(&self, square: Square) -> Option<Piece> {
+        self.mailbox[square]
+    }
 
-        if self.white_knights & mask != 0 {
-            return Some((PieceType::Knight, true));
-        }
+    // Get the attack map of white
+    pub fn white_attack_mask(&self, tables: &Tables) -> Bitboard {
+        self.attack_mask::<true>(tables, Bitboard::EMPTY)
+    }
 
-        if self.white_bishops & mask != 0 {
-            return Some((PieceType::Bishop, true));
-        }
+    // Get the attack map of black
+    pub fn black_attack_mask(&self, tables: &Tables) -> Bitboard {
+        self.attack_mask::<false>(tables, Bitboard::EMPTY)
+    }
+
+    // Get the attack map of a side, with sliders seeing through the transparent pieces
+    pub fn attack_mask<const WHITE: bool>(
+        &self,
+        tables: &Tables,
+        transparency: Bitboard,
+    ) -> Bitboard {
+        let color = Color::from_white(WHITE);
+        let occupancy = self.occupancy() & !transparency;
+        let pawn_attacks = match WHITE {
+            true => &tables.white_pawn_attacks,
+            false => &tables.black_pawn_attacks,
+        };
+        let queens = self.pieces(color, PieceType::Queen);
+        let mut attack_mask = Bitboard::EMPTY;
 
-        if self.white_rooks & mask != 0 {
-            return Some((PieceType::Rook, true));
+        for start_square in self.pieces(color, PieceType::Pawn) {
+            attack_mask |= pawn_attacks[start_square];
         }
-
-        if self.white_queens & mask != 0 {
-            return Some((PieceType::Queen, true));
+        for start_square in self.pieces(color, PieceType::Knight) {
+            attack_mask |= tables.knight_attacks[start_square];
         }
-
-        if self.white_king & mask != 0 {
-            return Some((PieceType::King, true));
+        for start_square in self.pieces(color, PieceType::Bishop) | queens {
+            attack_mask |= tables.get_bishop_attack(start_square, occupancy);
         }
-
-        if self.black_pawns & mask != 0 {
-            return Some((PieceType::Pawn, false));
+        for start_square in self.pieces(color, PieceType::Rook) | queens {
+            attack_mask |= tables.get_rook_attack(start_square, occupancy);
         }
-
-        if self.black_knights & mask != 0 {
-            return Some((PieceType::Knight, false));
+        for start_square in self.pieces(color, PieceType::King) {
+            attack_mask |= tables.king_attacks[start_square];
         }
 
-        if self.black_bishops & mask != 0 {
-            return Some((PieceType::Bishop, false));
-        }
+        attack_mask
+    }
 
-        if self.black_rooks & mask != 0 {
-            return Some((PieceType::Rook, false));
-        }
+    // Gets the mask of the pieces of a side that attack the given piece mask
+    pub fn attacking<const WHITE: bool>(&self, tables: &Tables, target: Square) -> Bitboard {
+        let color = Color::from_white(WHITE);
+        let piece_index = target;
+        let rook_like = self.pieces(color, PieceType::Rook) | self.pieces(color, PieceType::Queen);
+        let bishop_like =
+            self.pieces(color, PieceType::Bishop) | self.pieces(color, PieceType::Queen);
 
-        if self.black_queens & mask != 0 {
-            return Some((PieceType::Queen, false));
+        // NOTE Pawns are looked up with the other sides attacks, since pawn moves are not symetric
+        let pawn_attacks = match WHITE {
+            true => &tables.black_pawn_attacks,
+            false => &tables.white_pawn_attacks,
+        };
+        pawn_attacks[piece_index] & self.pieces(color, PieceType::Pawn)
+            | tables.knight_attacks[piece_index] & self.pieces(color, PieceType::Knight)
+            | tables.get_rook_attack(piece_index, self.occupancy()) & rook_like
+            | tables.get_bishop_attack(piece_index, self.occupancy()) & bishop_like
+            | tables.king_attacks[piece_index] & self.pieces(color, PieceType::King)
+    }
+
+    // Get the mask of the pieces of a side that 'block' the target. Similar to attacking, but with
+    // pawn pushes instead of attacks.
+    pub fn blocking<const WHITE: bool>(&self, tables: &Tables, target: Square) -> Bitboard {
+        let color = Color::from_white(WHITE);
+        let piece_index = target;
+        let pawns = self.pieces(color, PieceType::Pawn);
+        let rook_like = self.pieces(color, PieceType::Rook) | self.pieces(color, PieceType::Queen);
+        let bishop_like =
+            self.pieces(color, PieceType::Bishop) | self.pieces(color, PieceType::Queen);
+
+        // Pawns push onto the target from one or two squares behind it
+        let (single, double, start_rank) = match WHITE {
+            true => (target.bit().south(), target.bit() >> 16, Tables::RANK_2),
+            false => (target.bit().north(), target.bit() << 16, Tables::RANK_7),
+        };
+        let mut blocking_mask = single & pawns;
+        if (single & self.occupancy()).is_empty() {
+            blocking_mask |= double & start_rank & pawns;
         }
+        // NOTE No blocking kings because that should never happen
+        blocking_mask
+            | tables.knight_attacks[piece_index] & self.pieces(color, PieceType::Knight)
+            | tables.get_rook_attack(piece_index, self.occupancy()) & rook_like
+            | tables.get_bishop_attack(piece_index, self.occupancy()) & bishop_like
+    }
 
-        if self.black_king & mask != 0 {
-            return Some((PieceType::King, false));
+    /// Gets the mask of the pieces pinned to the target
+    pub fn pin_mask(&self, tables: &Tables, target: Square, white_to_move: bool) -> Bitboard {
+        let target_index = target;
+        let own_occupancy = self.color_occupancy(Color::from_white(white_to_move));
+        let enemy = !Color::from_white(white_to_move);
+        let queens = self.pieces(enemy, PieceType::Queen);
+        let rook_like = self.pieces(enemy, PieceType::Rook) | queens;
+        let bishop_like = self.pieces(enemy, PieceType::Bishop) | queens;
+
+        let mut mask = Bitboard::EMPTY;
+        // Project a rook ray without any blockers
+        let rook_ray = tables.get_rook_attack(target_index, rook_like);
+        // Get the pieces that could attack if there were no blockers
+        for attacker_index in rook_ray & rook_like {
+            // Look back to the target without any blockers
+            let attacker_ray = tables.get_rook_attack(attacker_index, target.bit());
+            // Get the mask which must protect the target
+            let blocker_mask = rook_ray & attacker_ray;
+            // If there is only one piece in the way, add it to the mask if its the current sides color
+            if (blocker_mask & self.occupancy()).count() == 1 {
+                mask |= blocker_mask & own_occupancy;
+            }
         }
 
-        None
-    }
-
-    // Get the attack map of white
-    pub fn white_attack_mask(&self, tables: &Tables) -> u64 {
-        let mut attack_mask = 0;
-
-        // White pawns
-        let mut pawn_bb = self.white_pawns;
-        while pawn_bb != 0 {
-            let start_square = pop_lsb(&mut pawn_bb);
-            attack_mask |= tables.white_pawn_attacks[start_square];
-        }
-
-        // White knights
-        let mut knight_bb = self.white_knights;
-        while knight_bb != 0 {
-            let start_square = pop_lsb(&mut knight_bb);
-            attack_mask |= tables.knight_attacks[start_square];
-        }
-
-        // White bishops
-        let mut bishop_bb = self.white_bishops;
-        while bishop_bb != 0 {
-            let start_square = pop_lsb(&mut bishop_bb);
-            let mut bishop_attacks = tables.get_bishop_attack(start_square, self.occupancy());
-            while bishop_attacks != 0 {
-                let attack_square = 1 << pop_lsb(&mut bishop_attacks) as u64;
-                attack_mask |= attack_square;
-            }
-        }
-        // White rooks
-        let mut rook_bb = self.white_rooks;
-        while rook_bb != 0 {
-            let start_square = pop_lsb(&mut rook_bb);
-            let mut rook_attacks = tables.get_rook_attack(start_square, self.occupancy());
-            while rook_attacks != 0 {
-                let attack_square = 1 << pop_lsb(&mut rook_attacks) as u64;
-                attack_mask |= attack_square;
-            }
-        }
-
-        // White queens
-
-        let mut bishop_bb_part = self.white_queens;
-        while bishop_bb_part != 0 {
-            let start_square = pop_lsb(&mut bishop_bb_part);
-            let mut bishop_part_attacks = tables.get_bishop_attack(start_square, self.occupancy());
-            while bishop_part_attacks != 0 {
-                let attack_square = 1 << pop_lsb(&mut bishop_part_attacks) as u64;
-                attack_mask |= attack_square;
-            }
-        }
-
-        let mut rook_bb_part = self.white_queens;
-        while rook_bb_part != 0 {
-            let start_square = pop_lsb(&mut rook_bb_part);
-            let mut rook_part_attacks = tables.get_rook_attack(start_square, self.occupancy());
-            while rook_part_attacks != 0 {
-                let attack_square = 1 << pop_lsb(&mut rook_part_attacks) as u64;
-                attack_mask |= attack_square;
-            }
-        }
-
-        // White king
-        let mut king_bb = self.white_king;
-        while king_bb != 0 {
-            let start_square = pop_lsb(&mut king_bb);
-            attack_mask |= tables.king_attacks[start_square];
-        }
-
-        attack_mask
-    }
-
-    // Get the attack map of white with transparency
-    pub fn white_attack_mask_with_transparency(&self, tables: &Tables, transparency: u64) -> u64 {
-        let mut attack_mask = 0;
-
-        // White pawns
-        let mut pawn_bb = self.white_pawns;
-        while pawn_bb != 0 {
-            let start_square = pop_lsb(&mut pawn_bb);
-            attack_mask |= tables.white_pawn_attacks[start_square];
-        }
-
-        // White knights
-        let mut knight_bb = self.white_knights;
-        while knight_bb != 0 {
-            let start_square = pop_lsb(&mut knight_bb);
-            attack_mask |= tables.knight_attacks[start_square];
-        }
-
-        // White bishops
-        let mut bishop_bb = self.white_bishops;
-        while bishop_bb != 0 {
-            let start_square = pop_lsb(&mut bishop_bb);
-            let mut bishop_attacks =
-                tables.get_bishop_attack(start_square, self.occupancy() & !transparency);
-            while bishop_attacks != 0 {
-                let attack_square = 1 << pop_lsb(&mut bishop_attacks) as u64;
-                attack_mask |= attack_square;
-            }
-        }
-        // White rooks
-        let mut rook_bb = self.white_rooks;
-        while rook_bb != 0 {
-            let start_square = pop_lsb(&mut rook_bb);
-            let mut rook_attacks =
-                tables.get_rook_attack(start_square, self.occupancy() & !transparency);
-            while rook_attacks != 0 {
-                let attack_square = 1 << pop_lsb(&mut rook_attacks) as u64;
-                attack_mask |= attack_square;
-            }
-        }
-
-        // White queens
-
-        let mut bishop_bb_part = self.white_queens;
-        while bishop_bb_part != 0 {
-            let start_square = pop_lsb(&mut bishop_bb_part);
-            let mut bishop_part_attacks =
-                tables.get_bishop_attack(start_square, self.occupancy() & !transparency);
-            while bishop_part_attacks != 0 {
-                let attack_square = 1 << pop_lsb(&mut bishop_part_attacks) as u64;
-                attack_mask |= attack_square;
-            }
-        }
-
-        let mut rook_bb_part = self.white_queens;
-        while rook_bb_part != 0 {
-            let start_square = pop_lsb(&mut rook_bb_part);
-            let mut rook_part_attacks =
-                tables.get_rook_attack(start_square, self.occupancy() & !transparency);
-            while rook_part_attacks != 0 {
-                let attack_square = 1 << pop_lsb(&mut rook_part_attacks) as u64;
-                attack_mask |= attack_square;
-            }
-        }
-
-        // White king
-        let mut king_bb = self.white_king;
-        while king_bb != 0 {
-            let start_square = pop_lsb(&mut king_bb);
-            attack_mask |= tables.king_attacks[start_square];
-        }
-
-        attack_mask
-    }
-
-    // Get the attack map of black
-    pub fn black_attack_mask(&self, table: &Tables) -> u64 {
-        let mut attack_mask = 0;
-
-        // black pawns
-        let mut pawn_bb = self.black_pawns;
-        while pawn_bb != 0 {
-            let start_square = pop_lsb(&mut pawn_bb);
-            attack_mask |= table.black_pawn_attacks[start_square];
-        }
-
-        // black knights
-        let mut knight_bb = self.black_knights;
-        while knight_bb != 0 {
-            let start_square = pop_lsb(&mut knight_bb);
-            attack_mask |= table.knight_attacks[start_square];
-        }
-
-        // black bishops
-        let mut bishop_bb = self.black_bishops;
-        while bishop_bb != 0 {
-            let start_square = pop_lsb(&mut bishop_bb);
-            let mut bishop_attacks = table.get_bishop_attack(start_square, self.occupancy());
-            while bishop_attacks != 0 {
-                let attack_square = 1 << pop_lsb(&mut bishop_attacks) as u64;
-                attack_mask |= attack_square;
-            }
-        }
-        // black rooks
-        let mut rook_bb = self.black_rooks;
-        while rook_bb != 0 {
-            let start_square = pop_lsb(&mut rook_bb);
-            let mut rook_attacks = table.get_rook_attack(start_square, self.occupancy());
-            while rook_attacks != 0 {
-                let attack_square = 1 << pop_lsb(&mut rook_attacks) as u64;
-                attack_mask |= attack_square;
-            }
-        }
-
-        // black queens
-
-        let mut bishop_bb_part = self.black_queens;
-        while bishop_bb_part != 0 {
-            let start_square = pop_lsb(&mut bishop_bb_part);
-            let mut bishop_part_attacks = table.get_bishop_attack(start_square, self.occupancy());
-            while bishop_part_attacks != 0 {
-                let attack_square = 1 << pop_lsb(&mut bishop_part_attacks) as u64;
-                attack_mask |= attack_square;
-            }
-        }
-
-        let mut rook_bb_part = self.black_queens;
-        while rook_bb_part != 0 {
-            let start_square = pop_lsb(&mut rook_bb_part);
-            let mut rook_part_attacks = table.get_rook_attack(start_square, self.occupancy());
-            while rook_part_attacks != 0 {
-                let attack_square = 1 << pop_lsb(&mut rook_part_attacks) as u64;
-                attack_mask |= attack_square;
-            }
-        }
-
-        // black king
-        let mut king_bb = self.black_king;
-        while king_bb != 0 {
-            let start_square = pop_lsb(&mut king_bb);
-            attack_mask |= table.king_attacks[start_square];
-        }
-
-        attack_mask
-    }
-
-    // Get the attack map of black with transparency
-    pub fn black_attack_mask_with_transparency(&self, table: &Tables, transparency: u64) -> u64 {
-        let mut attack_mask = 0;
-
-        // black pawns
-        let mut pawn_bb = self.black_pawns;
-        while pawn_bb != 0 {
-            let start_square = pop_lsb(&mut pawn_bb);
-            attack_mask |= table.black_pawn_attacks[start_square];
-        }
-
-        // black knights
-        let mut knight_bb = self.black_knights;
-        while knight_bb != 0 {
-            let start_square = pop_lsb(&mut knight_bb);
-            attack_mask |= table.knight_attacks[start_square];
-        }
-
-        // black bishops
-        let mut bishop_bb = self.black_bishops;
-        while bishop_bb != 0 {
-            let start_square = pop_lsb(&mut bishop_bb);
-            let mut bishop_attacks =
-                table.get_bishop_attack(start_square, self.occupancy() & !transparency);
-            while bishop_attacks != 0 {
-                let attack_square = 1 << pop_lsb(&mut bishop_attacks) as u64;
-                attack_mask |= attack_square;
-            }
-        }
-        // black rooks
-        let mut rook_bb = self.black_rooks;
-        while rook_bb != 0 {
-            let start_square = pop_lsb(&mut rook_bb);
-            let mut rook_attacks =
-                table.get_rook_attack(start_square, self.occupancy() & !transparency);
-            while rook_attacks != 0 {
-                let attack_square = 1 << pop_lsb(&mut rook_attacks) as u64;
-                attack_mask |= attack_square;
-            }
-        }
-
-        // black queens
-
-        let mut bishop_bb_part = self.black_queens;
-        while bishop_bb_part != 0 {
-            let start_square = pop_lsb(&mut bishop_bb_part);
-            let mut bishop_part_attacks =
-                table.get_bishop_attack(start_square, self.occupancy() & !transparency);
-            while bishop_part_attacks != 0 {
-                let attack_square = 1 << pop_lsb(&mut bishop_part_attacks) as u64;
-                attack_mask |= attack_square;
-            }
-        }
-
-        let mut rook_bb_part = self.black_queens;
-        while rook_bb_part != 0 {
-            let start_square = pop_lsb(&mut rook_bb_part);
-            let mut rook_part_attacks =
-                table.get_rook_attack(start_square, self.occupancy() & !transparency);
-            while rook_part_attacks != 0 {
-                let attack_square = 1 << pop_lsb(&mut rook_part_attacks) as u64;
-                attack_mask |= attack_square;
-            }
-        }
-
-        // black king
-        let mut king_bb = self.black_king;
-        while king_bb != 0 {
-            let start_square = pop_lsb(&mut king_bb);
-            attack_mask |= table.king_attacks[start_square];
-        }
-
-        attack_mask
-    }
-
-    // Gets the mask of the white pieces that attack the given piece mask
-    pub fn white_attacking(&self, tables: &Tables, target: u64) -> u64 {
-        // attacking mask
-        let mut attacking_mask = 0;
-        // turn the piece mask into an index
-        let piece_index = target.trailing_zeros() as usize;
-
-        // Check attacking pawns
-        // NOTE this case is diffrent from the rest since pawn moves are not reversible / symetric
-        attacking_mask |= tables.black_pawn_attacks[piece_index] & self.white_pawns;
-        // Check attacking knights
-        attacking_mask |= tables.knight_attacks[piece_index] & self.white_knights;
-        // Check attacking rooks
-        attacking_mask |= tables.get_rook_attack(piece_index, self.occupancy()) & self.white_rooks;
-        // Check attacking bishops
-        attacking_mask |=
-            tables.get_bishop_attack(piece_index, self.occupancy()) & self.white_bishops;
-        // Check attacking queens
-        attacking_mask |= tables.get_rook_attack(piece_index, self.occupancy()) & self.white_queens;
-        attacking_mask |=
-            tables.get_bishop_attack(piece_index, self.occupancy()) & self.white_queens;
-        // Check attacking kings
-        attacking_mask |= tables.king_attacks[piece_index] & self.white_king;
-
-        attacking_mask
-    }
-
-    // Get the mask of the white pices that 'block' the target. Similar to white_attacking, but with pawn pushes instead of attacks.
-    pub fn white_blocking(&self, tables: &Tables, target: u64) -> u64 {
-        // blocking mask
-        let mut blocking_mask = 0;
-        // turn the piece mask into an index
-        let piece_index = target.trailing_zeros() as usize;
-
-        // Check blocking pawns
-        // NOTE this case is diffrent from the rest since pawn moves are not reversible / symetric
-        // Single push
-        if target >> 8 & self.white_pawns != 0 {
-            blocking_mask |= target >> 8 & self.white_pawns;
-        }
-        // Double push
-        if target >> 16 & self.white_pawns != 0
-            && target >> 8 & self.occupancy() == 0
-            && target >> 16 & Tables::RANK_2 != 0
-        {
-            blocking_mask |= target >> 16 & self.white_pawns;
-        }
-        // Check blocking knights
-        blocking_mask |= tables.knight_attacks[piece_index] & self.white_knights;
-        // Check blocking rooks
-        blocking_mask |= tables.get_rook_attack(piece_index, self.occupancy()) & self.white_rooks;
-        // Check blocking bishops
-        blocking_mask |=
-            tables.get_bishop_attack(piece_index, self.occupancy()) & self.white_bishops;
-        // Check blocking queens
-        blocking_mask |= tables.get_rook_attack(piece_index, self.occupancy()) & self.white_queens;
-        blocking_mask |=
-            tables.get_bishop_attack(piece_index, self.occupancy()) & self.white_queens;
-        // NOTE No blocking kings because that should never happen
-
-        blocking_mask
-    }
-
-    // Gets the mask of the black pieces that attack the given piece mask
-    pub fn black_attacking(&self, tables: &Tables, target: u64) -> u64 {
-        // attacking mask
-        let mut attacking_mask = 0;
-        // turn the piece mask into an index
-        let piece_index = target.trailing_zeros() as usize;
-
-        // Check attacking pawns
-        // NOTE this case is diffrent from the rest since pawn moves are not reversible / symetric
-        attacking_mask |= tables.white_pawn_attacks[piece_index] & self.black_pawns;
-        // Check attacking knights
-        attacking_mask |= tables.knight_attacks[piece_index] & self.black_knights;
-        // Check attacking rooks
-        attacking_mask |= tables.get_rook_attack(piece_index, self.occupancy()) & self.black_rooks;
-        // Check attacking bishops
-        attacking_mask |=
-            tables.get_bishop_attack(piece_index, self.occupancy()) & self.black_bishops;
-        // Check attacking queens
-        attacking_mask |= tables.get_rook_attack(piece_index, self.occupancy()) & self.black_queens;
-        attacking_mask |=
-            tables.get_bishop_attack(piece_index, self.occupancy()) & self.black_queens;
-        // Check attacking kings
-        attacking_mask |= tables.king_attacks[piece_index] & self.black_king;
-
-        attacking_mask
-    }
-
-    // Get the mask of the black pices that 'block' the target. Similar to black_attacking, but with pawn pushes instead of attacks.
-    pub fn black_blocking(&self, tables: &Tables, target: u64) -> u64 {
-        // blocking mask
-        let mut blocking_mask = 0;
-        // turn the piece mask into an index
-        let piece_index = target.trailing_zeros() as usize;
-
-        // Check blocking pawns
-        // NOTE this case is diffrent from the rest since pawn moves are not reversible / symetric
-        // Single push
-        if target << 8 & self.black_pawns != 0 {
-            blocking_mask |= target << 8 & self.black_pawns;
-        }
-        // Double push
-        if target << 16 & self.black_pawns != 0
-            && target << 8 & self.occupancy() == 0
-            && target << 16 & Tables::RANK_7 != 0
-        {
-            blocking_mask |= target << 16 & self.black_pawns;
-        }
-        // Check blocking knights
-        blocking_mask |= tables.knight_attacks[piece_index] & self.black_knights;
-        // Check blocking rooks
-        blocking_mask |= tables.get_rook_attack(piece_index, self.occupancy()) & self.black_rooks;
-        // Check blocking bishops
-        blocking_mask |=
-            tables.get_bishop_attack(piece_index, self.occupancy()) & self.black_bishops;
-        // Check blocking queens
-        blocking_mask |= tables.get_rook_attack(piece_index, self.occupancy()) & self.black_queens;
-        blocking_mask |=
-            tables.get_bishop_attack(piece_index, self.occupancy()) & self.black_queens;
-        // NOTE No blocking kings because that should never happen
-
-        blocking_mask
-    }
-
-    /// Gets the mask of the pieces pinned to the target
-    pub fn pin_mask(&self, tables: &Tables, target: u64, white_to_move: bool) -> u64 {
-        let target_index = target.trailing_zeros() as usize;
-
-        match white_to_move {
-            true => {
-                let mut mask = 0;
-                // Project a rook ray without any blockers
-                let rook_ray =
-                    tables.get_rook_attack(target_index, self.black_rooks | self.black_queens);
-                // Get the pieces that could attack if there were no blockers
-                let mut connected_pieces = rook_ray & (self.black_rooks | self.black_queens);
-                while connected_pieces != 0 {
-                    let attacker_index = pop_lsb(&mut connected_pieces);
-                    // Look back to the target without any blockers
-                    let attacker_ray = tables.get_rook_attack(attacker_index, target);
-                    // Get the mask which must protect the target
-                    let blocker_mask = rook_ray & attacker_ray;
-                    // If there is only one piece in the way, add it to the mask if its the current sides color
-                    if (blocker_mask & self.occupancy()).count_ones() == 1 {
-                        mask |= blocker_mask & self.white_occupancy();
-                    }
-                }
-
-                // Now do the same thing with bishop rays
-                // Project a bishop ray without any blockers
-                let bishop_ray =
-                    tables.get_bishop_attack(target_index, self.black_bishops | self.black_queens);
-                // Get the pieces that could attack if there were no blockers
-                let mut connected_pieces = bishop_ray & (self.black_bishops | self.black_queens);
-                while connected_pieces != 0 {
-                    let attacker_index = pop_lsb(&mut connected_pieces);
-                    // Look back to the target without any blockers
-                    let attacker_ray = tables.get_bishop_attack(attacker_index, target);
-                    // Get the mask which must protect the target
-                    let blocker_mask = bishop_ray & attacker_ray;
-                    // If there is only one piece in the way, add it to the mask if its the current sides color
-                    if (blocker_mask & self.occupancy()).count_ones() == 1 {
-                        mask |= blocker_mask & self.white_occupancy();
-                    }
-                }
-                // Return the mask
-                mask
-            }
-            false => {
-                let mut mask = 0;
-                // Project a rook ray without any blockers
-                let rook_ray =
-                    tables.get_rook_attack(target_index, self.white_rooks | self.white_queens);
-                // Get the pieces that could attack if there were no blockers
-                let mut connected_pieces = rook_ray & (self.white_rooks | self.white_queens);
-                while connected_pieces != 0 {
-                    let attacker_index = pop_lsb(&mut connected_pieces);
-                    // Look back to the target without any blockers
-                    let attacker_ray = tables.get_rook_attack(attacker_index, target);
-                    // Get the mask which must protect the target
-                    let blocker_mask = rook_ray & attacker_ray;
-                    // If there is only one piece in the way, add it to the mask if its the current sides color
-                    if (blocker_mask & self.occupancy()).count_ones() == 1 {
-                        mask |= blocker_mask & self.black_occupancy();
-                    }
-                }
-
-                // Now do the same thing with bishop rays
-                // Project a bishop ray without any blockers
-                let bishop_ray =
-                    tables.get_bishop_attack(target_index, self.white_bishops | self.white_queens);
-                // Get the pieces that could attack if there were no blockers
-                let mut connected_pieces = bishop_ray & (self.white_bishops | self.white_queens);
-                while connected_pieces != 0 {
-                    let attacker_index = pop_lsb(&mut connected_pieces);
-                    // Look back to the target without any blockers
-                    let attacker_ray = tables.get_bishop_attack(attacker_index, target);
-                    // Get the mask which must protect the target
-                    let blocker_mask = bishop_ray & attacker_ray;
-                    // If there is only one piece in the way, add it to the mask if its the current sides color
-                    if (blocker_mask & self.occupancy()).count_ones() == 1 {
-                        mask |= blocker_mask & self.black_occupancy();
-                    }
-                }
-                // Return the mask
-                mask
+        // Now do the same thing with bishop rays
+        let bishop_ray = tables.get_bishop_attack(target_index, bishop_like);
+        for attacker_index in bishop_ray & bishop_like {
+            let attacker_ray = tables.get_bishop_attack(attacker_index, target.bit());
+            let blocker_mask = bishop_ray & attacker_ray;
+            if (blocker_mask & self.occupancy()).count() == 1 {
+                mask |= blocker_mask & own_occupancy;
             }
         }
+        mask
     }
 
     // Tests if the target is safe from a ray attack after the move rep
-    pub fn pin_safe(&self, tables: &Tables, target: u64, mv: &MoveRep) -> bool {
+    pub fn pin_safe(&self, tables: &Tables, target: Square, mv: &MoveRep) -> bool {
         // TODO Add special handling for en passant moves, because they can reveal an attack!
         // The occupancy after the move would be made
         // let after_occupancy;
         // Special en passant logic
-        let after_occupancy = if mv.ending_square == self.en_passant_target {
+        let (start, end) = (mv.starting_square.bit(), mv.ending_square.bit());
+        let after_occupancy = if Some(mv.ending_square) == self.en_passant_target {
             let en_passant_attacked = match self.white_to_move {
-                true => mv.ending_square >> 8,
-                false => mv.ending_square << 8,
+                true => end.south(),
+                false => end.north(),
             };
-            self.occupancy() & !mv.starting_square & !en_passant_attacked | mv.ending_square
+            self.occupancy() & !start & !en_passant_attacked | end
         } else {
             // Normal case
-            self.occupancy() & !mv.starting_square | mv.ending_square
+            self.occupancy() & !start | end
         };
-        let target_index = target.trailing_zeros() as usize;
+        let target_index = target;
         // A move which attacks the attacker is safe, unless the attackers space is also under attack
 
         // Get the relevent attackers, and remove them if they are attacked by the move
-        let rook_like_mask = match self.white_to_move {
-            true => (self.black_rooks | self.black_queens) & !mv.ending_square,
-            false => (self.white_rooks | self.white_queens) & !mv.ending_square,
-        };
-        let bishop_like_mask = match self.white_to_move {
-            true => (self.black_bishops | self.black_queens) & !mv.ending_square,
-            false => (self.white_bishops | self.white_queens) & !mv.ending_square,
-        };
+        let enemy = !Color::from_white(self.white_to_move);
+        let queens = self.pieces(enemy, PieceType::Queen);
+        let rook_like_mask = (self.pieces(enemy, PieceType::Rook) | queens) & !end;
+        let bishop_like_mask = (self.pieces(enemy, PieceType::Bishop) | queens) & !end;
 
         // Project rays from the target and check if the target could be attacked
         let rook_ray = tables.get_rook_attack(target_index, after_occupancy);
-        if rook_ray & rook_like_mask != 0 {
+        if !(rook_ray & rook_like_mask).is_empty() {
             return false;
         }
         let bishop_ray = tables.get_bishop_attack(target_index, after_occupancy);
-        if bishop_ray & bishop_like_mask != 0 {
+        if !(bishop_ray & bishop_like_mask).is_empty() {
             return false;
         }
 
@@ -1731,20 +1433,45 @@ impl BoardState {
     /// Get if the white king is in check
     pub fn white_in_check(&self, table: &Tables) -> bool {
         let black_attack_mask = self.black_attack_mask(table);
-        black_attack_mask & self.white_king != 0
+        !(black_attack_mask & self.pieces(Color::White, PieceType::King)).is_empty()
     }
 
     /// Get if the black king is in check
     pub fn black_in_check(&self, table: &Tables) -> bool {
         let white_attack_mask = self.white_attack_mask(table);
-        white_attack_mask & self.black_king != 0
+        !(white_attack_mask & self.pieces(Color::Black, PieceType::King)).is_empty()
+    }
+
+    /// Checks if neither side has enough material left to checkmate (KvK, KNvK, KBvK, or only bishops on one color)
+    pub fn insufficient_material(&self) -> bool {
+        if !(self.pieces(Color::White, PieceType::Pawn)
+            | self.pieces(Color::Black, PieceType::Pawn)
+            | self.pieces(Color::White, PieceType::Rook)
+            | self.pieces(Color::Black, PieceType::Rook)
+            | self.pieces(Color::White, PieceType::Queen)
+            | self.pieces(Color::Black, PieceType::Queen))
+        .is_empty()
+        {
+            return false;
+        }
+        let knights = self.pieces(Color::White, PieceType::Knight)
+            | self.pieces(Color::Black, PieceType::Knight);
+        let bishops = self.pieces(Color::White, PieceType::Bishop)
+            | self.pieces(Color::Black, PieceType::Bishop);
+        if (knights | bishops).count() <= 1 {
+            return true;
+        }
+        knights.is_empty()
+            && ((bishops & Tables::LIGHT_SQUARES).is_empty()
+                || (bishops & !Tables::LIGHT_SQUARES).is_empty())
     }
 
     /// Get if white is in stalemate
     #[cfg(test)]
     pub fn white_in_stalemate(&self, table: &Tables) -> bool {
         let black_attack_mask = self.black_attack_mask(table);
-        let king_attack = table.king_attacks[self.white_king.trailing_zeros() as usize];
+        let king_attack =
+            table.king_attacks[Square::from_bit(self.pieces(Color::White, PieceType::King))];
         king_attack & black_attack_mask == king_attack && !self.white_in_check(table)
     }
 
@@ -1752,7 +1479,8 @@ impl BoardState {
     #[cfg(test)]
     pub fn black_in_stalemate(&self, table: &Tables) -> bool {
         let white_attack_mask = self.white_attack_mask(table);
-        let king_attack = table.king_attacks[self.black_king.trailing_zeros() as usize];
+        let king_attack =
+            table.king_attacks[Square::from_bit(self.pieces(Color::Black, PieceType::King))];
         king_attack & white_attack_mask == king_attack && !self.black_in_check(table)
     }
 
@@ -1776,11 +1504,7 @@ impl BoardState {
 
 impl MoveRep {
     pub fn to_string(&self) -> String {
-        let start = self.starting_square;
-        let end = self.ending_square;
-        let mut mov = String::new();
-        mov.push_str(MoveRep::mask_to_string(start).unwrap().as_ref());
-        mov.push_str(MoveRep::mask_to_string(end).unwrap().as_ref());
+        let mut mov = format!("{}{}", self.starting_square, self.ending_square);
         if self.promotion.is_some() && self.promotion != Some(Promotion::Castle) {
             match self.promotion {
                 Some(Promotion::Queen) => mov.push('q'),
@@ -1793,6 +1517,86 @@ impl MoveRep {
         mov
     }
 
+    /// The move in uci notation. Given the castling rooks of a Chess960 game, castles are written as
+    /// the king taking its own rook
+    pub fn to_uci(self, chess960_rooks: Option<&[Square; 4]>) -> String {
+        let castle = chess960_rooks
+            .filter(|_| self.promotion == Some(Promotion::Castle))
+            .zip(BoardState::castle_index(&self));
+        match castle {
+            Some((rooks, index)) => format!("{}{}", self.starting_square, rooks[index]),
+            None => self.to_string(),
+        }
+    }
+
+    /// The move in standard algebraic notation, given the position it is played from
+    pub fn to_san(self, board: &BoardState, tables: &Tables) -> String {
+        let start = self.starting_square.to_string();
+        let mut san = String::new();
+        match (self.promotion, BoardState::castle_index(&self)) {
+            (Some(Promotion::Castle), Some(index)) if index % 2 == 0 => san.push_str("O-O"),
+            (Some(Promotion::Castle), _) => san.push_str("O-O-O"),
+            _ => {
+                if self.moved_type == PieceType::Pawn {
+                    if self.attacked_type.is_some() {
+                        san.extend(start.chars().next());
+                    }
+                } else {
+                    san.push(self.moved_type.letter());
+                    // Name the starting file, rank or both if another piece of the same type can
+                    // reach the same square
+                    let rivals: Vec<String> = generate(board, tables)
+                        .iter()
+                        .filter(|mv| {
+                            mv.moved_type == self.moved_type
+                                && mv.ending_square == self.ending_square
+                                && mv.starting_square != self.starting_square
+                                && mv.promotion != Some(Promotion::Castle)
+                        })
+                        .map(|mv| mv.starting_square.to_string())
+                        .collect();
+                    let shares = |index: usize| {
+                        rivals
+                            .iter()
+                            .any(|rival| rival.as_bytes()[index] == start.as_bytes()[index])
+                    };
+                    if !rivals.is_empty() {
+                        if !shares(0) {
+                            san.push_str(&start[..1]);
+                        } else if !shares(1) {
+                            san.push_str(&start[1..]);
+                        } else {
+                            san.push_str(&start);
+                        }
+                    }
+                }
+                if self.attacked_type.is_some() {
+                    san.push('x');
+                }
+                san.push_str(&self.ending_square.to_string());
+                if let Some(letter) = self.promotion.and_then(Promotion::letter) {
+                    san.push('=');
+                    san.push(letter);
+                }
+            }
+        }
+
+        // The keys only feed the hash of the scratch board, so any set will do
+        let mut after = board.clone();
+        after.make(&self, &ZobKeys::new());
+        let in_check = match after.white_to_move {
+            true => after.white_in_check(tables),
+            false => after.black_in_check(tables),
+        };
+        if in_check {
+            match generate(&after, tables).is_empty() {
+                true => san.push('#'),
+                false => san.push('+'),
+            }
+        }
+        san
+    }
+
     /// Returns if the move is reversible
     #[cfg(test)]
     pub fn is_reversible(&self) -> bool {
@@ -1813,10 +1617,10 @@ impl MoveRep {
 
         // If the move is a castle, is is not reversible
         if self.moved_type == PieceType::King
-            && (self.starting_square == 1 << Tables::E1 && self.ending_square == 1 << Tables::A1)
-            || (self.starting_square == 1 << Tables::E1 && self.ending_square == 1 << Tables::H1)
-            || (self.starting_square == 1 << Tables::E8 && self.ending_square == 1 << Tables::A8)
-            || (self.starting_square == 1 << Tables::E8 && self.ending_square == 1 << Tables::H8)
+            && (self.starting_square == Square::E1 && self.ending_square == Square::A1)
+            || (self.starting_square == Square::E1 && self.ending_square == Square::H1)
+            || (self.starting_square == Square::E8 && self.ending_square == Square::A8)
+            || (self.starting_square == Square::E8 && self.ending_square == Square::H8)
         {
             return false;
         }
@@ -1824,165 +1628,21 @@ impl MoveRep {
         // If we fell through the above conditions, the move is reversible
         true
     }
-
-    pub fn mask_to_string(mask: u64) -> Result<String, String> {
-        let mut pos = String::new();
-
-        let file = mask.ilog2() / 8;
-        let rank = mask.ilog2() % 8;
-        match rank {
-            0 => pos.push('h'),
-            1 => pos.push('g'),
-            2 => pos.push('f'),
-            3 => pos.push('e'),
-            4 => pos.push('d'),
-            5 => pos.push('c'),
-            6 => pos.push('b'),
-            7 => pos.push('a'),
-            _ => return Err("Invalid mask found".to_string()),
-        }
-        match file {
-            0 => pos.push('1'),
-            1 => pos.push('2'),
-            2 => pos.push('3'),
-            3 => pos.push('4'),
-            4 => pos.push('5'),
-            5 => pos.push('6'),
-            6 => pos.push('7'),
-            7 => pos.push('8'),
-            _ => return Err("Invalid mask found".to_string()),
-        }
-
-        Ok(pos)
-    }
 }
 
-fn position_to_mask(file: char, rank: char) -> Result<u64, String> {
-    let file_shift = match file {
-        'h' => 0,
-        'g' => 1,
-        'f' => 2,
-        'e' => 3,
-        'd' => 4,
-        'c' => 5,
-        'b' => 6,
-        'a' => 7,
-        _ => return Err(format!("Unrecognized value \"{file}\" found in file")),
-    };
-    let rank_shift: i32;
-    if let Some(rank_value) = rank.to_digit(10) {
-        rank_shift = rank_value as i32;
-    } else {
+fn position_to_square(file: char, rank: char) -> Result<Square, String> {
+    if !('a'..='h').contains(&file) {
+        return Err(format!("Unrecognized value \"{file}\" found in file"));
+    }
+    if !('1'..='8').contains(&rank) {
         return Err(format!("Unrecognized value \"{rank}\" found in rank"));
     }
-    Ok((1 << file_shift) << ((rank_shift - 1) * 8))
+    Ok(Square::from_file_rank(file as u8 - b'a', rank as u8 - b'1'))
 }
 
 #[allow(dead_code)]
-pub fn print_bitboard(bb: u64) {
-    fn get_bit(bb: u64, index: u64) -> char {
-        match bb & 1 << index {
-            0 => '0',
-            _ => '1',
-        }
-    }
-    println!(
-        "8   {} {} {} {} {} {} {} {}",
-        get_bit(bb, 63),
-        get_bit(bb, 62),
-        get_bit(bb, 61),
-        get_bit(bb, 60),
-        get_bit(bb, 59),
-        get_bit(bb, 58),
-        get_bit(bb, 57),
-        get_bit(bb, 56)
-    );
-
-    println!(
-        "7   {} {} {} {} {} {} {} {}",
-        get_bit(bb, 55),
-        get_bit(bb, 54),
-        get_bit(bb, 53),
-        get_bit(bb, 52),
-        get_bit(bb, 51),
-        get_bit(bb, 50),
-        get_bit(bb, 49),
-        get_bit(bb, 48)
-    );
-
-    println!(
-        "6   {} {} {} {} {} {} {} {}",
-        get_bit(bb, 47),
-        get_bit(bb, 46),
-        get_bit(bb, 45),
-        get_bit(bb, 44),
-        get_bit(bb, 43),
-        get_bit(bb, 42),
-        get_bit(bb, 41),
-        get_bit(bb, 40)
-    );
-
-    println!(
-        "5   {} {} {} {} {} {} {} {}",
-        get_bit(bb, 39),
-        get_bit(bb, 38),
-        get_bit(bb, 37),
-        get_bit(bb, 36),
-        get_bit(bb, 35),
-        get_bit(bb, 34),
-        get_bit(bb, 33),
-        get_bit(bb, 32)
-    );
-
-    println!(
-        "4   {} {} {} {} {} {} {} {}",
-        get_bit(bb, 31),
-        get_bit(bb, 30),
-        get_bit(bb, 29),
-        get_bit(bb, 28),
-        get_bit(bb, 27),
-        get_bit(bb, 26),
-        get_bit(bb, 25),
-        get_bit(bb, 24)
-    );
-
-    println!(
-        "3   {} {} {} {} {} {} {} {}",
-        get_bit(bb, 23),
-        get_bit(bb, 22),
-        get_bit(bb, 21),
-        get_bit(bb, 20),
-        get_bit(bb, 19),
-        get_bit(bb, 18),
-        get_bit(bb, 17),
-        get_bit(bb, 16)
-    );
-
-    println!(
-        "2   {} {} {} {} {} {} {} {}",
-        get_bit(bb, 15),
-        get_bit(bb, 14),
-        get_bit(bb, 13),
-        get_bit(bb, 12),
-        get_bit(bb, 11),
-        get_bit(bb, 10),
-        get_bit(bb, 9),
-        get_bit(bb, 8)
-    );
-
-    println!(
-        "1   {} {} {} {} {} {} {} {}",
-        get_bit(bb, 7),
-        get_bit(bb, 6),
-        get_bit(bb, 5),
-        get_bit(bb, 4),
-        get_bit(bb, 3),
-        get_bit(bb, 2),
-        get_bit(bb, 1),
-        get_bit(bb, 0)
-    );
-
-    println!("\n    a b c d e f g h");
+pub fn print_bitboard(bb: Bitboard) {
+    println!("{bb}");
     let _ = io::stdout().flush();
 }
 
@@ -2000,8 +1660,8 @@ mod tests {
         );
 
         let move_test = MoveRep {
-            starting_square: 1 << Tables::A2,
-            ending_square: 1 << Tables::A4,
+            starting_square: Square::A2,
+            ending_square: Square::A4,
             promotion: None,
             moved_type: PieceType::Pawn,
             attacked_type: None,
@@ -2010,8 +1670,12 @@ mod tests {
         pawn_test.make(&move_test, &zob_keys);
 
         assert!(!pawn_test.white_to_move);
-        assert!(pawn_test.white_pawns & 1 << Tables::A4 != 0);
-        assert!(pawn_test.white_pawns & 1 << Tables::A2 == 0);
+        assert!(pawn_test
+            .pieces(Color::White, PieceType::Pawn)
+            .contains(Square::A4));
+        assert!(!pawn_test
+            .pieces(Color::White, PieceType::Pawn)
+            .contains(Square::A2));
     }
 
     #[test]
@@ -2021,8 +1685,8 @@ mod tests {
         );
 
         let move_test = MoveRep {
-            starting_square: 1 << Tables::D7,
-            ending_square: 1 << Tables::D5,
+            starting_square: Square::D7,
+            ending_square: Square::D5,
             promotion: None,
             moved_type: PieceType::Pawn,
             attacked_type: None,
@@ -2032,8 +1696,12 @@ mod tests {
         black_pawn_test.make(&move_test, &zob_keys);
 
         assert!(black_pawn_test.white_to_move);
-        assert!(black_pawn_test.black_pawns & 1 << Tables::D7 == 0);
-        assert!(black_pawn_test.black_pawns & 1 << Tables::D5 != 0);
+        assert!(!black_pawn_test
+            .pieces(Color::Black, PieceType::Pawn)
+            .contains(Square::D7));
+        assert!(black_pawn_test
+            .pieces(Color::Black, PieceType::Pawn)
+            .contains(Square::D5));
     }
 
     #[test]
@@ -2043,8 +1711,8 @@ mod tests {
         );
 
         let move_test = MoveRep {
-            starting_square: 1 << Tables::A7,
-            ending_square: 1 << Tables::B6,
+            starting_square: Square::A7,
+            ending_square: Square::B6,
             promotion: None,
             moved_type: PieceType::Pawn,
             attacked_type: Some(PieceType::Pawn),
@@ -2054,9 +1722,15 @@ mod tests {
         black_pawn_attack_test.make(&move_test, &zob_keys);
 
         assert!(black_pawn_attack_test.white_to_move);
-        assert!(black_pawn_attack_test.black_pawns & 1 << Tables::A7 == 0);
-        assert!(black_pawn_attack_test.black_pawns & 1 << Tables::B6 != 0);
-        assert!(black_pawn_attack_test.white_pawns & 1 << Tables::B6 == 0);
+        assert!(!black_pawn_attack_test
+            .pieces(Color::Black, PieceType::Pawn)
+            .contains(Square::A7));
+        assert!(black_pawn_attack_test
+            .pieces(Color::Black, PieceType::Pawn)
+            .contains(Square::B6));
+        assert!(!black_pawn_attack_test
+            .pieces(Color::White, PieceType::Pawn)
+            .contains(Square::B6));
     }
 
     #[test]
@@ -2066,8 +1740,8 @@ mod tests {
         );
 
         let move_test = MoveRep {
-            starting_square: 1 << Tables::B2,
-            ending_square: 1 << Tables::C3,
+            starting_square: Square::B2,
+            ending_square: Square::C3,
             promotion: None,
             moved_type: PieceType::Pawn,
             attacked_type: Some(PieceType::Pawn),
@@ -2077,20 +1751,26 @@ mod tests {
         pawn_attack_test.make(&move_test, &zob_keys);
 
         assert!(!pawn_attack_test.white_to_move);
-        assert!(pawn_attack_test.white_pawns & 1 << Tables::B2 == 0);
-        assert!(pawn_attack_test.white_pawns & 1 << Tables::C3 != 0);
-        assert!(pawn_attack_test.black_pawns & 1 << Tables::C3 == 0);
+        assert!(!pawn_attack_test
+            .pieces(Color::White, PieceType::Pawn)
+            .contains(Square::B2));
+        assert!(pawn_attack_test
+            .pieces(Color::White, PieceType::Pawn)
+            .contains(Square::C3));
+        assert!(!pawn_attack_test
+            .pieces(Color::Black, PieceType::Pawn)
+            .contains(Square::C3));
     }
 
     #[test]
     fn test_white_knight() {
         let mut knight_test = BoardState::state_from_string_fen(
-            "rnbqkbnr/pppppppp/8/8/8/N7/PPPPPPPP/R1BQKBNR w KQkq - 0 1".to_string(),
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1".to_string(),
         );
 
         let move_test = MoveRep {
-            starting_square: 1 << Tables::B2,
-            ending_square: 1 << Tables::A3,
+            starting_square: Square::B1,
+            ending_square: Square::A3,
             promotion: None,
             moved_type: PieceType::Knight,
             attacked_type: None,
@@ -2100,8 +1780,12 @@ mod tests {
         knight_test.make(&move_test, &zob_keys);
 
         assert!(!knight_test.white_to_move);
-        assert!(knight_test.white_knights & 1 << Tables::A3 != 0);
-        assert!(knight_test.white_knights & 1 << Tables::B2 == 0);
+        assert!(knight_test
+            .pieces(Color::White, PieceType::Knight)
+            .contains(Square::A3));
+        assert!(!knight_test
+            .pieces(Color::White, PieceType::Knight)
+            .contains(Square::B1));
     }
 
     #[test]
@@ -2111,8 +1795,8 @@ mod tests {
         );
 
         let move_test = MoveRep {
-            starting_square: 1 << Tables::B8,
-            ending_square: 1 << Tables::A6,
+            starting_square: Square::B8,
+            ending_square: Square::A6,
             promotion: None,
             moved_type: PieceType::Knight,
             attacked_type: None,
@@ -2122,8 +1806,12 @@ mod tests {
         black_knight_test.make(&move_test, &zob_keys);
 
         assert!(black_knight_test.white_to_move);
-        assert!(black_knight_test.black_knights & 1 << Tables::B8 == 0);
-        assert!(black_knight_test.black_knights & 1 << Tables::A6 != 0);
+        assert!(!black_knight_test
+            .pieces(Color::Black, PieceType::Knight)
+            .contains(Square::B8));
+        assert!(black_knight_test
+            .pieces(Color::Black, PieceType::Knight)
+            .contains(Square::A6));
     }
 
     #[test]
@@ -2133,8 +1821,8 @@ mod tests {
         );
 
         let move_test = MoveRep {
-            starting_square: 1 << Tables::B1,
-            ending_square: 1 << Tables::C3,
+            starting_square: Square::B1,
+            ending_square: Square::C3,
             promotion: None,
             moved_type: PieceType::Knight,
             attacked_type: Some(PieceType::Pawn),
@@ -2144,9 +1832,15 @@ mod tests {
         white_knight_attack.make(&move_test, &zob_keys);
 
         assert!(!white_knight_attack.white_to_move);
-        assert!(white_knight_attack.white_knights & 1 << Tables::B1 == 0);
-        assert!(white_knight_attack.white_knights & 1 << Tables::C3 != 0);
-        assert!(white_knight_attack.black_pawns & 1 << Tables::C3 == 0);
+        assert!(!white_knight_attack
+            .pieces(Color::White, PieceType::Knight)
+            .contains(Square::B1));
+        assert!(white_knight_attack
+            .pieces(Color::White, PieceType::Knight)
+            .contains(Square::C3));
+        assert!(!white_knight_attack
+            .pieces(Color::Black, PieceType::Pawn)
+            .contains(Square::C3));
     }
 
     #[test]
@@ -2156,8 +1850,8 @@ mod tests {
         );
 
         let move_test = MoveRep {
-            starting_square: 1 << Tables::B8,
-            ending_square: 1 << Tables::C6,
+            starting_square: Square::B8,
+            ending_square: Square::C6,
             promotion: None,
             moved_type: PieceType::Knight,
             attacked_type: Some(PieceType::Pawn),
@@ -2167,9 +1861,15 @@ mod tests {
         black_knight_attack_test.make(&move_test, &zob_keys);
 
         assert!(black_knight_attack_test.white_to_move);
-        assert!(black_knight_attack_test.black_knights & 1 << Tables::B8 == 0);
-        assert!(black_knight_attack_test.black_knights & 1 << Tables::C6 != 0);
-        assert!(black_knight_attack_test.white_pawns & 1 << Tables::C6 == 0);
+        assert!(!black_knight_attack_test
+            .pieces(Color::Black, PieceType::Knight)
+            .contains(Square::B8));
+        assert!(black_knight_attack_test
+            .pieces(Color::Black, PieceType::Knight)
+            .contains(Square::C6));
+        assert!(!black_knight_attack_test
+            .pieces(Color::White, PieceType::Pawn)
+            .contains(Square::C6));
     }
 
     #[test]
@@ -2179,8 +1879,8 @@ mod tests {
         );
 
         let move_test = MoveRep {
-            starting_square: 1 << Tables::A1,
-            ending_square: 1 << Tables::A5,
+            starting_square: Square::A1,
+            ending_square: Square::A5,
             promotion: None,
             moved_type: PieceType::Rook,
             attacked_type: None,
@@ -2190,8 +1890,12 @@ mod tests {
         board.make(&move_test, &zob_keys);
 
         assert!(!board.white_to_move);
-        assert!(board.white_rooks & 1 << Tables::A1 == 0);
-        assert!(board.white_rooks & 1 << Tables::A5 != 0);
+        assert!(!board
+            .pieces(Color::White, PieceType::Rook)
+            .contains(Square::A1));
+        assert!(board
+            .pieces(Color::White, PieceType::Rook)
+            .contains(Square::A5));
     }
 
     #[test]
@@ -2201,8 +1905,8 @@ mod tests {
         );
 
         let move_test = MoveRep {
-            starting_square: 1 << Tables::A8,
-            ending_square: 1 << Tables::A3,
+            starting_square: Square::A8,
+            ending_square: Square::A3,
             promotion: None,
             moved_type: PieceType::Rook,
             attacked_type: None,
@@ -2211,8 +1915,12 @@ mod tests {
         board.make(&move_test, &zob_keys);
 
         assert!(board.white_to_move);
-        assert!(board.black_rooks & 1 << Tables::A8 == 0);
-        assert!(board.black_rooks & 1 << Tables::A3 != 0);
+        assert!(!board
+            .pieces(Color::Black, PieceType::Rook)
+            .contains(Square::A8));
+        assert!(board
+            .pieces(Color::Black, PieceType::Rook)
+            .contains(Square::A3));
     }
 
     #[test]
@@ -2222,8 +1930,8 @@ mod tests {
         );
 
         let move_test = MoveRep {
-            starting_square: 1 << Tables::A1,
-            ending_square: 1 << Tables::A4,
+            starting_square: Square::A1,
+            ending_square: Square::A4,
             promotion: None,
             moved_type: PieceType::Rook,
             attacked_type: Some(PieceType::Pawn),
@@ -2232,9 +1940,15 @@ mod tests {
         board.make(&move_test, &zob_keys);
 
         assert!(!board.white_to_move);
-        assert!(board.white_rooks & 1 << Tables::A1 == 0);
-        assert!(board.white_rooks & 1 << Tables::A4 != 0);
-        assert!(board.black_pawns & 1 << Tables::A4 == 0);
+        assert!(!board
+            .pieces(Color::White, PieceType::Rook)
+            .contains(Square::A1));
+        assert!(board
+            .pieces(Color::White, PieceType::Rook)
+            .contains(Square::A4));
+        assert!(!board
+            .pieces(Color::Black, PieceType::Pawn)
+            .contains(Square::A4));
     }
 
     #[test]
@@ -2244,8 +1958,8 @@ mod tests {
         );
 
         let move_test = MoveRep {
-            starting_square: 1 << Tables::A8,
-            ending_square: 1 << Tables::A4,
+            starting_square: Square::A8,
+            ending_square: Square::A4,
             promotion: None,
             moved_type: PieceType::Rook,
             attacked_type: Some(PieceType::Pawn),
@@ -2255,9 +1969,15 @@ mod tests {
         board.make(&move_test, &zob_keys);
 
         assert!(board.white_to_move);
-        assert!(board.black_rooks & 1 << Tables::A8 == 0);
-        assert!(board.black_rooks & 1 << Tables::A4 != 0);
-        assert!(board.white_pawns & 1 << Tables::A4 == 0);
+        assert!(!board
+            .pieces(Color::Black, PieceType::Rook)
+            .contains(Square::A8));
+        assert!(board
+            .pieces(Color::Black, PieceType::Rook)
+            .contains(Square::A4));
+        assert!(!board
+            .pieces(Color::White, PieceType::Pawn)
+            .contains(Square::A4));
     }
 
     #[test]
@@ -2267,8 +1987,8 @@ mod tests {
         );
 
         let move_test = MoveRep {
-            starting_square: 1 << Tables::D2,
-            ending_square: 1 << Tables::D4,
+            starting_square: Square::D2,
+            ending_square: Square::D4,
             promotion: None,
             moved_type: PieceType::Pawn,
             attacked_type: None,
@@ -2278,8 +1998,12 @@ mod tests {
         board.unmake(&move_test, &zob_keys);
 
         assert!(board.white_to_move);
-        assert!(board.white_pawns & 1 << Tables::D2 != 0);
-        assert!(board.white_pawns & 1 << Tables::D4 == 0);
+        assert!(board
+            .pieces(Color::White, PieceType::Pawn)
+            .contains(Square::D2));
+        assert!(!board
+            .pieces(Color::White, PieceType::Pawn)
+            .contains(Square::D4));
     }
 
     #[test]
@@ -2289,8 +2013,8 @@ mod tests {
         );
 
         let move_test = MoveRep {
-            starting_square: 1 << Tables::D2,
-            ending_square: 1 << Tables::C3,
+            starting_square: Square::D2,
+            ending_square: Square::C3,
             promotion: None,
             moved_type: PieceType::Pawn,
             attacked_type: Some(PieceType::Pawn),
@@ -2300,10 +2024,16 @@ mod tests {
         board.unmake(&move_test, &zob_keys);
 
         assert!(board.white_to_move);
-        assert!(board.white_pawns & 1 << Tables::D2 != 0);
-        assert!(board.white_pawns & 1 << Tables::C3 == 0);
-        print_bitboard(board.black_pawns);
-        assert!(board.black_pawns & 1 << Tables::C3 != 0);
+        assert!(board
+            .pieces(Color::White, PieceType::Pawn)
+            .contains(Square::D2));
+        assert!(!board
+            .pieces(Color::White, PieceType::Pawn)
+            .contains(Square::C3));
+        print_bitboard(board.pieces(Color::Black, PieceType::Pawn));
+        assert!(board
+            .pieces(Color::Black, PieceType::Pawn)
+            .contains(Square::C3));
     }
 
     #[test]
@@ -2313,8 +2043,8 @@ mod tests {
         );
 
         let move_test = MoveRep {
-            starting_square: 1 << Tables::H7,
-            ending_square: 1 << Tables::H5,
+            starting_square: Square::H7,
+            ending_square: Square::H5,
             promotion: None,
             moved_type: PieceType::Pawn,
             attacked_type: None,
@@ -2324,8 +2054,12 @@ mod tests {
         board.unmake(&move_test, &zob_keys);
 
         assert!(!board.white_to_move);
-        assert!(board.black_pawns & 1 << Tables::H7 != 0);
-        assert!(board.black_pawns & 1 << Tables::H5 == 0);
+        assert!(board
+            .pieces(Color::Black, PieceType::Pawn)
+            .contains(Square::H7));
+        assert!(!board
+            .pieces(Color::Black, PieceType::Pawn)
+            .contains(Square::H5));
     }
 
     #[test]
@@ -2335,8 +2069,8 @@ mod tests {
         );
 
         let move_test = MoveRep {
-            starting_square: 1 << Tables::B7,
-            ending_square: 1 << Tables::A6,
+            starting_square: Square::B7,
+            ending_square: Square::A6,
             promotion: None,
             moved_type: PieceType::Pawn,
             attacked_type: Some(PieceType::Pawn),
@@ -2346,9 +2080,15 @@ mod tests {
         board.unmake(&move_test, &zob_keys);
 
         assert!(!board.white_to_move);
-        assert!(board.black_pawns & 1 << Tables::B7 != 0);
-        assert!(board.black_pawns & 1 << Tables::A6 == 0);
-        assert!(board.white_pawns & 1 << Tables::A6 != 0);
+        assert!(board
+            .pieces(Color::Black, PieceType::Pawn)
+            .contains(Square::B7));
+        assert!(!board
+            .pieces(Color::Black, PieceType::Pawn)
+            .contains(Square::A6));
+        assert!(board
+            .pieces(Color::White, PieceType::Pawn)
+            .contains(Square::A6));
     }
 
     #[test]
@@ -2358,8 +2098,8 @@ mod tests {
         );
 
         let test_move = MoveRep {
-            starting_square: 1 << Tables::G1,
-            ending_square: 1 << Tables::H3,
+            starting_square: Square::G1,
+            ending_square: Square::H3,
             promotion: None,
             moved_type: PieceType::Knight,
             attacked_type: None,
@@ -2369,8 +2109,12 @@ mod tests {
         board.unmake(&test_move, &zob_keys);
 
         assert!(board.white_to_move);
-        assert!(board.white_knights & 1 << Tables::G1 != 0);
-        assert!(board.white_knights & 1 << Tables::H3 == 0);
+        assert!(board
+            .pieces(Color::White, PieceType::Knight)
+            .contains(Square::G1));
+        assert!(!board
+            .pieces(Color::White, PieceType::Knight)
+            .contains(Square::H3));
     }
 
     #[test]
@@ -2380,8 +2124,8 @@ mod tests {
         );
 
         let test_move = MoveRep {
-            starting_square: 1 << Tables::G1,
-            ending_square: 1 << Tables::H3,
+            starting_square: Square::G1,
+            ending_square: Square::H3,
             promotion: None,
             moved_type: PieceType::Knight,
             attacked_type: Some(PieceType::Pawn),
@@ -2391,9 +2135,15 @@ mod tests {
         board.unmake(&test_move, &zob_keys);
 
         assert!(board.white_to_move);
-        assert!(board.white_knights & 1 << Tables::G1 != 0);
-        assert!(board.white_knights & 1 << Tables::H3 == 0);
-        assert!(board.black_pawns & 1 << Tables::H3 != 0);
+        assert!(board
+            .pieces(Color::White, PieceType::Knight)
+            .contains(Square::G1));
+        assert!(!board
+            .pieces(Color::White, PieceType::Knight)
+            .contains(Square::H3));
+        assert!(board
+            .pieces(Color::Black, PieceType::Pawn)
+            .contains(Square::H3));
     }
 
     #[test]
@@ -2403,8 +2153,8 @@ mod tests {
         );
 
         let test_move = MoveRep {
-            starting_square: 1 << Tables::G8,
-            ending_square: 1 << Tables::F6,
+            starting_square: Square::G8,
+            ending_square: Square::F6,
             promotion: None,
             moved_type: PieceType::Knight,
             attacked_type: None,
@@ -2414,8 +2164,12 @@ mod tests {
         board.unmake(&test_move, &zob_keys);
 
         assert!(!board.white_to_move);
-        assert!(board.black_knights & 1 << Tables::G8 != 0);
-        assert!(board.black_knights & 1 << Tables::F6 == 0);
+        assert!(board
+            .pieces(Color::Black, PieceType::Knight)
+            .contains(Square::G8));
+        assert!(!board
+            .pieces(Color::Black, PieceType::Knight)
+            .contains(Square::F6));
     }
 
     #[test]
@@ -2425,8 +2179,8 @@ mod tests {
         );
 
         let test_move = MoveRep {
-            starting_square: 1 << Tables::G8,
-            ending_square: 1 << Tables::H6,
+            starting_square: Square::G8,
+            ending_square: Square::H6,
             promotion: None,
             moved_type: PieceType::Knight,
             attacked_type: Some(PieceType::Pawn),
@@ -2436,9 +2190,15 @@ mod tests {
         board.unmake(&test_move, &zob_keys);
 
         assert!(!board.white_to_move);
-        assert!(board.black_knights & 1 << Tables::G8 != 0);
-        assert!(board.black_knights & 1 << Tables::H6 == 0);
-        assert!(board.white_pawns & 1 << Tables::H6 != 0);
+        assert!(board
+            .pieces(Color::Black, PieceType::Knight)
+            .contains(Square::G8));
+        assert!(!board
+            .pieces(Color::Black, PieceType::Knight)
+            .contains(Square::H6));
+        assert!(board
+            .pieces(Color::White, PieceType::Pawn)
+            .contains(Square::H6));
     }
 
     #[test]
@@ -2448,8 +2208,8 @@ mod tests {
         );
 
         let test_move = MoveRep {
-            starting_square: 1 << Tables::A1,
-            ending_square: 1 << Tables::A5,
+            starting_square: Square::A1,
+            ending_square: Square::A5,
             promotion: None,
             moved_type: PieceType::Rook,
             attacked_type: None,
@@ -2459,8 +2219,12 @@ mod tests {
         board.unmake(&test_move, &zob_keys);
 
         assert!(board.white_to_move);
-        assert!(board.white_rooks & 1 << Tables::A1 != 0);
-        assert!(board.white_rooks & 1 << Tables::A5 == 0);
+        assert!(board
+            .pieces(Color::White, PieceType::Rook)
+            .contains(Square::A1));
+        assert!(!board
+            .pieces(Color::White, PieceType::Rook)
+            .contains(Square::A5));
     }
 
     #[test]
@@ -2470,8 +2234,8 @@ mod tests {
         );
 
         let test_move = MoveRep {
-            starting_square: 1 << Tables::A1,
-            ending_square: 1 << Tables::A5,
+            starting_square: Square::A1,
+            ending_square: Square::A5,
             promotion: None,
             moved_type: PieceType::Rook,
             attacked_type: Some(PieceType::Pawn),
@@ -2481,9 +2245,15 @@ mod tests {
         board.unmake(&test_move, &zob_keys);
 
         assert!(board.white_to_move);
-        assert!(board.white_rooks & 1 << Tables::A1 != 0);
-        assert!(board.white_rooks & 1 << Tables::A5 == 0);
-        assert!(board.black_pawns & 1 << Tables::A5 != 0);
+        assert!(board
+            .pieces(Color::White, PieceType::Rook)
+            .contains(Square::A1));
+        assert!(!board
+            .pieces(Color::White, PieceType::Rook)
+            .contains(Square::A5));
+        assert!(board
+            .pieces(Color::Black, PieceType::Pawn)
+            .contains(Square::A5));
     }
 
     #[test]
@@ -2493,8 +2263,8 @@ mod tests {
         );
 
         let test_move = MoveRep {
-            starting_square: 1 << Tables::A8,
-            ending_square: 1 << Tables::A3,
+            starting_square: Square::A8,
+            ending_square: Square::A3,
             promotion: None,
             moved_type: PieceType::Rook,
             attacked_type: None,
@@ -2504,8 +2274,12 @@ mod tests {
         board.unmake(&test_move, &zob_keys);
 
         assert!(!board.white_to_move);
-        assert!(board.black_rooks & 1 << Tables::A8 != 0);
-        assert!(board.black_rooks & 1 << Tables::A3 == 0);
+        assert!(board
+            .pieces(Color::Black, PieceType::Rook)
+            .contains(Square::A8));
+        assert!(!board
+            .pieces(Color::Black, PieceType::Rook)
+            .contains(Square::A3));
     }
 
     #[test]
@@ -2515,8 +2289,8 @@ mod tests {
         );
 
         let test_move = MoveRep {
-            starting_square: 1 << Tables::A8,
-            ending_square: 1 << Tables::A2,
+            starting_square: Square::A8,
+            ending_square: Square::A2,
             promotion: None,
             moved_type: PieceType::Rook,
             attacked_type: Some(PieceType::Pawn),
@@ -2526,16 +2300,22 @@ mod tests {
         board.unmake(&test_move, &zob_keys);
 
         assert!(!board.white_to_move);
-        assert!(board.black_rooks & 1 << Tables::A8 != 0);
-        assert!(board.black_rooks & 1 << Tables::A2 == 0);
-        assert!(board.white_pawns & 1 << Tables::A2 != 0);
+        assert!(board
+            .pieces(Color::Black, PieceType::Rook)
+            .contains(Square::A8));
+        assert!(!board
+            .pieces(Color::Black, PieceType::Rook)
+            .contains(Square::A2));
+        assert!(board
+            .pieces(Color::White, PieceType::Pawn)
+            .contains(Square::A2));
     }
 
     #[test]
     fn white_attack_mask_pawn() {
         let board = BoardState::state_from_string_fen("8/8/8/8/8/8/7P/8 w - - 0 1".to_string());
         let tables = Tables::new();
-        let expected = 0x20000;
+        let expected = Bitboard(0x20000);
         let result = board.white_attack_mask(&tables);
         assert_eq!(expected, result);
     }
@@ -2544,7 +2324,7 @@ mod tests {
     fn white_attack_mask_knight() {
         let board = BoardState::state_from_string_fen("8/8/8/3N4/8/8/8/8 w - - 0 1".to_string());
         let tables = Tables::new();
-        let expected = 0x28440044280000;
+        let expected = Bitboard(0x28440044280000);
         let result = board.white_attack_mask(&tables);
         assert_eq!(expected, result);
     }
@@ -2553,7 +2333,7 @@ mod tests {
     fn white_attack_mask_rook() {
         let board = BoardState::state_from_string_fen("8/8/8/8/8/8/8/3R4 w - - 0 1".to_string());
         let tables = Tables::new();
-        let expected = 0x10101010101010ef;
+        let expected = Bitboard(0x10101010101010ef);
         let result = board.white_attack_mask(&tables);
         assert_eq!(expected, result);
     }
@@ -2562,7 +2342,7 @@ mod tests {
     fn white_attack_mask_rook_with_blocker() {
         let board = BoardState::state_from_string_fen("8/8/8/8/3P4/8/8/3R4 w - - 0 1".to_string());
         let tables = Tables::new();
-        let expected = 0x28101010ef;
+        let expected = Bitboard(0x28101010ef);
         let result = board.white_attack_mask(&tables);
         assert_eq!(expected, result);
     }
@@ -2571,7 +2351,7 @@ mod tests {
     fn white_attack_mask_bishop() {
         let board = BoardState::state_from_string_fen("8/8/8/8/8/8/8/5B2 w - - 0 1".to_string());
         let tables = Tables::new();
-        let expected = 0x804020110a00;
+        let expected = Bitboard(0x804020110a00);
         let result = board.white_attack_mask(&tables);
         assert_eq!(expected, result);
     }
@@ -2580,7 +2360,7 @@ mod tests {
     fn white_attack_mask_bishop_with_blocker() {
         let board = BoardState::state_from_string_fen("8/8/8/8/2P5/8/8/5B2 w - - 0 1".to_string());
         let tables = Tables::new();
-        let expected = 0x5020110a00;
+        let expected = Bitboard(0x5020110a00);
         let result = board.white_attack_mask(&tables);
         assert_eq!(expected, result);
     }
@@ -2589,7 +2369,7 @@ mod tests {
     fn white_attack_mask_queen() {
         let board = BoardState::state_from_string_fen("8/8/8/8/3Q4/8/8/8 w - - 0 1".to_string());
         let tables = Tables::new();
-        let expected = 0x11925438ef385492;
+        let expected = Bitboard(0x11925438ef385492);
         let result = board.white_attack_mask(&tables);
         assert_eq!(expected, result);
     }
@@ -2600,7 +2380,7 @@ mod tests {
             "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1".to_string(),
         );
         let tables = Tables::new();
-        let expected = 0xffff7e;
+        let expected = Bitboard(0xffff7e);
         let result = board.white_attack_mask(&tables);
         assert_eq!(expected, result);
     }
@@ -2609,7 +2389,7 @@ mod tests {
     fn black_attack_mask_pawn() {
         let board = BoardState::state_from_string_fen("8/8/3p4/8/8/8/8/8 b - - 0 1".to_string());
         let tables = Tables::new();
-        let expected = 0x2800000000;
+        let expected = Bitboard(0x2800000000);
         let result = board.black_attack_mask(&tables);
         assert_eq!(expected, result);
     }
@@ -2618,7 +2398,7 @@ mod tests {
     fn black_attack_mask_knight() {
         let board = BoardState::state_from_string_fen("8/8/3n4/8/8/8/8/8 b - - 0 1".to_string());
         let tables = Tables::new();
-        let expected = 0x2844004428000000;
+        let expected = Bitboard(0x2844004428000000);
         let result = board.black_attack_mask(&tables);
         assert_eq!(expected, result);
     }
@@ -2627,7 +2407,7 @@ mod tests {
     fn black_attack_mask_rook() {
         let board = BoardState::state_from_string_fen("8/8/8/3r4/8/8/8/8 b - - 0 1".to_string());
         let tables = Tables::new();
-        let expected = 0x101010ef10101010;
+        let expected = Bitboard(0x101010ef10101010);
         let result = board.black_attack_mask(&tables);
         assert_eq!(expected, result);
     }
@@ -2636,7 +2416,7 @@ mod tests {
     fn black_attack_mask_rook_with_blocker() {
         let board = BoardState::state_from_string_fen("8/8/8/3r1p2/8/8/8/8 b - - 0 1".to_string());
         let tables = Tables::new();
-        let expected = 0x101010ec1a101010;
+        let expected = Bitboard(0x101010ec1a101010);
         let result = board.black_attack_mask(&tables);
         assert_eq!(expected, result);
     }
@@ -2645,7 +2425,7 @@ mod tests {
     fn black_attack_mask_bishop() {
         let board = BoardState::state_from_string_fen("8/8/8/3b4/8/8/8/8 b - - 0 1".to_string());
         let tables = Tables::new();
-        let expected = 0x8244280028448201;
+        let expected = Bitboard(0x8244280028448201);
         let result = board.black_attack_mask(&tables);
         assert_eq!(expected, result);
     }
@@ -2654,7 +2434,7 @@ mod tests {
     fn black_attack_mask_bishop_with_blocker() {
         let board = BoardState::state_from_string_fen("8/8/8/8/2p5/8/8/5b2 b - - 0 1".to_string());
         let tables = Tables::new();
-        let expected = 0x20510a00;
+        let expected = Bitboard(0x20510a00);
         let result = board.black_attack_mask(&tables);
         assert_eq!(expected, result);
     }
@@ -2663,7 +2443,7 @@ mod tests {
     fn black_attack_mask_queen() {
         let board = BoardState::state_from_string_fen("8/8/8/8/3q4/8/8/8 w - - 0 1".to_string());
         let tables = Tables::new();
-        let expected = 0x11925438ef385492;
+        let expected = Bitboard(0x11925438ef385492);
         let result = board.black_attack_mask(&tables);
         assert_eq!(expected, result);
     }
@@ -2763,8 +2543,8 @@ mod tests {
         );
         let tables = Tables::new();
 
-        let expected = 1 << Tables::E5;
-        let result = board.white_attacking(&tables, 1 << Tables::E8);
+        let expected = Square::E5.bit();
+        let result = board.attacking::<true>(&tables, Square::E8);
         assert_eq!(expected, result);
     }
 
@@ -2775,8 +2555,8 @@ mod tests {
         );
 
         let tables = Tables::new();
-        let expected = 0;
-        let result = board.white_attacking(&tables, 1 << Tables::E8);
+        let expected = Bitboard::EMPTY;
+        let result = board.attacking::<true>(&tables, Square::E8);
         assert_eq!(expected, result);
     }
 
@@ -2787,8 +2567,8 @@ mod tests {
         );
 
         let tables = Tables::new();
-        let expected = 0x78;
-        let result = board.white_attacking(&tables, 1 << Tables::D2);
+        let expected = Bitboard(0x78);
+        let result = board.attacking::<true>(&tables, Square::D2);
         assert_eq!(expected, result);
     }
 
@@ -2798,8 +2578,8 @@ mod tests {
             "rnbqkbnr/pppppppp/3N4/8/8/8/PPPPPPPP/RNBQKB1R w KQkq - 0 1".to_string(),
         );
         let tables = Tables::new();
-        let expected = 1 << Tables::D6;
-        let result = board.white_attacking(&tables, 1 << Tables::C8);
+        let expected = Square::D6.bit();
+        let result = board.attacking::<true>(&tables, Square::C8);
         assert_eq!(expected, result);
     }
 
@@ -2809,8 +2589,8 @@ mod tests {
             "rnbqkbnr/pppppppp/3P4/8/8/8/PPP1PPPP/RNBQKBNR w KQkq - 0 1".to_string(),
         );
         let tables = Tables::new();
-        let expected = 1 << Tables::D6;
-        let result = board.white_attacking(&tables, 1 << Tables::E7);
+        let expected = Square::D6.bit();
+        let result = board.attacking::<true>(&tables, Square::E7);
         assert_eq!(expected, result);
     }
 
@@ -2820,8 +2600,8 @@ mod tests {
             "rnbqkbnr/ppp1pppp/8/8/8/8/PPP1PPPP/RNBQKBNR w KQkq - 0 1".to_string(),
         );
         let tables = Tables::new();
-        let expected = 1 << Tables::D8;
-        let result = board.black_attacking(&tables, 1 << Tables::D1);
+        let expected = Square::D8.bit();
+        let result = board.attacking::<false>(&tables, Square::D1);
         assert_eq!(expected, result);
     }
 
@@ -2831,8 +2611,8 @@ mod tests {
             "rnbqkbnr/pppp1ppp/8/8/8/4p3/PPPPPPPP/RNBQKBNR w KQkq - 0 1".to_string(),
         );
         let tables = Tables::new();
-        let expected = 1 << Tables::E3;
-        let result = board.black_attacking(&tables, 1 << Tables::F2);
+        let expected = Square::E3.bit();
+        let result = board.attacking::<false>(&tables, Square::F2);
         assert_eq!(expected, result);
     }
 
@@ -2842,8 +2622,8 @@ mod tests {
             "rnbqk1nr/pppppppp/8/8/1b6/8/PPP1PPPP/RNBQKBNR w KQkq - 0 1".to_string(),
         );
         let tables = Tables::new();
-        let expected = 1 << Tables::B4;
-        let result = board.black_attacking(&tables, 1 << Tables::E1);
+        let expected = Square::B4.bit();
+        let result = board.attacking::<false>(&tables, Square::E1);
         assert_eq!(expected, result);
     }
 
@@ -2853,8 +2633,8 @@ mod tests {
             "rnbqkbnr/1ppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1".to_string(),
         );
         let tables = Tables::new();
-        let expected = 1 << Tables::A8;
-        let result = board.black_attacking(&tables, 1 << Tables::A2);
+        let expected = Square::A8.bit();
+        let result = board.attacking::<false>(&tables, Square::A2);
         assert_eq!(expected, result);
     }
 
@@ -2864,8 +2644,8 @@ mod tests {
             "r1bqkbnr/pppppppp/8/8/8/6n1/PPPPPPPP/RNBQKBNR w KQkq - 0 1".to_string(),
         );
         let tables = Tables::new();
-        let expected = 1 << Tables::G3;
-        let result = board.black_attacking(&tables, 1 << Tables::H1);
+        let expected = Square::G3.bit();
+        let result = board.attacking::<false>(&tables, Square::H1);
         assert_eq!(expected, result);
     }
 
@@ -2875,13 +2655,7 @@ mod tests {
             "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1".to_string(),
         );
         let tables = Tables::new();
-        let play = MoveRep::new(
-            1 << Tables::E2,
-            1 << Tables::E4,
-            None,
-            PieceType::Pawn,
-            None,
-        );
+        let play = MoveRep::new(Square::E2, Square::E4, None, PieceType::Pawn, None);
         let zob_keys = ZobKeys::new();
         let result = board.move_safe_for_king(&tables, &play, &zob_keys);
         assert!(result);
@@ -2893,13 +2667,7 @@ mod tests {
             "rn1qkbnr/p1pppppp/b7/8/8/8/PPPP1PPP/RNBQKBNR w KQkq - 0 1".to_string(),
         );
         let tables = Tables::new();
-        let play = MoveRep::new(
-            1 << Tables::E1,
-            1 << Tables::E2,
-            None,
-            PieceType::King,
-            None,
-        );
+        let play = MoveRep::new(Square::E1, Square::E2, None, PieceType::King, None);
         let zob_keys = ZobKeys::new();
         let result = board.move_safe_for_king(&tables, &play, &zob_keys);
         assert!(!result);
@@ -2911,13 +2679,7 @@ mod tests {
             "rnbqkbnr/pppp1ppp/8/8/8/8/PPPPPPPP/RNBQKBNR b KQkq - 0 1".to_string(),
         );
         let tables = Tables::new();
-        let play = MoveRep::new(
-            1 << Tables::E8,
-            1 << Tables::E7,
-            None,
-            PieceType::King,
-            None,
-        );
+        let play = MoveRep::new(Square::E8, Square::E7, None, PieceType::King, None);
         let zob_keys = ZobKeys::new();
         let result = board.move_safe_for_king(&tables, &play, &zob_keys);
         assert!(result);
@@ -2929,13 +2691,7 @@ mod tests {
             "rnbqkbnr/pppppppp/8/8/Q7/8/PP1PPPPP/RNB1KBNR b KQkq - 0 1".to_string(),
         );
         let tables = Tables::new();
-        let play = MoveRep::new(
-            1 << Tables::D7,
-            1 << Tables::D6,
-            None,
-            PieceType::Pawn,
-            None,
-        );
+        let play = MoveRep::new(Square::D7, Square::D6, None, PieceType::Pawn, None);
         let zob_keys = ZobKeys::new();
         let result = board.move_safe_for_king(&tables, &play, &zob_keys);
         assert!(!result);
@@ -2943,13 +2699,7 @@ mod tests {
 
     #[test]
     fn test_is_reversible_1() {
-        let mv = MoveRep::new(
-            1 << Tables::D2,
-            1 << Tables::D4,
-            None,
-            PieceType::Pawn,
-            None,
-        );
+        let mv = MoveRep::new(Square::D2, Square::D4, None, PieceType::Pawn, None);
 
         let result = mv.is_reversible();
         assert!(!result);
@@ -2957,13 +2707,7 @@ mod tests {
 
     #[test]
     fn test_is_reversible_2() {
-        let mv = MoveRep::new(
-            1 << Tables::B1,
-            1 << Tables::A3,
-            None,
-            PieceType::Knight,
-            None,
-        );
+        let mv = MoveRep::new(Square::B1, Square::A3, None, PieceType::Knight, None);
 
         let result = mv.is_reversible();
         assert!(result);
@@ -2972,8 +2716,8 @@ mod tests {
     #[test]
     fn test_is_reversible_3() {
         let mv = MoveRep::new(
-            1 << Tables::C1,
-            1 << Tables::G5,
+            Square::C1,
+            Square::G5,
             None,
             PieceType::Bishop,
             Some(PieceType::Pawn),
@@ -2985,13 +2729,7 @@ mod tests {
 
     #[test]
     fn test_is_reversible_4() {
-        let mv = MoveRep::new(
-            1 << Tables::E1,
-            1 << Tables::A1,
-            None,
-            PieceType::King,
-            None,
-        );
+        let mv = MoveRep::new(Square::E1, Square::A1, None, PieceType::King, None);
 
         let result = mv.is_reversible();
         assert!(!result);
@@ -3000,8 +2738,8 @@ mod tests {
     #[test]
     fn test_is_reversible_5() {
         let mv = MoveRep::new(
-            1 << Tables::G7,
-            1 << Tables::G8,
+            Square::G7,
+            Square::G8,
             Some(Promotion::Queen),
             PieceType::Pawn,
             None,
@@ -3015,67 +2753,43 @@ mod tests {
     fn test_en_passant_1() {
         let mut board = BoardState::starting_state();
 
-        let mv_1 = MoveRep::new(
-            1 << Tables::B2,
-            1 << Tables::B4,
-            None,
-            PieceType::Pawn,
-            None,
-        );
+        let mv_1 = MoveRep::new(Square::B2, Square::B4, None, PieceType::Pawn, None);
 
-        let mv_2 = MoveRep::new(
-            1 << Tables::G8,
-            1 << Tables::F6,
-            None,
-            PieceType::Knight,
-            None,
-        );
+        let mv_2 = MoveRep::new(Square::G8, Square::F6, None, PieceType::Knight, None);
         let zob_keys = ZobKeys::new();
-        assert_eq!(board.en_passant_target, 0);
+        assert_eq!(board.en_passant_target, None);
         board.make(&mv_1, &zob_keys);
-        assert_eq!(board.en_passant_target, 1 << Tables::B3);
+        assert_eq!(board.en_passant_target, Some(Square::B3));
         board.make(&mv_2, &zob_keys);
-        assert_eq!(board.en_passant_target, 0);
+        assert_eq!(board.en_passant_target, None);
         board.unmake(&mv_2, &zob_keys);
-        assert_eq!(board.en_passant_target, 1 << Tables::B3);
+        assert_eq!(board.en_passant_target, Some(Square::B3));
         board.unmake(&mv_1, &zob_keys);
-        assert_eq!(board.en_passant_target, 0);
+        assert_eq!(board.en_passant_target, None);
     }
 
     #[test]
     fn test_en_passant_2() {
         let mut board = BoardState::starting_state();
 
-        let mv_1 = MoveRep::new(
-            1 << Tables::B1,
-            1 << Tables::C3,
-            None,
-            PieceType::Knight,
-            None,
-        );
+        let mv_1 = MoveRep::new(Square::B1, Square::C3, None, PieceType::Knight, None);
 
         let zob_keys = ZobKeys::new();
         board.make(&mv_1, &zob_keys);
-        assert_eq!(board.en_passant_target, 0);
+        assert_eq!(board.en_passant_target, None);
         board.unmake(&mv_1, &zob_keys);
-        assert_eq!(board.en_passant_target, 0);
+        assert_eq!(board.en_passant_target, None);
     }
 
     #[test]
     fn test_en_passant_3() {
         let mut board = BoardState::starting_state();
 
-        let mv_1 = MoveRep::new(
-            1 << Tables::B2,
-            1 << Tables::B3,
-            None,
-            PieceType::Pawn,
-            None,
-        );
+        let mv_1 = MoveRep::new(Square::B2, Square::B3, None, PieceType::Pawn, None);
 
         let zob_keys = ZobKeys::new();
         board.make(&mv_1, &zob_keys);
-        assert_eq!(board.en_passant_target, 0);
+        assert_eq!(board.en_passant_target, None);
     }
 
     #[test]
@@ -3084,19 +2798,13 @@ mod tests {
             "rnbqkbnr/pppppppp/8/8/8/2N5/PPPPPPPP/R1BQKBNR b KQkq - 0 1".to_string(),
         );
 
-        let mv_1 = MoveRep::new(
-            1 << Tables::E7,
-            1 << Tables::E5,
-            None,
-            PieceType::Pawn,
-            None,
-        );
+        let mv_1 = MoveRep::new(Square::E7, Square::E5, None, PieceType::Pawn, None);
 
         let zob_keys = ZobKeys::new();
         board.make(&mv_1, &zob_keys);
-        assert_eq!(board.en_passant_target, 1 << Tables::E6);
+        assert_eq!(board.en_passant_target, Some(Square::E6));
         board.unmake(&mv_1, &zob_keys);
-        assert_eq!(board.en_passant_target, 0);
+        assert_eq!(board.en_passant_target, None);
     }
 
     #[test]
@@ -3108,8 +2816,8 @@ mod tests {
         let tables = Tables::new();
 
         let expected_mv = MoveRep::new(
-            1 << Tables::D4,
-            1 << Tables::C3,
+            Square::D4,
+            Square::C3,
             None,
             PieceType::Pawn,
             Some(PieceType::Pawn),
@@ -3128,8 +2836,8 @@ mod tests {
         let tables = Tables::new();
 
         let expected_mv = MoveRep::new(
-            1 << Tables::C5,
-            1 << Tables::D6,
+            Square::C5,
+            Square::D6,
             None,
             PieceType::Pawn,
             Some(PieceType::Pawn),
@@ -3146,8 +2854,8 @@ mod tests {
         );
 
         let expected_mv = MoveRep::new(
-            1 << Tables::A5,
-            1 << Tables::B6,
+            Square::A5,
+            Square::B6,
             None,
             PieceType::Pawn,
             Some(PieceType::Pawn),
@@ -3156,9 +2864,15 @@ mod tests {
         let zob_keys = ZobKeys::new();
         board.make(&expected_mv, &zob_keys);
         print_bitboard(board.occupancy());
-        assert_eq!(board.black_pawns, 0xbf000000000000);
+        assert_eq!(
+            board.pieces(Color::Black, PieceType::Pawn),
+            Bitboard(0xbf000000000000)
+        );
         board.unmake(&expected_mv, &zob_keys);
-        assert_eq!(board.black_pawns, 0xbf004000000000);
+        assert_eq!(
+            board.pieces(Color::Black, PieceType::Pawn),
+            Bitboard(0xbf004000000000)
+        );
     }
 
     #[test]
@@ -3168,8 +2882,8 @@ mod tests {
         );
         let tables = Tables::new();
 
-        let target = 1 << Tables::E1;
-        let expected = 1 << Tables::E2;
+        let target = Square::E1;
+        let expected = Square::E2.bit();
         let mask = board.pin_mask(&tables, target, board.white_to_move);
         assert_eq!(mask, expected);
     }
@@ -3181,8 +2895,8 @@ mod tests {
         );
         let tables = Tables::new();
 
-        let target = 1 << Tables::E8;
-        let expected = 1 << Tables::E7;
+        let target = Square::E8;
+        let expected = Square::E7.bit();
         let mask = board.pin_mask(&tables, target, board.white_to_move);
         assert_eq!(mask, expected);
     }
@@ -3194,8 +2908,8 @@ mod tests {
         );
         let tables = Tables::new();
 
-        let target = 1 << Tables::E1;
-        let expected = 1 << Tables::C3;
+        let target = Square::E1;
+        let expected = Square::C3.bit();
         let mask = board.pin_mask(&tables, target, board.white_to_move);
         assert_eq!(mask, expected);
     }
@@ -3207,8 +2921,8 @@ mod tests {
         );
         let tables = Tables::new();
 
-        let target = 1 << Tables::E1;
-        let expected = (1 << Tables::C3) | (1 << Tables::E2);
+        let target = Square::E1;
+        let expected = Square::C3.bit() | Square::E2.bit();
         let mask = board.pin_mask(&tables, target, board.white_to_move);
         assert_eq!(mask, expected);
     }
@@ -3219,14 +2933,8 @@ mod tests {
             "1nb1kbnr/pp1ppppp/2p1r3/q7/8/2NP4/PPP1PPPP/R1BQKBNR w - - 0 1".to_string(),
         );
         let tables = Tables::new();
-        let target = 1 << Tables::E1;
-        let mv = MoveRep::new(
-            1 << Tables::G1,
-            1 << Tables::H3,
-            None,
-            PieceType::Knight,
-            None,
-        );
+        let target = Square::E1;
+        let mv = MoveRep::new(Square::G1, Square::H3, None, PieceType::Knight, None);
 
         let result = board.pin_safe(&tables, target, &mv);
         assert!(result);
@@ -3238,14 +2946,8 @@ mod tests {
             "1nb1kbnr/pp1ppppp/2p1r3/q7/8/2NP4/PPP1PPPP/R1BQKBNR w - - 0 1".to_string(),
         );
         let tables = Tables::new();
-        let target = 1 << Tables::E1;
-        let mv = MoveRep::new(
-            1 << Tables::E2,
-            1 << Tables::E3,
-            None,
-            PieceType::Pawn,
-            None,
-        );
+        let target = Square::E1;
+        let mv = MoveRep::new(Square::E2, Square::E3, None, PieceType::Pawn, None);
 
         let result = board.pin_safe(&tables, target, &mv);
         assert!(result);
@@ -3257,14 +2959,8 @@ mod tests {
             "1nb1kbnr/pp1ppppp/2p1r3/q7/8/2NP4/PPP1PPPP/R1BQKBNR w - - 0 1".to_string(),
         );
         let tables = Tables::new();
-        let target = 1 << Tables::E1;
-        let mv = MoveRep::new(
-            1 << Tables::C3,
-            1 << Tables::E4,
-            None,
-            PieceType::Knight,
-            None,
-        );
+        let target = Square::E1;
+        let mv = MoveRep::new(Square::C3, Square::E4, None, PieceType::Knight, None);
 
         let result = board.pin_safe(&tables, target, &mv);
         assert!(!result);
@@ -3276,14 +2972,8 @@ mod tests {
             "rnbqkbnr/pppppppp/8/8/Q7/8/PP1PPPPP/RNB1KBNR b KQkq - 0 1".to_string(),
         );
         let tables = Tables::new();
-        let target = 1 << Tables::E8;
-        let mv = MoveRep::new(
-            1 << Tables::D7,
-            1 << Tables::D5,
-            None,
-            PieceType::Pawn,
-            None,
-        );
+        let target = Square::E8;
+        let mv = MoveRep::new(Square::D7, Square::D5, None, PieceType::Pawn, None);
 
         let result = board.pin_safe(&tables, target, &mv);
         assert!(!result);
@@ -3295,10 +2985,10 @@ mod tests {
             "rnbqkbnr/p1p1pppp/8/1p1p4/Q7/P1P5/1P1PPPPP/RNB1KBNR b KQkq - 0 1".to_string(),
         );
         let tables = Tables::new();
-        let target = 1 << Tables::E8;
+        let target = Square::E8;
         let mv = MoveRep::new(
-            1 << Tables::B5,
-            1 << Tables::A4,
+            Square::B5,
+            Square::A4,
             None,
             PieceType::Pawn,
             Some(PieceType::Queen),
@@ -3314,22 +3004,34 @@ mod tests {
             "rnbqkbnr/pppppppp/8/8/Q7/3BPN2/PP1P1PPP/RNB1K2R w KQkq - 0 1".to_string(),
         );
         let mv = MoveRep::new(
-            1 << Tables::E1,
-            1 << Tables::G1,
+            Square::E1,
+            Square::G1,
             Some(Promotion::Castle),
             PieceType::King,
             None,
         );
         let zob_keys = ZobKeys::new();
         board.make(&mv, &zob_keys);
-        assert_eq!(board.white_king, 1 << Tables::G1);
-        assert_eq!(board.white_rooks & 1 << Tables::F1, 1 << Tables::F1);
+        assert_eq!(
+            board.pieces(Color::White, PieceType::King),
+            Square::G1.bit()
+        );
+        assert_eq!(
+            board.pieces(Color::White, PieceType::Rook) & Square::F1.bit(),
+            Square::F1.bit()
+        );
         assert!(!board.white_kingside_castle_rights);
         assert!(!board.white_queenside_castle_rights);
 
         board.unmake(&mv, &zob_keys);
-        assert_eq!(board.white_king, 1 << Tables::E1);
-        assert_eq!(board.white_rooks & 1 << Tables::H1, 1 << Tables::H1);
+        assert_eq!(
+            board.pieces(Color::White, PieceType::King),
+            Square::E1.bit()
+        );
+        assert_eq!(
+            board.pieces(Color::White, PieceType::Rook) & Square::H1.bit(),
+            Square::H1.bit()
+        );
         assert!(board.white_kingside_castle_rights);
         assert!(board.white_queenside_castle_rights);
     }
@@ -3339,22 +3041,34 @@ mod tests {
             "rnbqkbnr/pppppppp/8/8/3P4/2NQB3/PPP1PPPP/R3KBNR w KQkq - 0 1".to_string(),
         );
         let mv = MoveRep::new(
-            1 << Tables::E1,
-            1 << Tables::C1,
+            Square::E1,
+            Square::C1,
             Some(Promotion::Castle),
             PieceType::King,
             None,
         );
         let zob_keys = ZobKeys::new();
         board.make(&mv, &zob_keys);
-        assert_eq!(board.white_king, 1 << Tables::C1);
-        assert_eq!(board.white_rooks & 1 << Tables::D1, 1 << Tables::D1);
+        assert_eq!(
+            board.pieces(Color::White, PieceType::King),
+            Square::C1.bit()
+        );
+        assert_eq!(
+            board.pieces(Color::White, PieceType::Rook) & Square::D1.bit(),
+            Square::D1.bit()
+        );
         assert!(!board.white_kingside_castle_rights);
         assert!(!board.white_queenside_castle_rights);
 
         board.unmake(&mv, &zob_keys);
-        assert_eq!(board.white_king, 1 << Tables::E1);
-        assert_eq!(board.white_rooks & 1 << Tables::A1, 1 << Tables::A1);
+        assert_eq!(
+            board.pieces(Color::White, PieceType::King),
+            Square::E1.bit()
+        );
+        assert_eq!(
+            board.pieces(Color::White, PieceType::Rook) & Square::A1.bit(),
+            Square::A1.bit()
+        );
         assert!(board.white_kingside_castle_rights);
         assert!(board.white_queenside_castle_rights);
     }
@@ -3364,22 +3078,34 @@ mod tests {
             "rnbqk2r/pppppp1p/5n1b/6p1/8/8/PPPPPPPP/RNBQKBNR b KQkq - 0 1".to_string(),
         );
         let mv = MoveRep::new(
-            1 << Tables::E8,
-            1 << Tables::G8,
+            Square::E8,
+            Square::G8,
             Some(Promotion::Castle),
             PieceType::King,
             None,
         );
         let zob_keys = ZobKeys::new();
         board.make(&mv, &zob_keys);
-        assert_eq!(board.black_king, 1 << Tables::G8);
-        assert_eq!(board.black_rooks & 1 << Tables::F8, 1 << Tables::F8);
+        assert_eq!(
+            board.pieces(Color::Black, PieceType::King),
+            Square::G8.bit()
+        );
+        assert_eq!(
+            board.pieces(Color::Black, PieceType::Rook) & Square::F8.bit(),
+            Square::F8.bit()
+        );
         assert!(!board.black_kingside_castle_rights);
         assert!(!board.black_queenside_castle_rights);
 
         board.unmake(&mv, &zob_keys);
-        assert_eq!(board.black_king, 1 << Tables::E8);
-        assert_eq!(board.black_rooks & 1 << Tables::H8, 1 << Tables::H8);
+        assert_eq!(
+            board.pieces(Color::Black, PieceType::King),
+            Square::E8.bit()
+        );
+        assert_eq!(
+            board.pieces(Color::Black, PieceType::Rook) & Square::H8.bit(),
+            Square::H8.bit()
+        );
         assert!(board.black_kingside_castle_rights);
         assert!(board.black_queenside_castle_rights);
     }
@@ -3389,22 +3115,34 @@ mod tests {
             "r3kbnr/ppp1pppp/2nqb3/3p4/8/8/PPPPPPPP/RNBQKBNR b KQkq - 0 1".to_string(),
         );
         let mv = MoveRep::new(
-            1 << Tables::E8,
-            1 << Tables::C8,
+            Square::E8,
+            Square::C8,
             Some(Promotion::Castle),
             PieceType::King,
             None,
         );
         let zob_keys = ZobKeys::new();
         board.make(&mv, &zob_keys);
-        assert_eq!(board.black_king, 1 << Tables::C8);
-        assert_eq!(board.black_rooks & 1 << Tables::D8, 1 << Tables::D8);
+        assert_eq!(
+            board.pieces(Color::Black, PieceType::King),
+            Square::C8.bit()
+        );
+        assert_eq!(
+            board.pieces(Color::Black, PieceType::Rook) & Square::D8.bit(),
+            Square::D8.bit()
+        );
         assert!(!board.black_kingside_castle_rights);
         assert!(!board.black_queenside_castle_rights);
 
         board.unmake(&mv, &zob_keys);
-        assert_eq!(board.black_king, 1 << Tables::E8);
-        assert_eq!(board.black_rooks & 1 << Tables::A8, 1 << Tables::A8);
+        assert_eq!(
+            board.pieces(Color::Black, PieceType::King),
+            Square::E8.bit()
+        );
+        assert_eq!(
+            board.pieces(Color::Black, PieceType::Rook) & Square::A8.bit(),
+            Square::A8.bit()
+        );
         assert!(board.black_kingside_castle_rights);
         assert!(board.black_queenside_castle_rights);
     }
@@ -3414,13 +3152,7 @@ mod tests {
             "rnbqkbnr/pppppppp/8/8/7P/8/PPPPPPP1/RNBQKBNR w KQkq - 0 1".to_string(),
         );
 
-        let kingside_rook_move = MoveRep::new(
-            1 << Tables::H1,
-            1 << Tables::H2,
-            None,
-            PieceType::Rook,
-            None,
-        );
+        let kingside_rook_move = MoveRep::new(Square::H1, Square::H2, None, PieceType::Rook, None);
         let zob_keys = ZobKeys::new();
         board.make(&kingside_rook_move, &zob_keys);
         assert!(!board.white_kingside_castle_rights);
@@ -3432,13 +3164,7 @@ mod tests {
             "rnbqkbnr/pppppppp/8/8/P6P/8/1PPPPPP1/RNBQKBNR w KQkq - 0 1".to_string(),
         );
 
-        let queenside_rook_move = MoveRep::new(
-            1 << Tables::A1,
-            1 << Tables::A2,
-            None,
-            PieceType::Rook,
-            None,
-        );
+        let queenside_rook_move = MoveRep::new(Square::A1, Square::A2, None, PieceType::Rook, None);
 
         let zob_keys = ZobKeys::new();
         board.make(&queenside_rook_move, &zob_keys);
@@ -3451,13 +3177,7 @@ mod tests {
             "rnbqkbnr/pppppppp/8/8/P3P2P/8/1PPP1PP1/RNBQKBNR w KQkq - 0 1".to_string(),
         );
 
-        let king_move = MoveRep::new(
-            1 << Tables::E1,
-            1 << Tables::E2,
-            None,
-            PieceType::King,
-            None,
-        );
+        let king_move = MoveRep::new(Square::E1, Square::E2, None, PieceType::King, None);
 
         let zob_keys = ZobKeys::new();
         board.make(&king_move, &zob_keys);
@@ -3470,13 +3190,7 @@ mod tests {
             "rnbqkbnr/ppppppp1/8/7p/8/8/PPPPPPPP/RNBQKBNR b KQkq - 0 1".to_string(),
         );
 
-        let kingside_rook_move = MoveRep::new(
-            1 << Tables::H8,
-            1 << Tables::H7,
-            None,
-            PieceType::Rook,
-            None,
-        );
+        let kingside_rook_move = MoveRep::new(Square::H8, Square::H7, None, PieceType::Rook, None);
 
         let zob_keys = ZobKeys::new();
         board.make(&kingside_rook_move, &zob_keys);
@@ -3489,13 +3203,7 @@ mod tests {
             "rnbqkbnr/1pppppp1/8/p6p/8/8/PPPPPPPP/RNBQKBNR b KQkq - 0 1".to_string(),
         );
 
-        let queenside_rook_move = MoveRep::new(
-            1 << Tables::A8,
-            1 << Tables::A7,
-            None,
-            PieceType::Rook,
-            None,
-        );
+        let queenside_rook_move = MoveRep::new(Square::A8, Square::A7, None, PieceType::Rook, None);
 
         let zob_keys = ZobKeys::new();
         board.make(&queenside_rook_move, &zob_keys);
@@ -3508,13 +3216,7 @@ mod tests {
             "rnbqkbnr/1pppppp1/8/p6p/8/8/PPPPPPPP/RNBQKBNR b KQkq - 0 1".to_string(),
         );
 
-        let king_move = MoveRep::new(
-            1 << Tables::E8,
-            1 << Tables::E7,
-            None,
-            PieceType::King,
-            None,
-        );
+        let king_move = MoveRep::new(Square::E8, Square::E7, None, PieceType::King, None);
 
         let zob_keys = ZobKeys::new();
         board.make(&king_move, &zob_keys);
@@ -3529,16 +3231,21 @@ mod tests {
         );
 
         let mv = MoveRep::new(
-            1 << Tables::H7,
-            1 << Tables::H8,
+            Square::H7,
+            Square::H8,
             Some(Promotion::Queen),
             PieceType::Pawn,
             None,
         );
         let zob_keys = ZobKeys::new();
         board.make(&mv, &zob_keys);
-        assert_eq!(board.white_queens, 1 << Tables::D1 | 1 << Tables::H8);
-        assert_eq!(board.white_pawns & 1 << Tables::H8, 0);
+        assert_eq!(
+            board.pieces(Color::White, PieceType::Queen),
+            Square::D1.bit() | Square::H8.bit()
+        );
+        assert!(!board
+            .pieces(Color::White, PieceType::Pawn)
+            .contains(Square::H8));
     }
 
     #[test]
@@ -3580,4 +3287,606 @@ mod tests {
         let stalemate = board.black_in_stalemate(&tables);
         assert!(!stalemate);
     }
+
+    #[test]
+    fn insufficient_material_dead_positions() {
+        for fen in [
+            "8/8/4k3/8/8/2K5/8/8 w - - 0 1",
+            "8/8/4k3/8/8/2K5/5N2/8 w - - 0 1",
+            "8/8/4k3/8/8/2K5/8/6b1 b - - 0 1",
+            "8/8/4kb2/8/8/2K5/8/2B5 w - - 0 1",
+        ] {
+            let board = BoardState::state_from_string_fen(fen.to_string());
+            assert!(board.insufficient_material(), "{fen}");
+        }
+    }
+
+    #[test]
+    fn insufficient_material_live_positions() {
+        for fen in [
+            "8/8/4k3/8/8/2K5/5P2/8 w - - 0 1",
+            "8/8/4k3/8/8/2K5/5NN1/8 w - - 0 1",
+            "8/8/4k1b1/8/8/2K5/8/2B5 w - - 0 1",
+            "8/8/4k3/8/8/2K5/5n2/2B5 w - - 0 1",
+            "8/8/4k3/8/8/2K5/8/2R5 w - - 0 1",
+        ] {
+            let board = BoardState::state_from_string_fen(fen.to_string());
+            assert!(!board.insufficient_material(), "{fen}");
+        }
+    }
+
+    #[test]
+    fn halfmove_clock() {
+        let tables = Tables::new();
+        let zob_keys = ZobKeys::new();
+        let mut board = BoardState::starting_state();
+        board
+            .apply_string_move("g1f3".to_string(), &tables, &zob_keys)
+            .unwrap();
+        board
+            .apply_string_move("g8f6".to_string(), &tables, &zob_keys)
+            .unwrap();
+        assert_eq!(board.reversable_move_counter, 2);
+        assert_eq!(board.full_move_counter, 2);
+        board
+            .apply_string_move("e2e4".to_string(), &tables, &zob_keys)
+            .unwrap();
+        assert_eq!(board.reversable_move_counter, 0);
+        assert_eq!(board.full_move_counter, 2);
+    }
+
+    #[test]
+    fn repetition() {
+        let tables = Tables::new();
+        let zob_keys = ZobKeys::new();
+        let mut board = BoardState::starting_state();
+        for mv in ["g1f3", "g8f6", "f3g1"] {
+            board
+                .apply_string_move(mv.to_string(), &tables, &zob_keys)
+                .unwrap();
+            assert!(!board.is_repetition());
+        }
+        board
+            .apply_string_move("f6g8".to_string(), &tables, &zob_keys)
+            .unwrap();
+        assert!(board.is_repetition());
+    }
+
+    #[test]
+    fn chess960_castle_fields() {
+        // Shredder-FEN names the rook files
+        let board = BoardState::state_from_string_fen(
+            "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9".to_string(),
+        );
+        assert_eq!(
+            board.castle_rooks,
+            [Square::H1, Square::F1, Square::H8, Square::F8]
+        );
+
+        // X-FEN takes the outermost rook on each side
+        let board = BoardState::state_from_string_fen(
+            "rr2k1r1/8/8/8/8/8/8/1R1RK1RR w KQkq - 0 1".to_string(),
+        );
+        assert_eq!(
+            board.castle_rooks,
+            [Square::H1, Square::B1, Square::G8, Square::A8]
+        );
+        assert!(board.white_kingside_castle_rights && board.black_queenside_castle_rights);
+
+        let board = BoardState::state_from_string_fen(
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1".to_string(),
+        );
+        assert_eq!(board, BoardState::starting_state());
+        assert!(BoardState::state_from_fen("8/8/8/8/8/8/8/4K3 w X - 0 1".split(' ')).is_err());
+    }
+
+    #[test]
+    fn castle_rights_without_rooks_dropped() {
+        let board =
+            BoardState::state_from_string_fen("4k3/8/8/8/8/8/8/4K3 w KQkq - 0 1".to_string());
+        for index in 0..4 {
+            assert!(!board.castle_right(index));
+        }
+        assert_eq!(board.to_fen(), "4k3/8/8/8/8/8/8/4K3 w - - 0 1");
+
+        // Only the sides with a rook keep their right, whether named by KQkq or by file
+        let board =
+            BoardState::state_from_string_fen("r3k3/8/8/8/8/8/8/4K2R w KQkq - 0 1".to_string());
+        assert_eq!(board.to_fen(), "r3k3/8/8/8/8/8/8/4K2R w Kq - 0 1");
+        let board =
+            BoardState::state_from_string_fen("r3k3/8/8/8/8/8/8/4K2R w HAha - 0 1".to_string());
+        assert_eq!(board.to_fen(), "r3k3/8/8/8/8/8/8/4K2R w Kq - 0 1");
+
+        // Nor can a king off its back rank castle
+        let board =
+            BoardState::state_from_string_fen("4k3/8/8/8/8/8/4K3/R6R w KQ - 0 1".to_string());
+        assert_eq!(board.to_fen(), "4k3/8/8/8/8/8/4K3/R6R w - - 0 1");
+    }
+
+    #[test]
+    fn chess960_castle_make_unmake() {
+        let zob_keys = ZobKeys::new();
+        let tables = Tables::new();
+        // Kings landing on their rooks squares, and rooks on their kings squares
+        let mut board =
+            BoardState::state_from_string_fen("rk5r/8/8/8/8/8/8/RK3R2 w FAha - 0 1".to_string());
+        let original = board.clone();
+        let castles: Vec<MoveRep> = generate(&board, &tables)
+            .into_iter()
+            .filter(|mv| mv.promotion == Some(Promotion::Castle))
+            .collect();
+        assert_eq!(castles.len(), 2);
+        for mv in castles {
+            board.make(&mv, &zob_keys);
+            assert_eq!(board.hash, zob_keys.generate_hash(&board));
+            assert_eq!(board.piece_square_score, piece_square_score(&board));
+            assert!(!board.white_kingside_castle_rights && !board.white_queenside_castle_rights);
+            board.unmake(&mv, &zob_keys);
+            assert_eq!(board, original);
+        }
+
+        // The queenside castle puts the king on c1 and the rook on d1
+        board
+            .apply_string_move("b1a1".to_string(), &tables, &zob_keys)
+            .unwrap();
+        assert_eq!(
+            board.pieces(Color::White, PieceType::King),
+            Square::C1.bit()
+        );
+        assert_eq!(
+            board.pieces(Color::White, PieceType::Rook),
+            Square::D1.bit() | Square::F1.bit()
+        );
+
+        // Moving or losing a castling rook takes away its right
+        let mut board = original;
+        board
+            .apply_string_move("a1a8".to_string(), &tables, &zob_keys)
+            .unwrap();
+        assert!(board.white_kingside_castle_rights && !board.white_queenside_castle_rights);
+        assert!(board.black_kingside_castle_rights && !board.black_queenside_castle_rights);
+        assert_eq!(board.hash, zob_keys.generate_hash(&board));
+    }
+
+    #[test]
+    fn chess960_notation() {
+        let tables = Tables::new();
+        let zob_keys = ZobKeys::new();
+        let mut board =
+            BoardState::state_from_string_fen("4k3/8/8/8/8/8/8/RK6 w A - 0 1".to_string());
+        let castle = MoveRep::new(
+            Square::B1,
+            Square::C1,
+            Some(Promotion::Castle),
+            PieceType::King,
+            None,
+        );
+        assert_eq!(board.move_to_uci(&castle), "b1c1");
+        board.set_chess960(true);
+        assert_eq!(board.move_to_uci(&castle), "b1a1");
+        board
+            .apply_string_move("b1a1".to_string(), &tables, &zob_keys)
+            .unwrap();
+        assert_eq!(
+            board.pieces(Color::White, PieceType::King),
+            Square::C1.bit()
+        );
+        assert_eq!(
+            board.pieces(Color::White, PieceType::Rook),
+            Square::D1.bit()
+        );
+
+        // Either way of writing a standard castle is understood
+        let mut board =
+            BoardState::state_from_string_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1".to_string());
+        board
+            .apply_string_move("e1h1".to_string(), &tables, &zob_keys)
+            .unwrap();
+        assert_eq!(
+            board.pieces(Color::White, PieceType::King),
+            Square::G1.bit()
+        );
+        board
+            .apply_string_move("e8c8".to_string(), &tables, &zob_keys)
+            .unwrap();
+        assert_eq!(
+            board.pieces(Color::Black, PieceType::King),
+            Square::C8.bit()
+        );
+        assert_eq!(
+            board.pieces(Color::Black, PieceType::Rook),
+            Square::D8.bit() | Square::H8.bit()
+        );
+    }
+
+    #[test]
+    fn apply_illegal_string_moves() {
+        let tables = Tables::new();
+        let zob_keys = ZobKeys::new();
+        let start = BoardState::starting_state();
+        for play in ["e3e4", "e2e5", "e7e5", "g1g3", "e1g1", "zz", "e2", ""] {
+            let mut board = start.clone();
+            assert!(board
+                .apply_string_move(play.to_string(), &tables, &zob_keys)
+                .is_err());
+            assert_eq!(board, start);
+        }
+
+        // Promotions must name the piece
+        let mut board =
+            BoardState::state_from_string_fen("k7/4P3/8/8/8/8/8/4K3 w - - 0 1".to_string());
+        assert!(board
+            .apply_string_move("e7e8".to_string(), &tables, &zob_keys)
+            .is_err());
+        board
+            .apply_string_move("e7e8N".to_string(), &tables, &zob_keys)
+            .unwrap();
+        assert_eq!(
+            board.pieces(Color::White, PieceType::Knight),
+            Square::E8.bit()
+        );
+
+        // A plain king move is not mistaken for the castle written the same way
+        let mut board =
+            BoardState::state_from_string_fen("4k3/8/8/8/8/8/8/RK6 w A - 0 1".to_string());
+        board
+            .apply_string_move("b1c1".to_string(), &tables, &zob_keys)
+            .unwrap();
+        assert_eq!(
+            board.pieces(Color::White, PieceType::Rook),
+            Square::A1.bit()
+        );
+    }
+
+    #[test]
+    fn packed_move_flags() {
+        assert_eq!(std::mem::size_of::<Move>(), 2);
+        let tables = Tables::new();
+        let check = |fen: &str, uci: &str, flag: u16| {
+            let board = BoardState::state_from_string_fen(fen.to_string());
+            let mv = generate(&board, &tables)
+                .into_iter()
+                .find(|mv| mv.to_string() == uci)
+                .expect(uci);
+            assert_eq!(
+                Move::from_rep(&mv, &board),
+                Move::new(mv.starting_square, mv.ending_square, flag),
+                "{uci}"
+            );
+        };
+
+        let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        check(start, "g1f3", Move::QUIET);
+        check(start, "e2e3", Move::QUIET);
+        check(start, "e2e4", Move::DOUBLE_PUSH);
+
+        let castles = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+        check(castles, "e1g1", Move::KING_CASTLE);
+        check(castles, "e1c1", Move::QUEEN_CASTLE);
+        check(castles, "a1a8", Move::CAPTURE);
+        let chess960 = "rk5r/8/8/8/8/8/8/RK3R1R w Fha - 0 1";
+        check(chess960, "b1g1", Move::KING_CASTLE);
+
+        let en_passant = "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1";
+        check(en_passant, "e5d6", Move::EN_PASSANT);
+        check(en_passant, "e5e6", Move::QUIET);
+
+        let promotion = "3r3k/4P3/8/8/8/8/8/4K3 w - - 0 1";
+        check(promotion, "e7d8q", Move::QUEEN_PROMOTION | Move::CAPTURE);
+        check(promotion, "e7d8n", Move::KNIGHT_PROMOTION | Move::CAPTURE);
+        check(promotion, "e7e8r", Move::ROOK_PROMOTION);
+        check(promotion, "e7e8b", Move::BISHOP_PROMOTION);
+    }
+
+    #[test]
+    fn packed_moves_differ() {
+        use std::collections::HashSet;
+
+        use rand_core::{RngCore, SeedableRng};
+        use rand_xorshift::XorShiftRng;
+
+        let tables = Tables::new();
+        let zob_keys = ZobKeys::new();
+        let mut rng = XorShiftRng::seed_from_u64(47);
+        let starts = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q2/PPPBBPpP/R3K2R w KQkq - 0 1",
+            "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+            "rk5r/8/8/8/8/8/8/RK3R1R w Fha - 0 1",
+        ];
+        for game in 0..100 {
+            let mut board =
+                BoardState::state_from_string_fen(starts[game % starts.len()].to_string());
+            for _ in 0..150 {
+                let moves = generate(&board, &tables);
+                if moves.is_empty() {
+                    break;
+                }
+                // Every legal move packs to its own value, so packed moves can stand in for them
+                let packed: HashSet<Move> =
+                    moves.iter().map(|mv| Move::from_rep(mv, &board)).collect();
+                assert_eq!(packed.len(), moves.len());
+                board.make(&moves[rng.next_u32() as usize % moves.len()], &zob_keys);
+            }
+        }
+    }
+
+    #[test]
+    fn to_fen_known() {
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q2/PPPBBPpP/R3K2R w KQkq - 0 1",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 b - - 15 40",
+            "4k3/8/8/8/8/8/8/4K2R w K - 0 1",
+            // X-FEN only names the rook when it is not the outermost one
+            "rk5r/8/8/8/8/8/8/RK3R1R w Fkq - 0 1",
+        ] {
+            let board = BoardState::state_from_string_fen(fen.to_string());
+            assert_eq!(board.to_fen(), fen);
+        }
+
+        // Shredder-FEN is written as X-FEN
+        let board = BoardState::state_from_string_fen(
+            "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9".to_string(),
+        );
+        assert_eq!(
+            board.to_fen(),
+            "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w KQkq - 2 9"
+        );
+        assert_eq!(
+            BoardState::starting_state().to_fen(),
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"
+        );
+    }
+
+    #[test]
+    fn fen_bad_en_passant_target() {
+        let parse = |target: &str| {
+            let fen = format!("4k3/8/8/8/8/8/8/4K3 w - {target} 0 1");
+            BoardState::state_from_fen(fen.split(' '))
+        };
+        for target in ["e9", "e0", "e3", "i6", "e", "e6e", "66"] {
+            assert!(parse(target).is_err(), "{target}");
+        }
+        assert_eq!(parse("e6").unwrap().en_passant_target, Some(Square::E6));
+    }
+
+    #[test]
+    fn fen_illegal_kings() {
+        let parse = |fen: &str| BoardState::state_from_fen(fen.split(' '));
+        // Missing and extra kings
+        assert!(parse("8/8/8/8/8/8/8/4K3 w - - 0 1").is_err());
+        assert!(parse("4k3/8/8/8/8/8/8/8 b - - 0 1").is_err());
+        assert!(parse("4k3/8/8/8/8/8/8/3KK3 w - - 0 1").is_err());
+        // The side not to move is in check
+        assert!(parse("4k3/8/8/8/8/8/8/4RK2 w - - 0 1").is_err());
+        assert!(parse("4k3/8/8/8/8/8/8/4RK2 b - - 0 1").is_ok());
+    }
+
+    #[test]
+    fn fen_round_trip() {
+        use rand_core::{RngCore, SeedableRng};
+        use rand_xorshift::XorShiftRng;
+
+        // Random games from a few starting points, checking every position along the way
+        let tables = Tables::new();
+        let zob_keys = ZobKeys::new();
+        let mut rng = XorShiftRng::seed_from_u64(40);
+        let starts = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q2/PPPBBPpP/R3K2R w KQkq - 0 1",
+            "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+            "rk5r/8/8/8/8/8/8/RK3R1R w Fha - 0 1",
+        ];
+        let mut positions = 0;
+        for game in 0..200 {
+            let mut board =
+                BoardState::state_from_string_fen(starts[game % starts.len()].to_string());
+            for _ in 0..150 {
+                let fen = board.to_fen();
+                let parsed = BoardState::state_from_fen(fen.split(' ')).unwrap();
+                assert_eq!(parsed.to_fen(), fen);
+                assert_eq!(parsed.hash, board.hash, "{fen}");
+                for index in (0..4).filter(|&index| board.castle_right(index)) {
+                    assert_eq!(
+                        parsed.castle_rooks[index], board.castle_rooks[index],
+                        "{fen}"
+                    );
+                }
+                positions += 1;
+
+                let moves = generate(&board, &tables);
+                if moves.is_empty() {
+                    break;
+                }
+                board.make(&moves[rng.next_u32() as usize % moves.len()], &zob_keys);
+            }
+        }
+        assert!(positions > 10000);
+    }
+
+    #[test]
+    fn san_known() {
+        let tables = Tables::new();
+        let san = |fen: &str, uci: &str| {
+            let board = BoardState::state_from_string_fen(fen.to_string());
+            let mv = generate(&board, &tables)
+                .into_iter()
+                .find(|mv| board.move_to_uci(mv) == uci)
+                .unwrap();
+            mv.to_san(&board, &tables)
+        };
+
+        let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        assert_eq!(san(start, "g1f3"), "Nf3");
+        assert_eq!(san(start, "e2e4"), "e4");
+
+        let rooks = "1k5K/8/8/R7/8/8/8/R6R w - - 0 1";
+        assert_eq!(san(rooks, "a1d1"), "Rad1");
+        assert_eq!(san(rooks, "h1d1"), "Rhd1");
+        assert_eq!(san(rooks, "a1a3"), "R1a3");
+        assert_eq!(san(rooks, "a5a3"), "R5a3");
+
+        let queens = "1k5K/8/8/8/8/Q7/8/Q1Q5 w - - 0 1";
+        assert_eq!(san(queens, "a1b2"), "Qa1b2#");
+        assert_eq!(san(queens, "a3b2"), "Q3b2#");
+        assert_eq!(san(queens, "c1b2"), "Qcb2+");
+
+        let promotion = "k7/4P3/8/8/8/8/8/4K3 w - - 0 1";
+        assert_eq!(san(promotion, "e7e8q"), "e8=Q+");
+        assert_eq!(san(promotion, "e7e8n"), "e8=N");
+
+        assert_eq!(san("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", "a1a8"), "Ra8#");
+        assert_eq!(san("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5d6"), "exd6");
+
+        let castles = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+        assert_eq!(san(castles, "e1g1"), "O-O");
+        assert_eq!(san(castles, "e1c1"), "O-O-O");
+        assert_eq!(san(castles, "a1a8"), "Rxa8+");
+        assert_eq!(san("rk5r/8/8/8/8/8/8/RK3R1R w Fha - 0 1", "b1g1"), "O-O");
+    }
+
+    #[test]
+    fn parse_san_variants() {
+        let tables = Tables::new();
+        let parse = |fen: &str, san: &str| {
+            let board = BoardState::state_from_string_fen(fen.to_string());
+            board
+                .parse_san(san, &tables)
+                .map(|mv| board.move_to_uci(&mv))
+        };
+
+        let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        assert_eq!(parse(start, "Nf3"), Ok("g1f3".to_string()));
+        assert_eq!(parse(start, "Nf3+!?"), Ok("g1f3".to_string()));
+        assert_eq!(parse(start, "Ng1-f3"), Ok("g1f3".to_string()));
+        assert_eq!(parse(start, "e4"), Ok("e2e4".to_string()));
+        assert!(parse(start, "Nf4").is_err());
+        assert!(parse(start, "e5").is_err());
+        assert!(parse(start, "Zz").is_err());
+        assert!(parse(start, "Nf9").is_err());
+        assert!(parse(start, "e0").is_err());
+
+        let castles = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+        assert_eq!(parse(castles, "0-0"), Ok("e1g1".to_string()));
+        assert_eq!(parse(castles, "O-O-O"), Ok("e1c1".to_string()));
+        assert_eq!(
+            parse(castles, "Kg1"),
+            Err("Illegal move \"Kg1\"".to_string())
+        );
+        let chess960 = "rk5r/8/8/8/8/8/8/RK3R1R w Fha - 0 1";
+        assert_eq!(parse(chess960, "O-O"), Ok("b1g1".to_string()));
+        assert!(parse(chess960, "O-O-O").is_err());
+
+        let en_passant = "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1";
+        assert_eq!(parse(en_passant, "exd6"), Ok("e5d6".to_string()));
+        assert_eq!(parse(en_passant, "exd6e.p."), Ok("e5d6".to_string()));
+        assert_eq!(parse(en_passant, "exd6 e.p."), Ok("e5d6".to_string()));
+        assert_eq!(parse(en_passant, "ed6"), Ok("e5d6".to_string()));
+
+        let promotion = "k7/4P3/8/8/8/8/8/4K3 w - - 0 1";
+        assert_eq!(parse(promotion, "e8=Q"), Ok("e7e8q".to_string()));
+        assert_eq!(parse(promotion, "e8Q+"), Ok("e7e8q".to_string()));
+        assert_eq!(parse(promotion, "e8=n"), Ok("e7e8n".to_string()));
+        assert!(parse(promotion, "e8").is_err());
+        assert!(parse(promotion, "e8=K").is_err());
+
+        let rooks = "1k5K/8/8/R7/8/8/8/R6R w - - 0 1";
+        assert_eq!(
+            parse(rooks, "Rd1"),
+            Err("Ambiguous move \"Rd1\"".to_string())
+        );
+        assert_eq!(parse(rooks, "Rhd1"), Ok("h1d1".to_string()));
+        assert_eq!(parse(rooks, "R1a3"), Ok("a1a3".to_string()));
+        assert_eq!(parse(rooks, "Ra1a3"), Ok("a1a3".to_string()));
+    }
+
+    #[test]
+    fn san_round_trip() {
+        use rand_core::{RngCore, SeedableRng};
+        use rand_xorshift::XorShiftRng;
+
+        // Every legal move along some random games must survive writing and reading back
+        let tables = Tables::new();
+        let zob_keys = ZobKeys::new();
+        let mut rng = XorShiftRng::seed_from_u64(41);
+        let starts = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q2/PPPBBPpP/R3K2R w KQkq - 0 1",
+            "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+            "rk5r/8/8/8/8/8/8/RK3R1R w Fha - 0 1",
+        ];
+        for game in 0..40 {
+            let mut board =
+                BoardState::state_from_string_fen(starts[game % starts.len()].to_string());
+            for _ in 0..100 {
+                let moves = generate(&board, &tables);
+                if moves.is_empty() {
+                    break;
+                }
+                let written: Vec<String> =
+                    moves.iter().map(|mv| mv.to_san(&board, &tables)).collect();
+                for (mv, san) in moves.iter().zip(&written) {
+                    assert_eq!(board.parse_san(san, &tables).as_ref(), Ok(mv), "{san}");
+                    assert_eq!(written.iter().filter(|other| *other == san).count(), 1);
+                }
+                board.make(&moves[rng.next_u32() as usize % moves.len()], &zob_keys);
+            }
+        }
+    }
+
+    #[test]
+    fn mailbox_follows_the_bitboards() {
+        use rand_core::{RngCore, SeedableRng};
+        use rand_xorshift::XorShiftRng;
+
+        // The mailbox and occupancy must agree with the piece bitboards after every make, and
+        // unmake must give back the same board
+        fn check(board: &BoardState) {
+            for color in [Color::White, Color::Black] {
+                let occupancy = PieceType::ALL.iter().fold(Bitboard::EMPTY, |acc, kind| {
+                    acc | board.pieces(color, *kind)
+                });
+                assert_eq!(board.color_occupancy[color.index()], occupancy);
+            }
+            for index in 0..64 {
+                let square = Square::new(index);
+                let expected = PieceType::ALL.iter().find_map(|kind| {
+                    [Color::White, Color::Black]
+                        .into_iter()
+                        .find(|color| board.pieces(*color, *kind).contains(square))
+                        .map(|color| Piece::new(color, *kind))
+                });
+                assert_eq!(board.mailbox[square], expected, "{square}");
+            }
+        }
+
+        let tables = Tables::new();
+        let zob_keys = ZobKeys::new();
+        let mut rng = XorShiftRng::seed_from_u64(7);
+        let starts = [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q2/PPPBBPpP/R3K2R w KQkq - 0 1",
+            "rk5r/8/8/8/8/8/8/RK3R1R w Fha - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        ];
+        for game in 0..12 {
+            let mut board =
+                BoardState::state_from_string_fen(starts[game % starts.len()].to_string());
+            for _ in 0..60 {
+                let moves = generate(&board, &tables);
+                if moves.is_empty() {
+                    break;
+                }
+                for mv in &moves {
+                    let before = board.clone();
+                    board.make(mv, &zob_keys);
+                    check(&board);
+                    board.unmake(mv, &zob_keys);
+                    assert_eq!(board, before);
+                }
+                board.make(&moves[rng.next_u32() as usize % moves.len()], &zob_keys);
+            }
+        }
+    }
 }
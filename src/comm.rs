@@ -16,12 +16,13 @@ NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FO
 DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT
 OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
 */
-use std::io;
+use std::io::BufRead;
 
-// Returns a string from input
-pub fn engine_in() -> String {
+// Returns a line from input, or None once input is closed
+pub fn engine_in(input: &mut impl BufRead) -> Option<String> {
     let mut buffer = String::new();
-    let stdin = io::stdin();
-    stdin.read_line(&mut buffer).unwrap();
-    buffer.trim().to_string()
+    match input.read_line(&mut buffer) {
+        Ok(0) | Err(_) => None,
+        Ok(_) => Some(buffer.trim().to_string()),
+    }
 }
@@ -0,0 +1,840 @@
+/*
+Copyright 2025 Ethan Thummel
+
+Permission is hereby granted, free of charge, to any person obtaining a copy of this software and
+associated documentation files (the "Software"), to deal in the Software without restriction,
+including without limitation the rights to use, copy, modify, merge, publish, distribute,
+sublicense, and/or sell copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all copies or substantial
+portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT
+NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT
+OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+*/
+use std::io::{self, BufRead, Write};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rand_core::{RngCore, SeedableRng};
+use rand_xorshift::XorShiftRng;
+
+use crate::board::{BoardState, MoveRep};
+use crate::comm;
+use crate::eval::{self, EvalParams};
+use crate::generate::generate;
+use crate::nnue::Network;
+use crate::search::{id_search, perft, SearchClock, SearchOptions, SearchResult};
+use crate::strength::{self, Strength};
+use crate::tablebase::Tablebases;
+use crate::tables::Tables;
+use crate::tt::ZobKeys;
+use crate::uci::{GoOptions, UciCommand, UciError};
+
+// Most lines the MultiPV option allows
+const MAX_MULTIPV: usize = 256;
+const DEFAULT_ELO: u32 = 1600;
+
+// A search running on its own thread
+struct RunningSearch {
+    clock: Arc<SearchClock>,
+    // The time limit to switch to on ponderhit
+    ponder_limit: Option<u128>,
+    handle: JoinHandle<()>,
+}
+
+/// The uci side of the engine, reading commands from `input` and answering on `output`. Searches run
+/// on their own thread, so commands like stop are read while they think
+pub struct Engine<R: BufRead, W: Write + Send + 'static> {
+    input: R,
+    output: Arc<Mutex<W>>,
+    board: BoardState,
+    zob_keys: Arc<ZobKeys>,
+    tables: Arc<Tables>,
+    eval_params: Arc<EvalParams>,
+    network: Option<Arc<Network>>,
+    use_nnue: bool,
+    tablebases: Option<Arc<Tablebases>>,
+    multipv: usize,
+    // With UCI_LimitStrength the level comes from UCI_Elo, otherwise from Skill Level
+    limit_strength: bool,
+    elo: u32,
+    skill_level: u8,
+    chess960: bool,
+    // Weakened levels pick their moves at random
+    rng: XorShiftRng,
+    search: Option<RunningSearch>,
+}
+
+impl<R: BufRead, W: Write + Send + 'static> Engine<R, W> {
+    pub fn new(input: R, output: W) -> Engine<R, W> {
+        Engine {
+            input,
+            output: Arc::new(Mutex::new(output)),
+            board: BoardState::starting_state(),
+            zob_keys: Arc::new(ZobKeys::new()),
+            tables: Arc::new(Tables::new()),
+            eval_params: Arc::new(EvalParams::default()),
+            network: None,
+            use_nnue: false,
+            tablebases: None,
+            multipv: 1,
+            limit_strength: false,
+            elo: DEFAULT_ELO,
+            skill_level: strength::MAX_LEVEL,
+            chess960: false,
+            rng: XorShiftRng::seed_from_u64(
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |d| d.as_nanos() as u64),
+            ),
+            search: None,
+        }
+    }
+
+    /// Sets the evaluation weights, as the EvalFile option does
+    pub fn set_eval_params(&mut self, params: Arc<EvalParams>) {
+        self.eval_params = params;
+        self.attach_board_options();
+    }
+
+    fn out(&self) -> MutexGuard<'_, W> {
+        self.output.lock().unwrap()
+    }
+
+    /// Handles commands until quit, or until the input is closed
+    pub fn run(&mut self) -> io::Result<()> {
+        let mut quit = false;
+        while let Some(line) = comm::engine_in(&mut self.input) {
+            let running = match UciCommand::parse(&line) {
+                Ok(command) => self.handle(command)?,
+                Err(UciError::Empty) => true,
+                Err(e) => {
+                    writeln!(self.out(), "info string {e}")?;
+                    true
+                }
+            };
+            // The gui waits on our answers, so they can not sit in a buffer
+            self.out().flush()?;
+            if !running {
+                quit = true;
+                break;
+            }
+        }
+        // A search nobody can stop any more is stopped here, others are left to finish
+        self.finish_search(quit);
+        Ok(())
+    }
+
+    // Returns false once the engine should stop
+    fn handle(&mut self, command: UciCommand) -> io::Result<bool> {
+        match command {
+            UciCommand::Uci => {
+                let mut out = self.out();
+                writeln!(out, "id name nuttchess")?;
+                writeln!(out, "id author UraniumNutt / Ethan Thummel")?;
+                writeln!(out, "option name EvalFile type string default <empty>")?;
+                writeln!(out, "option name NNUEFile type string default <empty>")?;
+                writeln!(out, "option name UseNNUE type check default false")?;
+                writeln!(out, "option name TablebasePath type string default <empty>")?;
+                writeln!(out, "option name Ponder type check default false")?;
+                writeln!(
+                    out,
+                    "option name MultiPV type spin default 1 min 1 max {MAX_MULTIPV}"
+                )?;
+                writeln!(out, "option name UCI_Chess960 type check default false")?;
+                writeln!(
+                    out,
+                    "option name UCI_LimitStrength type check default false"
+                )?;
+                writeln!(
+                    out,
+                    "option name UCI_Elo type spin default {DEFAULT_ELO} min {} max {}",
+                    strength::MIN_ELO,
+                    strength::MAX_ELO
+                )?;
+                writeln!(
+                    out,
+                    "option name Skill Level type spin default {0} min 0 max {0}",
+                    strength::MAX_LEVEL
+                )?;
+                writeln!(out, "uciok")?;
+            }
+            UciCommand::IsReady => {
+                writeln!(self.out(), "readyok")?;
+            }
+            UciCommand::UciNewGame => {}
+            UciCommand::SetOption { name, value } => self.set_option(&name, &value)?,
+            UciCommand::Position { fen, moves } => {
+                let parsed_board = match fen {
+                    Some(fen) => BoardState::state_from_fen(fen.split(' ')),
+                    None => Ok(BoardState::starting_state()),
+                };
+                match parsed_board {
+                    Ok(b) => {
+                        let previous = std::mem::replace(&mut self.board, b);
+                        self.attach_board_options();
+                        for mv in moves {
+                            let played =
+                                self.board
+                                    .apply_string_move(mv, &self.tables, &self.zob_keys);
+                            // Keep the last good position instead of one part way through the moves
+                            if let Err(e) = played {
+                                self.board = previous;
+                                writeln!(self.out(), "info string Error in position moves: {e}")?;
+                                break;
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        writeln!(self.out(), "info string Error parsing fen string: {e}")?;
+                    }
+                }
+            }
+            UciCommand::Print => {
+                // Pretty print the board state
+                write!(self.out(), "{}", self.board.pretty_board())?;
+            }
+            UciCommand::Fen => {
+                writeln!(self.out(), "{}", self.board.to_fen())?;
+            }
+            UciCommand::Eval => {
+                // Print how the evaluation sees the current position. Search scores a position at
+                // the end of a line with its own move count standing in for the one before it
+                let number_moves = generate(&self.board, &self.tables).len();
+                let traced = eval::trace(&self.board, &self.tables, number_moves, number_moves);
+                write!(self.out(), "{traced}")?;
+            }
+            UciCommand::SaveEval(path) => {
+                // Write the evaluation parameters in use to a file
+                if let Err(e) = self.eval_params.save(&path) {
+                    writeln!(self.out(), "info string {e}")?;
+                }
+            }
+            UciCommand::Perft(depth) => {
+                if depth == 0 {
+                    writeln!(self.out(), "info string Perft depth must be at least 1")?;
+                } else {
+                    let mut out = self.output.lock().unwrap();
+                    perft(&mut self.board, depth, &self.zob_keys, &mut *out)?;
+                }
+            }
+            UciCommand::Go(options) => self.go(options)?,
+            UciCommand::PonderHit => {
+                // The expected move was played, so carry on searching as if it were our turn
+                if let Some(search) = &self.search {
+                    search.clock.release(search.ponder_limit);
+                }
+            }
+            UciCommand::Stop => self.finish_search(true),
+            UciCommand::Quit => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    // Gives the current board the evaluation and tables picked with setoption
+    fn attach_board_options(&mut self) {
+        self.board.set_eval_params(self.eval_params.clone());
+        self.board.set_network(match self.use_nnue {
+            true => self.network.clone(),
+            false => None,
+        });
+        self.board.set_tablebases(self.tablebases.clone());
+        self.board.set_chess960(self.chess960);
+    }
+
+    fn set_option(&mut self, name: &str, value: &str) -> io::Result<()> {
+        let unset = value.is_empty() || value == "<empty>";
+        match name {
+            "EvalFile" => {
+                if unset {
+                    self.eval_params = Arc::new(EvalParams::default());
+                } else {
+                    match EvalParams::load(value) {
+                        Ok(params) => self.eval_params = Arc::new(params),
+                        Err(e) => writeln!(self.out(), "info string {e}")?,
+                    }
+                }
+            }
+            "NNUEFile" => {
+                if unset {
+                    self.network = None;
+                } else {
+                    match Network::load(value) {
+                        Ok(n) => self.network = Some(Arc::new(n)),
+                        Err(e) => writeln!(self.out(), "info string {e}")?,
+                    }
+                }
+            }
+            "UseNNUE" => {
+                self.use_nnue = value == "true";
+                if self.use_nnue && self.network.is_none() {
+                    writeln!(
+                        self.out(),
+                        "info string UseNNUE is set, but no NNUEFile is loaded"
+                    )?;
+                }
+            }
+            "TablebasePath" => {
+                if unset {
+                    self.tablebases = None;
+                } else {
+                    match Tablebases::load_dir(value) {
+                        Ok(t) => {
+                            writeln!(self.out(), "info string Loaded {} tablebases", t.len())?;
+                            self.tablebases = Some(Arc::new(t));
+                        }
+                        Err(e) => writeln!(self.out(), "info string {e}")?,
+                    }
+                }
+            }
+            // The gui decides when to ponder, this only tells us it might
+            "Ponder" => {}
+            "MultiPV" => match value.parse::<usize>() {
+                Ok(lines) if (1..=MAX_MULTIPV).contains(&lines) => self.multipv = lines,
+                _ => writeln!(self.out(), "info string Invalid MultiPV value {value}")?,
+            },
+            "UCI_Chess960" => self.chess960 = value == "true",
+            "UCI_LimitStrength" => self.limit_strength = value == "true",
+            "UCI_Elo" => match value.parse::<u32>() {
+                Ok(elo) if (strength::MIN_ELO..=strength::MAX_ELO).contains(&elo) => self.elo = elo,
+                _ => writeln!(self.out(), "info string Invalid UCI_Elo value {value}")?,
+            },
+            "Skill Level" => match value.parse::<u8>() {
+                Ok(level) if level <= strength::MAX_LEVEL => self.skill_level = level,
+                _ => writeln!(self.out(), "info string Invalid Skill Level value {value}")?,
+            },
+            n => {
+                writeln!(self.out(), "info string Unknown option {n}")?;
+                return Ok(());
+            }
+        }
+        self.attach_board_options();
+        Ok(())
+    }
+
+    fn strength(&self) -> Strength {
+        match self.limit_strength {
+            true => Strength::from_elo(self.elo),
+            false => Strength::new(self.skill_level),
+        }
+    }
+
+    // Waits for the running search to print its move, stopping it first if asked to, or if it would
+    // never end by itself
+    fn finish_search(&mut self, stop: bool) {
+        if let Some(search) = self.search.take() {
+            if stop || search.clock.is_held() {
+                search.clock.stop();
+            }
+            let _ = search.handle.join();
+        }
+    }
+
+    fn go(&mut self, options: GoOptions) -> io::Result<()> {
+        self.finish_search(true);
+        if generate(&self.board, &self.tables).is_empty() {
+            writeln!(self.out(), "info string No legal moves")?;
+            return writeln!(self.out(), "bestmove 0000");
+        }
+
+        let (time, inc) = match self.board.white_to_move {
+            true => (options.wtime, options.winc),
+            false => (options.btime, options.binc),
+        };
+        let time_limit = match (options.movetime, time) {
+            (Some(ms), _) => Some(ms as u128),
+            (None, Some(time)) => {
+                let moves_left = options.movestogo.unwrap_or(20).max(2);
+                Some((time / moves_left + inc.unwrap_or(0) / 2) as u128)
+            }
+            (None, None) => None,
+        };
+        // Pondering and infinite searches go on until stop or ponderhit, and only then give a move
+        let unlimited = time_limit.is_none()
+            && options.depth.is_none()
+            && options.nodes.is_none()
+            && options.mate.is_none();
+        let clock = match options.ponder || options.infinite || unlimited {
+            true => SearchClock::held(),
+            false => SearchClock::new(time_limit),
+        };
+        if let Some(nodes) = options.nodes {
+            clock.set_node_limit(nodes);
+        }
+        // A mate in N moves is found within 2N plies
+        let mut max_depth = options.depth.unwrap_or(SearchOptions::default().max_depth);
+        if let Some(mate) = options.mate {
+            max_depth = max_depth.min(2 * mate as usize);
+        }
+        let search_options = SearchOptions {
+            max_depth: max_depth.max(1),
+            multipv: self.multipv,
+        };
+        // Moves that are not legal here are ignored, and if none are left every move is searched
+        let legal = generate(&self.board, &self.tables);
+        let root_moves: Vec<MoveRep> = legal
+            .into_iter()
+            .filter(|mv| {
+                let uci = mv.to_uci(self.chess960.then_some(&self.board.castle_rooks));
+                options.searchmoves.contains(&uci)
+            })
+            .collect();
+        // Weaker levels may search more lines than were asked for, but only those are shown
+        let strength = self.strength();
+        let search_options = strength.limit(search_options, &clock);
+        let shown = self.multipv;
+        let seed = self.rng.next_u64();
+        // Castles are written the Chess960 way with the castling rooks of the game
+        let chess960_rooks = self.chess960.then_some(self.board.castle_rooks);
+
+        let clock = Arc::new(clock);
+        let mut board = self.board.clone();
+        let (tables, zob_keys) = (self.tables.clone(), self.zob_keys.clone());
+        let (search_clock, output) = (clock.clone(), self.output.clone());
+        let handle = thread::spawn(move || {
+            let mut report = |depth: usize, lines: &[SearchResult]| {
+                let mut out = output.lock().unwrap();
+                for (k, line) in lines.iter().take(shown).enumerate() {
+                    let pv: Vec<String> = line
+                        .pv
+                        .iter()
+                        .map(|mv| mv.to_uci(chess960_rooks.as_ref()))
+                        .collect();
+                    let _ = writeln!(
+                        out,
+                        "info depth {depth} multipv {} score {} time {} pv {}",
+                        k + 1,
+                        line.uci_score(depth),
+                        search_clock.elapsed_ms(),
+                        pv.join(" ")
+                    );
+                }
+                let _ = out.flush();
+            };
+            let lines = id_search(
+                &mut board,
+                &tables,
+                &zob_keys,
+                &search_clock,
+                search_options,
+                &root_moves,
+                &mut report,
+            );
+            let result = strength.choose(&lines, &mut XorShiftRng::seed_from_u64(seed));
+            search_clock.wait();
+            let mut out = output.lock().unwrap();
+            let best = result.best_move().to_uci(chess960_rooks.as_ref());
+            let _ = match result.pv.get(1) {
+                Some(ponder) => writeln!(
+                    out,
+                    "bestmove {best} ponder {}",
+                    ponder.to_uci(chess960_rooks.as_ref())
+                ),
+                None => writeln!(out, "bestmove {best}"),
+            };
+            let _ = out.flush();
+        });
+        self.search = Some(RunningSearch {
+            clock,
+            ponder_limit: if options.ponder { time_limit } else { None },
+            handle,
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::MoveRep;
+
+    // Keeps everything the engine wrote, and what had been written each time it flushed
+    #[derive(Default)]
+    struct Recording {
+        written: Vec<u8>,
+        flushes: Vec<String>,
+    }
+
+    #[derive(Clone, Default)]
+    struct SharedOutput(Arc<Mutex<Recording>>);
+
+    impl Write for SharedOutput {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().written.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            let mut recording = self.0.lock().unwrap();
+            let text = String::from_utf8(recording.written.clone()).unwrap();
+            recording.flushes.push(text);
+            Ok(())
+        }
+    }
+
+    // Runs a whole session, returning everything the engine wrote
+    fn session(script: &str) -> String {
+        let output = SharedOutput::default();
+        Engine::new(script.as_bytes(), output.clone())
+            .run()
+            .unwrap();
+        let written = output.0.lock().unwrap().written.clone();
+        String::from_utf8(written).unwrap()
+    }
+
+    // A session without the search's progress lines
+    fn replies(script: &str) -> String {
+        session(script)
+            .lines()
+            .filter(|line| !line.starts_with("info depth"))
+            .map(|line| format!("{line}\n"))
+            .collect()
+    }
+
+    #[test]
+    fn handshake() {
+        assert_eq!(
+            session("uci\nisready\nquit\n"),
+            "id name nuttchess\n\
+             id author UraniumNutt / Ethan Thummel\n\
+             option name EvalFile type string default <empty>\n\
+             option name NNUEFile type string default <empty>\n\
+             option name UseNNUE type check default false\n\
+             option name TablebasePath type string default <empty>\n\
+             option name Ponder type check default false\n\
+             option name MultiPV type spin default 1 min 1 max 256\n\
+             option name UCI_Chess960 type check default false\n\
+             option name UCI_LimitStrength type check default false\n\
+             option name UCI_Elo type spin default 1600 min 800 max 2400\n\
+             option name Skill Level type spin default 20 min 0 max 20\n\
+             uciok\n\
+             readyok\n"
+        );
+    }
+
+    #[test]
+    fn position_go_stop() {
+        assert_eq!(
+            replies("position fen 6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1\ngo depth 2\nstop\nquit\n"),
+            "bestmove a1a8\n"
+        );
+        assert_eq!(
+            replies("position startpos moves e2e4 f7f6 d2d4 g7g5\ngo depth 2\nquit\n"),
+            "bestmove d1h5\n"
+        );
+    }
+
+    #[test]
+    fn bad_input() {
+        // Errors are reported and the session carries on, until the input runs out
+        assert_eq!(
+            session("go movetime\nposition\n\nfly\nsetoption name Hash value 1\nisready"),
+            "info string Expected a value after movetime\n\
+             info string Expected a value after position\n\
+             info string Unknown command fly\n\
+             info string Unknown option Hash\n\
+             readyok\n"
+        );
+        assert_eq!(
+            replies("position fen 7k/5Q2/6K1/8/8/8/8/8 b - - 0 1\ngo depth 3\n"),
+            "info string No legal moves\nbestmove 0000\n"
+        );
+    }
+
+    #[test]
+    fn quit() {
+        assert_eq!(session("quit\nisready\n"), "");
+    }
+
+    #[test]
+    fn output_flushed() {
+        let output = SharedOutput::default();
+        Engine::new("isready\nuci\nisready\n".as_bytes(), output.clone())
+            .run()
+            .unwrap();
+        let recording = output.0.lock().unwrap();
+        assert_eq!(recording.flushes.len(), 3);
+        assert_eq!(recording.flushes[0], "readyok\n");
+        assert!(recording.flushes[1].ends_with("uciok\n"));
+        assert!(recording.flushes[2].ends_with("uciok\nreadyok\n"));
+        assert_eq!(recording.flushes[2].len(), recording.written.len());
+    }
+
+    #[test]
+    fn ponder_move() {
+        let output = replies("position startpos\ngo depth 3\n");
+        let tokens: Vec<&str> = output.split_whitespace().collect();
+        assert_eq!(tokens.len(), 4, "{output}");
+        assert_eq!((tokens[0], tokens[2]), ("bestmove", "ponder"));
+
+        // The ponder move is a reply to the best move
+        let tables = Tables::new();
+        let zob_keys = ZobKeys::new();
+        let mut board = BoardState::starting_state();
+        board
+            .apply_string_move(tokens[1].to_string(), &tables, &zob_keys)
+            .unwrap();
+        let replies: Vec<String> = generate(&board, &tables)
+            .iter()
+            .map(MoveRep::to_string)
+            .collect();
+        assert!(replies.contains(&tokens[3].to_string()));
+    }
+
+    #[test]
+    fn ponderhit() {
+        // Nothing is played while pondering, even once the search is done
+        assert_eq!(
+            replies(
+                "position fen 6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1\n\
+                 go ponder wtime 100 btime 100\nisready\nponderhit\n"
+            ),
+            "readyok\nbestmove a1a8\n"
+        );
+        // A ponder miss stops the search, and its move is still given
+        assert_eq!(
+            replies(
+                "position fen 6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1\n\
+                 go ponder wtime 100 btime 100\nstop\nisready\n"
+            ),
+            "bestmove a1a8\nreadyok\n"
+        );
+    }
+
+    #[test]
+    fn infinite_until_stop() {
+        let output = replies("position startpos\ngo infinite\nisready\nstop\nisready\n");
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 3, "{output}");
+        assert_eq!(lines[0], "readyok");
+        assert!(lines[1].starts_with("bestmove "));
+        assert_eq!(lines[2], "readyok");
+    }
+
+    #[test]
+    fn multipv() {
+        let output = session("setoption name MultiPV value 3\nposition startpos\ngo depth 3\n");
+        for depth in 1..=3 {
+            let prefix = format!("info depth {depth} multipv ");
+            let lines: Vec<&str> = output.lines().filter(|l| l.starts_with(&prefix)).collect();
+            assert_eq!(lines.len(), 3, "{output}");
+            let mut first_moves = Vec::new();
+            for (k, line) in lines.iter().enumerate() {
+                let tokens: Vec<&str> = line.split_whitespace().collect();
+                assert_eq!(tokens[4], (k + 1).to_string());
+                let pv = tokens.iter().position(|&t| t == "pv").unwrap();
+                first_moves.push(tokens[pv + 1]);
+            }
+            first_moves.sort();
+            first_moves.dedup();
+            assert_eq!(first_moves.len(), 3, "{output}");
+        }
+        assert!(output.ends_with("\n") && output.contains("bestmove "));
+
+        // More lines than legal moves just gives every move
+        let output = session(
+            "setoption name MultiPV value 5\n\
+             position fen 7k/8/8/8/8/8/8/K7 w - - 0 1\ngo depth 2\n",
+        );
+        assert_eq!(
+            output
+                .lines()
+                .filter(|l| l.starts_with("info depth 2 "))
+                .count(),
+            3
+        );
+
+        assert_eq!(
+            session("setoption name MultiPV value 0\nsetoption name MultiPV value x\n"),
+            "info string Invalid MultiPV value 0\ninfo string Invalid MultiPV value x\n"
+        );
+    }
+
+    #[test]
+    fn mate_score() {
+        let output = session("position fen 6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1\ngo depth 3\n");
+        assert!(
+            output.contains("info depth 3 multipv 1 score mate 1 "),
+            "{output}"
+        );
+        let output = session("position fen 4r3/8/8/7p/8/p6P/P1k5/K7 w - - 0 1\ngo depth 3\n");
+        assert!(
+            output.contains("info depth 3 multipv 1 score mate -1 "),
+            "{output}"
+        );
+    }
+
+    #[test]
+    fn tablebase_mate_score() {
+        use crate::tablebase::{self, TbValue};
+        let directory = std::env::temp_dir().join(format!("nuttchess-tb-{}", std::process::id()));
+        let directory = directory.to_str().unwrap().to_string();
+        tablebase::run(&[directory.clone(), "KQK".to_string()]).unwrap();
+        let fen = "8/8/8/2k5/8/8/8/K2Q4 w - - 0 1";
+        let value = tablebase::Tablebases::load_dir(&directory)
+            .unwrap()
+            .probe(&BoardState::state_from_string_fen(fen.to_string()));
+        let output = session(&format!(
+            "setoption name TablebasePath value {directory}\nposition fen {fen}\ngo depth 2\n"
+        ));
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        // Tablebase wins are reported as mates, counting the plies stored in the table
+        let Some(TbValue::Win(plies)) = value else {
+            panic!("{value:?}");
+        };
+        let mate = format!("info depth 2 multipv 1 score mate {} ", plies.div_ceil(2));
+        assert!(output.contains(&mate), "{output}");
+    }
+
+    #[test]
+    fn go_nodes() {
+        // A node limit alone ends the search by itself, without a stop
+        let output = replies("position startpos\ngo nodes 1000\n");
+        assert!(output.starts_with("bestmove "), "{output}");
+    }
+
+    #[test]
+    fn go_mate() {
+        // A mate in one is searched no deeper than two plies
+        let output = session("position fen 6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1\ngo mate 1\n");
+        assert!(
+            output.contains("info depth 2 multipv 1 score mate 1 "),
+            "{output}"
+        );
+        assert!(!output.contains("info depth 3 "), "{output}");
+        assert!(output.ends_with("bestmove a1a8\n"), "{output}");
+    }
+
+    #[test]
+    fn go_searchmoves() {
+        // Only the listed moves are tried at the root, even when another move mates
+        let fen = "6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1";
+        let output = replies(&format!(
+            "position fen {fen}\ngo searchmoves g1f2 depth 3\n"
+        ));
+        assert!(output.starts_with("bestmove g1f2 "), "{output}");
+        let output = replies("position startpos\ngo searchmoves a2a3 depth 3\n");
+        assert!(output.starts_with("bestmove a2a3"), "{output}");
+    }
+
+    #[test]
+    fn startup_eval_params() {
+        // Parameters given at startup hold for the first position and every one after it
+        let with_params = |script: &str| {
+            let output = SharedOutput::default();
+            let mut engine = Engine::new(script.as_bytes(), output.clone());
+            engine.set_eval_params(Arc::new(EvalParams {
+                knight_table: [0; 64],
+                ..EvalParams::default()
+            }));
+            engine.run().unwrap();
+            let written = output.0.lock().unwrap().written.clone();
+            String::from_utf8(written).unwrap()
+        };
+        for script in ["eval\n", "position startpos moves g1f3\neval\n"] {
+            assert_ne!(with_params(script), session(script), "{script}");
+        }
+    }
+
+    #[test]
+    fn limit_strength() {
+        // The lowest level only searches one ply, whichever way it is set
+        for options in [
+            "setoption name Skill Level value 0\n",
+            "setoption name UCI_LimitStrength value true\nsetoption name UCI_Elo value 800\n",
+        ] {
+            let output = session(&format!("{options}position startpos\ngo depth 4\n"));
+            let lines: Vec<&str> = output.lines().collect();
+            assert_eq!(lines.len(), 2, "{output}");
+            assert!(lines[0].starts_with("info depth 1 multipv 1 "));
+            assert!(lines[1].starts_with("bestmove "));
+        }
+
+        // Limiting strength uses the Elo in place of the skill level
+        let output = replies(
+            "setoption name Skill Level value 0\nsetoption name UCI_LimitStrength value true\n\
+             setoption name UCI_Elo value 2400\n\
+             position fen 6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1\ngo depth 2\n",
+        );
+        assert_eq!(output, "bestmove a1a8\n");
+
+        assert_eq!(
+            session("setoption name UCI_Elo value 3000\nsetoption name Skill Level value 21\n"),
+            "info string Invalid UCI_Elo value 3000\ninfo string Invalid Skill Level value 21\n"
+        );
+    }
+
+    #[test]
+    fn chess960() {
+        // Castles are read as the king taking its rook, with or without the option
+        let script =
+            "position fen 1r2k3/3p4/8/8/8/8/1P6/RK6 w Ab - 0 1 moves b1a1 e8b8\ngo perft 1\n";
+        for options in ["", "setoption name UCI_Chess960 value true\n"] {
+            let output = session(&format!("{options}{script}"));
+            assert!(output.contains("d1d7 1\n"), "{output}");
+        }
+
+        // and only written that way with it. Otherwise the castle and the king stepping to c1 look
+        // the same
+        let script = "position fen 4k3/8/8/8/8/8/8/RK6 w A - 0 1\ngo perft 1\n";
+        let output = session(script);
+        assert_eq!(output.matches("b1c1 1\n").count(), 2, "{output}");
+        let output = session(&format!("setoption name UCI_Chess960 value true\n{script}"));
+        assert_eq!(output.matches("b1c1 1\n").count(), 1, "{output}");
+        assert!(output.contains("b1a1 1\n"), "{output}");
+    }
+
+    #[test]
+    fn fen() {
+        assert_eq!(
+            session("position startpos moves e2e4 c7c5 g1f3\nfen\n"),
+            "rnbqkbnr/pp1ppppp/8/2p5/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2\n"
+        );
+    }
+
+    #[test]
+    fn illegal_position_moves() {
+        // The whole move list is refused, leaving the previous position
+        assert_eq!(
+            session(
+                "position startpos moves e2e4
+position startpos moves e2e4 e7e5 e1e3
+fen
+"
+            ),
+            "info string Error in position moves: Illegal move \"e1e3\"\n\
+             rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1\n"
+        );
+        assert_eq!(
+            session(
+                "position startpos moves e3e4
+fen
+"
+            ),
+            "info string Error in position moves: Illegal move \"e3e4\"\n\
+             rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1\n"
+        );
+    }
+
+    #[test]
+    fn illegal_position_fen() {
+        // A fen with a missing king is refused, leaving the previous position
+        assert_eq!(
+            session(
+                "position startpos moves e2e4
+position fen 8/8/8/8/8/8/8/4K3 w - - 0 1
+fen
+"
+            ),
+            "info string Error parsing fen string: Expected one Black king, found 0\n\
+             rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1\n"
+        );
+    }
+}
@@ -0,0 +1,340 @@
+/*
+Copyright 2025 Ethan Thummel
+
+Permission is hereby granted, free of charge, to any person obtaining a copy of this software and
+associated documentation files (the "Software"), to deal in the Software without restriction,
+including without limitation the rights to use, copy, modify, merge, publish, distribute,
+sublicense, and/or sell copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all copies or substantial
+portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT
+NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT
+OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+*/
+use std::sync::Arc;
+
+use crate::{
+    board::{BoardState, MoveRep},
+    eval::EvalParams,
+    search::{id_search, SearchClock, SearchOptions, MAX_DEPTH},
+    tables::Tables,
+    tt::ZobKeys,
+};
+
+/// A position from an EPD line, with the operations written after it
+#[derive(Clone, Debug)]
+pub struct EpdPosition {
+    pub board: BoardState,
+    /// Opcodes and their operands in the order they were written. Quotes are removed from strings
+    pub operations: Vec<(String, Vec<String>)>,
+}
+
+impl EpdPosition {
+    /// Operands of the first operation with the opcode
+    pub fn operands(&self, opcode: &str) -> Option<&[String]> {
+        self.operations
+            .iter()
+            .find(|(op, _)| op == opcode)
+            .map(|(_, operands)| operands.as_slice())
+    }
+
+    /// The `id` of the position, or an empty string
+    pub fn id(&self) -> &str {
+        match self.operands("id").and_then(|operands| operands.first()) {
+            Some(id) => id,
+            None => "",
+        }
+    }
+
+    /// The moves given in SAN by an operation such as `bm` or `am`
+    pub fn moves(&self, opcode: &str, tables: &Tables) -> Result<Vec<MoveRep>, String> {
+        match self.operands(opcode) {
+            Some(operands) => operands
+                .iter()
+                .map(|san| self.board.parse_san(san, tables))
+                .collect(),
+            None => Ok(Vec::new()),
+        }
+    }
+}
+
+/// Splits operations on semicolons and operands on whitespace, keeping quoted strings whole
+fn parse_operations(text: &str) -> Result<Vec<(String, Vec<String>)>, String> {
+    let mut operations = Vec::new();
+    let mut tokens: Vec<String> = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            ';' => {
+                if tokens.is_empty() {
+                    return Err("Empty operation".to_string());
+                }
+                let opcode = tokens.remove(0);
+                operations.push((opcode, std::mem::take(&mut tokens)));
+            }
+            '"' => {
+                let mut string = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => string.extend(chars.next()),
+                        Some(c) => string.push(c),
+                        None => return Err("Unterminated string".to_string()),
+                    }
+                }
+                tokens.push(string);
+            }
+            c if c.is_whitespace() => {}
+            c => {
+                let mut token = c.to_string();
+                while let Some(&next) = chars.peek() {
+                    if next.is_whitespace() || next == ';' || next == '"' {
+                        break;
+                    }
+                    token.push(next);
+                    chars.next();
+                }
+                tokens.push(token);
+            }
+        }
+    }
+    if !tokens.is_empty() {
+        return Err(format!(
+            "Operation \"{}\" is missing its \";\"",
+            tokens.join(" ")
+        ));
+    }
+    Ok(operations)
+}
+
+/// Parses an EPD line: the first four fields of a fen string followed by operations such as
+/// `bm Qxf7+; id "WAC.001";`. The `hmvc` and `fmvn` operations give the move counters
+pub fn parse_epd(line: &str) -> Result<EpdPosition, String> {
+    let line = line.trim();
+    let mut fields = Vec::with_capacity(4);
+    let mut rest = line;
+    for _ in 0..4 {
+        rest = rest.trim_start();
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        if end == 0 {
+            return Err(format!("Not enough fields in \"{line}\""));
+        }
+        fields.push(&rest[..end]);
+        rest = &rest[end..];
+    }
+
+    let operations = parse_operations(rest).map_err(|e| format!("{e} in \"{line}\""))?;
+    let counter = |opcode: &str, default| {
+        operations
+            .iter()
+            .find(|(op, _)| op == opcode)
+            .and_then(|(_, operands)| operands.first())
+            .map_or(default, |operand| operand.as_str())
+    };
+    fields.push(counter("hmvc", "0"));
+    fields.push(counter("fmvn", "1"));
+    let board = BoardState::state_from_fen(fields.into_iter())?;
+    Ok(EpdPosition { board, operations })
+}
+
+/// Searches the position, returning the move played and if it solved the position: the move must
+/// be one of the `bm` moves, if there are any, and none of the `am` moves
+pub fn solve(
+    position: &EpdPosition,
+    tables: &Tables,
+    zob_keys: &ZobKeys,
+    clock: &SearchClock,
+    max_depth: usize,
+) -> Result<(MoveRep, bool), String> {
+    let best = position.moves("bm", tables)?;
+    let avoid = position.moves("am", tables)?;
+    if best.is_empty() && avoid.is_empty() {
+        return Err("No bm or am operation".to_string());
+    }
+
+    let mut board = position.board.clone();
+    let options = SearchOptions {
+        max_depth,
+        ..SearchOptions::default()
+    };
+    let lines = id_search(
+        &mut board,
+        tables,
+        zob_keys,
+        clock,
+        options,
+        &[],
+        &mut |_, _| {},
+    );
+    let played = match lines[0].pv.first() {
+        Some(mv) => *mv,
+        None => return Err("No legal moves".to_string()),
+    };
+    let solved = (best.is_empty() || best.contains(&played)) && !avoid.contains(&played);
+    Ok((played, solved))
+}
+
+/// Time for each position when no limit is given
+pub const DEFAULT_LIMIT: (Option<u128>, usize) = (Some(1000), MAX_DEPTH);
+
+/// Parses a `movetime <ms>` or `depth <depth>` limit into a time limit and maximum depth
+pub fn parse_limit(name: &str, value: &str) -> Result<(Option<u128>, usize), String> {
+    match name {
+        "movetime" => match value.parse::<u128>() {
+            Ok(t) => Ok((Some(t), MAX_DEPTH)),
+            Err(_) => Err(format!("Error parsing movetime \"{value}\"")),
+        },
+        "depth" => match value.parse::<usize>() {
+            Ok(d) if d > 0 => Ok((None, d)),
+            _ => Err(format!("Error parsing depth \"{value}\"")),
+        },
+        _ => Err(format!("Unknown limit \"{name}\"")),
+    }
+}
+
+/// Runs a test suite of EPD positions for a fixed time or depth, listing the positions that were
+/// failed
+pub fn run(args: &[String], params: &Arc<EvalParams>) -> Result<(), String> {
+    let usage = || "Usage: testsuite <epd file> [movetime <ms> | depth <depth>]".to_string();
+    let path = match args.first() {
+        Some(p) => p,
+        None => return Err(usage()),
+    };
+    let (movetime, max_depth) = match (args.get(1), args.get(2)) {
+        (None, _) => DEFAULT_LIMIT,
+        (Some(name), Some(value)) => parse_limit(name, value)?,
+        _ => return Err(usage()),
+    };
+    let text = match std::fs::read_to_string(path) {
+        Ok(t) => t,
+        Err(e) => return Err(format!("Could not read \"{path}\": {e}")),
+    };
+
+    let tables = Tables::new();
+    let zob_keys = ZobKeys::new();
+    let (mut solved, mut failed, mut errors) = (0, 0, 0);
+    for (line_number, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let result = parse_epd(line).and_then(|mut position| {
+            position.board.set_eval_params(params.clone());
+            let clock = SearchClock::new(movetime);
+            let (played, ok) = solve(&position, &tables, &zob_keys, &clock, max_depth)?;
+            Ok((position, played, ok))
+        });
+        match result {
+            Ok((_, _, true)) => solved += 1,
+            Ok((position, played, false)) => {
+                failed += 1;
+                let expected: Vec<String> = ["bm", "am"]
+                    .into_iter()
+                    .filter_map(|op| Some(format!("{op} {}", position.operands(op)?.join(" "))))
+                    .collect();
+                let name = match position.id() {
+                    "" => format!("line {}", line_number + 1),
+                    id => id.to_string(),
+                };
+                println!(
+                    "info string failed {name} played {}, expected {}",
+                    played.to_san(&position.board, &tables),
+                    expected.join(", ")
+                );
+            }
+            Err(e) => {
+                errors += 1;
+                println!("info string line {}: {e}", line_number + 1);
+            }
+        }
+    }
+    println!(
+        "info string solved {solved}/{} positions, {failed} failed, {errors} errors",
+        solved + failed
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_wac() {
+        let tables = Tables::new();
+        let position = parse_epd(
+            "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id \"WAC.001\";",
+        )
+        .unwrap();
+        assert_eq!(position.id(), "WAC.001");
+        assert_eq!(
+            position.board.to_fen(),
+            "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - 0 1"
+        );
+        let best = position.moves("bm", &tables).unwrap();
+        assert_eq!(best.len(), 1);
+        assert_eq!(position.board.move_to_uci(&best[0]), "g3g6");
+        assert!(position.moves("am", &tables).unwrap().is_empty());
+    }
+
+    #[test]
+    fn parse_operations() {
+        let position = parse_epd(
+            "4k3/8/8/8/8/8/4P3/4K3 b - - acd 12;ce -35; c0 \"a \\\"quoted\\\"; string\"; \
+             bm Kd7 Ke7; hmvc 3; fmvn 40; noop;",
+        )
+        .unwrap();
+        assert_eq!(position.board.to_fen(), "4k3/8/8/8/8/8/4P3/4K3 b - - 3 40");
+        assert_eq!(position.operands("acd"), Some(&["12".to_string()][..]));
+        assert_eq!(position.operands("ce"), Some(&["-35".to_string()][..]));
+        assert_eq!(
+            position.operands("c0"),
+            Some(&["a \"quoted\"; string".to_string()][..])
+        );
+        assert_eq!(position.operands("bm").unwrap().len(), 2);
+        assert_eq!(position.operands("noop"), Some(&[][..]));
+        assert_eq!(position.operands("pv"), None);
+        assert_eq!(position.id(), "");
+
+        assert!(parse_epd("4k3/8/8/8/8/8/4P3/4K3 b -").is_err());
+        assert!(parse_epd("4k3/8/8/8/8/8/4P3/4K3 b - - bm Kd7").is_err());
+        assert!(parse_epd("4k3/8/8/8/8/8/4P3/4K3 b - - c0 \"open;").is_err());
+        assert!(parse_epd("4k3/8/8/8/8/8/4P3/4K3 b - - ; bm Kd7;").is_err());
+        assert!(parse_epd("4k3/8/8/8/8/8/4P3/4K3 b - - 0 1").is_err());
+    }
+
+    #[test]
+    fn solve_positions() {
+        let tables = Tables::new();
+        let zob_keys = ZobKeys::new();
+        let solve = |line: &str| {
+            let position = parse_epd(line).unwrap();
+            solve(&position, &tables, &zob_keys, &SearchClock::new(None), 3)
+                .map(|(played, solved)| (position.board.move_to_uci(&played), solved))
+        };
+
+        let mate = "6k1/5ppp/8/8/8/8/8/R5K1 w - -";
+        assert_eq!(
+            solve(&format!("{mate} bm Ra8#;")),
+            Ok(("a1a8".to_string(), true))
+        );
+        assert_eq!(
+            solve(&format!("{mate} am Ra7;")),
+            Ok(("a1a8".to_string(), true))
+        );
+        assert_eq!(
+            solve(&format!("{mate} bm Kf1;")),
+            Ok(("a1a8".to_string(), false))
+        );
+        assert_eq!(
+            solve(&format!("{mate} am Ra8;")),
+            Ok(("a1a8".to_string(), false))
+        );
+        assert!(solve(&format!("{mate} id \"no answer\";")).is_err());
+        assert!(solve(&format!("{mate} bm Ra9;")).is_err());
+    }
+}
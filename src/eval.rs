@@ -16,9 +16,10 @@ NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FO
 DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT
 OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
 */
+use crate::bitbase;
 use crate::board::*;
-use crate::generate::*;
 use crate::tables::*;
+use crate::types::{Color, Square};
 
 // Constants
 pub const WIN: isize = 10000;
@@ -129,43 +130,588 @@ pub const BLACK_MAP: [usize; 64] = [
     63, 62, 61, 60, 59, 58, 57, 56,  
 ];
 
+/// All of the weights used by the evaluation. The constants above are the built in default set
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EvalParams {
+    pub king: isize,
+    pub queen: isize,
+    pub rook: isize,
+    pub bishop: isize,
+    pub knight: isize,
+    pub pawn: isize,
+
+    pub pawn_table: [isize; 64],
+    pub knight_table: [isize; 64],
+    pub bishop_table: [isize; 64],
+    pub rook_table: [isize; 64],
+    pub queen_table: [isize; 64],
+    pub king_table: [isize; 64],
+}
+
+impl Default for EvalParams {
+    fn default() -> EvalParams {
+        EvalParams {
+            king: KING,
+            queen: QUEEN,
+            rook: ROOK,
+            bishop: BISHOP,
+            knight: KNIGHT,
+            pawn: PAWN,
+
+            pawn_table: PAWN_TABLE,
+            knight_table: KNIGHT_TABLE,
+            bishop_table: BISHOP_TABLE,
+            rook_table: ROOK_TABLE,
+            queen_table: QUEEN_TABLE,
+            king_table: KING_TABLE,
+        }
+    }
+}
+
+impl EvalParams {
+    /// Names of every parameter, in the order they are written to a parameter file
+    pub const NAMES: [&'static str; 12] = [
+        "king",
+        "queen",
+        "rook",
+        "bishop",
+        "knight",
+        "pawn",
+        "pawn_table",
+        "knight_table",
+        "bishop_table",
+        "rook_table",
+        "queen_table",
+        "king_table",
+    ];
+
+    /// Get the piece square table of a piece type
+    pub fn table(&self, piece: PieceType) -> &[isize; 64] {
+        match piece {
+            PieceType::Pawn => &self.pawn_table,
+            PieceType::Knight => &self.knight_table,
+            PieceType::Bishop => &self.bishop_table,
+            PieceType::Rook => &self.rook_table,
+            PieceType::Queen => &self.queen_table,
+            PieceType::King => &self.king_table,
+        }
+    }
+
+    /// Get a parameter by name. Single values are returned as a slice of length one
+    pub fn field(&self, name: &str) -> Option<&[isize]> {
+        match name {
+            "king" => Some(std::slice::from_ref(&self.king)),
+            "queen" => Some(std::slice::from_ref(&self.queen)),
+            "rook" => Some(std::slice::from_ref(&self.rook)),
+            "bishop" => Some(std::slice::from_ref(&self.bishop)),
+            "knight" => Some(std::slice::from_ref(&self.knight)),
+            "pawn" => Some(std::slice::from_ref(&self.pawn)),
+            "pawn_table" => Some(&self.pawn_table),
+            "knight_table" => Some(&self.knight_table),
+            "bishop_table" => Some(&self.bishop_table),
+            "rook_table" => Some(&self.rook_table),
+            "queen_table" => Some(&self.queen_table),
+            "king_table" => Some(&self.king_table),
+            _ => None,
+        }
+    }
+
+    /// Get a mutable parameter by name. Single values are returned as a slice of length one
+    pub fn field_mut(&mut self, name: &str) -> Option<&mut [isize]> {
+        match name {
+            "king" => Some(std::slice::from_mut(&mut self.king)),
+            "queen" => Some(std::slice::from_mut(&mut self.queen)),
+            "rook" => Some(std::slice::from_mut(&mut self.rook)),
+            "bishop" => Some(std::slice::from_mut(&mut self.bishop)),
+            "knight" => Some(std::slice::from_mut(&mut self.knight)),
+            "pawn" => Some(std::slice::from_mut(&mut self.pawn)),
+            "pawn_table" => Some(&mut self.pawn_table),
+            "knight_table" => Some(&mut self.knight_table),
+            "bishop_table" => Some(&mut self.bishop_table),
+            "rook_table" => Some(&mut self.rook_table),
+            "queen_table" => Some(&mut self.queen_table),
+            "king_table" => Some(&mut self.king_table),
+            _ => None,
+        }
+    }
+
+    /// Parse a parameter file. Each entry is a name followed by its value(s), tables being 64 values
+    /// laid out like `PAWN_TABLE`. Values may be separated by whitespace or commas, and anything after a
+    /// '#' is a comment. Entries that are left out keep their default value
+    pub fn parse(text: &str) -> Result<EvalParams, String> {
+        let mut params = EvalParams::default();
+        let mut tokens = text
+            .lines()
+            .map(|line| line.split('#').next().unwrap_or(""))
+            .flat_map(|line| line.split(|c: char| c.is_whitespace() || c == ','))
+            .filter(|token| !token.is_empty());
+
+        while let Some(name) = tokens.next() {
+            let field = match params.field_mut(name) {
+                Some(f) => f,
+                None => return Err(format!("Unknown parameter \"{name}\"")),
+            };
+            for value in field.iter_mut() {
+                match tokens.next() {
+                    Some(token) => match token.parse::<isize>() {
+                        Ok(v) => *value = v,
+                        Err(_) => {
+                            return Err(format!("Error parsing value \"{token}\" of \"{name}\""))
+                        }
+                    },
+                    None => return Err(format!("Not enough values found for \"{name}\"")),
+                }
+            }
+        }
+        Ok(params)
+    }
+
+    /// Write the parameters out in the format read by `parse`
+    pub fn to_text(&self) -> String {
+        let mut text = String::from("# nuttchess evaluation parameters\n");
+        for name in EvalParams::NAMES {
+            let values = self.field(name).unwrap();
+            if values.len() == 1 {
+                text.push_str(&format!("{name} {}\n", values[0]));
+            } else {
+                text.push_str(&format!("\n{name}\n"));
+                for row in values.chunks(8) {
+                    let row: Vec<String> = row.iter().map(|v| format!("{v:4}")).collect();
+                    text.push_str(&row.join(","));
+                    text.push('\n');
+                }
+            }
+        }
+        text
+    }
+
+    /// Write the parameters out as rust constants, laid out like the defaults in this file
+    pub fn to_rust(&self) -> String {
+        let mut text = String::from("// Piece values\n");
+        for name in EvalParams::NAMES {
+            let values = self.field(name).unwrap();
+            let const_name = name.to_uppercase();
+            if values.len() == 1 {
+                text.push_str(&format!("pub const {const_name}: isize = {};\n", values[0]));
+            } else {
+                text.push_str(&format!(
+                    "\n#[rustfmt::skip]\npub const {const_name}: [isize; 64] = [\n"
+                ));
+                for row in values.chunks(8) {
+                    let row: String = row.iter().map(|v| format!("{v:3},")).collect();
+                    text.push_str(&format!("    {row}\n"));
+                }
+                text.push_str("];\n");
+            }
+        }
+        text
+    }
+
+    /// Load the parameters from a file
+    pub fn load(path: &str) -> Result<EvalParams, String> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => EvalParams::parse(&text),
+            Err(e) => Err(format!("Could not read \"{path}\": {e}")),
+        }
+    }
+
+    /// Save the parameters to a file
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        std::fs::write(path, self.to_text()).map_err(|e| format!("Could not write \"{path}\": {e}"))
+    }
+}
+
+/// Receives each term of the evaluation as it is computed. All values are from whites point of view
+pub trait Tracer {
+    // Lets the evaluation skip the per side breakdown when nobody is listening
+    const ACTIVE: bool;
+    fn term(
+        &mut self,
+        name: &'static str,
+        white: Option<isize>,
+        black: Option<isize>,
+        total: isize,
+    );
+    fn scale(&mut self, _factor: isize) {}
+}
+
+/// The tracer used by search, which records nothing
+pub struct NoTrace;
+
+impl Tracer for NoTrace {
+    const ACTIVE: bool = false;
+    #[inline]
+    fn term(&mut self, _: &'static str, _: Option<isize>, _: Option<isize>, _: isize) {}
+}
+
+/// A recorded breakdown of the evaluation, made by the `eval` uci command
+#[derive(Debug, Default)]
+pub struct EvalTrace {
+    pub terms: Vec<(&'static str, Option<isize>, Option<isize>, isize)>,
+    pub phase: isize,
+    pub scale: isize,
+    pub white_to_move: bool,
+    // The move counts the mobility term was given
+    pub number_moves: usize,
+    pub last_number_moves: usize,
+    pub score: isize,
+}
+
+impl Tracer for EvalTrace {
+    const ACTIVE: bool = true;
+    fn term(
+        &mut self,
+        name: &'static str,
+        white: Option<isize>,
+        black: Option<isize>,
+        total: isize,
+    ) {
+        self.terms.push((name, white, black, total));
+    }
+    fn scale(&mut self, factor: isize) {
+        self.scale = factor;
+    }
+}
+
+impl std::fmt::Display for EvalTrace {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        fn cell(value: Option<isize>) -> String {
+            value.map_or("-".to_string(), |v| v.to_string())
+        }
+        writeln!(
+            f,
+            "{:>14} | {:>7} | {:>7} | {:>7}",
+            "Term", "White", "Black", "Total"
+        )?;
+        writeln!(f, "{:-<15}+{:-<9}+{:-<9}+{:-<8}", "", "", "", "")?;
+        for (name, white, black, total) in &self.terms {
+            writeln!(
+                f,
+                "{name:>14} | {:>7} | {:>7} | {total:>7}",
+                cell(*white),
+                cell(*black)
+            )?;
+        }
+        writeln!(f)?;
+        writeln!(f, "Phase: {} / {}", self.phase, MAX_PHASE)?;
+        writeln!(f, "Scale factor: {} / {}", self.scale, SCALE_NORMAL)?;
+        writeln!(
+            f,
+            "Mobility: {} moves against {} for the position before",
+            self.number_moves, self.last_number_moves
+        )?;
+        let white_score = if self.white_to_move {
+            self.score
+        } else {
+            -self.score
+        };
+        writeln!(
+            f,
+            "Final evaluation: {} (side to move), {white_score} (white)",
+            self.score
+        )
+    }
+}
+
 /// Returns a score for the given board position
-#[allow(unused_variables)]
 pub fn eval(
     board: &BoardState,
     tables: &Tables,
     number_moves: usize,
     last_number_moves: usize,
 ) -> isize {
+    eval_traced(board, tables, number_moves, last_number_moves, &mut NoTrace)
+}
+
+/// The evaluation, reporting each term to the tracer
+#[allow(unused_variables)]
+pub fn eval_traced<T: Tracer>(
+    board: &BoardState,
+    tables: &Tables,
+    number_moves: usize,
+    last_number_moves: usize,
+    trace: &mut T,
+) -> isize {
+    // Used to turn side to move relative terms into white relative ones for the tracer
+    let sign = if board.white_to_move { 1 } else { -1 };
+
+    if let Some(network) = &board.network {
+        let score = network.evaluate(&board.accumulator, board.white_to_move);
+        if T::ACTIVE {
+            trace.term("NNUE", None, None, sign * score);
+        }
+        return score;
+    }
+
     // Material evaluation
-    material_value(board)
-        + (0.1 * (number_moves as f64 - last_number_moves as f64)) as isize
-        + board.piece_square_score
+    let material = material_value(board);
+    let mobility = (0.1 * (number_moves as f64 - last_number_moves as f64)) as isize;
+    let piece_square = board.piece_square_score;
+    let mop_up = match board.white_to_move {
+        true => mop_up_side(board, tables, true) - mop_up_side(board, tables, false),
+        false => mop_up_side(board, tables, false) - mop_up_side(board, tables, true),
+    };
+    let known_win = match board.white_to_move {
+        true => known_win_side(board, true) - known_win_side(board, false),
+        false => known_win_side(board, false) - known_win_side(board, true),
+    };
+    if T::ACTIVE {
+        trace.term(
+            "Material",
+            Some(material_side(board, true)),
+            Some(material_side(board, false)),
+            sign * material,
+        );
+        trace.term(
+            "Piece square",
+            Some(piece_square_side(board, true)),
+            Some(piece_square_side(board, false)),
+            sign * piece_square,
+        );
+        trace.term("Mobility", None, None, sign * mobility);
+        trace.term(
+            "Mop up",
+            Some(mop_up_side(board, tables, true)),
+            Some(mop_up_side(board, tables, false)),
+            sign * mop_up,
+        );
+        trace.term(
+            "Known win",
+            Some(known_win_side(board, true)),
+            Some(known_win_side(board, false)),
+            sign * known_win,
+        );
+    }
+
+    // Known drawish material pulls the score towards a draw
+    let unscaled = material + mobility + piece_square + mop_up + known_win;
+    let scale = scale_factor(board);
+    let score = unscaled * scale / SCALE_NORMAL;
+    if T::ACTIVE {
+        trace.scale(scale);
+        trace.term("Scaling", None, None, sign * (score - unscaled));
+    }
+    score
+}
+
+/// Runs the evaluation on a position with a tracer attached. The move counts for the mobility term
+/// are taken the same way as by `eval`
+pub fn trace(
+    board: &BoardState,
+    tables: &Tables,
+    number_moves: usize,
+    last_number_moves: usize,
+) -> EvalTrace {
+    let mut trace = EvalTrace {
+        phase: game_phase(board),
+        scale: SCALE_NORMAL,
+        white_to_move: board.white_to_move,
+        number_moves,
+        last_number_moves,
+        ..EvalTrace::default()
+    };
+    trace.score = eval_traced(board, tables, number_moves, last_number_moves, &mut trace);
+    trace
+}
+
+// The phase of the starting position
+pub const MAX_PHASE: isize = 24;
+
+/// How much non pawn material is left, from `MAX_PHASE` at the start down to 0 in a pawn ending
+pub fn game_phase(board: &BoardState) -> isize {
+    let minors = (board.pieces(Color::White, PieceType::Knight)
+        | board.pieces(Color::White, PieceType::Bishop)
+        | board.pieces(Color::Black, PieceType::Knight)
+        | board.pieces(Color::Black, PieceType::Bishop))
+    .count() as isize;
+    let rooks = (board.pieces(Color::White, PieceType::Rook)
+        | board.pieces(Color::Black, PieceType::Rook))
+    .count() as isize;
+    let queens = (board.pieces(Color::White, PieceType::Queen)
+        | board.pieces(Color::Black, PieceType::Queen))
+    .count() as isize;
+    (minors + 2 * rooks + 4 * queens).min(MAX_PHASE)
+}
+
+// Scale factors are out of this
+pub const SCALE_NORMAL: isize = 64;
+const SCALE_OPPOSITE_BISHOPS: isize = 32;
+
+/// How much of the evaluation to keep for drawish material, out of `SCALE_NORMAL`
+pub fn scale_factor(board: &BoardState) -> isize {
+    if board.insufficient_material() || bitbase::probe(board) == Some(false) {
+        return 0;
+    }
+    let white_minors_majors = board.pieces(Color::White, PieceType::Knight)
+        | board.pieces(Color::White, PieceType::Bishop)
+        | board.pieces(Color::White, PieceType::Rook)
+        | board.pieces(Color::White, PieceType::Queen);
+    let black_minors_majors = board.pieces(Color::Black, PieceType::Knight)
+        | board.pieces(Color::Black, PieceType::Bishop)
+        | board.pieces(Color::Black, PieceType::Rook)
+        | board.pieces(Color::Black, PieceType::Queen);
+
+    // Opposite colored bishops, with nothing else but pawns
+    if white_minors_majors == board.pieces(Color::White, PieceType::Bishop)
+        && black_minors_majors == board.pieces(Color::Black, PieceType::Bishop)
+        && board.pieces(Color::White, PieceType::Bishop).count() == 1
+        && board.pieces(Color::Black, PieceType::Bishop).count() == 1
+        && (board.pieces(Color::White, PieceType::Bishop) & Tables::LIGHT_SQUARES).is_empty()
+            != (board.pieces(Color::Black, PieceType::Bishop) & Tables::LIGHT_SQUARES).is_empty()
+    {
+        return SCALE_OPPOSITE_BISHOPS;
+    }
+
+    for white in [true, false] {
+        let (pawns, knights, bishops, minors_majors, enemy, enemy_pawns, enemy_king) = match white {
+            true => (
+                board.pieces(Color::White, PieceType::Pawn),
+                board.pieces(Color::White, PieceType::Knight),
+                board.pieces(Color::White, PieceType::Bishop),
+                white_minors_majors,
+                black_minors_majors,
+                board.pieces(Color::Black, PieceType::Pawn),
+                board.pieces(Color::Black, PieceType::King),
+            ),
+            false => (
+                board.pieces(Color::Black, PieceType::Pawn),
+                board.pieces(Color::Black, PieceType::Knight),
+                board.pieces(Color::Black, PieceType::Bishop),
+                black_minors_majors,
+                white_minors_majors,
+                board.pieces(Color::White, PieceType::Pawn),
+                board.pieces(Color::White, PieceType::King),
+            ),
+        };
+        if !(enemy | enemy_pawns).is_empty() {
+            continue;
+        }
+
+        // Two knights can not force mate against a bare king
+        if pawns.is_empty() && minors_majors == knights && knights.count() == 2 {
+            return 0;
+        }
+
+        // Rook pawns with a bishop that does not cover the queening square, when the defending king
+        // already sits in front of them
+        if !pawns.is_empty() && minors_majors == bishops {
+            let queening_square = if (pawns & !Tables::FILE_A).is_empty() {
+                if white {
+                    Square::A8
+                } else {
+                    Square::A1
+                }
+            } else if (pawns & !Tables::FILE_H).is_empty() {
+                if white {
+                    Square::H8
+                } else {
+                    Square::H1
+                }
+            } else {
+                continue;
+            };
+            let queening_light = Tables::LIGHT_SQUARES.contains(queening_square);
+            let right_bishop = match queening_light {
+                true => !(bishops & Tables::LIGHT_SQUARES).is_empty(),
+                false => !(bishops & !Tables::LIGHT_SQUARES).is_empty(),
+            };
+            if !right_bishop && distance(Square::from_bit(enemy_king), queening_square) <= 1 {
+                return 0;
+            }
+        }
+    }
+    SCALE_NORMAL
+}
+
+// Weights of the mop up term
+const MOP_UP_CENTER: isize = 60;
+const MOP_UP_KINGS: isize = 10;
+const MOP_UP_FREEDOM: isize = 20;
+
+/// Gives a side which is winning an ending without pawns a way towards mate, by rewarding driving the
+/// enemy king to the edge and bringing its own king closer
+pub fn mop_up_side(board: &BoardState, tables: &Tables, white: bool) -> isize {
+    let (pawns, king, enemy_king) = match white {
+        true => (
+            board.pieces(Color::White, PieceType::Pawn),
+            board.pieces(Color::White, PieceType::King),
+            board.pieces(Color::Black, PieceType::King),
+        ),
+        false => (
+            board.pieces(Color::Black, PieceType::Pawn),
+            board.pieces(Color::Black, PieceType::King),
+            board.pieces(Color::White, PieceType::King),
+        ),
+    };
+    if !pawns.is_empty()
+        || king.is_empty()
+        || enemy_king.is_empty()
+        || material_side(board, white) - material_side(board, !white) < board.eval_params.rook
+    {
+        return 0;
+    }
+    // Squares the enemy king could step to, looking through the king for sliding attacks
+    let attacked = match white {
+        true => board.attack_mask::<true>(tables, enemy_king),
+        false => board.attack_mask::<false>(tables, enemy_king),
+    };
+    let enemy_king_square = Square::from_bit(enemy_king);
+    let free_squares = (tables.king_attacks[enemy_king_square] & !attacked).count() as isize;
+
+    // The king table wants the king tucked away, which is the opposite of what is needed here
+    let king_square = Square::from_bit(king);
+    let king_table = match white {
+        true => board.eval_params.king_table[WHITE_MAP[king_square]],
+        false => board.eval_params.king_table[BLACK_MAP[king_square]],
+    };
+
+    let kings_distance = king_square.file().abs_diff(enemy_king_square.file())
+        + king_square.rank().abs_diff(enemy_king_square.rank());
+    MOP_UP_CENTER * center_distance(enemy_king_square) as isize
+        + MOP_UP_KINGS * (14 - kings_distance as isize)
+        + MOP_UP_FREEDOM * (8 - free_squares)
+        - king_table
+}
+
+// Bonus for a king and pawn ending the bitbase says is won, plus a bit for each step the pawn takes
+const KNOWN_WIN: isize = 600;
+const KNOWN_WIN_ADVANCE: isize = 20;
+
+/// Makes king and pawn endings the bitbase says are won score as a clear win
+pub fn known_win_side(board: &BoardState, white: bool) -> isize {
+    let pawns = match white {
+        true => board.pieces(Color::White, PieceType::Pawn),
+        false => board.pieces(Color::Black, PieceType::Pawn),
+    };
+    if pawns.is_empty() || bitbase::probe(board) != Some(true) {
+        return 0;
+    }
+    let rank = Square::from_bit(pawns).rank() as isize;
+    let advance = match white {
+        true => rank - 1,
+        false => 6 - rank,
+    };
+    KNOWN_WIN + KNOWN_WIN_ADVANCE * advance
+}
+
+/// How many steps a square is from the center four squares
+pub fn center_distance(square: Square) -> u8 {
+    let (file, rank) = (square.file(), square.rank());
+    (3u8.saturating_sub(file) + file.saturating_sub(4))
+        + (3u8.saturating_sub(rank) + rank.saturating_sub(4))
+}
+
+/// The number of king moves between two squares
+pub fn distance(a: Square, b: Square) -> u8 {
+    let file_distance = a.file().abs_diff(b.file());
+    let rank_distance = a.rank().abs_diff(b.rank());
+    file_distance.max(rank_distance)
 }
 
 /// Get the value of the material relative to the side to move
 pub fn material_value(board: &BoardState) -> isize {
     // White relative value
-    let king_delta =
-        board.white_king.count_ones() as isize - board.black_king.count_ones() as isize;
-    let queen_delta =
-        board.white_queens.count_ones() as isize - board.black_queens.count_ones() as isize;
-    let rook_delta =
-        board.white_rooks.count_ones() as isize - board.black_rooks.count_ones() as isize;
-    let bishop_delta =
-        board.white_bishops.count_ones() as isize - board.black_bishops.count_ones() as isize;
-    let knight_delta =
-        board.white_knights.count_ones() as isize - board.black_knights.count_ones() as isize;
-    let pawn_delta =
-        board.white_pawns.count_ones() as isize - board.black_pawns.count_ones() as isize;
-
-    // Get the total value
-    let white_relative_value = KING * king_delta
-        + QUEEN * queen_delta
-        + ROOK * rook_delta
-        + BISHOP * bishop_delta
-        + KNIGHT * knight_delta
-        + PAWN * pawn_delta;
+    let white_relative_value = material_side(board, true) - material_side(board, false);
     if board.white_to_move {
         white_relative_value
     } else {
@@ -173,204 +719,142 @@ pub fn material_value(board: &BoardState) -> isize {
     }
 }
 
+/// Get the value of one sides material
+pub fn material_side(board: &BoardState, white: bool) -> isize {
+    let params = &board.eval_params;
+    let (king, queens, rooks, bishops, knights, pawns) = match white {
+        true => (
+            board.pieces(Color::White, PieceType::King),
+            board.pieces(Color::White, PieceType::Queen),
+            board.pieces(Color::White, PieceType::Rook),
+            board.pieces(Color::White, PieceType::Bishop),
+            board.pieces(Color::White, PieceType::Knight),
+            board.pieces(Color::White, PieceType::Pawn),
+        ),
+        false => (
+            board.pieces(Color::Black, PieceType::King),
+            board.pieces(Color::Black, PieceType::Queen),
+            board.pieces(Color::Black, PieceType::Rook),
+            board.pieces(Color::Black, PieceType::Bishop),
+            board.pieces(Color::Black, PieceType::Knight),
+            board.pieces(Color::Black, PieceType::Pawn),
+        ),
+    };
+    params.king * king.count() as isize
+        + params.queen * queens.count() as isize
+        + params.rook * rooks.count() as isize
+        + params.bishop * bishops.count() as isize
+        + params.knight * knights.count() as isize
+        + params.pawn * pawns.count() as isize
+}
+
 /// Get the boards piece square value
 pub fn piece_square_score(board: &BoardState) -> isize {
-    // Start to score as if white is to move
-    let mut white_score = 0;
-    let mut black_score = 0;
-
-    let mut white_pawns = board.white_pawns;
-    while white_pawns != 0 {
-        white_score += PAWN_TABLE[WHITE_MAP[pop_lsb(&mut white_pawns)]];
-    }
-    let mut white_knights = board.white_knights;
-    while white_knights != 0 {
-        white_score += KNIGHT_TABLE[WHITE_MAP[pop_lsb(&mut white_knights)]];
-    }
-    let mut white_rooks = board.white_rooks;
-    while white_rooks != 0 {
-        white_score += ROOK_TABLE[WHITE_MAP[pop_lsb(&mut white_rooks)]];
-    }
-    let mut white_bishops = board.white_bishops;
-    while white_bishops != 0 {
-        white_score += BISHOP_TABLE[WHITE_MAP[pop_lsb(&mut white_bishops)]];
-    }
-    let mut white_queens = board.white_queens;
-    while white_queens != 0 {
-        white_score += QUEEN_TABLE[WHITE_MAP[pop_lsb(&mut white_queens)]];
-    }
-    let mut white_king = board.white_king;
-    while white_king != 0 {
-        white_score += KING_TABLE[WHITE_MAP[pop_lsb(&mut white_king)]];
-    }
-
-    let mut black_pawns = board.black_pawns;
-    while black_pawns != 0 {
-        black_score += PAWN_TABLE[BLACK_MAP[pop_lsb(&mut black_pawns)]];
-    }
-    let mut black_knights = board.black_knights;
-    while black_knights != 0 {
-        black_score += KNIGHT_TABLE[BLACK_MAP[pop_lsb(&mut black_knights)]];
-    }
-    let mut black_rooks = board.black_rooks;
-    while black_rooks != 0 {
-        black_score += ROOK_TABLE[BLACK_MAP[pop_lsb(&mut black_rooks)]];
-    }
-    let mut black_bishops = board.black_bishops;
-    while black_bishops != 0 {
-        black_score += BISHOP_TABLE[BLACK_MAP[pop_lsb(&mut black_bishops)]];
-    }
-    let mut black_queens = board.black_queens;
-    while black_queens != 0 {
-        black_score += QUEEN_TABLE[BLACK_MAP[pop_lsb(&mut black_queens)]];
-    }
-    let mut black_king = board.black_king;
-    while black_king != 0 {
-        black_score += KING_TABLE[BLACK_MAP[pop_lsb(&mut black_king)]];
-    }
-
+    let white_score = piece_square_side(board, true);
+    let black_score = piece_square_side(board, false);
     match board.white_to_move {
         true => white_score - black_score,
         false => black_score - white_score,
     }
 }
 
+/// Get the piece square value of one side
+pub fn piece_square_side(board: &BoardState, white: bool) -> isize {
+    let params = &board.eval_params;
+    let color = Color::from_white(white);
+    let map = match white {
+        true => &WHITE_MAP,
+        false => &BLACK_MAP,
+    };
+    let mut score = 0;
+    for kind in PieceType::ALL {
+        let table = params.table(kind);
+        for square in board.pieces(color, kind) {
+            score += table[map[square]];
+        }
+    }
+    score
+}
+
 /// Get the delta piece square score from a `MoveRep`
 pub fn delta_ps_score(board: &BoardState, mv: &MoveRep) -> isize {
+    let params = &board.eval_params;
     let mut score = 0;
     match mv.promotion {
         // Castling requries even more special handling
         Some(Promotion::Castle) => {
-            match mv.ending_square {
-                // White kingside
-                e if e == (1 << Tables::G1) => {
-                    // Remove king start score
-                    score -= KING_TABLE[WHITE_MAP[mv.starting_square.trailing_zeros() as usize]];
-                    // Add king end score
-                    score += KING_TABLE[WHITE_MAP[mv.ending_square.trailing_zeros() as usize]];
-                    // Remove rook start score
-                    score -= ROOK_TABLE[WHITE_MAP[Tables::H1 as usize]];
-                    // Add rook end score
-                    score += ROOK_TABLE[WHITE_MAP[Tables::F1 as usize]];
-                }
-                // White queenside
-                e if e == (1 << Tables::C1) => {
-                    // Remove king start score
-                    score -= KING_TABLE[WHITE_MAP[mv.starting_square.trailing_zeros() as usize]];
-                    // Add king end score
-                    score += KING_TABLE[WHITE_MAP[mv.ending_square.trailing_zeros() as usize]];
-                    // Remove rook start score
-                    score -= ROOK_TABLE[WHITE_MAP[Tables::A1 as usize]];
-                    // Add rook end score
-                    score += ROOK_TABLE[WHITE_MAP[Tables::D1 as usize]];
-                }
-                // Black kingside
-                e if e == (1 << Tables::G8) => {
-                    // Remove king start score
-                    score -= KING_TABLE[BLACK_MAP[mv.starting_square.trailing_zeros() as usize]];
-                    // Add king end score
-                    score += KING_TABLE[BLACK_MAP[mv.ending_square.trailing_zeros() as usize]];
-                    // Remove rook start score
-                    score -= ROOK_TABLE[BLACK_MAP[Tables::H8 as usize]];
-                    // Add rook end score
-                    score += ROOK_TABLE[BLACK_MAP[Tables::F8 as usize]];
-                }
-                // Black queenside
-                e if e == (1 << Tables::C8) => {
-                    // Remove king start score
-                    score -= KING_TABLE[BLACK_MAP[mv.starting_square.trailing_zeros() as usize]];
-                    // Add king end score
-                    score += KING_TABLE[BLACK_MAP[mv.ending_square.trailing_zeros() as usize]];
-                    // Remove rook start score
-                    score -= ROOK_TABLE[BLACK_MAP[Tables::A8 as usize]];
-                    // Add rook end score
-                    score += ROOK_TABLE[BLACK_MAP[Tables::D8 as usize]];
-                }
+            // The rook may start anywhere in Chess960, so the board knows where it is
+            let Some((rook_start, rook_end)) = board.castle_rook_squares(mv) else {
                 // No other case should occur!
-                _ => panic!(),
-            }
+                panic!()
+            };
+            let map = match Tables::RANK_1.contains(mv.ending_square) {
+                true => &WHITE_MAP,
+                false => &BLACK_MAP,
+            };
+            // Move the king
+            score -= params.king_table[map[mv.starting_square]];
+            score += params.king_table[map[mv.ending_square]];
+            // Move the rook
+            score -= params.rook_table[map[rook_start]];
+            score += params.rook_table[map[rook_end]];
             score
         }
         // Everything else can be done in the same way
         _ => {
             // The moved piece should always be Some
-            let (moved_piece, moved_color) = board.get_piece_and_color(mv.starting_square).unwrap();
+            let moved = board.get_piece_and_color(mv.starting_square).unwrap();
+            let moved_piece = moved.kind;
             // Get the color corrected index into the piece square table
-            let (move_start, move_end) = match moved_color {
-                true => (
-                    WHITE_MAP[mv.starting_square.trailing_zeros() as usize],
-                    WHITE_MAP[mv.ending_square.trailing_zeros() as usize],
-                ),
-                false => (
-                    BLACK_MAP[mv.starting_square.trailing_zeros() as usize],
-                    BLACK_MAP[mv.ending_square.trailing_zeros() as usize],
-                ),
+            let (move_start, move_end) = match moved.color {
+                Color::White => (WHITE_MAP[mv.starting_square], WHITE_MAP[mv.ending_square]),
+                Color::Black => (BLACK_MAP[mv.starting_square], BLACK_MAP[mv.ending_square]),
             };
             // Adjust the score for the change of the moved piece
-            score -= match moved_piece {
-                PieceType::Pawn => PAWN_TABLE[move_start],
-                PieceType::Knight => KNIGHT_TABLE[move_start],
-                PieceType::Bishop => BISHOP_TABLE[move_start],
-                PieceType::Rook => ROOK_TABLE[move_start],
-                PieceType::Queen => QUEEN_TABLE[move_start],
-                PieceType::King => KING_TABLE[move_start],
-            };
+            score -= params.table(moved_piece)[move_start];
 
             // Make sure to adjust if there is a promotion
             if mv.promotion.is_none() {
-                score += match moved_piece {
-                    PieceType::Pawn => PAWN_TABLE[move_end],
-                    PieceType::Knight => KNIGHT_TABLE[move_end],
-                    PieceType::Bishop => BISHOP_TABLE[move_end],
-                    PieceType::Rook => ROOK_TABLE[move_end],
-                    PieceType::Queen => QUEEN_TABLE[move_end],
-                    PieceType::King => KING_TABLE[move_end],
-                };
+                score += params.table(moved_piece)[move_end];
             }
             // If there is a promotion
             else {
                 score += match mv.promotion.unwrap() {
-                    Promotion::Queen => QUEEN_TABLE[move_end],
-                    Promotion::Rook => ROOK_TABLE[move_end],
-                    Promotion::Bishop => BISHOP_TABLE[move_end],
-                    Promotion::Knight => KNIGHT_TABLE[move_end],
+                    Promotion::Queen => params.queen_table[move_end],
+                    Promotion::Rook => params.rook_table[move_end],
+                    Promotion::Bishop => params.bishop_table[move_end],
+                    Promotion::Knight => params.knight_table[move_end],
                     // This should never happen!
                     Promotion::Castle => panic!(),
                 };
             }
 
             // Adjust the score for any attacked piece
-            if let Some((attacked_piece, attacked_color)) =
-                board.get_piece_and_color(mv.ending_square)
-            {
-                let attack_index = match attacked_color {
-                    true => WHITE_MAP[mv.ending_square.trailing_zeros() as usize],
-                    false => BLACK_MAP[mv.ending_square.trailing_zeros() as usize],
+            if let Some(attacked) = board.get_piece_and_color(mv.ending_square) {
+                let attack_index = match attacked.color {
+                    Color::White => WHITE_MAP[mv.ending_square],
+                    Color::Black => BLACK_MAP[mv.ending_square],
                 };
                 // Since the attacked piece is opposite to the side to move, removing a piece increased the current sides score
-                score += match attacked_piece {
+                score += match attacked.kind {
                     // Adjust for en passent moves
                     PieceType::Pawn => match board.en_passant_target {
                         // Normal case
-                        0 => PAWN_TABLE[attack_index],
+                        None => params.pawn_table[attack_index],
                         // En passant case
-                        _ => {
+                        Some(target) => {
                             let en_passant_index = match board.white_to_move {
-                                true => {
-                                    WHITE_MAP[board.en_passant_target.trailing_zeros() as usize]
-                                }
-                                false => {
-                                    BLACK_MAP[board.en_passant_target.trailing_zeros() as usize]
-                                }
+                                true => WHITE_MAP[target],
+                                false => BLACK_MAP[target],
                             };
-                            PAWN_TABLE[en_passant_index]
+                            params.pawn_table[en_passant_index]
                         }
                     },
-                    PieceType::Knight => KNIGHT_TABLE[attack_index],
-                    PieceType::Bishop => BISHOP_TABLE[attack_index],
-                    PieceType::Rook => ROOK_TABLE[attack_index],
-                    PieceType::Queen => QUEEN_TABLE[attack_index],
-                    PieceType::King => KING_TABLE[attack_index],
+                    PieceType::Knight => params.knight_table[attack_index],
+                    PieceType::Bishop => params.bishop_table[attack_index],
+                    PieceType::Rook => params.rook_table[attack_index],
+                    PieceType::Queen => params.queen_table[attack_index],
+                    PieceType::King => params.king_table[attack_index],
                 };
             }
             // Return the score
@@ -434,13 +918,7 @@ mod tests {
             "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1".to_string(),
         );
 
-        let mv = MoveRep::new(
-            1 << Tables::D2,
-            1 << Tables::D4,
-            None,
-            PieceType::Pawn,
-            None,
-        );
+        let mv = MoveRep::new(Square::D2, Square::D4, None, PieceType::Pawn, None);
 
         let delta_score = delta_ps_score(&board, &mv);
         assert_eq!(delta_score, 40);
@@ -461,13 +939,7 @@ mod tests {
             "rnbqkbnr/pppppppp/8/8/3P4/8/PPP1PPPP/RNBQKBNR b KQkq - 0 1".to_string(),
         );
 
-        let mv = MoveRep::new(
-            1 << Tables::D7,
-            1 << Tables::D5,
-            None,
-            PieceType::Pawn,
-            None,
-        );
+        let mv = MoveRep::new(Square::D7, Square::D5, None, PieceType::Pawn, None);
 
         let delta_score = delta_ps_score(&board, &mv);
         assert_eq!(delta_score, 40);
@@ -488,13 +960,7 @@ mod tests {
             "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1".to_string(),
         );
 
-        let mv = MoveRep::new(
-            1 << Tables::G1,
-            1 << Tables::F3,
-            None,
-            PieceType::Knight,
-            None,
-        );
+        let mv = MoveRep::new(Square::G1, Square::F3, None, PieceType::Knight, None);
 
         let delta_score = delta_ps_score(&board, &mv);
         assert_eq!(delta_score, 50);
@@ -515,13 +981,7 @@ mod tests {
             "rnbqkbnr/pppppppp/8/8/8/5N2/PPPPPPPP/RNBQKB1R b KQkq - 0 1".to_string(),
         );
 
-        let mv = MoveRep::new(
-            1 << Tables::G8,
-            1 << Tables::F6,
-            None,
-            PieceType::Knight,
-            None,
-        );
+        let mv = MoveRep::new(Square::G8, Square::F6, None, PieceType::Knight, None);
 
         let delta_score = delta_ps_score(&board, &mv);
         assert_eq!(delta_score, 50);
@@ -542,13 +1002,7 @@ mod tests {
             "rnbqkbnr/pppppppp/8/8/N7/8/PPPPPPPP/R1BQKBNR w KQkq - 0 1".to_string(),
         );
 
-        let mv = MoveRep::new(
-            1 << Tables::A4,
-            1 << Tables::C5,
-            None,
-            PieceType::Knight,
-            None,
-        );
+        let mv = MoveRep::new(Square::A4, Square::C5, None, PieceType::Knight, None);
 
         let delta_score = delta_ps_score(&board, &mv);
         assert_eq!(delta_score, 45);
@@ -569,13 +1023,7 @@ mod tests {
             "rnbqkb1r/ppppp1pp/5n2/8/N7/8/PPPPPPPP/R1BQKBNR b KQkq - 0 1".to_string(),
         );
 
-        let mv = MoveRep::new(
-            1 << Tables::F6,
-            1 << Tables::G4,
-            None,
-            PieceType::Knight,
-            None,
-        );
+        let mv = MoveRep::new(Square::F6, Square::G4, None, PieceType::Knight, None);
 
         let delta_score = delta_ps_score(&board, &mv);
         assert_eq!(delta_score, -5);
@@ -596,13 +1044,7 @@ mod tests {
             "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 1".to_string(),
         );
 
-        let mv = MoveRep::new(
-            1 << Tables::F1,
-            1 << Tables::C4,
-            None,
-            PieceType::Bishop,
-            None,
-        );
+        let mv = MoveRep::new(Square::F1, Square::C4, None, PieceType::Bishop, None);
 
         let delta_score = delta_ps_score(&board, &mv);
         assert_eq!(delta_score, 20);
@@ -623,13 +1065,7 @@ mod tests {
             "rnbqkbnr/pppppp1p/8/6p1/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1".to_string(),
         );
 
-        let mv = MoveRep::new(
-            1 << Tables::F8,
-            1 << Tables::H6,
-            None,
-            PieceType::Bishop,
-            None,
-        );
+        let mv = MoveRep::new(Square::F8, Square::H6, None, PieceType::Bishop, None);
 
         let delta_score = delta_ps_score(&board, &mv);
         assert_eq!(delta_score, 0);
@@ -650,13 +1086,7 @@ mod tests {
             "rnbqkbnr/pppppp1p/8/8/8/8/PPPPPPR1/RNBQKBN1 w Qkq - 0 1".to_string(),
         );
 
-        let mv = MoveRep::new(
-            1 << Tables::G2,
-            1 << Tables::G7,
-            None,
-            PieceType::Rook,
-            None,
-        );
+        let mv = MoveRep::new(Square::G2, Square::G7, None, PieceType::Rook, None);
 
         let delta_score = delta_ps_score(&board, &mv);
         assert_eq!(delta_score, 10);
@@ -677,13 +1107,7 @@ mod tests {
             "rnbqkbnr/1ppppp1p/8/8/8/8/PPPPPPR1/RNBQKBN1 b Qkq - 0 1".to_string(),
         );
 
-        let mv = MoveRep::new(
-            1 << Tables::A8,
-            1 << Tables::A4,
-            None,
-            PieceType::Rook,
-            None,
-        );
+        let mv = MoveRep::new(Square::A8, Square::A4, None, PieceType::Rook, None);
 
         let delta_score = delta_ps_score(&board, &mv);
         assert_eq!(delta_score, -5);
@@ -704,13 +1128,7 @@ mod tests {
             "r6r/1ppbbp1p/1nqppkn1/8/8/8/PPPPPP2/RNBQKBNR b - - 0 1".to_string(),
         );
 
-        let mv = MoveRep::new(
-            1 << Tables::A8,
-            1 << Tables::D8,
-            None,
-            PieceType::Rook,
-            None,
-        );
+        let mv = MoveRep::new(Square::A8, Square::D8, None, PieceType::Rook, None);
 
         let delta_score = delta_ps_score(&board, &mv);
         assert_eq!(delta_score, 5);
@@ -731,13 +1149,7 @@ mod tests {
             "r6r/1ppbbp1p/1nqppkn1/8/8/8/PPPPPP2/R1K4R w - - 0 1".to_string(),
         );
 
-        let mv = MoveRep::new(
-            1 << Tables::H1,
-            1 << Tables::E1,
-            None,
-            PieceType::Rook,
-            None,
-        );
+        let mv = MoveRep::new(Square::H1, Square::E1, None, PieceType::Rook, None);
 
         let delta_score = delta_ps_score(&board, &mv);
         assert_eq!(delta_score, 5);
@@ -758,13 +1170,7 @@ mod tests {
             "rnbqkbnr/pppppppp/8/8/8/8/PPP1PPPP/RNBQKBNR w KQkq - 0 1".to_string(),
         );
 
-        let mv = MoveRep::new(
-            1 << Tables::D1,
-            1 << Tables::D4,
-            None,
-            PieceType::Queen,
-            None,
-        );
+        let mv = MoveRep::new(Square::D1, Square::D4, None, PieceType::Queen, None);
 
         let delta_score = delta_ps_score(&board, &mv);
         assert_eq!(delta_score, 10);
@@ -785,13 +1191,7 @@ mod tests {
             "rnbqkbn1/pppppp1p/8/8/8/8/PQP1PPPP/RNB1KBNR w KQq - 0 1".to_string(),
         );
 
-        let mv = MoveRep::new(
-            1 << Tables::B2,
-            1 << Tables::H8,
-            None,
-            PieceType::Queen,
-            None,
-        );
+        let mv = MoveRep::new(Square::B2, Square::H8, None, PieceType::Queen, None);
 
         let delta_score = delta_ps_score(&board, &mv);
         assert_eq!(delta_score, -20);
@@ -812,13 +1212,7 @@ mod tests {
             "rnbqkbn1/pppppp1p/8/8/8/8/PQP1KPPP/RNB2BNR w q - 0 1".to_string(),
         );
 
-        let mv = MoveRep::new(
-            1 << Tables::E2,
-            1 << Tables::E3,
-            None,
-            PieceType::King,
-            None,
-        );
+        let mv = MoveRep::new(Square::E2, Square::E3, None, PieceType::King, None);
         let delta_score = delta_ps_score(&board, &mv);
         assert_eq!(delta_score, -20);
         let init_score = piece_square_score(&board);
@@ -838,13 +1232,7 @@ mod tests {
             "rnbqk1n1/ppppppbp/8/8/8/8/PQP1KPPP/RNB2BNR b q - 0 1".to_string(),
         );
 
-        let mv = MoveRep::new(
-            1 << Tables::E8,
-            1 << Tables::F8,
-            None,
-            PieceType::King,
-            None,
-        );
+        let mv = MoveRep::new(Square::E8, Square::F8, None, PieceType::King, None);
         let delta_score = delta_ps_score(&board, &mv);
         assert_eq!(delta_score, 10);
         let init_score = piece_square_score(&board);
@@ -865,8 +1253,8 @@ mod tests {
         );
 
         let mv = MoveRep::new(
-            1 << Tables::E2,
-            1 << Tables::F3,
+            Square::E2,
+            Square::F3,
             None,
             PieceType::Pawn,
             Some(PieceType::Pawn),
@@ -892,8 +1280,8 @@ mod tests {
         );
 
         let mv = MoveRep::new(
-            1 << Tables::E6,
-            1 << Tables::D7,
+            Square::E6,
+            Square::D7,
             None,
             PieceType::Pawn,
             Some(PieceType::Pawn),
@@ -918,8 +1306,8 @@ mod tests {
             BoardState::state_from_string_fen("8/3P2K1/8/8/8/8/6k1/8 w - - 0 1".to_string());
 
         let mv = MoveRep::new(
-            1 << Tables::D7,
-            1 << Tables::D8,
+            Square::D7,
+            Square::D8,
             Some(Promotion::Queen),
             PieceType::Pawn,
             None,
@@ -944,8 +1332,8 @@ mod tests {
             BoardState::state_from_string_fen("4r3/3P2K1/8/8/8/8/6k1/8 w - - 0 1".to_string());
 
         let mv = MoveRep::new(
-            1 << Tables::D7,
-            1 << Tables::E8,
+            Square::D7,
+            Square::E8,
             Some(Promotion::Queen),
             PieceType::Pawn,
             Some(PieceType::Rook),
@@ -970,8 +1358,8 @@ mod tests {
             BoardState::state_from_string_fen("8/6K1/8/8/8/8/3p2k1/4R3 b - - 0 1".to_string());
 
         let mv = MoveRep::new(
-            1 << Tables::D2,
-            1 << Tables::E1,
+            Square::D2,
+            Square::E1,
             Some(Promotion::Queen),
             PieceType::Pawn,
             Some(PieceType::Rook),
@@ -997,8 +1385,8 @@ mod tests {
         );
 
         let mv = MoveRep::new(
-            1 << Tables::E1,
-            1 << Tables::G1,
+            Square::E1,
+            Square::G1,
             Some(Promotion::Castle),
             PieceType::King,
             None,
@@ -1024,8 +1412,8 @@ mod tests {
         );
 
         let mv = MoveRep::new(
-            1 << Tables::E1,
-            1 << Tables::C1,
+            Square::E1,
+            Square::C1,
             Some(Promotion::Castle),
             PieceType::King,
             None,
@@ -1051,8 +1439,8 @@ mod tests {
         );
 
         let mv = MoveRep::new(
-            1 << Tables::E8,
-            1 << Tables::G8,
+            Square::E8,
+            Square::G8,
             Some(Promotion::Castle),
             PieceType::King,
             None,
@@ -1078,8 +1466,8 @@ mod tests {
         );
 
         let mv = MoveRep::new(
-            1 << Tables::E8,
-            1 << Tables::C8,
+            Square::E8,
+            Square::C8,
             Some(Promotion::Castle),
             PieceType::King,
             None,
@@ -1104,7 +1492,173 @@ mod tests {
         let mut board = BoardState::starting_state();
         let init_score = board.piece_square_score;
         let zob_keys = ZobKeys::new();
-        perft(&mut board, 5, &zob_keys);
+        perft(&mut board, 5, &zob_keys, &mut std::io::sink()).unwrap();
         assert_eq!(board.piece_square_score, init_score);
     }
+
+    #[test]
+    fn eval_params_round_trip() {
+        let params = EvalParams::default();
+        let parsed = EvalParams::parse(&params.to_text()).unwrap();
+        assert_eq!(params, parsed);
+    }
+
+    #[test]
+    fn eval_params_partial_file() {
+        let parsed = EvalParams::parse("# only change the queen\nqueen 950\n").unwrap();
+        assert_eq!(parsed.queen, 950);
+        assert_eq!(parsed.rook, ROOK);
+        assert_eq!(parsed.pawn_table, PAWN_TABLE);
+    }
+
+    #[test]
+    fn eval_params_bad_file() {
+        assert!(EvalParams::parse("bishop 330 unicorn 1").is_err());
+        assert!(EvalParams::parse("rook five").is_err());
+        assert!(EvalParams::parse("pawn_table 1 2 3").is_err());
+    }
+
+    #[test]
+    fn eval_params_rescore() {
+        let mut board = BoardState::state_from_string_fen(
+            "rnbqkbnr/pppppppp/8/8/8/5N2/PPPPPPPP/RNBQKB1R b KQkq - 0 1".to_string(),
+        );
+        let params = EvalParams {
+            knight_table: [0; 64],
+            ..EvalParams::default()
+        };
+        board.set_eval_params(std::sync::Arc::new(params));
+        assert_eq!(board.piece_square_score, piece_square_score(&board));
+
+        let mv = MoveRep::new(Square::G8, Square::F6, None, PieceType::Knight, None);
+        assert_eq!(delta_ps_score(&board, &mv), 0);
+    }
+
+    #[test]
+    fn eval_params_to_rust() {
+        let text = EvalParams::default().to_rust();
+        assert!(text.contains("pub const QUEEN: isize = 900;"));
+        assert!(text.contains("pub const PAWN_TABLE: [isize; 64] = [\n      0,  0,"));
+    }
+
+    #[test]
+    fn trace_matches_eval() {
+        let tables = Tables::new();
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R b KQkq - 0 1",
+        ] {
+            let board = BoardState::state_from_string_fen(fen.to_string());
+            // Move counts as search would pass them a ply into the tree, so mobility is not zero
+            for (number_moves, last_number_moves) in [(48, 48), (48, 20), (10, 60)] {
+                let traced = trace(&board, &tables, number_moves, last_number_moves);
+                let white_total: isize = traced.terms.iter().map(|t| t.3).sum();
+                let sign = if board.white_to_move { 1 } else { -1 };
+                assert_eq!(traced.score, sign * white_total);
+                assert_eq!(
+                    traced.score,
+                    eval(&board, &tables, number_moves, last_number_moves)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn trace_sides() {
+        let tables = Tables::new();
+        let board = BoardState::starting_state();
+        let traced = trace(&board, &tables, 0, 0);
+        let (_, white, black, total) = traced.terms[0];
+        assert_eq!(white, black);
+        assert_eq!(total, 0);
+        assert_eq!(traced.phase, MAX_PHASE);
+
+        let board = BoardState::state_from_string_fen("4k3/8/8/8/8/8/8/3QK3 b - - 0 1".to_string());
+        let traced = trace(&board, &tables, 0, 0);
+        assert_eq!(traced.terms[0].3, board.eval_params.queen);
+        assert_eq!(traced.phase, 4);
+        assert!(traced.score < 0);
+    }
+
+    #[test]
+    fn scale_opposite_bishops() {
+        let board = BoardState::state_from_string_fen(
+            "8/5k2/1p4b1/8/2P5/1P6/2K5/2B5 w - - 0 1".to_string(),
+        );
+        assert_eq!(scale_factor(&board), SCALE_OPPOSITE_BISHOPS);
+        let board = BoardState::state_from_string_fen(
+            "8/5k2/1p3b2/8/2P5/1P6/2K5/2B5 w - - 0 1".to_string(),
+        );
+        assert_eq!(scale_factor(&board), SCALE_NORMAL);
+    }
+
+    #[test]
+    fn scale_wrong_rook_pawn() {
+        // Dark squared bishop can not cover a8
+        let board =
+            BoardState::state_from_string_fen("1k6/8/8/P7/8/8/8/2B1K3 w - - 0 1".to_string());
+        assert_eq!(scale_factor(&board), 0);
+        // The king is too far away to reach the corner in time
+        let board =
+            BoardState::state_from_string_fen("8/8/8/P5k1/8/8/8/2B1K3 w - - 0 1".to_string());
+        assert_eq!(scale_factor(&board), SCALE_NORMAL);
+        // The light squared bishop covers a8
+        let board =
+            BoardState::state_from_string_fen("1k6/8/8/P7/8/8/8/3BK3 w - - 0 1".to_string());
+        assert_eq!(scale_factor(&board), SCALE_NORMAL);
+        // Black pawns on the h file, queening on h1 which is light
+        let board =
+            BoardState::state_from_string_fen("4k3/8/8/8/7p/8/7p/4b1K1 b - - 0 1".to_string());
+        assert_eq!(scale_factor(&board), 0);
+    }
+
+    #[test]
+    fn scale_two_knights() {
+        let board =
+            BoardState::state_from_string_fen("8/8/4k3/8/8/2K5/5NN1/8 w - - 0 1".to_string());
+        assert_eq!(scale_factor(&board), 0);
+        assert_eq!(eval(&board, &Tables::new(), 0, 0), 0);
+        let board =
+            BoardState::state_from_string_fen("8/8/4k3/8/6p1/2K5/5NN1/8 w - - 0 1".to_string());
+        assert_eq!(scale_factor(&board), SCALE_NORMAL);
+    }
+
+    #[test]
+    fn scale_dead_position() {
+        let board =
+            BoardState::state_from_string_fen("8/8/4k3/8/8/2K5/8/2B5 w - - 0 1".to_string());
+        assert_eq!(eval(&board, &Tables::new(), 0, 0), 0);
+    }
+
+    #[test]
+    fn mop_up_drives_king_to_edge() {
+        let tables = Tables::new();
+        let center =
+            BoardState::state_from_string_fen("8/8/8/3k4/8/8/8/3QK3 w - - 0 1".to_string());
+        let edge = BoardState::state_from_string_fen("3k4/8/8/8/8/8/8/3QK3 w - - 0 1".to_string());
+        assert!(mop_up_side(&edge, &tables, true) > mop_up_side(&center, &tables, true));
+        assert_eq!(mop_up_side(&edge, &tables, false), 0);
+
+        // Not active with pawns left
+        let pawns =
+            BoardState::state_from_string_fen("3k4/8/8/8/8/8/P7/3QK3 w - - 0 1".to_string());
+        assert_eq!(mop_up_side(&pawns, &tables, true), 0);
+    }
+
+    #[test]
+    fn king_and_pawn() {
+        let tables = Tables::new();
+        // Drawn by the bitbase, even though white is a pawn up
+        let drawn = BoardState::state_from_string_fen("k7/8/8/8/P7/8/8/K7 w - - 0 1".to_string());
+        assert_eq!(eval(&drawn, &tables, 0, 0), 0);
+
+        let won = BoardState::state_from_string_fen("8/1K6/8/P7/8/8/8/7k w - - 0 1".to_string());
+        assert!(eval(&won, &tables, 0, 0) > KNOWN_WIN);
+        assert!(known_win_side(&won, true) > 0);
+        assert_eq!(known_win_side(&won, false), 0);
+        // Black with the pawn, seen from white
+        let lost = BoardState::state_from_string_fen("7K/8/8/8/p7/8/1k6/8 w - - 0 1".to_string());
+        assert!(eval(&lost, &tables, 0, 0) < -KNOWN_WIN);
+        assert_eq!(known_win_side(&lost, false), known_win_side(&won, true));
+    }
 }
@@ -19,254 +19,176 @@ OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWA
 use crate::{
     board::{BoardState, MoveRep, PieceType, Promotion},
     tables::Tables,
+    tt::ZobKeys,
+    types::{Bitboard, Color, Square},
 };
 
 /// Generate a vector of possible moves from the current board state
 pub fn generate(board: &BoardState, tables: &Tables) -> Vec<MoveRep> {
     // Move list
     let mut moves = Vec::with_capacity(256);
+    match board.white_to_move {
+        true => side_moves::<true>(board, tables, &mut moves),
+        false => side_moves::<false>(board, tables, &mut moves),
+    }
+    moves
+}
 
+// Generate the moves of the side to move, which is white if WHITE is set
+fn side_moves<const WHITE: bool>(board: &BoardState, tables: &Tables, moves: &mut Vec<MoveRep>) {
     // Get the sides to moves king
-    let king = match board.white_to_move {
-        true => board.white_king,
-        false => board.black_king,
-    };
+    let king = Square::from_bit(board.pieces(Color::from_white(WHITE), PieceType::King));
 
     // Get the pinned pieces
-    let pinned_pieces = board.pin_mask(tables, king, board.white_to_move);
-
-    // white to move
-    if board.white_to_move {
-        if !board.white_in_check(tables) {
-            // White pawn moves
-            white_pawn_moves(board, tables, pinned_pieces, king, &mut moves);
+    let pinned_pieces = board.pin_mask(tables, king, WHITE);
 
-            // White Knights
-            white_knight_attacks(board, tables, pinned_pieces, king, &mut moves);
-
-            // White Rooks
-            white_rook_attacks(board, tables, pinned_pieces, king, &mut moves);
-
-            // White Bishops
-            white_bishop_attacks(board, tables, pinned_pieces, king, &mut moves);
-
-            // White Queens
-            white_queen_attacks(board, tables, pinned_pieces, king, &mut moves);
-
-            // White King
-            white_king_attacks(board, tables, pinned_pieces, king, &mut moves);
-
-            // White castle
-            if board.white_queenside_castle_rights || board.white_kingside_castle_rights {
-                if board.white_queenside_castle_rights
-                    && board.black_attacking(tables, board.white_king) == 0
-                    && board.black_attacking(tables, board.white_king << 1) == 0
-                    && board.black_attacking(tables, board.white_king << 2) == 0
-                    && board.occupancy() & 0x70 == 0
-                {
-                    let mv = MoveRep::new(
-                        1 << Tables::E1,
-                        1 << Tables::C1,
-                        Some(Promotion::Castle),
-                        PieceType::King,
-                        None,
-                    );
-                    moves.push(mv);
-                }
-                if board.white_kingside_castle_rights
-                    && board.black_attacking(tables, board.white_king) == 0
-                    && board.black_attacking(tables, board.white_king >> 1) == 0
-                    && board.black_attacking(tables, board.white_king >> 2) == 0
-                    && board.occupancy() & 0x6 == 0
-                {
-                    let mv = MoveRep::new(
-                        1 << Tables::E1,
-                        1 << Tables::G1,
-                        Some(Promotion::Castle),
-                        PieceType::King,
-                        None,
-                    );
-                    moves.push(mv);
-                }
-            }
-        }
-        // If the king is in check
-        else {
-            // If the king is in check, there are three valid responses
-            // 1. Attack the attacking piece
-            // 2. Block the attacking piece(s)
-            // 3. Move the king to safety
-
-            // Try attacking and blocking the piece - this can only work if there is only one attacking piece
-            if board.black_attacking(tables, board.white_king).count_ones() == 1 {
-                let target = board.black_attacking(tables, board.white_king);
-                moves.append(&mut generate_attacking_moves(board, tables, target));
-                // Also try to generate en passant moves which attack the target
-
-                if board.en_passant_target >> 8 == target {
-                    let en_passant_index = board.en_passant_target.trailing_zeros() as usize;
-                    let mut attacking_mask =
-                        tables.black_pawn_attacks[en_passant_index] & board.white_pawns;
-                    while attacking_mask != 0 {
-                        let attacking_index = pop_lsb(&mut attacking_mask);
-                        let mv = MoveRep::new(
-                            1 << attacking_index,
-                            board.en_passant_target,
-                            None,
-                            PieceType::Pawn,
-                            Some(PieceType::Pawn),
-                        );
-                        if board.pin_safe(tables, board.white_king, &mv) {
-                            moves.push(mv);
-                        }
-                    }
-                }
-                moves.append(&mut generate_blocking_moves(
-                    board,
-                    tables,
-                    board.white_king,
-                    target,
-                ));
-            }
-            // Now try moving the king to safety
-            moves.append(&mut move_king_to_safety(board, tables));
-        }
+    let checkers = match WHITE {
+        true => board.attacking::<false>(tables, king),
+        false => board.attacking::<true>(tables, king),
+    };
+    if checkers.is_empty() {
+        pawn_moves::<WHITE>(board, tables, pinned_pieces, king, moves);
+        piece_moves::<WHITE>(board, tables, PieceType::Knight, pinned_pieces, king, moves);
+        piece_moves::<WHITE>(board, tables, PieceType::Rook, pinned_pieces, king, moves);
+        piece_moves::<WHITE>(board, tables, PieceType::Bishop, pinned_pieces, king, moves);
+        queen_moves::<WHITE>(board, tables, pinned_pieces, king, moves);
+        king_moves::<WHITE>(board, tables, pinned_pieces, king, moves);
+        castle_moves(board, tables, moves);
+        return;
     }
-    // Black to move
-    else if !board.black_in_check(tables) {
-        // Black pawn moves
-        black_pawn_moves(board, tables, pinned_pieces, king, &mut moves);
-
-        // Black Knights
-        black_knight_attacks(board, tables, pinned_pieces, king, &mut moves);
 
-        // Black Rooks
-        black_rook_attacks(board, tables, pinned_pieces, king, &mut moves);
+    // If the king is in check, there are three valid responses
+    // 1. Attack the attacking piece
+    // 2. Block the attacking piece(s)
+    // 3. Move the king to safety
 
-        // Black Bishops
-        black_bishop_attacks(board, tables, pinned_pieces, king, &mut moves);
+    // Try attacking and blocking the piece - this can only work if there is only one attacking piece
+    if checkers.count() == 1 {
+        let target = Square::from_bit(checkers);
+        moves.append(&mut generate_attacking_moves(board, tables, target));
 
-        // Black Queens
-        black_queen_attacks(board, tables, pinned_pieces, king, &mut moves);
-
-        // Black King
-        black_king_attacks(board, tables, pinned_pieces, king, &mut moves);
-
-        // Black castling
-        if board.black_queenside_castle_rights || board.black_kingside_castle_rights {
-            if board.black_queenside_castle_rights
-                && board.white_attacking(tables, board.black_king) == 0
-                && board.white_attacking(tables, board.black_king << 1) == 0
-                && board.white_attacking(tables, board.black_king << 2) == 0
-                && board.occupancy() & 0x7000000000000000 == 0
-            {
-                let mv = MoveRep::new(
-                    1 << Tables::E8,
-                    1 << Tables::C8,
-                    Some(Promotion::Castle),
-                    PieceType::King,
-                    None,
-                );
-                moves.push(mv);
-            }
-            if board.black_kingside_castle_rights
-                && board.white_attacking(tables, board.black_king) == 0
-                && board.white_attacking(tables, board.black_king >> 1) == 0
-                && board.white_attacking(tables, board.black_king >> 2) == 0
-                && board.occupancy() & 0x600000000000000 == 0
-            {
+        // Also try to generate en passant moves which attack the target
+        let behind_attacks = match WHITE {
+            true => &tables.black_pawn_attacks,
+            false => &tables.white_pawn_attacks,
+        };
+        let captured = board.en_passant_target.map(|square| match WHITE {
+            true => square.south(),
+            false => square.north(),
+        });
+        if let Some(en_passant_square) =
+            board.en_passant_target.filter(|_| captured == Some(target))
+        {
+            let attacking_mask = behind_attacks[en_passant_square]
+                & board.pieces(Color::from_white(WHITE), PieceType::Pawn);
+            for attacking_index in attacking_mask {
                 let mv = MoveRep::new(
-                    1 << Tables::E8,
-                    1 << Tables::G8,
-                    Some(Promotion::Castle),
-                    PieceType::King,
+                    attacking_index,
+                    en_passant_square,
                     None,
+                    PieceType::Pawn,
+                    Some(PieceType::Pawn),
                 );
-                moves.push(mv);
+                if board.pin_safe(tables, king, &mv) {
+                    moves.push(mv);
+                }
             }
         }
+        moves.append(&mut generate_blocking_moves(board, tables, king, target));
     }
-    // If the king is in check
-    else {
-        // If the king is in check, there are three valid responses
-        // 1. Attack the attacking piece
-        // 2. Block the attacking piece(s)
-        // 3. Move the king to safety
-
-        // Try attacking and blocking the piece - this can only work if there is only one attacking piece
-        if board.white_attacking(tables, board.black_king).count_ones() == 1 {
-            let target = board.white_attacking(tables, board.black_king);
-            moves.append(&mut generate_attacking_moves(board, tables, target));
-            // Also try to generate en passant moves which attack the target
-
-            if board.en_passant_target << 8 == target {
-                let en_passant_index = board.en_passant_target.trailing_zeros() as usize;
-                let mut attacking_mask =
-                    tables.white_pawn_attacks[en_passant_index] & board.black_pawns;
-                while attacking_mask != 0 {
-                    let attacking_index = pop_lsb(&mut attacking_mask);
-                    let mv = MoveRep::new(
-                        1 << attacking_index,
-                        board.en_passant_target,
-                        None,
-                        PieceType::Pawn,
-                        Some(PieceType::Pawn),
-                    );
-                    if board.pin_safe(tables, board.black_king, &mv) {
-                        moves.push(mv);
-                    }
-                }
-            }
-            moves.append(&mut generate_blocking_moves(
-                board,
-                tables,
-                board.black_king,
-                target,
+    // Now try moving the king to safety
+    moves.append(&mut move_king_to_safety(board, tables));
+}
+
+// Castles for the side to move, which must not be in check. The rooks can start anywhere on the back
+// rank in Chess960, but the king and rook always land on the usual squares, so everything between
+// where they start and land has to be empty apart from the two of them. The king may not pass
+// through an attacked square
+fn castle_moves(board: &BoardState, tables: &Tables, moves: &mut Vec<MoveRep>) {
+    let us = Color::from_white(board.white_to_move);
+    let king = Square::from_bit(board.pieces(us, PieceType::King));
+    let indices = match board.white_to_move {
+        true => [
+            ZobKeys::WHITE_KINGSIDE_INDEX,
+            ZobKeys::WHITE_QUEENSIDE_INDEX,
+        ],
+        false => [
+            ZobKeys::BLACK_KINGSIDE_INDEX,
+            ZobKeys::BLACK_QUEENSIDE_INDEX,
+        ],
+    };
+    for index in indices {
+        if !board.castle_right(index) {
+            continue;
+        }
+        let rook = board.castle_rooks[index];
+        // The right is only any use with our rook still on its square
+        if !board.pieces(us, PieceType::Rook).contains(rook) {
+            continue;
+        }
+        let (king_target, rook_target) = BoardState::CASTLE_TARGETS[index];
+        let king_path = span(king, king_target);
+        let others = board.occupancy() & !king.bit() & !rook.bit();
+        if !((king_path | span(rook, rook_target)) & others).is_empty() {
+            continue;
+        }
+        // The rook is seen through, as it may be blocking an attack on the king's landing square
+        let attacked = match board.white_to_move {
+            true => board.attack_mask::<false>(tables, rook.bit()),
+            false => board.attack_mask::<true>(tables, rook.bit()),
+        };
+        if (attacked & king_path).is_empty() {
+            moves.push(MoveRep::new(
+                king,
+                king_target,
+                Some(Promotion::Castle),
+                PieceType::King,
+                None,
             ));
         }
-        // Now try moving the king to safety
-        moves.append(&mut move_king_to_safety(board, tables));
     }
+}
 
-    moves
+// Every square from one square to another on the same rank, including both
+fn span(a: Square, b: Square) -> Bitboard {
+    let (low, high) = (a.min(b).bit().0, a.max(b).bit().0);
+    Bitboard((high << 1).wrapping_sub(low))
 }
 
 // Generate moves which attack the target
-pub fn generate_attacking_moves(board: &BoardState, tables: &Tables, target: u64) -> Vec<MoveRep> {
+pub fn generate_attacking_moves(
+    board: &BoardState,
+    tables: &Tables,
+    target: Square,
+) -> Vec<MoveRep> {
     let mut moves = Vec::with_capacity(256);
-    // Get the pinned pieces
-    let pinned_pieces = match board.white_to_move {
-        true => board.pin_mask(tables, board.white_king, board.white_to_move),
-        false => board.pin_mask(tables, board.black_king, board.white_to_move),
-    };
     // Get the sides to moves king
-    let king = match board.white_to_move {
-        true => board.white_king,
-        false => board.black_king,
-    };
+    let king =
+        Square::from_bit(board.pieces(Color::from_white(board.white_to_move), PieceType::King));
+    // Get the pinned pieces
+    let pinned_pieces = board.pin_mask(tables, king, board.white_to_move);
 
     // Get the type of piece of the target
     let target_piece_type = board.get_piece_type(target);
 
     // Get the mask of pieces which can attack the target
-    let mut possible_attacks = match board.white_to_move {
-        true => board.white_attacking(tables, target),
-        false => board.black_attacking(tables, target),
+    let possible_attacks = match board.white_to_move {
+        true => board.attacking::<true>(tables, target),
+        false => board.attacking::<false>(tables, target),
     };
     // If the possible attacks is empty, there are no capturing moves, so return early
-    if possible_attacks == 0 {
+    if possible_attacks.is_empty() {
         return moves;
     }
 
     // Generate the moves
-    while possible_attacks != 0 {
-        let start_square = pop_lsb(&mut possible_attacks);
-        let piece_type = board.get_piece_type(1 << start_square);
+    for start_square in possible_attacks {
+        let piece_type = board.get_piece_type(start_square);
 
         // NOTE We dont generate en passant attacks here because a diffrent function handles that logic
 
         let mv = MoveRep {
-            starting_square: 1 << start_square,
+            starting_square: start_square,
             ending_square: target,
             promotion: None,
             moved_type: piece_type.unwrap(),
@@ -276,41 +198,9 @@ pub fn generate_attacking_moves(board: &BoardState, tables: &Tables, target: u64
         if mv.moved_type == PieceType::King {
             continue;
         }
-        if mv.starting_square & pinned_pieces == 0 || board.pin_safe(tables, king, &mv) {
+        if !pinned_pieces.contains(mv.starting_square) || board.pin_safe(tables, king, &mv) {
             if mv.moved_type == PieceType::Pawn {
-                if board.white_to_move && mv.ending_square & Tables::RANK_8 != 0 {
-                    // White promotion
-                    let mut queen_promotion = mv;
-                    queen_promotion.promotion = Some(Promotion::Queen);
-                    let mut rook_promotion = mv;
-                    rook_promotion.promotion = Some(Promotion::Rook);
-                    let mut bishop_promotion = mv;
-                    bishop_promotion.promotion = Some(Promotion::Bishop);
-                    let mut knight_promotion = mv;
-                    knight_promotion.promotion = Some(Promotion::Knight);
-                    moves.push(queen_promotion);
-                    moves.push(rook_promotion);
-                    moves.push(bishop_promotion);
-                    moves.push(knight_promotion);
-                }
-                if !board.white_to_move && mv.ending_square & Tables::RANK_1 != 0 {
-                    // Black promotion
-                    let mut queen_promotion = mv;
-                    queen_promotion.promotion = Some(Promotion::Queen);
-                    let mut rook_promotion = mv;
-                    rook_promotion.promotion = Some(Promotion::Rook);
-                    let mut bishop_promotion = mv;
-                    bishop_promotion.promotion = Some(Promotion::Bishop);
-                    let mut knight_promotion = mv;
-                    knight_promotion.promotion = Some(Promotion::Knight);
-                    moves.push(queen_promotion);
-                    moves.push(rook_promotion);
-                    moves.push(bishop_promotion);
-                    moves.push(knight_promotion);
-                }
-                if (mv.ending_square & (Tables::RANK_1 | Tables::RANK_8)) == 0 {
-                    moves.push(mv);
-                }
+                push_pawn_move(mv, Tables::RANK_1 | Tables::RANK_8, &mut moves);
             } else {
                 moves.push(mv);
             }
@@ -324,50 +214,44 @@ pub fn generate_attacking_moves(board: &BoardState, tables: &Tables, target: u64
 pub fn generate_target_blocking(
     board: &BoardState,
     tables: &Tables,
-    target: u64,
-    protect_target: u64,
+    target: Square,
+    protect_target: Square,
 ) -> Vec<MoveRep> {
     let mut moves = Vec::with_capacity(256);
 
     // Get the type of piece of the target
     let target_piece_type = board.get_piece_type(target);
-    // Get the pinned pieces
-    let pinned_pieces = match board.white_to_move {
-        true => board.pin_mask(tables, board.white_king, board.white_to_move),
-        false => board.pin_mask(tables, board.black_king, board.white_to_move),
-    };
     // Get the sides to moves king
-    let king = match board.white_to_move {
-        true => board.white_king,
-        false => board.black_king,
-    };
+    let king =
+        Square::from_bit(board.pieces(Color::from_white(board.white_to_move), PieceType::King));
+    // Get the pinned pieces
+    let pinned_pieces = board.pin_mask(tables, king, board.white_to_move);
 
     // Get the mask of pieces which can attack the target
     let mut possible_attacks = match board.white_to_move {
-        true => board.white_blocking(tables, target),
-        false => board.black_blocking(tables, target),
+        true => board.blocking::<true>(tables, target),
+        false => board.blocking::<false>(tables, target),
     };
 
     // Remove any attacks which use the protect piece, it cant protect itself
-    possible_attacks &= !protect_target;
+    possible_attacks &= !protect_target.bit();
 
     // If the possible attacks is empty, there are no capturing moves, so return early
-    if possible_attacks == 0 {
+    if possible_attacks.is_empty() {
         return moves;
     }
 
     // Generate the moves
-    while possible_attacks != 0 {
-        let start_square = pop_lsb(&mut possible_attacks);
-        let piece_type = board.get_piece_type(1 << start_square);
+    for start_square in possible_attacks {
+        let piece_type = board.get_piece_type(start_square);
         let mv = MoveRep {
-            starting_square: 1 << start_square,
+            starting_square: start_square,
             ending_square: target,
             promotion: None,
             moved_type: piece_type.unwrap(),
             attacked_type: target_piece_type,
         };
-        if pinned_pieces & mv.starting_square == 0 || board.pin_safe(tables, king, &mv) {
+        if !pinned_pieces.contains(mv.starting_square) || board.pin_safe(tables, king, &mv) {
             moves.push(mv);
         }
     }
@@ -379,42 +263,35 @@ pub fn generate_target_blocking(
 pub fn generate_blocking_moves(
     board: &BoardState,
     tables: &Tables,
-    protect_target: u64,
-    attacking_target: u64,
+    protect_target: Square,
+    attacking_target: Square,
 ) -> Vec<MoveRep> {
     let mut moves = Vec::with_capacity(256);
     // Get the mask of the moves which can be blocked
-    let attacking_target_index = attacking_target.trailing_zeros() as u64;
-    let protect_target_index = protect_target.trailing_zeros() as u64;
+    let attacking_square = attacking_target;
+    let protect_square = protect_target;
     let piece_type = board.get_piece_type(attacking_target);
-    let mut blockable_attack_mask = match piece_type {
+    let blockable_attack_mask = match piece_type {
         Some(PieceType::Rook) => {
             // The intersection between the attackers mask, and the attack mask as if the attacking piece was on the protected
             // square is the blockable attack mask
-            let attackers_mask =
-                tables.get_rook_attack(attacking_target_index as usize, board.occupancy());
-            let protected_mask =
-                tables.get_rook_attack(protect_target_index as usize, board.occupancy());
+            let attackers_mask = tables.get_rook_attack(attacking_square, board.occupancy());
+            let protected_mask = tables.get_rook_attack(protect_square, board.occupancy());
             attackers_mask & protected_mask
         }
         Some(PieceType::Bishop) => {
-            let attackers_mask =
-                tables.get_bishop_attack(attacking_target_index as usize, board.occupancy());
-            let protected_mask =
-                tables.get_bishop_attack(protect_target_index as usize, board.occupancy());
+            let attackers_mask = tables.get_bishop_attack(attacking_square, board.occupancy());
+            let protected_mask = tables.get_bishop_attack(protect_square, board.occupancy());
             attackers_mask & protected_mask
         }
         Some(PieceType::Queen) => {
-            let attackers_mask_rook =
-                tables.get_rook_attack(attacking_target_index as usize, board.occupancy());
-            let protected_mask_rook =
-                tables.get_rook_attack(protect_target_index as usize, board.occupancy());
+            let attackers_mask_rook = tables.get_rook_attack(attacking_square, board.occupancy());
+            let protected_mask_rook = tables.get_rook_attack(protect_square, board.occupancy());
             let attackers_mask_bishop =
-                tables.get_bishop_attack(attacking_target_index as usize, board.occupancy());
-            let protected_mask_bishop =
-                tables.get_bishop_attack(protect_target_index as usize, board.occupancy());
+                tables.get_bishop_attack(attacking_square, board.occupancy());
+            let protected_mask_bishop = tables.get_bishop_attack(protect_square, board.occupancy());
             // We need to find if the ray is rook, or bishop like
-            if attackers_mask_rook & protect_target != 0 {
+            if attackers_mask_rook.contains(protect_target) {
                 // Rook like
                 attackers_mask_rook & protected_mask_rook
             } else {
@@ -422,18 +299,17 @@ pub fn generate_blocking_moves(
                 attackers_mask_bishop & protected_mask_bishop
             }
         }
-        _ => 0,
+        _ => Bitboard::EMPTY,
     };
 
     // If the mask is empty, then there are no moves to block
-    if blockable_attack_mask == 0 {
+    if blockable_attack_mask.is_empty() {
         return moves;
     }
 
     // Now that we have a mask of the squares that can block the attack, find the moves that attack those squares
-    while blockable_attack_mask != 0 {
-        let square = pop_lsb(&mut blockable_attack_mask);
-        moves.append(generate_target_blocking(board, tables, 1 << square, protect_target).as_mut());
+    for square in blockable_attack_mask {
+        moves.append(generate_target_blocking(board, tables, square, protect_target).as_mut());
     }
 
     moves
@@ -444,214 +320,130 @@ pub fn move_king_to_safety(board: &BoardState, tables: &Tables) -> Vec<MoveRep>
     let mut moves = Vec::with_capacity(256);
 
     // Get the king position
-    let king = match board.white_to_move {
-        true => board.white_king,
-        false => board.black_king,
-    };
+    let color = Color::from_white(board.white_to_move);
+    let king = board.pieces(color, PieceType::King);
 
-    let king_index = king.trailing_zeros();
-    let mut safe_squares = match board.white_to_move {
-        true => {
-            tables.king_attacks[king_index as usize]
-                & !board.black_attack_mask_with_transparency(tables, king)
-                & !board.white_occupancy()
-        }
-        false => {
-            tables.king_attacks[king_index as usize]
-                & !board.white_attack_mask_with_transparency(tables, king)
-                & !board.black_occupancy()
-        }
+    // The king is seen through, as it can not step back along the line of a check
+    let attacked = match board.white_to_move {
+        true => board.attack_mask::<false>(tables, king),
+        false => board.attack_mask::<true>(tables, king),
     };
-    while safe_squares != 0 {
-        let end_square = pop_lsb(&mut safe_squares);
-        let attacked_type = board.get_piece_type(1 << end_square);
-        let mv = MoveRep::new(king, 1 << end_square, None, PieceType::King, attacked_type);
+    let safe_squares =
+        tables.king_attacks[Square::from_bit(king)] & !attacked & !board.color_occupancy(color);
+    for end_square in safe_squares {
+        let attacked_type = board.get_piece_type(end_square);
+        let mv = MoveRep::new(
+            Square::from_bit(king),
+            end_square,
+            None,
+            PieceType::King,
+            attacked_type,
+        );
         moves.push(mv);
     }
 
     moves
 }
 
-fn white_pawn_moves(
+// Push the move, or all of its promotions if it reaches the last rank
+fn push_pawn_move(mv: MoveRep, last_rank: Bitboard, moves: &mut Vec<MoveRep>) {
+    if !last_rank.contains(mv.ending_square) {
+        moves.push(mv);
+        return;
+    }
+    for promotion in [
+        Promotion::Queen,
+        Promotion::Rook,
+        Promotion::Bishop,
+        Promotion::Knight,
+    ] {
+        moves.push(MoveRep {
+            promotion: Some(promotion),
+            ..mv
+        });
+    }
+}
+
+fn pawn_moves<const WHITE: bool>(
     board: &BoardState,
     tables: &Tables,
-    pinned_pieces: u64,
-    king: u64,
+    pinned_pieces: Bitboard,
+    king: Square,
     moves: &mut Vec<MoveRep>,
 ) {
-    let black_occupancy = board.black_occupancy();
+    let color = Color::from_white(WHITE);
+    let enemy_occupancy = board.color_occupancy(!color);
     let occupancy = board.occupancy();
+    // The attacks of the other side are used to look 'backward' from a square
+    let (pushes, attacks, behind_attacks, last_rank) = match WHITE {
+        true => (
+            &tables.white_pawn_push,
+            &tables.white_pawn_attacks,
+            &tables.black_pawn_attacks,
+            Tables::RANK_8,
+        ),
+        false => (
+            &tables.black_pawn_push,
+            &tables.black_pawn_attacks,
+            &tables.white_pawn_attacks,
+            Tables::RANK_1,
+        ),
+    };
+    let forward = |square: Square| match WHITE {
+        true => square.north(),
+        false => square.south(),
+    };
+    let pawn_bb = board.pieces(color, PieceType::Pawn);
 
-    let mut pawn_bb = board.white_pawns;
-    // White Pawn Pushes
-    while pawn_bb != 0 {
-        let start_square = pop_lsb(&mut pawn_bb);
-        let mut pushes = tables.white_pawn_push[start_square];
-        while pushes != 0 {
-            let end_square = 1 << pop_lsb(&mut pushes);
-            let attacked_type = board.get_piece_type(end_square);
+    // Pawn Pushes
+    for start_square in pawn_bb {
+        for end_square in pushes[start_square] {
             // Check that a double push does not skip over a piece
-            if (end_square >> 16) == (1u64 << start_square) as u64
-                && (end_square >> 8) & occupancy != 0
-            {
+            let single = forward(start_square);
+            if end_square != single && occupancy.contains(single) {
                 continue;
             }
-            if (end_square & occupancy) == 0 {
-                let push = MoveRep::new(
-                    1 << start_square,
-                    end_square,
-                    None,
-                    PieceType::Pawn,
-                    attacked_type,
-                );
-                if push.starting_square & pinned_pieces == 0 || board.pin_safe(tables, king, &push)
+            if !occupancy.contains(end_square) {
+                let push = MoveRep::new(start_square, end_square, None, PieceType::Pawn, None);
+                if !pinned_pieces.contains(push.starting_square)
+                    || board.pin_safe(tables, king, &push)
                 {
-                    if end_square & Tables::RANK_8 == 0 {
-                        moves.push(push);
-                    } else {
-                        // Promotion
-                        let mut queen_promotion = push;
-                        queen_promotion.promotion = Some(Promotion::Queen);
-                        let mut rook_promotion = push;
-                        rook_promotion.promotion = Some(Promotion::Rook);
-                        let mut bishop_promotion = push;
-                        bishop_promotion.promotion = Some(Promotion::Bishop);
-                        let mut knight_promotion = push;
-                        knight_promotion.promotion = Some(Promotion::Knight);
-                        moves.push(queen_promotion);
-                        moves.push(rook_promotion);
-                        moves.push(bishop_promotion);
-                        moves.push(knight_promotion);
-                    }
+                    push_pawn_move(push, last_rank, moves);
                 }
             }
         }
     }
-    // White Pawn Attacks
-    pawn_bb = board.white_pawns;
-    while pawn_bb != 0 {
-        let start_square = pop_lsb(&mut pawn_bb);
-        let mut attacks = tables.white_pawn_attacks[start_square] & black_occupancy;
-        while attacks != 0 {
-            let end_square = 1 << pop_lsb(&mut attacks);
+    // Pawn Attacks
+    for start_square in pawn_bb {
+        for end_square in attacks[start_square] & enemy_occupancy {
             let attacked_type = board.get_piece_type(end_square);
             let attack = MoveRep::new(
-                1 << start_square,
+                start_square,
                 end_square,
                 None,
                 PieceType::Pawn,
                 attacked_type,
             );
-            if attack.starting_square & pinned_pieces == 0 || board.pin_safe(tables, king, &attack)
+            if !pinned_pieces.contains(attack.starting_square)
+                || board.pin_safe(tables, king, &attack)
             {
-                if end_square & Tables::RANK_8 == 0 {
-                    moves.push(attack);
-                } else {
-                    // Promotion
-                    let mut queen_promotion = attack;
-                    queen_promotion.promotion = Some(Promotion::Queen);
-                    let mut rook_promotion = attack;
-                    rook_promotion.promotion = Some(Promotion::Rook);
-                    let mut bishop_promotion = attack;
-                    bishop_promotion.promotion = Some(Promotion::Bishop);
-                    let mut knight_promotion = attack;
-                    knight_promotion.promotion = Some(Promotion::Knight);
-                    moves.push(queen_promotion);
-                    moves.push(rook_promotion);
-                    moves.push(bishop_promotion);
-                    moves.push(knight_promotion);
-                }
-            }
-        }
-    }
-    // White Pawn En Passant Attacks
-    // Get relevent white pawns (look 'backward' so use opposite color in attack lookup)
-    if board.en_passant_target != 0 {
-        pawn_bb = board.white_pawns
-            & tables.black_pawn_attacks[board.en_passant_target.trailing_zeros() as usize];
-        while pawn_bb != 0 {
-            let start_square = pop_lsb(&mut pawn_bb);
-            let mut attacks = tables.white_pawn_attacks[start_square] & board.en_passant_target;
-            while attacks != 0 {
-                let end_square = 1 << pop_lsb(&mut attacks);
-                // We know its a pawn
-                let attacked_type = PieceType::Pawn;
-                let attack = MoveRep::new(
-                    1 << start_square,
-                    end_square,
-                    None,
-                    attacked_type,
-                    Some(PieceType::Pawn),
-                );
-                // This uses AND instead of OR to prevent discoverd en passant attacks
-                if attack.starting_square & pinned_pieces == 0
-                    && board.pin_safe(tables, king, &attack)
-                {
-                    moves.push(attack);
-                }
-            }
-        }
-    }
-}
-
-fn white_knight_attacks(
-    board: &BoardState,
-    tables: &Tables,
-    pinned_pieces: u64,
-    king: u64,
-    moves: &mut Vec<MoveRep>,
-) {
-    let white_occupancy = board.white_occupancy();
-    let mut knight_bb = board.white_knights;
-    while knight_bb != 0 {
-        let start_square = pop_lsb(&mut knight_bb);
-        let mut attacks = tables.knight_attacks[start_square];
-        while attacks != 0 {
-            let end_square = 1 << pop_lsb(&mut attacks);
-            let attacked_type = board.get_piece_type(end_square);
-            if (end_square & white_occupancy) == 0 {
-                let attack = MoveRep::new(
-                    1 << start_square,
-                    end_square,
-                    None,
-                    PieceType::Knight,
-                    attacked_type,
-                );
-                if attack.starting_square & pinned_pieces == 0
-                    || board.pin_safe(tables, king, &attack)
-                {
-                    moves.push(attack);
-                }
+                push_pawn_move(attack, last_rank, moves);
             }
         }
     }
-}
-
-fn white_rook_attacks(
-    board: &BoardState,
-    tables: &Tables,
-    pinned_pieces: u64,
-    king: u64,
-    moves: &mut Vec<MoveRep>,
-) {
-    let white_occupancy = board.white_occupancy();
-    let occupancy = board.occupancy();
-
-    let mut rook_bb = board.white_rooks;
-    while rook_bb != 0 {
-        let start_square = pop_lsb(&mut rook_bb);
-        let mut attacks = tables.get_rook_attack(start_square, occupancy) & !white_occupancy;
-        while attacks != 0 {
-            let end_square = pop_lsb(&mut attacks);
-            let attacked_type = board.get_piece_type(1 << end_square);
+    // Pawn En Passant Attacks
+    if let Some(en_passant_square) = board.en_passant_target {
+        for start_square in pawn_bb & behind_attacks[en_passant_square] {
             let attack = MoveRep::new(
-                1 << start_square,
-                1 << end_square,
+                start_square,
+                en_passant_square,
                 None,
-                PieceType::Rook,
-                attacked_type,
+                PieceType::Pawn,
+                Some(PieceType::Pawn),
             );
-            if attack.starting_square & pinned_pieces == 0 || board.pin_safe(tables, king, &attack)
+            // This uses AND instead of OR to prevent discoverd en passant attacks
+            if !pinned_pieces.contains(attack.starting_square)
+                && board.pin_safe(tables, king, &attack)
             {
                 moves.push(attack);
             }
@@ -659,421 +451,99 @@ fn white_rook_attacks(
     }
 }
 
-fn white_bishop_attacks(
+// Moves of the knights, rooks or bishops of a side
+fn piece_moves<const WHITE: bool>(
     board: &BoardState,
     tables: &Tables,
-    pinned_pieces: u64,
-    king: u64,
+    kind: PieceType,
+    pinned_pieces: Bitboard,
+    king: Square,
     moves: &mut Vec<MoveRep>,
 ) {
-    let white_occupancy = board.white_occupancy();
     let occupancy = board.occupancy();
-    let mut bishop_bb = board.white_bishops;
-    while bishop_bb != 0 {
-        let start_square = pop_lsb(&mut bishop_bb);
-        let mut attacks = tables.get_bishop_attack(start_square, occupancy) & !white_occupancy;
-        while attacks != 0 {
-            let end_square = pop_lsb(&mut attacks);
-            let attacked_type = board.get_piece_type(1 << end_square);
-            let attack = MoveRep {
-                starting_square: 1 << start_square,
-                ending_square: 1 << end_square,
-                promotion: None,
-                moved_type: PieceType::Bishop,
-                attacked_type,
-            };
-            if attack.starting_square & pinned_pieces == 0 || board.pin_safe(tables, king, &attack)
-            {
-                moves.push(attack);
-            }
-        }
-    }
+    let targets = |square| match kind {
+        PieceType::Knight => tables.knight_attacks[square],
+        PieceType::Rook => tables.get_rook_attack(square, occupancy),
+        PieceType::Bishop => tables.get_bishop_attack(square, occupancy),
+        _ => unreachable!(),
+    };
+    targeted_moves::<WHITE>(board, tables, kind, targets, pinned_pieces, king, moves);
 }
 
-fn white_queen_attacks(
+fn queen_moves<const WHITE: bool>(
     board: &BoardState,
     tables: &Tables,
-    pinned_pieces: u64,
-    king: u64,
+    pinned_pieces: Bitboard,
+    king: Square,
     moves: &mut Vec<MoveRep>,
 ) {
-    let white_occupancy = board.white_occupancy();
     let occupancy = board.occupancy();
     // Rook like
-    let mut rook_bb = board.white_queens;
-    while rook_bb != 0 {
-        let start_square = pop_lsb(&mut rook_bb);
-        let mut attacks = tables.get_rook_attack(start_square, occupancy) & !white_occupancy;
-        while attacks != 0 {
-            let end_square = pop_lsb(&mut attacks);
-            let attacked_type = board.get_piece_type(1 << end_square);
-            let attack = MoveRep::new(
-                1 << start_square,
-                1 << end_square,
-                None,
-                PieceType::Queen,
-                attacked_type,
-            );
-            if attack.starting_square & pinned_pieces == 0 || board.pin_safe(tables, king, &attack)
-            {
-                moves.push(attack);
-            }
-        }
-    }
-
+    let rook_like = |square| tables.get_rook_attack(square, occupancy);
+    targeted_moves::<WHITE>(
+        board,
+        tables,
+        PieceType::Queen,
+        rook_like,
+        pinned_pieces,
+        king,
+        moves,
+    );
     // Bishop like
-    let mut bishop_bb = board.white_queens;
-    while bishop_bb != 0 {
-        let start_square = pop_lsb(&mut bishop_bb);
-        let mut attacks = tables.get_bishop_attack(start_square, occupancy) & !white_occupancy;
-        while attacks != 0 {
-            let end_square = pop_lsb(&mut attacks);
-            let attacked_type = board.get_piece_type(1 << end_square);
-            let attack = MoveRep {
-                starting_square: 1 << start_square,
-                ending_square: 1 << end_square,
-                promotion: None,
-                moved_type: PieceType::Queen,
-                attacked_type,
-            };
-            if attack.starting_square & pinned_pieces == 0 || board.pin_safe(tables, king, &attack)
-            {
-                moves.push(attack);
-            }
-        }
-    }
-}
-
-fn white_king_attacks(
-    board: &BoardState,
-    tables: &Tables,
-    pinned_pieces: u64,
-    king: u64,
-    moves: &mut Vec<MoveRep>,
-) {
-    let white_occupancy = board.white_occupancy();
-    let mut king_bb = board.white_king;
-    while king_bb != 0 {
-        let start_square = pop_lsb(&mut king_bb) as u64;
-        let mut attacks = tables.king_attacks[start_square as usize] & !white_occupancy;
-        while attacks != 0 {
-            let end_square = pop_lsb(&mut attacks) as u64;
-            let attacked_type = board.get_piece_type(1 << end_square);
-            let black_attack_mask = board.black_attack_mask(tables);
-            let attack = MoveRep {
-                starting_square: 1 << start_square,
-                ending_square: 1 << end_square,
-                promotion: None,
-                moved_type: PieceType::King,
-                attacked_type,
-            };
-            if black_attack_mask & 1 << end_square == 0
-                && (attack.starting_square & pinned_pieces == 0
-                    || board.pin_safe(tables, king, &attack))
-            {
-                moves.push(attack);
-            }
-        }
-    }
+    let bishop_like = |square| tables.get_bishop_attack(square, occupancy);
+    targeted_moves::<WHITE>(
+        board,
+        tables,
+        PieceType::Queen,
+        bishop_like,
+        pinned_pieces,
+        king,
+        moves,
+    );
 }
 
-fn black_pawn_moves(
+fn king_moves<const WHITE: bool>(
     board: &BoardState,
     tables: &Tables,
-    pinned_pieces: u64,
-    king: u64,
+    pinned_pieces: Bitboard,
+    king: Square,
     moves: &mut Vec<MoveRep>,
 ) {
-    let white_occupancy = board.white_occupancy();
-    let occupancy = board.occupancy();
-
-    let mut pawn_bb = board.black_pawns;
-    // Black Pawn Pushes
-    while pawn_bb != 0 {
-        let start_square = pop_lsb(&mut pawn_bb);
-        let mut pushes = tables.black_pawn_push[start_square];
-        while pushes != 0 {
-            let end_square = 1 << pop_lsb(&mut pushes);
-            let attacked_type = board.get_piece_type(end_square);
-            // Check that a double push does not skip over a piece
-            if (end_square << 16) == (1u64 << start_square) as u64
-                && (end_square << 8) & occupancy != 0
-            {
-                continue;
-            }
-            if (end_square & occupancy) == 0 {
-                let push = MoveRep::new(
-                    1 << start_square,
-                    end_square,
-                    None,
-                    PieceType::Pawn,
-                    attacked_type,
-                );
-                if push.starting_square & pinned_pieces == 0 || board.pin_safe(tables, king, &push)
-                {
-                    if end_square & Tables::RANK_1 == 0 {
-                        moves.push(push);
-                    } else {
-                        // Promotion
-                        let mut queen_promotion = push;
-                        queen_promotion.promotion = Some(Promotion::Queen);
-                        let mut rook_promotion = push;
-                        rook_promotion.promotion = Some(Promotion::Rook);
-                        let mut bishop_promotion = push;
-                        bishop_promotion.promotion = Some(Promotion::Bishop);
-                        let mut knight_promotion = push;
-                        knight_promotion.promotion = Some(Promotion::Knight);
-                        moves.push(queen_promotion);
-                        moves.push(rook_promotion);
-                        moves.push(bishop_promotion);
-                        moves.push(knight_promotion);
-                    }
-                }
-            }
-        }
-    }
-    // Black Pawn Attacks
-    pawn_bb = board.black_pawns;
-    while pawn_bb != 0 {
-        let start_square = pop_lsb(&mut pawn_bb);
-        let mut attacks = tables.black_pawn_attacks[start_square] & white_occupancy;
-        while attacks != 0 {
-            let end_square = 1 << pop_lsb(&mut attacks);
-            let attacked_type = board.get_piece_type(end_square);
-            let attack = MoveRep::new(
-                1 << start_square,
-                end_square,
-                None,
-                PieceType::Pawn,
-                attacked_type,
-            );
-            if attack.starting_square & pinned_pieces == 0 || board.pin_safe(tables, king, &attack)
-            {
-                if end_square & Tables::RANK_1 == 0 {
-                    moves.push(attack);
-                } else {
-                    // Promotion
-                    let mut queen_promotion = attack;
-                    queen_promotion.promotion = Some(Promotion::Queen);
-                    let mut rook_promotion = attack;
-                    rook_promotion.promotion = Some(Promotion::Rook);
-                    let mut bishop_promotion = attack;
-                    bishop_promotion.promotion = Some(Promotion::Bishop);
-                    let mut knight_promotion = attack;
-                    knight_promotion.promotion = Some(Promotion::Knight);
-                    moves.push(queen_promotion);
-                    moves.push(rook_promotion);
-                    moves.push(bishop_promotion);
-                    moves.push(knight_promotion);
-                }
-            }
-        }
-    }
-
-    // Black Pawn En Passant Attacks
-    // Get relevent black pawns (look 'backward' so use opposite color in attack lookup)
-    if board.en_passant_target != 0 {
-        pawn_bb = board.black_pawns
-            & tables.white_pawn_attacks[board.en_passant_target.trailing_zeros() as usize];
-        while pawn_bb != 0 {
-            let start_square = pop_lsb(&mut pawn_bb);
-            let mut attacks = tables.black_pawn_attacks[start_square] & board.en_passant_target;
-            while attacks != 0 {
-                let end_square = 1 << pop_lsb(&mut attacks);
-                // We know its a pawn
-                let attacked_type = PieceType::Pawn;
-                let attack = MoveRep::new(
-                    1 << start_square,
-                    end_square,
-                    None,
-                    attacked_type,
-                    Some(PieceType::Pawn),
-                );
-                // This uses AND instead of OR to prevent discoverd en passant attacks
-                if attack.starting_square & pinned_pieces == 0
-                    && board.pin_safe(tables, king, &attack)
-                {
-                    moves.push(attack);
-                }
-            }
-        }
-    }
+    let enemy_attack_mask = match WHITE {
+        true => board.black_attack_mask(tables),
+        false => board.white_attack_mask(tables),
+    };
+    let safe = |square| tables.king_attacks[square] & !enemy_attack_mask;
+    targeted_moves::<WHITE>(
+        board,
+        tables,
+        PieceType::King,
+        safe,
+        pinned_pieces,
+        king,
+        moves,
+    );
 }
 
-fn black_knight_attacks(
+// Moves of every piece of a kind to the squares given by targets, apart from those holding a piece
+// of the same side
+fn targeted_moves<const WHITE: bool>(
     board: &BoardState,
     tables: &Tables,
-    pinned_pieces: u64,
-    king: u64,
+    kind: PieceType,
+    targets: impl Fn(Square) -> Bitboard,
+    pinned_pieces: Bitboard,
+    king: Square,
     moves: &mut Vec<MoveRep>,
 ) {
-    let black_occupancy = board.black_occupancy();
-    let mut knight_bb = board.black_knights;
-    while knight_bb != 0 {
-        let start_square = pop_lsb(&mut knight_bb);
-        let mut attacks = tables.knight_attacks[start_square];
-        while attacks != 0 {
-            let end_square = 1 << pop_lsb(&mut attacks);
+    let color = Color::from_white(WHITE);
+    let own_occupancy = board.color_occupancy(color);
+    for start_square in board.pieces(color, kind) {
+        for end_square in targets(start_square) & !own_occupancy {
             let attacked_type = board.get_piece_type(end_square);
-            if (end_square & black_occupancy) == 0 {
-                let attack = MoveRep::new(
-                    1 << start_square,
-                    end_square,
-                    None,
-                    PieceType::Knight,
-                    attacked_type,
-                );
-                if attack.starting_square & pinned_pieces == 0
-                    || board.pin_safe(tables, king, &attack)
-                {
-                    moves.push(attack);
-                }
-            }
-        }
-    }
-}
-
-fn black_rook_attacks(
-    board: &BoardState,
-    tables: &Tables,
-    pinned_pieces: u64,
-    king: u64,
-    moves: &mut Vec<MoveRep>,
-) {
-    let black_occupancy = board.black_occupancy();
-    let occupancy = board.occupancy();
-    let mut rook_bb = board.black_rooks;
-    while rook_bb != 0 {
-        let start_square = pop_lsb(&mut rook_bb);
-        let mut attacks = tables.get_rook_attack(start_square, occupancy) & !black_occupancy;
-        while attacks != 0 {
-            let end_square = pop_lsb(&mut attacks);
-            let attacked_type = board.get_piece_type(1 << end_square);
-            let attack = MoveRep::new(
-                1 << start_square,
-                1 << end_square,
-                None,
-                PieceType::Rook,
-                attacked_type,
-            );
-            if attack.starting_square & pinned_pieces == 0 || board.pin_safe(tables, king, &attack)
-            {
-                moves.push(attack);
-            }
-        }
-    }
-}
-
-fn black_bishop_attacks(
-    board: &BoardState,
-    tables: &Tables,
-    pinned_pieces: u64,
-    king: u64,
-    moves: &mut Vec<MoveRep>,
-) {
-    let black_occupancy = board.black_occupancy();
-    let occupancy = board.occupancy();
-    let mut bishop_bb = board.black_bishops;
-    while bishop_bb != 0 {
-        let start_square = pop_lsb(&mut bishop_bb);
-        let mut attacks = tables.get_bishop_attack(start_square, occupancy) & !black_occupancy;
-        while attacks != 0 {
-            let end_square = pop_lsb(&mut attacks);
-            let attacked_type = board.get_piece_type(1 << end_square);
-            let attack = MoveRep {
-                starting_square: 1 << start_square,
-                ending_square: 1 << end_square,
-                promotion: None,
-                moved_type: PieceType::Bishop,
-                attacked_type,
-            };
-            if attack.starting_square & pinned_pieces == 0 || board.pin_safe(tables, king, &attack)
-            {
-                moves.push(attack);
-            }
-        }
-    }
-}
-
-fn black_queen_attacks(
-    board: &BoardState,
-    tables: &Tables,
-    pinned_pieces: u64,
-    king: u64,
-    moves: &mut Vec<MoveRep>,
-) {
-    let black_occupancy = board.black_occupancy();
-    let occupancy = board.occupancy();
-    let mut rook_bb = board.black_queens;
-    while rook_bb != 0 {
-        let start_square = pop_lsb(&mut rook_bb);
-        let mut attacks = tables.get_rook_attack(start_square, occupancy) & !black_occupancy;
-        while attacks != 0 {
-            let end_square = pop_lsb(&mut attacks);
-            let attacked_type = board.get_piece_type(1 << end_square);
-            let attack = MoveRep::new(
-                1 << start_square,
-                1 << end_square,
-                None,
-                PieceType::Queen,
-                attacked_type,
-            );
-            if attack.starting_square & pinned_pieces == 0 || board.pin_safe(tables, king, &attack)
-            {
-                moves.push(attack);
-            }
-        }
-    }
-
-    // Bishop like
-    let mut bishop_bb = board.black_queens;
-    while bishop_bb != 0 {
-        let start_square = pop_lsb(&mut bishop_bb);
-        let mut attacks = tables.get_bishop_attack(start_square, occupancy) & !black_occupancy;
-        while attacks != 0 {
-            let end_square = pop_lsb(&mut attacks);
-            let attacked_type = board.get_piece_type(1 << end_square);
-            let attack = MoveRep {
-                starting_square: 1 << start_square,
-                ending_square: 1 << end_square,
-                promotion: None,
-                moved_type: PieceType::Queen,
-                attacked_type,
-            };
-            if attack.starting_square & pinned_pieces == 0 || board.pin_safe(tables, king, &attack)
-            {
-                moves.push(attack);
-            }
-        }
-    }
-}
-
-fn black_king_attacks(
-    board: &BoardState,
-    tables: &Tables,
-    pinned_pieces: u64,
-    king: u64,
-    moves: &mut Vec<MoveRep>,
-) {
-    let black_occupancy = board.black_occupancy();
-    let mut king_bb = board.black_king;
-    while king_bb != 0 {
-        let start_square = pop_lsb(&mut king_bb) as u64;
-        let mut attacks = tables.king_attacks[start_square as usize] & !black_occupancy;
-        while attacks != 0 {
-            let end_square = pop_lsb(&mut attacks) as u64;
-            let attacked_type = board.get_piece_type(1 << end_square);
-            let white_attack_mask = board.white_attack_mask(tables);
-            let attack = MoveRep {
-                starting_square: 1 << start_square,
-                ending_square: 1 << end_square,
-                promotion: None,
-                moved_type: PieceType::King,
-                attacked_type,
-            };
-            if white_attack_mask & 1 << end_square == 0
-                && (attack.starting_square & pinned_pieces == 0
-                    || board.pin_safe(tables, king, &attack))
+            let attack = MoveRep::new(start_square, end_square, None, kind, attacked_type);
+            if !pinned_pieces.contains(attack.starting_square)
+                || board.pin_safe(tables, king, &attack)
             {
                 moves.push(attack);
             }
@@ -1081,14 +551,6 @@ fn black_king_attacks(
     }
 }
 
-#[inline]
-/// Get and remove the lsb as a square index
-pub fn pop_lsb(bb: &mut u64) -> usize {
-    let lsb = bb.trailing_zeros() as usize;
-    *bb ^= 1 << lsb;
-    lsb
-}
-
 #[cfg(test)]
 mod tests {
 
@@ -1096,33 +558,25 @@ mod tests {
 
     use super::*;
 
-    #[test]
-    fn test_pop_lsb() {
-        let mut bb = 3;
-        let least_sig = pop_lsb(&mut bb);
-        assert_eq!(least_sig, 0);
-        assert_eq!(bb, 2);
-    }
-
     #[test]
     fn test_gen_attacking_moves_1() {
         let board = BoardState::state_from_string_fen(
             "rnbqkbnr/pppppppp/1P6/8/5B2/3P4/P1P1PPPP/RN1QKBNR w KQkq - 0 1".to_string(),
         );
         let tables = Tables::new();
-        let target = 1 << Tables::C7;
+        let target = Square::C7;
 
         let expected_move_1 = MoveRep {
-            starting_square: 1 << Tables::F4,
-            ending_square: 1 << Tables::C7,
+            starting_square: Square::F4,
+            ending_square: Square::C7,
             promotion: None,
             moved_type: PieceType::Bishop,
             attacked_type: Some(PieceType::Pawn),
         };
 
         let expected_move_2 = MoveRep {
-            starting_square: 1 << Tables::B6,
-            ending_square: 1 << Tables::C7,
+            starting_square: Square::B6,
+            ending_square: Square::C7,
             promotion: None,
             moved_type: PieceType::Pawn,
             attacked_type: Some(PieceType::Pawn),
@@ -1140,7 +594,7 @@ mod tests {
             "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1".to_string(),
         );
         let tables = Tables::new();
-        let results = generate_attacking_moves(&board, &tables, 1 << Tables::E8);
+        let results = generate_attacking_moves(&board, &tables, Square::E8);
         assert_eq!(results.len(), 0);
     }
 
@@ -1150,11 +604,11 @@ mod tests {
             "rnbqkbnr/ppp1pppp/8/8/8/8/PPP1PPPP/RNBQKBNR w KQkq - 0 1".to_string(),
         );
         let tables = Tables::new();
-        let target = 1 << Tables::D8;
+        let target = Square::D8;
 
         let expected_move = MoveRep {
-            starting_square: 1 << Tables::D1,
-            ending_square: 1 << Tables::D8,
+            starting_square: Square::D1,
+            ending_square: Square::D8,
             promotion: None,
             moved_type: PieceType::Queen,
             attacked_type: Some(PieceType::Queen),
@@ -1171,19 +625,19 @@ mod tests {
             "rnbqkbnr/pp2pppp/8/2p5/3Q4/8/PPP1PPPP/RNB1KBNR b KQkq - 0 1".to_string(),
         );
         let tables = Tables::new();
-        let target = 1 << Tables::D4;
+        let target = Square::D4;
 
         let expected_move_1 = MoveRep {
-            starting_square: 1 << Tables::D8,
-            ending_square: 1 << Tables::D4,
+            starting_square: Square::D8,
+            ending_square: Square::D4,
             promotion: None,
             moved_type: PieceType::Queen,
             attacked_type: Some(PieceType::Queen),
         };
 
         let expected_move_2 = MoveRep {
-            starting_square: 1 << Tables::C5,
-            ending_square: 1 << Tables::D4,
+            starting_square: Square::C5,
+            ending_square: Square::D4,
             promotion: None,
             moved_type: PieceType::Pawn,
             attacked_type: Some(PieceType::Queen),
@@ -1203,14 +657,14 @@ mod tests {
         let tables = Tables::new();
 
         let expected_move = MoveRep {
-            starting_square: 1 << Tables::C3,
-            ending_square: 1 << Tables::E4,
+            starting_square: Square::C3,
+            ending_square: Square::E4,
             promotion: None,
             moved_type: PieceType::Knight,
             attacked_type: None,
         };
 
-        let results = generate_blocking_moves(&board, &tables, 1 << Tables::D3, 1 << Tables::F5);
+        let results = generate_blocking_moves(&board, &tables, Square::D3, Square::F5);
         assert_eq!(results.len(), 1);
         assert!(results.contains(&expected_move));
     }
@@ -1222,39 +676,15 @@ mod tests {
         );
         let tables = Tables::new();
 
-        let expected_move_1 = MoveRep::new(
-            1 << Tables::C7,
-            1 << Tables::C6,
-            None,
-            PieceType::Pawn,
-            None,
-        );
+        let expected_move_1 = MoveRep::new(Square::C7, Square::C6, None, PieceType::Pawn, None);
 
-        let expected_move_2 = MoveRep::new(
-            1 << Tables::D7,
-            1 << Tables::D5,
-            None,
-            PieceType::Pawn,
-            None,
-        );
+        let expected_move_2 = MoveRep::new(Square::D7, Square::D5, None, PieceType::Pawn, None);
 
-        let expected_move_3 = MoveRep::new(
-            1 << Tables::B8,
-            1 << Tables::C6,
-            None,
-            PieceType::Knight,
-            None,
-        );
+        let expected_move_3 = MoveRep::new(Square::B8, Square::C6, None, PieceType::Knight, None);
 
-        let expected_move_4 = MoveRep::new(
-            1 << Tables::C8,
-            1 << Tables::B7,
-            None,
-            PieceType::Bishop,
-            None,
-        );
+        let expected_move_4 = MoveRep::new(Square::C8, Square::B7, None, PieceType::Bishop, None);
 
-        let results = generate_blocking_moves(&board, &tables, 1 << Tables::A8, 1 << Tables::G2);
+        let results = generate_blocking_moves(&board, &tables, Square::A8, Square::G2);
         assert_eq!(results.len(), 4);
         assert!(results.contains(&expected_move_1));
         assert!(results.contains(&expected_move_2));
@@ -1269,39 +699,15 @@ mod tests {
         );
         let tables = Tables::new();
 
-        let expected_move_1 = MoveRep::new(
-            1 << Tables::G8,
-            1 << Tables::E7,
-            None,
-            PieceType::Knight,
-            None,
-        );
+        let expected_move_1 = MoveRep::new(Square::G8, Square::E7, None, PieceType::Knight, None);
 
-        let expected_move_2 = MoveRep::new(
-            1 << Tables::D8,
-            1 << Tables::E7,
-            None,
-            PieceType::Queen,
-            None,
-        );
+        let expected_move_2 = MoveRep::new(Square::D8, Square::E7, None, PieceType::Queen, None);
 
-        let expected_move_3 = MoveRep::new(
-            1 << Tables::D7,
-            1 << Tables::D6,
-            None,
-            PieceType::Pawn,
-            None,
-        );
+        let expected_move_3 = MoveRep::new(Square::D7, Square::D6, None, PieceType::Pawn, None);
 
-        let expected_move_4 = MoveRep::new(
-            1 << Tables::C7,
-            1 << Tables::C5,
-            None,
-            PieceType::Pawn,
-            None,
-        );
+        let expected_move_4 = MoveRep::new(Square::C7, Square::C5, None, PieceType::Pawn, None);
 
-        let results = generate_blocking_moves(&board, &tables, 1 << Tables::F8, 1 << Tables::A3);
+        let results = generate_blocking_moves(&board, &tables, Square::F8, Square::A3);
         assert_eq!(results.len(), 4);
         assert!(results.contains(&expected_move_1));
         assert!(results.contains(&expected_move_2));
@@ -1317,23 +723,11 @@ mod tests {
 
         let tables = Tables::new();
 
-        let expected_move_1 = MoveRep::new(
-            1 << Tables::C8,
-            1 << Tables::D7,
-            None,
-            PieceType::Bishop,
-            None,
-        );
+        let expected_move_1 = MoveRep::new(Square::C8, Square::D7, None, PieceType::Bishop, None);
 
-        let expected_move_2 = MoveRep::new(
-            1 << Tables::B8,
-            1 << Tables::D7,
-            None,
-            PieceType::Knight,
-            None,
-        );
+        let expected_move_2 = MoveRep::new(Square::B8, Square::D7, None, PieceType::Knight, None);
 
-        let results = generate_blocking_moves(&board, &tables, 1 << Tables::D8, 1 << Tables::D1);
+        let results = generate_blocking_moves(&board, &tables, Square::D8, Square::D1);
         assert_eq!(results.len(), 2);
         assert!(results.contains(&expected_move_1));
         assert!(results.contains(&expected_move_2));
@@ -1346,48 +740,12 @@ mod tests {
         );
         let tables = Tables::new();
 
-        let expected_move_1 = MoveRep::new(
-            1 << Tables::E5,
-            1 << Tables::F6,
-            None,
-            PieceType::King,
-            None,
-        );
-        let expected_move_2 = MoveRep::new(
-            1 << Tables::E5,
-            1 << Tables::E6,
-            None,
-            PieceType::King,
-            None,
-        );
-        let expected_move_3 = MoveRep::new(
-            1 << Tables::E5,
-            1 << Tables::D6,
-            None,
-            PieceType::King,
-            None,
-        );
-        let expected_move_4 = MoveRep::new(
-            1 << Tables::E5,
-            1 << Tables::D4,
-            None,
-            PieceType::King,
-            None,
-        );
-        let expected_move_5 = MoveRep::new(
-            1 << Tables::E5,
-            1 << Tables::E4,
-            None,
-            PieceType::King,
-            None,
-        );
-        let expected_move_6 = MoveRep::new(
-            1 << Tables::E5,
-            1 << Tables::F4,
-            None,
-            PieceType::King,
-            None,
-        );
+        let expected_move_1 = MoveRep::new(Square::E5, Square::F6, None, PieceType::King, None);
+        let expected_move_2 = MoveRep::new(Square::E5, Square::E6, None, PieceType::King, None);
+        let expected_move_3 = MoveRep::new(Square::E5, Square::D6, None, PieceType::King, None);
+        let expected_move_4 = MoveRep::new(Square::E5, Square::D4, None, PieceType::King, None);
+        let expected_move_5 = MoveRep::new(Square::E5, Square::E4, None, PieceType::King, None);
+        let expected_move_6 = MoveRep::new(Square::E5, Square::F4, None, PieceType::King, None);
 
         let results = move_king_to_safety(&board, &tables);
         assert_eq!(results.len(), 6);
@@ -1404,48 +762,12 @@ mod tests {
         let board = BoardState::state_from_string_fen("8/8/8/8/4k3/2KP4/8/8 b - - 0 1".to_string());
         let tables = Tables::new();
 
-        let expected_move_1 = MoveRep::new(
-            1 << Tables::E4,
-            1 << Tables::E3,
-            None,
-            PieceType::King,
-            None,
-        );
-        let expected_move_2 = MoveRep::new(
-            1 << Tables::E4,
-            1 << Tables::F3,
-            None,
-            PieceType::King,
-            None,
-        );
-        let expected_move_3 = MoveRep::new(
-            1 << Tables::E4,
-            1 << Tables::F4,
-            None,
-            PieceType::King,
-            None,
-        );
-        let expected_move_4 = MoveRep::new(
-            1 << Tables::E4,
-            1 << Tables::F5,
-            None,
-            PieceType::King,
-            None,
-        );
-        let expected_move_5 = MoveRep::new(
-            1 << Tables::E4,
-            1 << Tables::E5,
-            None,
-            PieceType::King,
-            None,
-        );
-        let expected_move_6 = MoveRep::new(
-            1 << Tables::E4,
-            1 << Tables::D5,
-            None,
-            PieceType::King,
-            None,
-        );
+        let expected_move_1 = MoveRep::new(Square::E4, Square::E3, None, PieceType::King, None);
+        let expected_move_2 = MoveRep::new(Square::E4, Square::F3, None, PieceType::King, None);
+        let expected_move_3 = MoveRep::new(Square::E4, Square::F4, None, PieceType::King, None);
+        let expected_move_4 = MoveRep::new(Square::E4, Square::F5, None, PieceType::King, None);
+        let expected_move_5 = MoveRep::new(Square::E4, Square::E5, None, PieceType::King, None);
+        let expected_move_6 = MoveRep::new(Square::E4, Square::D5, None, PieceType::King, None);
 
         let results = move_king_to_safety(&board, &tables);
         assert_eq!(results.len(), 6);
@@ -1473,13 +795,7 @@ mod tests {
         );
         let tables = Tables::new();
 
-        let expected_move = MoveRep::new(
-            1 << Tables::E1,
-            1 << Tables::D2,
-            None,
-            PieceType::King,
-            None,
-        );
+        let expected_move = MoveRep::new(Square::E1, Square::D2, None, PieceType::King, None);
         let results = move_king_to_safety(&board, &tables);
         for mv in &results {
             println!("{mv:?}");
@@ -1509,13 +825,7 @@ mod tests {
         );
         let tables = Tables::new();
 
-        let unexpected_move = MoveRep::new(
-            1 << Tables::B3,
-            1 << Tables::B5,
-            None,
-            PieceType::Pawn,
-            None,
-        );
+        let unexpected_move = MoveRep::new(Square::B3, Square::B5, None, PieceType::Pawn, None);
 
         let results = generate(&board, &tables);
         // for mv in &results {
@@ -1533,8 +843,8 @@ mod tests {
         let tables = Tables::new();
 
         let expected_move = MoveRep::new(
-            1 << Tables::C3,
-            1 << Tables::C4,
+            Square::C3,
+            Square::C4,
             None,
             PieceType::King,
             Some(PieceType::Pawn),
@@ -1555,8 +865,8 @@ mod tests {
         let tables = Tables::new();
 
         let expected_move = MoveRep::new(
-            1 << Tables::B2,
-            1 << Tables::H8,
+            Square::B2,
+            Square::H8,
             None,
             PieceType::Bishop,
             Some(PieceType::Rook),
@@ -1574,8 +884,8 @@ mod tests {
         let tables = Tables::new();
 
         let expected_move = MoveRep::new(
-            1 << Tables::E1,
-            1 << Tables::G1,
+            Square::E1,
+            Square::G1,
             Some(Promotion::Castle),
             PieceType::King,
             None,
@@ -1592,8 +902,8 @@ mod tests {
         let tables = Tables::new();
 
         let expected_move = MoveRep::new(
-            1 << Tables::E1,
-            1 << Tables::C1,
+            Square::E1,
+            Square::C1,
             Some(Promotion::Castle),
             PieceType::King,
             None,
@@ -1610,8 +920,8 @@ mod tests {
         let tables = Tables::new();
 
         let expected_move = MoveRep::new(
-            1 << Tables::E8,
-            1 << Tables::G8,
+            Square::E8,
+            Square::G8,
             Some(Promotion::Castle),
             PieceType::King,
             None,
@@ -1628,8 +938,8 @@ mod tests {
         let tables = Tables::new();
 
         let expected_move = MoveRep::new(
-            1 << Tables::E8,
-            1 << Tables::C8,
+            Square::E8,
+            Square::C8,
             Some(Promotion::Castle),
             PieceType::King,
             None,
@@ -1638,6 +948,20 @@ mod tests {
         assert!(moves.contains(&expected_move));
     }
 
+    #[test]
+    fn test_no_castle_without_rook() {
+        let mut board =
+            BoardState::state_from_string_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1".to_string());
+        board.white_kingside_castle_rights = true;
+        board.white_queenside_castle_rights = true;
+        let tables = Tables::new();
+
+        let moves = generate(&board, &tables);
+        assert!(!moves
+            .iter()
+            .any(|mv| mv.promotion == Some(Promotion::Castle)));
+    }
+
     #[test]
     fn test_castle_blocked_1() {
         let board = BoardState::state_from_string_fen(
@@ -1646,8 +970,8 @@ mod tests {
         let tables = Tables::new();
 
         let unexpected_move = MoveRep::new(
-            1 << Tables::E8,
-            1 << Tables::C8,
+            Square::E8,
+            Square::C8,
             Some(Promotion::Castle),
             PieceType::King,
             None,
@@ -1664,8 +988,8 @@ mod tests {
         );
         let tables = Tables::new();
         let unexpected_move = MoveRep::new(
-            1 << Tables::E8,
-            1 << Tables::C8,
+            Square::E8,
+            Square::C8,
             Some(Promotion::Castle),
             PieceType::King,
             None,
@@ -1683,8 +1007,8 @@ mod tests {
         let tables = Tables::new();
 
         let unexpected_move = MoveRep::new(
-            1 << Tables::E1,
-            1 << Tables::C1,
+            Square::E1,
+            Square::C1,
             Some(Promotion::Castle),
             PieceType::King,
             None,
@@ -1702,8 +1026,8 @@ mod tests {
         let tables = Tables::new();
 
         let unexpected_move = MoveRep::new(
-            1 << Tables::E1,
-            1 << Tables::G1,
+            Square::E1,
+            Square::G1,
             Some(Promotion::Castle),
             PieceType::King,
             None,
@@ -1721,17 +1045,81 @@ mod tests {
         let tables = Tables::new();
 
         let unexpected_mov = MoveRep::new(
-            1 << Tables::E1,
-            1 << Tables::G1,
+            Square::E1,
+            Square::G1,
             Some(Promotion::Castle),
             PieceType::King,
             None,
         );
         let results = generate(&board, &tables);
-        print_bitboard(board.white_king >> 1);
-        print_bitboard(board.white_king >> 2);
-        print_bitboard(board.black_attacking(&tables, board.white_king >> 1));
-        print_bitboard(board.black_attacking(&tables, board.white_king >> 2));
+        print_bitboard(Square::F1.bit());
+        print_bitboard(Square::G1.bit());
+        print_bitboard(board.attacking::<false>(&tables, Square::F1));
+        print_bitboard(board.attacking::<false>(&tables, Square::G1));
         assert!(!results.contains(&unexpected_mov));
     }
+
+    #[test]
+    fn test_chess960_castle_rook_blocking_check() {
+        // The rook on b1 shields the king from a1, but not once it has castled
+        let board = BoardState::state_from_string_fen("7k/8/8/8/8/8/8/rRK5 w B - 0 1".to_string());
+        let tables = Tables::new();
+        let results = generate(&board, &tables);
+        assert!(!results
+            .iter()
+            .any(|mv| mv.promotion == Some(Promotion::Castle)));
+    }
+
+    #[test]
+    fn test_chess960_castle_swap() {
+        // The king and rook swap squares
+        let board = BoardState::state_from_string_fen("4k3/8/8/8/8/8/8/5KR1 w G - 0 1".to_string());
+        let tables = Tables::new();
+        let expected_mov = MoveRep::new(
+            Square::F1,
+            Square::G1,
+            Some(Promotion::Castle),
+            PieceType::King,
+            None,
+        );
+        assert!(generate(&board, &tables).contains(&expected_mov));
+    }
+
+    #[test]
+    fn test_mirrored_positions() {
+        // Both sides share the generators, so a position and its color flipped mirror must have
+        // mirrored moves
+        let pairs = [
+            (
+                "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+                "r3k2r/pppbbppp/2n2q1P/1P2p3/3pn3/BN2PNP1/P1PPQPB1/R3K2R b KQkq - 0 1",
+            ),
+            (
+                "8/2P3k1/8/3pP3/8/8/5p2/K7 w - d6 0 1",
+                "k7/5P2/8/8/3Pp3/8/2p3K1/8 b - d3 0 1",
+            ),
+            (
+                "4k3/8/8/8/1b6/8/8/R3K3 w Q - 0 1",
+                "r3k3/8/8/1B6/8/8/8/4K3 b q - 0 1",
+            ),
+        ];
+        let tables = Tables::new();
+
+        for (fen, mirrored_fen) in pairs {
+            let moves = generate(&BoardState::state_from_string_fen(fen.to_string()), &tables);
+            let mirrored = generate(
+                &BoardState::state_from_string_fen(mirrored_fen.to_string()),
+                &tables,
+            );
+            assert_eq!(moves.len(), mirrored.len(), "{fen}");
+            for mv in moves {
+                let flipped = MoveRep {
+                    starting_square: mv.starting_square.flip(),
+                    ending_square: mv.ending_square.flip(),
+                    ..mv
+                };
+                assert!(mirrored.contains(&flipped), "{fen} {flipped:?}");
+            }
+        }
+    }
 }
@@ -16,148 +16,76 @@ NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FO
 DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT
 OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
 */
-use std::time::Instant;
+use std::io;
+mod bitbase;
 mod board;
 mod comm;
+mod engine;
+mod epd;
 mod eval;
 mod generate;
+mod nnue;
+mod pgn;
+mod puzzle;
 mod search;
+mod strength;
+mod tablebase;
 mod tables;
 mod tt;
+mod tune;
+mod types;
+mod uci;
 
-use board::BoardState;
-use search::{id_search, negamax, perft};
-use tables::Tables;
-use tt::ZobKeys;
+use engine::Engine;
+use eval::EvalParams;
+use std::sync::Arc;
 
-fn main() {
-    let mut board = BoardState::starting_state();
-    let zob_keys = ZobKeys::new();
-    let mut running = true;
+// `--eval-file <path>` before any subcommand loads the evaluation weights, such as those written by
+// tune, for the engine and the offline tools alike
+fn eval_params(args: &mut Vec<String>) -> Result<Arc<EvalParams>, String> {
+    if args.get(1).map(String::as_str) != Some("--eval-file") {
+        return Ok(Arc::new(EvalParams::default()));
+    }
+    let Some(path) = args.get(2) else {
+        return Err("Missing file after --eval-file".to_string());
+    };
+    let params = EvalParams::load(path)?;
+    args.drain(1..3);
+    Ok(Arc::new(params))
+}
 
-    let tables = Tables::new();
-    while running {
-        let line = comm::engine_in();
-        let mut tokens = line.split(" ");
-        match tokens.next().unwrap() {
-            "uci" => {
-                println!("id name nuttchess");
-                println!("id author UraniumNutt / Ethan Thummel");
-                println!("uciok");
-            }
-            "isready" => {
-                println!("readyok");
-            }
-            "ucinewgame" => {}
-            "position" => match tokens.next().unwrap() {
-                "startpos" => {
-                    board = BoardState::starting_state();
-                    if tokens.next().is_some() {
-                        for mv in tokens.by_ref() {
-                            board.apply_string_move(mv.to_string(), &zob_keys);
-                        }
-                    }
-                }
-                "fen" => {
-                    let parsed_board = BoardState::state_from_fen(&mut tokens);
-                    match parsed_board {
-                        Ok(b) => {
-                            board = b;
-                        }
-                        Err(b) => {
-                            println!("Error parsing fen string: {b}");
-                        }
-                    }
-                    if tokens.next().is_some() {
-                        for mv in tokens.by_ref() {
-                            board.apply_string_move(mv.to_string(), &zob_keys);
-                        }
-                    }
-                }
-                e => println!("Unexpected value {e}"),
-            },
-            "print" => {
-                // Pretty print the board state
-                board.pretty_print_board();
-            }
-            "go" => match tokens.next().unwrap() {
-                "perft" => {
-                    let depth = tokens.next();
-                    match depth {
-                        Some(d) => {
-                            if let Ok(parsed_d) = d.parse::<u64>() {
-                                perft(&mut board, parsed_d as usize, &zob_keys);
-                            } else {
-                                println!("Error parsing value of depth token {d}");
-                            }
-                        }
-                        None => {
-                            println!("Expected depth after token perft");
-                        }
-                    }
-                }
-                // TODO implement more / the proper searches
-                "wtime" => {
-                    // FIXME this is just for testing
-                    let starting_time = Instant::now();
-                    // Just discard these for now
-                    let w_time = tokens.next().unwrap().parse::<u64>().unwrap();
-                    let _ = tokens.next();
-                    let b_time = tokens.next().unwrap().parse::<u64>().unwrap();
-                    let _ = tokens.next();
-                    let w_inc = tokens.next().unwrap_or("").parse::<u64>().unwrap_or(0);
-                    let _ = tokens.next();
-                    let b_inc = tokens.next().unwrap_or("").parse::<u64>().unwrap_or(0);
-                    let time_to_spend = match board.white_to_move {
-                        true => (w_time / 20 + w_inc / 2) as u128,
-                        false => (b_time / 20 + b_inc / 2) as u128,
-                    };
-                    let best_move = id_search(
-                        &mut board,
-                        &tables,
-                        &zob_keys,
-                        Some(starting_time),
-                        Some(time_to_spend),
-                    );
-                    println!("bestmove {}", best_move.to_string());
-                }
-                "movetime" => {
-                    let starting_time = Instant::now();
-                    let ms = tokens.next().unwrap().parse::<u64>().unwrap();
-                    let best_move = id_search(
-                        &mut board,
-                        &tables,
-                        &zob_keys,
-                        Some(starting_time),
-                        Some(ms as u128),
-                    );
-                    println!("bestmove {}", best_move.to_string());
-                }
-                "depth" => {
-                    let depth = tokens.next();
-                    match depth {
-                        Some(d) => {
-                            if let Ok(depth_number) = d.parse::<u64>() {
-                                let best_move = negamax(
-                                    &mut board,
-                                    &tables,
-                                    &zob_keys,
-                                    depth_number as usize,
-                                    None,
-                                    None,
-                                );
-                                println!("bestmove {}", best_move.to_string());
-                            }
-                        }
-                        None => println!("Expected depth token"),
-                    }
-                }
-                _ => {}
-            },
-            "quit" => {
-                running = false;
-            }
-            _ => {}
+fn main() {
+    // Offline tools are run as subcommands, otherwise talk uci
+    let mut args: Vec<String> = std::env::args().collect();
+    let params = match eval_params(&mut args) {
+        Ok(params) => params,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
         }
+    };
+    if let Some(command) = args.get(1) {
+        let result = match command.as_str() {
+            "tune" => tune::run(&args[2..], &params),
+            "tbgen" => tablebase::run(&args[2..]),
+            "calibrate" => strength::run(&args[2..], &params),
+            "pgn" => pgn::run(&args[2..]),
+            "testsuite" => epd::run(&args[2..], &params),
+            "puzzles" => puzzle::run(&args[2..], &params),
+            c => Err(format!("Unknown command {c}")),
+        };
+        if let Err(e) = result {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    bitbase::init();
+    let mut engine = Engine::new(io::stdin().lock(), io::stdout());
+    engine.set_eval_params(params);
+    if let Err(e) = engine.run() {
+        eprintln!("{e}");
+        std::process::exit(1);
     }
 }
@@ -0,0 +1,255 @@
+/*
+Copyright 2025 Ethan Thummel
+
+Permission is hereby granted, free of charge, to any person obtaining a copy of this software and
+associated documentation files (the "Software"), to deal in the Software without restriction,
+including without limitation the rights to use, copy, modify, merge, publish, distribute,
+sublicense, and/or sell copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all copies or substantial
+portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT
+NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT
+OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+*/
+use crate::board::{BoardState, PieceType};
+use crate::types::{Color, Square};
+
+// The network file starts with this, followed by the hidden layer size as a u32
+const MAGIC: &[u8; 8] = b"NUTTNNUE";
+
+// One feature for every (perspective relative color, piece, square)
+pub const FEATURES: usize = 768;
+
+// Quantization used by the network
+const QA: i32 = 255;
+const QB: i32 = 64;
+const SCALE: i32 = 400;
+
+/// A small quantized network with a 768 -> hidden x2 -> 1 layout.
+///
+/// File layout, all little endian:
+/// - `NUTTNNUE` magic
+/// - u32 hidden size
+/// - i16 feature weights, `FEATURES` rows of hidden size
+/// - i16 feature biases, hidden size
+/// - i16 output weights, side to move half then the other half
+/// - i32 output bias
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Network {
+    hidden: usize,
+    feature_weights: Vec<i16>,
+    feature_biases: Vec<i16>,
+    output_weights: Vec<i16>,
+    output_bias: i32,
+}
+
+/// The first layer values as seen from each side. Updated by `BoardState::make` / `unmake`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Accumulator {
+    pub white: Vec<i16>,
+    pub black: Vec<i16>,
+}
+
+impl Network {
+    pub fn load(path: &str) -> Result<Network, String> {
+        let bytes =
+            std::fs::read(path).map_err(|e| format!("Could not read network {path}: {e}"))?;
+        Network::from_bytes(&bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Network, String> {
+        if bytes.len() < 12 || &bytes[0..8] != MAGIC {
+            return Err("Not a nuttchess network file".to_string());
+        }
+        let hidden = u32::from_le_bytes(bytes[8..12].try_into().unwrap()) as usize;
+        let expected = 12 + 2 * (FEATURES * hidden + hidden + 2 * hidden) + 4;
+        if hidden == 0 || bytes.len() != expected {
+            return Err(format!(
+                "Network has {} bytes, expected {expected} for a hidden size of {hidden}",
+                bytes.len()
+            ));
+        }
+
+        let mut values = bytes[12..bytes.len() - 4]
+            .chunks_exact(2)
+            .map(|c| i16::from_le_bytes([c[0], c[1]]));
+        let feature_weights = values.by_ref().take(FEATURES * hidden).collect();
+        let feature_biases = values.by_ref().take(hidden).collect();
+        let output_weights = values.by_ref().take(2 * hidden).collect();
+        let output_bias = i32::from_le_bytes(bytes[bytes.len() - 4..].try_into().unwrap());
+
+        Ok(Network {
+            hidden,
+            feature_weights,
+            feature_biases,
+            output_weights,
+            output_bias,
+        })
+    }
+
+    // Index of a piece in the feature layer for each perspective
+    #[inline]
+    fn feature_indices(piece: PieceType, white: bool, square: Square) -> (usize, usize) {
+        let piece_index = match piece {
+            PieceType::Pawn => 0,
+            PieceType::Knight => 1,
+            PieceType::Bishop => 2,
+            PieceType::Rook => 3,
+            PieceType::Queen => 4,
+            PieceType::King => 5,
+        };
+        // Black sees the board flipped, with its own pieces first
+        let white_index = (!white as usize * 6 + piece_index) * 64 + square.index();
+        let black_index = (white as usize * 6 + piece_index) * 64 + square.flip().index();
+        (white_index, black_index)
+    }
+
+    /// Adds a piece to the accumulator
+    #[inline]
+    pub fn add(&self, acc: &mut Accumulator, piece: PieceType, white: bool, square: Square) {
+        let (white_index, black_index) = Network::feature_indices(piece, white, square);
+        let white_row = &self.feature_weights[white_index * self.hidden..][..self.hidden];
+        let black_row = &self.feature_weights[black_index * self.hidden..][..self.hidden];
+        for (a, w) in acc.white.iter_mut().zip(white_row) {
+            *a = a.wrapping_add(*w);
+        }
+        for (a, w) in acc.black.iter_mut().zip(black_row) {
+            *a = a.wrapping_add(*w);
+        }
+    }
+
+    /// Removes a piece from the accumulator
+    #[inline]
+    pub fn remove(&self, acc: &mut Accumulator, piece: PieceType, white: bool, square: Square) {
+        let (white_index, black_index) = Network::feature_indices(piece, white, square);
+        let white_row = &self.feature_weights[white_index * self.hidden..][..self.hidden];
+        let black_row = &self.feature_weights[black_index * self.hidden..][..self.hidden];
+        for (a, w) in acc.white.iter_mut().zip(white_row) {
+            *a = a.wrapping_sub(*w);
+        }
+        for (a, w) in acc.black.iter_mut().zip(black_row) {
+            *a = a.wrapping_sub(*w);
+        }
+    }
+
+    /// Builds the accumulator for a board from scratch
+    pub fn refresh(&self, board: &BoardState) -> Accumulator {
+        let mut acc = Accumulator {
+            white: self.feature_biases.clone(),
+            black: self.feature_biases.clone(),
+        };
+        for color in [Color::White, Color::Black] {
+            for piece in PieceType::ALL {
+                for square in board.pieces(color, piece) {
+                    self.add(&mut acc, piece, color.is_white(), square);
+                }
+            }
+        }
+        acc
+    }
+
+    /// Returns a score relative to the side to move
+    pub fn evaluate(&self, acc: &Accumulator, white_to_move: bool) -> isize {
+        let (us, them) = match white_to_move {
+            true => (&acc.white, &acc.black),
+            false => (&acc.black, &acc.white),
+        };
+        let mut sum = self.output_bias;
+        for (a, w) in us.iter().chain(them.iter()).zip(&self.output_weights) {
+            sum += (*a as i32).clamp(0, QA) * *w as i32;
+        }
+        (sum * SCALE / (QA * QB)) as isize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::BoardState;
+    use crate::generate::generate;
+    use crate::tables::Tables;
+    use crate::tt::ZobKeys;
+    use std::sync::Arc;
+
+    // The test network scores material (pawn 100, knight 300, bishop 325, rook 500, queen 900),
+    // plus a few random hidden neurons so that square mistakes show up
+    const TINY: &[u8] = include_bytes!("../nets/tiny.nnue");
+
+    fn tiny_board(fen: &str) -> BoardState {
+        let mut board = BoardState::state_from_string_fen(fen.to_string());
+        board.set_network(Some(Arc::new(Network::from_bytes(TINY).unwrap())));
+        board
+    }
+
+    #[test]
+    fn load_tiny_network() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/nets/tiny.nnue");
+        let network = Network::load(path).unwrap();
+        assert_eq!(network, Network::from_bytes(TINY).unwrap());
+        assert_eq!(network.hidden, 16);
+    }
+
+    #[test]
+    fn reject_bad_network() {
+        assert!(Network::from_bytes(b"NUTTNNUE").is_err());
+        assert!(Network::from_bytes(&TINY[..TINY.len() - 2]).is_err());
+        assert!(Network::load("does/not/exist.nnue").is_err());
+    }
+
+    #[test]
+    fn material_imbalance() {
+        let board = tiny_board("4k3/8/8/8/8/8/8/3QK3 w - - 0 1");
+        let network = board.network.as_ref().unwrap();
+        let white = network.evaluate(&board.accumulator, true);
+        let black = network.evaluate(&board.accumulator, false);
+        assert!(white > 700, "{white}");
+        assert!(black < -700, "{black}");
+    }
+
+    #[test]
+    fn mirrored_positions_agree() {
+        let white = tiny_board("r3k2r/pp3ppp/2n5/3p4/8/5N2/PPP2PPP/R1B1K2R w KQkq - 0 1");
+        let black = tiny_board("r1b1k2r/ppp2ppp/5n2/8/3P4/2N5/PP3PPP/R3K2R b KQkq - 0 1");
+        let network = white.network.as_ref().unwrap();
+        assert_eq!(
+            network.evaluate(&white.accumulator, true),
+            network.evaluate(&black.accumulator, false)
+        );
+    }
+
+    // Walk the move tree and check the incremental accumulator against a refresh at every node
+    fn check_tree(board: &mut BoardState, tables: &Tables, zob_keys: &ZobKeys, depth: usize) {
+        let network = board.network.clone().unwrap();
+        assert_eq!(board.accumulator, network.refresh(board));
+        if depth == 0 {
+            return;
+        }
+        let before = board.accumulator.clone();
+        for mv in generate(board, tables) {
+            board.make(&mv, zob_keys);
+            check_tree(board, tables, zob_keys, depth - 1);
+            board.unmake(&mv, zob_keys);
+            assert_eq!(board.accumulator, before);
+        }
+    }
+
+    #[test]
+    fn incremental_matches_refresh() {
+        let tables = Tables::new();
+        let zob_keys = ZobKeys::new();
+        // Castling, promotions and en passant are all reachable from these
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        ] {
+            let mut board = tiny_board(fen);
+            check_tree(&mut board, &tables, &zob_keys, 2);
+        }
+    }
+}
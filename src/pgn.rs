@@ -0,0 +1,850 @@
+/*
+Copyright 2025 Ethan Thummel
+
+Permission is hereby granted, free of charge, to any person obtaining a copy of this software and
+associated documentation files (the "Software"), to deal in the Software without restriction,
+including without limitation the rights to use, copy, modify, merge, publish, distribute,
+sublicense, and/or sell copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all copies or substantial
+portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT
+NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT
+OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Lines};
+use std::time::Duration;
+
+use crate::{
+    board::{BoardState, MoveRep},
+    generate::generate,
+    search::mate_in,
+    tables::Tables,
+    tt::ZobKeys,
+};
+
+/// The game termination markers
+const RESULTS: [&str; 4] = ["1-0", "0-1", "1/2-1/2", "*"];
+
+/// A game read from pgn
+#[derive(Clone, Debug)]
+pub struct PgnGame {
+    /// Tag pairs in the order they were read
+    pub tags: Vec<(String, String)>,
+    /// Position the game starts from, which is the FEN tag if there is one
+    pub start: BoardState,
+    /// Moves of the main line. Variations are checked but not kept
+    pub moves: Vec<MoveRep>,
+    /// One of `1-0`, `0-1`, `1/2-1/2` or `*`
+    pub result: String,
+}
+
+impl PgnGame {
+    /// Value of the first tag with the name
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag, _)| tag == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Every position of the main line, from the start through the position after the last move
+    pub fn positions(&self, zob_keys: &ZobKeys) -> Vec<BoardState> {
+        let mut board = self.start.clone();
+        let mut positions = Vec::with_capacity(self.moves.len() + 1);
+        positions.push(board.clone());
+        for mv in &self.moves {
+            board.make(mv, zob_keys);
+            positions.push(board.clone());
+        }
+        positions
+    }
+}
+
+/// A game part way through being read
+struct PartialGame {
+    game: PgnGame,
+    /// If any movetext has been read, after which tags start the next game
+    in_movetext: bool,
+    /// Position of the line being read, set once the movetext starts
+    board: Option<BoardState>,
+    /// The last move played in the line being read, which a variation replaces
+    last: Option<MoveRep>,
+    /// Position and last move of each line a variation branched from
+    variations: Vec<(BoardState, Option<MoveRep>)>,
+    result: Option<String>,
+    /// The first error found. The rest of the game is skipped over
+    error: Option<String>,
+}
+
+impl PartialGame {
+    fn new() -> PartialGame {
+        PartialGame {
+            game: PgnGame {
+                tags: Vec::new(),
+                start: BoardState::starting_state(),
+                moves: Vec::new(),
+                result: String::new(),
+            },
+            in_movetext: false,
+            board: None,
+            last: None,
+            variations: Vec::new(),
+            result: None,
+            error: None,
+        }
+    }
+
+    fn fail(&mut self, line_number: usize, error: String) {
+        self.error
+            .get_or_insert(format!("Line {line_number}: {error}"));
+    }
+
+    /// Sets up the starting position from the tags
+    fn begin(&mut self) -> Result<&mut BoardState, String> {
+        if self.board.is_none() {
+            if let Some(fen) = self.game.tag("FEN") {
+                self.game.start = BoardState::state_from_fen(fen.split_whitespace())
+                    .map_err(|e| format!("Invalid FEN tag: {e}"))?;
+            }
+            self.board = Some(self.game.start.clone());
+        }
+        Ok(self.board.as_mut().unwrap())
+    }
+
+    /// Reads a line of movetext, returning true once the game termination marker is found
+    fn read_movetext(
+        &mut self,
+        text: &str,
+        in_comment: &mut bool,
+        line_number: usize,
+        tables: &Tables,
+        zob_keys: &ZobKeys,
+    ) -> bool {
+        self.in_movetext = true;
+        let mut rest = text;
+        loop {
+            if *in_comment {
+                match rest.find('}') {
+                    Some(end) => {
+                        *in_comment = false;
+                        rest = &rest[end + 1..];
+                    }
+                    None => return false,
+                }
+                continue;
+            }
+
+            rest = rest.trim_start();
+            let end = match rest.chars().next() {
+                None | Some(';') => return false,
+                Some('{' | '}' | '(' | ')') => 1,
+                Some(_) => rest
+                    .find(|c: char| c.is_whitespace() || "{}();".contains(c))
+                    .unwrap_or(rest.len()),
+            };
+            let token = &rest[..end];
+            rest = &rest[end..];
+
+            if token == "{" {
+                *in_comment = true;
+            } else if self.error.is_some() {
+                if RESULTS.contains(&token) {
+                    return true;
+                }
+            } else {
+                match self.read_token(token, tables, zob_keys) {
+                    Ok(true) => return true,
+                    Ok(false) => {}
+                    Err(e) => self.fail(line_number, e),
+                }
+            }
+        }
+    }
+
+    /// Reads a token of movetext, returning true if it ends the game
+    fn read_token(
+        &mut self,
+        token: &str,
+        tables: &Tables,
+        zob_keys: &ZobKeys,
+    ) -> Result<bool, String> {
+        self.begin()?;
+        let board = self.board.as_mut().unwrap();
+        match token {
+            "(" => {
+                let mv = match self.last {
+                    Some(mv) => mv,
+                    None => return Err("Variation does not follow a move".to_string()),
+                };
+                self.variations.push((board.clone(), self.last));
+                board.unmake(&mv, zob_keys);
+                self.last = None;
+            }
+            ")" => match self.variations.pop() {
+                Some((parent, last)) => {
+                    *board = parent;
+                    self.last = last;
+                }
+                None => return Err("Unmatched \")\"".to_string()),
+            },
+            "}" => return Err("Unmatched \"}\"".to_string()),
+            _ if RESULTS.contains(&token) => {
+                if !self.variations.is_empty() {
+                    return Err(format!("Result {token} inside a variation"));
+                }
+                self.result = Some(token.to_string());
+                return Ok(true);
+            }
+            _ if token.starts_with('$') => {
+                if token[1..].parse::<u8>().is_err() {
+                    return Err(format!("Invalid NAG \"{token}\""));
+                }
+            }
+            _ if token.chars().all(|c| c == '!' || c == '?') => {}
+            _ => {
+                // Move numbers may be written against the move that follows them
+                let san = match token.starts_with("0-0") {
+                    true => token,
+                    false => token
+                        .trim_start_matches(|c: char| c.is_ascii_digit())
+                        .trim_start_matches('.'),
+                };
+                if !san.is_empty() {
+                    let mv = board.parse_san(san, tables)?;
+                    board.make(&mv, zob_keys);
+                    if self.variations.is_empty() {
+                        self.game.moves.push(mv);
+                    }
+                    self.last = Some(mv);
+                }
+            }
+        }
+        Ok(false)
+    }
+
+    fn finish(mut self, line_number: usize) -> Result<PgnGame, String> {
+        if let Err(e) = self.begin() {
+            self.fail(line_number, e);
+        }
+        if !self.variations.is_empty() {
+            self.fail(line_number, "Unclosed variation".to_string());
+        }
+        if let Some(e) = self.error {
+            return Err(e);
+        }
+        // A missing termination marker falls back to the Result tag
+        self.game.result = match self.result {
+            Some(result) => result,
+            None => self.game.tag("Result").unwrap_or("*").to_string(),
+        };
+        Ok(self.game)
+    }
+}
+
+/// Parses a tag pair line such as `[White "Carlsen, Magnus"]`
+fn parse_tag(line: &str) -> Result<(String, String), String> {
+    let malformed = || format!("Malformed tag \"{line}\"");
+    let inner = match line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+        Some(inner) => inner.trim(),
+        None => return Err(malformed()),
+    };
+    let (name, value) = match inner.split_once(char::is_whitespace) {
+        Some(pair) => pair,
+        None => return Err(malformed()),
+    };
+    match value
+        .trim()
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+    {
+        Some(value) => Ok((
+            name.to_string(),
+            value.replace("\\\"", "\"").replace("\\\\", "\\"),
+        )),
+        None => Err(malformed()),
+    }
+}
+
+/// Streams the games out of pgn text, checking every move of them is legal. A malformed game is
+/// given as an error naming the line it went wrong on, and reading carries on with the next game
+pub struct PgnReader<'a, R: BufRead> {
+    lines: Lines<R>,
+    line_number: usize,
+    /// A tag line that was read past the end of the previous game
+    pending: Option<String>,
+    tables: &'a Tables,
+    zob_keys: &'a ZobKeys,
+}
+
+impl<'a, R: BufRead> PgnReader<'a, R> {
+    pub fn new(reader: R, tables: &'a Tables, zob_keys: &'a ZobKeys) -> PgnReader<'a, R> {
+        PgnReader {
+            lines: reader.lines(),
+            line_number: 0,
+            pending: None,
+            tables,
+            zob_keys,
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for PgnReader<'_, R> {
+    type Item = Result<PgnGame, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut game: Option<PartialGame> = None;
+        let mut in_comment = false;
+        loop {
+            let line = match self.pending.take() {
+                Some(line) => line,
+                None => match self.lines.next() {
+                    Some(Ok(line)) => {
+                        self.line_number += 1;
+                        line
+                    }
+                    Some(Err(e)) => {
+                        self.line_number += 1;
+                        return Some(Err(format!("Line {}: {e}", self.line_number)));
+                    }
+                    None => break,
+                },
+            };
+            let line = line.trim();
+
+            if !in_comment && line.starts_with('[') {
+                // Tags after the movetext belong to the next game
+                if let Some(partial) = game.take_if(|partial| partial.in_movetext) {
+                    self.pending = Some(line.to_string());
+                    return Some(partial.finish(self.line_number));
+                }
+                let partial = game.get_or_insert_with(PartialGame::new);
+                match parse_tag(line) {
+                    Ok(tag) => partial.game.tags.push(tag),
+                    Err(e) => partial.fail(self.line_number, e),
+                }
+                continue;
+            }
+            // Lines starting with % are escaped from pgn
+            if line.is_empty() || (!in_comment && line.starts_with('%')) {
+                continue;
+            }
+
+            let partial = game.get_or_insert_with(PartialGame::new);
+            let done = partial.read_movetext(
+                line,
+                &mut in_comment,
+                self.line_number,
+                self.tables,
+                self.zob_keys,
+            );
+            if done {
+                return game.map(|partial| partial.finish(self.line_number));
+            }
+        }
+
+        if let Some(partial) = game.as_mut().filter(|_| in_comment) {
+            partial.fail(self.line_number, "Unterminated comment".to_string());
+        }
+        game.map(|partial| partial.finish(self.line_number))
+    }
+}
+
+/// What an engine thought of a move, written as a comment such as `{+0.35/12 1.2s}`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Annotation {
+    /// Search score from the point of view of the side that made the move
+    pub score: isize,
+    pub depth: usize,
+    pub time: Duration,
+}
+
+impl fmt::Display for Annotation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match mate_in(self.score, self.depth) {
+            Some(moves) if moves > 0 => write!(f, "+M{moves}")?,
+            Some(moves) => write!(f, "-M{}", -moves)?,
+            None => write!(f, "{:+.2}", self.score as f64 / 100.0)?,
+        }
+        write!(f, "/{} {:.1}s", self.depth, self.time.as_secs_f64())
+    }
+}
+
+/// A move to be written, with everything attached to it
+#[derive(Clone, Debug)]
+struct WrittenMove {
+    mv: MoveRep,
+    san: String,
+    nags: Vec<u8>,
+    comment: Option<String>,
+    /// Lines played instead of this move
+    variations: Vec<Vec<WrittenMove>>,
+}
+
+impl WrittenMove {
+    fn new(mv: MoveRep, board: &BoardState, tables: &Tables) -> WrittenMove {
+        WrittenMove {
+            mv,
+            san: mv.to_san(board, tables),
+            nags: Vec::new(),
+            comment: None,
+            variations: Vec::new(),
+        }
+    }
+}
+
+/// Builds up a game and writes it as pgn: the Seven Tag Roster and any other tags, then the moves
+/// in SAN with their comments, NAGs and variations, wrapped to 80 columns
+pub struct PgnWriter<'a> {
+    tags: Vec<(String, String)>,
+    /// Ply of the first move, counted from the first move of a game from the starting position
+    first_ply: usize,
+    /// Position after the last move of the main line
+    board: BoardState,
+    moves: Vec<WrittenMove>,
+    result: String,
+    tables: &'a Tables,
+    zob_keys: &'a ZobKeys,
+}
+
+impl<'a> PgnWriter<'a> {
+    /// Maximum length of a line of movetext
+    const LINE_LENGTH: usize = 80;
+
+    /// Starts a game from the position. The Seven Tag Roster is filled with unknown values, and a
+    /// position other than the usual start is written as a FEN tag
+    pub fn new(start: &BoardState, tables: &'a Tables, zob_keys: &'a ZobKeys) -> PgnWriter<'a> {
+        let mut writer = PgnWriter {
+            tags: Vec::new(),
+            first_ply: 2 * (start.full_move_counter.max(1) as usize - 1)
+                + usize::from(!start.white_to_move),
+            board: start.clone(),
+            moves: Vec::new(),
+            result: "*".to_string(),
+            tables,
+            zob_keys,
+        };
+        for (name, value) in [
+            ("Event", "?"),
+            ("Site", "?"),
+            ("Date", "????.??.??"),
+            ("Round", "?"),
+            ("White", "?"),
+            ("Black", "?"),
+            ("Result", "*"),
+        ] {
+            writer.set_tag(name, value);
+        }
+        if start.chess960 {
+            writer.set_tag("Variant", "Chess960");
+        }
+        let fen = start.to_fen();
+        if fen != BoardState::starting_state().to_fen() {
+            writer.set_tag("SetUp", "1");
+            writer.set_tag("FEN", &fen);
+        }
+        writer
+    }
+
+    /// Sets a tag, replacing any earlier value
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(tag, _)| tag == name) {
+            Some((_, old)) => *old = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    /// Sets the game result, one of `1-0`, `0-1`, `1/2-1/2` or `*`
+    pub fn set_result(&mut self, result: &str) {
+        self.result = result.to_string();
+        self.set_tag("Result", result);
+    }
+
+    /// Plays a legal move on the main line
+    pub fn push(&mut self, mv: MoveRep) {
+        self.moves
+            .push(WrittenMove::new(mv, &self.board, self.tables));
+        self.board.make(&mv, self.zob_keys);
+    }
+
+    /// Adds a comment to the last move
+    pub fn comment(&mut self, text: &str) {
+        if let Some(last) = self.moves.last_mut() {
+            // A closing brace would end the comment early
+            let text = text.replace('}', ")");
+            last.comment = Some(match last.comment.take() {
+                Some(comment) => format!("{comment} {text}"),
+                None => text,
+            });
+        }
+    }
+
+    /// Adds the engine's score, depth and time to the last move
+    pub fn annotate(&mut self, annotation: &Annotation) {
+        self.comment(&annotation.to_string());
+    }
+
+    /// Adds a numeric annotation glyph, such as 1 for a good move, to the last move
+    #[allow(dead_code)]
+    pub fn nag(&mut self, nag: u8) {
+        if let Some(last) = self.moves.last_mut() {
+            last.nags.push(nag);
+        }
+    }
+
+    /// Adds a line that could have been played instead of the last move
+    pub fn variation(&mut self, line: &[MoveRep]) -> Result<(), String> {
+        let last = match self.moves.last_mut() {
+            Some(last) => last,
+            None => return Err("Variation does not follow a move".to_string()),
+        };
+        let mut board = self.board.clone();
+        board.unmake(&last.mv, self.zob_keys);
+        let mut variation = Vec::with_capacity(line.len());
+        for mv in line {
+            if !generate(&board, self.tables).contains(mv) {
+                return Err(format!("Illegal move {} in variation", mv.to_string()));
+            }
+            variation.push(WrittenMove::new(*mv, &board, self.tables));
+            board.make(mv, self.zob_keys);
+        }
+        last.variations.push(variation);
+        Ok(())
+    }
+
+    /// Writes the words of a line of moves, starting at the ply
+    fn write_line(words: &mut Vec<String>, moves: &[WrittenMove], first_ply: usize) {
+        // Black's moves are numbered at the start of a line and after anything between moves
+        let mut numbered = false;
+        for (ply, mv) in (first_ply..).zip(moves) {
+            match (ply % 2 == 0, numbered) {
+                (true, _) => words.push(format!("{}.", ply / 2 + 1)),
+                (false, false) => words.push(format!("{}...", ply / 2 + 1)),
+                (false, true) => {}
+            }
+            words.push(mv.san.clone());
+            numbered = true;
+            for nag in &mv.nags {
+                words.push(format!("${nag}"));
+            }
+            if let Some(comment) = &mv.comment {
+                words.push(format!("{{{comment}}}"));
+                numbered = false;
+            }
+            for variation in mv.variations.iter().filter(|v| !v.is_empty()) {
+                let first = words.len();
+                PgnWriter::write_line(words, variation, ply);
+                words[first].insert(0, '(');
+                words.last_mut().unwrap().push(')');
+                numbered = false;
+            }
+        }
+    }
+}
+
+impl fmt::Display for PgnWriter<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (name, value) in &self.tags {
+            let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+            writeln!(f, "[{name} \"{value}\"]")?;
+        }
+        writeln!(f)?;
+
+        let mut words = Vec::new();
+        PgnWriter::write_line(&mut words, &self.moves, self.first_ply);
+        words.push(self.result.clone());
+        // Comments are split into words too, so long ones wrap as well
+        let mut line = String::new();
+        for word in words.iter().flat_map(|word| word.split(' ')) {
+            if !line.is_empty() && line.len() + 1 + word.len() > PgnWriter::LINE_LENGTH {
+                writeln!(f, "{line}")?;
+                line.clear();
+            } else if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(word);
+        }
+        writeln!(f, "{line}")?;
+        writeln!(f)
+    }
+}
+
+/// Checks every game in a pgn file, reporting the malformed ones
+pub fn run(args: &[String]) -> Result<(), String> {
+    let path = match args.first() {
+        Some(path) => path,
+        None => return Err("Usage: pgn <file>".to_string()),
+    };
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) => return Err(format!("Could not read \"{path}\": {e}")),
+    };
+
+    let tables = Tables::new();
+    let zob_keys = ZobKeys::new();
+    let (mut games, mut moves, mut errors) = (0, 0, 0);
+    for game in PgnReader::new(BufReader::new(file), &tables, &zob_keys) {
+        match game {
+            Ok(game) => {
+                games += 1;
+                moves += game.moves.len();
+            }
+            Err(e) => {
+                errors += 1;
+                println!("info string {e}");
+            }
+        }
+    }
+    println!("info string read {games} games with {moves} moves, {errors} malformed");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::WIN;
+
+    fn read(text: &str) -> Vec<Result<PgnGame, String>> {
+        let tables = Tables::new();
+        let zob_keys = ZobKeys::new();
+        PgnReader::new(text.as_bytes(), &tables, &zob_keys).collect()
+    }
+
+    fn final_fen(game: &PgnGame) -> String {
+        game.positions(&ZobKeys::new()).last().unwrap().to_fen()
+    }
+
+    #[test]
+    fn read_games() {
+        let text = r#"[Event "Test"]
+[White "A \"quoted\" name"]
+[Black "B"]
+[Result "1-0"]
+
+1. e4 {best by test} e5 2. Nf3 $1 (2. f4 exf4 (2... d5) 3. Nf3) 2... Nc6 3. Bb5 a6
+{a comment over
+[two lines]} 4. Ba4 Nf6 5. O-O Be7 ; the rest of the line is a comment (
+6.Re1 b5!? 7. Bb3 d6 1-0
+[FEN "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1"]
+[SetUp "1"]
+1. exd6e.p. Kd7 2. Kf2 Kxd6 *
+
+1. d4 d5
+"#;
+        let games = read(text);
+        assert_eq!(games.len(), 3);
+
+        let game = games[0].as_ref().unwrap();
+        assert_eq!(game.tag("White"), Some("A \"quoted\" name"));
+        assert_eq!(game.tag("Round"), None);
+        assert_eq!(game.moves.len(), 14);
+        assert_eq!(game.result, "1-0");
+        assert_eq!(
+            final_fen(game),
+            "r1bqk2r/2p1bppp/p1np1n2/1p2p3/4P3/1B3N2/PPPP1PPP/RNBQR1K1 w kq - 0 8"
+        );
+
+        let game = games[1].as_ref().unwrap();
+        assert_eq!(game.start.to_fen(), "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1");
+        assert_eq!(game.result, "*");
+        assert_eq!(final_fen(game), "8/8/3k4/8/8/8/5K2/8 w - - 0 3");
+        assert_eq!(game.positions(&ZobKeys::new()).len(), 5);
+
+        let game = games[2].as_ref().unwrap();
+        assert!(game.tags.is_empty());
+        assert_eq!(game.moves.len(), 2);
+        assert_eq!(game.result, "*");
+    }
+
+    #[test]
+    fn malformed_games() {
+        let text = r#"[Event "Illegal move"]
+
+1. e4 e5 2. Nf4 Nc6 1/2-1/2
+
+[Event "Unmatched variation"]
+1. e4 e5) 2. Nf3 0-1
+
+[Event "Result in a variation"]
+1. e4 (1. d4 1-0) 0-1
+
+[Event "Bad tag
+1. e4 1-0
+
+[FEN "not a fen"]
+1. e4 1-0
+
+[Event "Fine"]
+[Result "0-1"]
+1. f3 e5 2. g4 Qh4#
+
+[Event "Unterminated comment"]
+1. e4 { never closed
+"#;
+        let games = read(text);
+        assert_eq!(games.len(), 7);
+        assert_eq!(
+            games[0].as_ref().unwrap_err(),
+            "Line 3: Illegal move \"Nf4\""
+        );
+        assert_eq!(games[1].as_ref().unwrap_err(), "Line 6: Unmatched \")\"");
+        assert_eq!(
+            games[2].as_ref().unwrap_err(),
+            "Line 9: Result 1-0 inside a variation"
+        );
+        assert!(games[3]
+            .as_ref()
+            .unwrap_err()
+            .starts_with("Line 11: Malformed tag"));
+        assert!(games[4]
+            .as_ref()
+            .unwrap_err()
+            .starts_with("Line 15: Invalid FEN tag"));
+
+        // The Result tag stands in for the missing termination marker
+        let game = games[5].as_ref().unwrap();
+        assert_eq!(game.moves.len(), 4);
+        assert_eq!(game.result, "0-1");
+
+        assert_eq!(
+            games[6].as_ref().unwrap_err(),
+            "Line 22: Unterminated comment"
+        );
+    }
+
+    fn find(board: &BoardState, tables: &Tables, san: &str) -> MoveRep {
+        board.parse_san(san, tables).unwrap()
+    }
+
+    #[test]
+    fn annotation_format() {
+        let annotation = |score, depth, millis| {
+            Annotation {
+                score,
+                depth,
+                time: Duration::from_millis(millis),
+            }
+            .to_string()
+        };
+        assert_eq!(annotation(35, 12, 1234), "+0.35/12 1.2s");
+        assert_eq!(annotation(-150, 3, 50), "-1.50/3 0.1s");
+        assert_eq!(annotation(0, 1, 0), "+0.00/1 0.0s");
+        assert_eq!(annotation(WIN * 3, 4, 0), "+M1/4 0.0s");
+        assert_eq!(annotation(-WIN, 4, 0), "-M2/4 0.0s");
+    }
+
+    #[test]
+    fn write_game() {
+        let tables = Tables::new();
+        let zob_keys = ZobKeys::new();
+        let mut board = BoardState::starting_state();
+        let mut writer = PgnWriter::new(&board, &tables, &zob_keys);
+        writer.set_tag("White", "Some \"quoted\" \\ name");
+        writer.set_tag("Annotator", "test");
+
+        let e4 = find(&board, &tables, "e4");
+        writer.push(e4);
+        writer.annotate(&Annotation {
+            score: 35,
+            depth: 12,
+            time: Duration::from_millis(1200),
+        });
+        board.make(&e4, &zob_keys);
+        let e5 = find(&board, &tables, "e5");
+        let c5 = find(&board, &tables, "c5");
+        writer.push(e5);
+        board.make(&c5, &zob_keys);
+        let nf3 = find(&board, &tables, "Nf3");
+        writer.variation(&[c5, nf3]).unwrap();
+        assert!(writer.variation(&[nf3]).is_err());
+        writer.push(nf3);
+        writer.nag(1);
+        board.unmake(&c5, &zob_keys);
+        board.make(&e5, &zob_keys);
+        board.make(&nf3, &zob_keys);
+        writer.push(find(&board, &tables, "Nc6"));
+        writer.comment("a } brace");
+        writer.set_result("1/2-1/2");
+
+        let text = writer.to_string();
+        assert_eq!(
+            text,
+            r#"[Event "?"]
+[Site "?"]
+[Date "????.??.??"]
+[Round "?"]
+[White "Some \"quoted\" \\ name"]
+[Black "?"]
+[Result "1/2-1/2"]
+[Annotator "test"]
+
+1. e4 {+0.35/12 1.2s} 1... e5 (1... c5 2. Nf3) 2. Nf3 $1 Nc6 {a ) brace} 1/2-1/2
+
+"#
+        );
+
+        let games = read(&text);
+        let game = games[0].as_ref().unwrap();
+        assert_eq!(game.tag("White"), Some("Some \"quoted\" \\ name"));
+        assert_eq!(game.moves.len(), 4);
+        assert_eq!(game.result, "1/2-1/2");
+    }
+
+    #[test]
+    fn write_and_read_random_games() {
+        use rand_core::{RngCore, SeedableRng};
+        use rand_xorshift::XorShiftRng;
+
+        // Long games from set up positions, with every move commented, must wrap and read back
+        let tables = Tables::new();
+        let zob_keys = ZobKeys::new();
+        let mut rng = XorShiftRng::seed_from_u64(43);
+        let starts = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR b HFhf - 2 9",
+        ];
+        let mut text = String::new();
+        let mut played = Vec::new();
+        for game in 0..10 {
+            let mut board = BoardState::state_from_string_fen(starts[game % 2].to_string());
+            board.set_chess960(game % 2 == 1);
+            let mut writer = PgnWriter::new(&board, &tables, &zob_keys);
+            let mut moves = Vec::new();
+            for ply in 0..150 {
+                let legal = generate(&board, &tables);
+                if legal.is_empty() {
+                    break;
+                }
+                let mv = legal[rng.next_u32() as usize % legal.len()];
+                writer.push(mv);
+                writer.annotate(&Annotation {
+                    score: ply - 75,
+                    depth: 1,
+                    time: Duration::ZERO,
+                });
+                board.make(&mv, &zob_keys);
+                moves.push(mv);
+            }
+            text.push_str(&writer.to_string());
+            played.push(moves);
+        }
+
+        assert!(text.lines().all(|line| line.len() <= 80));
+        let games = read(&text);
+        assert_eq!(games.len(), played.len());
+        for (game, moves) in games.iter().zip(&played) {
+            let game = game.as_ref().unwrap();
+            assert_eq!(&game.moves, moves);
+        }
+        assert_eq!(games[1].as_ref().unwrap().tag("Variant"), Some("Chess960"));
+        let start = BoardState::state_from_string_fen(starts[1].to_string());
+        assert_eq!(games[1].as_ref().unwrap().start.to_fen(), start.to_fen());
+    }
+}
@@ -0,0 +1,398 @@
+/*
+Copyright 2025 Ethan Thummel
+
+Permission is hereby granted, free of charge, to any person obtaining a copy of this software and
+associated documentation files (the "Software"), to deal in the Software without restriction,
+including without limitation the rights to use, copy, modify, merge, publish, distribute,
+sublicense, and/or sell copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all copies or substantial
+portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT
+NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT
+OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+*/
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::sync::Arc;
+
+use crate::{
+    board::{BoardState, MoveRep},
+    epd::{parse_limit, DEFAULT_LIMIT},
+    eval::EvalParams,
+    generate::generate,
+    search::{id_search, SearchClock, SearchOptions},
+    tables::Tables,
+    tt::ZobKeys,
+};
+
+// Width of the rating buckets in the report
+const RATING_BUCKET: u32 = 200;
+
+/// A puzzle from the lichess puzzle database
+#[derive(Clone, Debug)]
+pub struct Puzzle {
+    pub id: String,
+    /// Position before the opponent's move that sets up the puzzle
+    pub board: BoardState,
+    /// The opponent's move, then the solution with the opponent's replies between its moves
+    pub moves: Vec<MoveRep>,
+    pub rating: u32,
+    pub themes: Vec<String>,
+}
+
+/// Columns of the puzzle fields in the csv, found from the header
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Columns {
+    id: usize,
+    fen: usize,
+    moves: usize,
+    rating: usize,
+    themes: usize,
+}
+
+impl Default for Columns {
+    /// The order of the lichess database
+    fn default() -> Columns {
+        Columns {
+            id: 0,
+            fen: 1,
+            moves: 2,
+            rating: 3,
+            themes: 7,
+        }
+    }
+}
+
+impl Columns {
+    /// Reads the header line, or returns None if the line is not a header
+    pub fn from_header(line: &str) -> Option<Columns> {
+        let names: Vec<&str> = line.trim().split(',').collect();
+        let column = |name: &str| names.iter().position(|n| *n == name);
+        Some(Columns {
+            id: column("PuzzleId")?,
+            fen: column("FEN")?,
+            moves: column("Moves")?,
+            rating: column("Rating")?,
+            themes: column("Themes")?,
+        })
+    }
+}
+
+/// Parses a line of the puzzle csv
+pub fn parse_puzzle(
+    line: &str,
+    columns: &Columns,
+    tables: &Tables,
+    zob_keys: &ZobKeys,
+) -> Result<Puzzle, String> {
+    let fields: Vec<&str> = line.trim().split(',').collect();
+    let field = |index: usize| match fields.get(index) {
+        Some(field) => Ok(*field),
+        None => Err(format!("Missing column {} in \"{line}\"", index + 1)),
+    };
+
+    let board = BoardState::state_from_fen(field(columns.fen)?.split_whitespace())?;
+    let mut moves = Vec::new();
+    let mut replay = board.clone();
+    for uci in field(columns.moves)?.split_whitespace() {
+        moves.push(replay.apply_string_move(uci.to_string(), tables, zob_keys)?);
+    }
+    if moves.len() < 2 {
+        return Err(format!("No solution in \"{line}\""));
+    }
+    let rating = match field(columns.rating)?.parse::<u32>() {
+        Ok(rating) => rating,
+        Err(_) => return Err(format!("Error parsing rating in \"{line}\"")),
+    };
+    Ok(Puzzle {
+        id: field(columns.id)?.to_string(),
+        board,
+        moves,
+        rating,
+        themes: field(columns.themes)?
+            .split_whitespace()
+            .map(str::to_string)
+            .collect(),
+    })
+}
+
+/// Plays the puzzle, searching for each of the solver's moves. Every move has to match the solution,
+/// except that any mate ends the puzzle as solved
+pub fn solve(
+    puzzle: &Puzzle,
+    tables: &Tables,
+    zob_keys: &ZobKeys,
+    movetime: Option<u128>,
+    max_depth: usize,
+) -> bool {
+    let mut board = puzzle.board.clone();
+    for (ply, mv) in puzzle.moves.iter().enumerate() {
+        if ply % 2 == 1 {
+            let clock = SearchClock::new(movetime);
+            let options = SearchOptions {
+                max_depth,
+                ..SearchOptions::default()
+            };
+            let lines = id_search(
+                &mut board,
+                tables,
+                zob_keys,
+                &clock,
+                options,
+                &[],
+                &mut |_, _| {},
+            );
+            let played = lines[0].best_move();
+            if played != *mv {
+                board.make(&played, zob_keys);
+                let in_check = match board.white_to_move {
+                    true => board.white_in_check(tables),
+                    false => board.black_in_check(tables),
+                };
+                return in_check && generate(&board, tables).is_empty();
+            }
+        }
+        board.make(mv, zob_keys);
+    }
+    true
+}
+
+/// Puzzles solved out of those tried
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Tally {
+    pub solved: usize,
+    pub total: usize,
+}
+
+impl Tally {
+    fn add(&mut self, solved: bool) {
+        self.solved += usize::from(solved);
+        self.total += 1;
+    }
+}
+
+impl fmt::Display for Tally {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let percent = 100.0 * self.solved as f64 / self.total.max(1) as f64;
+        write!(f, "{}/{} ({percent:.1}%)", self.solved, self.total)
+    }
+}
+
+/// Solve rates overall, by theme and by rating
+#[derive(Clone, Debug, Default)]
+pub struct Report {
+    pub overall: Tally,
+    pub themes: BTreeMap<String, Tally>,
+    /// Keyed by the lowest rating in the bucket
+    pub ratings: BTreeMap<u32, Tally>,
+}
+
+impl Report {
+    pub fn add(&mut self, puzzle: &Puzzle, solved: bool) {
+        self.overall.add(solved);
+        for theme in &puzzle.themes {
+            self.themes.entry(theme.clone()).or_default().add(solved);
+        }
+        let bucket = puzzle.rating / RATING_BUCKET * RATING_BUCKET;
+        self.ratings.entry(bucket).or_default().add(solved);
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "info string solved {}", self.overall)?;
+        for (bucket, tally) in &self.ratings {
+            writeln!(
+                f,
+                "info string rating {bucket}-{} solved {tally}",
+                bucket + RATING_BUCKET - 1
+            )?;
+        }
+        for (theme, tally) in &self.themes {
+            writeln!(f, "info string theme {theme} solved {tally}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Runs the engine over puzzles in the lichess csv format, listing the failed puzzles and reporting
+/// the solve rate by theme and rating
+pub fn run(args: &[String], params: &Arc<EvalParams>) -> Result<(), String> {
+    let usage =
+        || "Usage: puzzles <csv file> [movetime <ms> | depth <depth>] [count <n>]".to_string();
+    let path = match args.first() {
+        Some(p) => p,
+        None => return Err(usage()),
+    };
+    let (mut movetime, mut max_depth) = DEFAULT_LIMIT;
+    let mut count = usize::MAX;
+    for pair in args[1..].chunks(2) {
+        match pair {
+            [name, value] if name == "count" => match value.parse::<usize>() {
+                Ok(c) => count = c,
+                Err(_) => return Err(format!("Error parsing count \"{value}\"")),
+            },
+            [name, value] => (movetime, max_depth) = parse_limit(name, value)?,
+            _ => return Err(usage()),
+        }
+    }
+    let file = match File::open(path) {
+        Ok(f) => f,
+        Err(e) => return Err(format!("Could not read \"{path}\": {e}")),
+    };
+
+    let tables = Tables::new();
+    let zob_keys = ZobKeys::new();
+    let mut columns = Columns::default();
+    let mut report = Report::default();
+    for (line_number, line) in BufReader::new(file).lines().enumerate() {
+        if report.overall.total >= count {
+            break;
+        }
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => return Err(format!("Could not read \"{path}\": {e}")),
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Some(header) = Columns::from_header(&line) {
+            columns = header;
+            continue;
+        }
+        match parse_puzzle(&line, &columns, &tables, &zob_keys) {
+            Ok(mut puzzle) => {
+                puzzle.board.set_eval_params(params.clone());
+                let solved = solve(&puzzle, &tables, &zob_keys, movetime, max_depth);
+                if !solved {
+                    println!("info string failed {} rated {}", puzzle.id, puzzle.rating);
+                }
+                report.add(&puzzle, solved);
+            }
+            Err(e) => println!("info string line {}: {e}", line_number + 1),
+        }
+    }
+    print!("{report}");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: &str = "PuzzleId,FEN,Moves,Rating,RatingDeviation,Popularity,NbPlays,Themes,\
+                          GameUrl,OpeningTags";
+    const MATE_IN_2: &str = "00sHx,q3k1nr/1pp1nQpp/3p4/1P2p3/4P3/B1PP1b2/B5PP/5K2 b k - 0 17,\
+                             e8d7 a2e6 d7d8 f7f8,1760,80,83,72,mate mateIn2 middlegame short,\
+                             https://lichess.org/yyznGmXsq#34,Italian_Game";
+
+    fn parse(line: &str) -> Result<Puzzle, String> {
+        parse_puzzle(line, &Columns::default(), &Tables::new(), &ZobKeys::new())
+    }
+
+    #[test]
+    fn parse_lichess() {
+        assert_eq!(Columns::from_header(HEADER), Some(Columns::default()));
+        assert_eq!(Columns::from_header(MATE_IN_2), None);
+        let columns = Columns::from_header("Moves,FEN,Themes,Rating,PuzzleId").unwrap();
+        assert_eq!(columns.fen, 1);
+        assert_eq!(columns.id, 4);
+
+        let puzzle = parse(MATE_IN_2).unwrap();
+        assert_eq!(puzzle.id, "00sHx");
+        assert_eq!(puzzle.moves.len(), 4);
+        assert_eq!(puzzle.rating, 1760);
+        assert_eq!(puzzle.themes, ["mate", "mateIn2", "middlegame", "short"]);
+
+        let fen = "q3k1nr/1pp1nQpp/3p4/1P2p3/4P3/B1PP1b2/B5PP/5K2 b k - 0 17";
+        assert!(parse(&format!("x,{fen},e8d7 a2e7,1500,0,0,0,mate")).is_err());
+        assert!(parse(&format!("x,{fen},e8d7,1500,0,0,0,mate")).is_err());
+        assert!(parse(&format!("x,{fen},e8d7 a2e6,high,0,0,0,mate")).is_err());
+        assert!(parse(&format!("x,{fen},e8d7 a2e6,1500")).is_err());
+    }
+
+    #[test]
+    fn solve_puzzles() {
+        let tables = Tables::new();
+        let zob_keys = ZobKeys::new();
+        let solve = |line: &str| solve(&parse(line).unwrap(), &tables, &zob_keys, None, 3);
+
+        assert!(solve(MATE_IN_2));
+        // The solution walks into a capture of the queen
+        assert!(!solve(
+            "x,3qk3/8/8/8/8/8/8/3RK3 b - - 0 1,d8d2 e1f1,1500,0,0,0,crushing"
+        ));
+        // Either mate solves it, whichever one the solution gives
+        let two_mates = "6k1/2p2ppp/8/8/8/8/1R6/R5K1 b - - 0 1";
+        assert!(solve(&format!("x,{two_mates},c7c6 a1a8,600,0,0,0,mateIn1")));
+        assert!(solve(&format!("x,{two_mates},c7c6 b2b8,600,0,0,0,mateIn1")));
+    }
+
+    #[test]
+    fn report_breakdown() {
+        let mut puzzle = parse(MATE_IN_2).unwrap();
+        let mut report = Report::default();
+        report.add(&puzzle, true);
+        puzzle.rating = 1799;
+        puzzle.themes = vec!["mate".to_string(), "fork".to_string()];
+        report.add(&puzzle, false);
+        puzzle.rating = 1800;
+        report.add(&puzzle, true);
+
+        assert_eq!(
+            report.overall,
+            Tally {
+                solved: 2,
+                total: 3
+            }
+        );
+        assert_eq!(
+            report.themes["mate"],
+            Tally {
+                solved: 2,
+                total: 3
+            }
+        );
+        assert_eq!(
+            report.themes["fork"],
+            Tally {
+                solved: 1,
+                total: 2
+            }
+        );
+        assert_eq!(
+            report.themes["short"],
+            Tally {
+                solved: 1,
+                total: 1
+            }
+        );
+        assert_eq!(
+            report.ratings[&1600],
+            Tally {
+                solved: 1,
+                total: 2
+            }
+        );
+        assert_eq!(
+            report.ratings[&1800],
+            Tally {
+                solved: 1,
+                total: 1
+            }
+        );
+
+        let text = report.to_string();
+        assert!(text.starts_with("info string solved 2/3 (66.7%)\n"));
+        assert!(text.contains("info string rating 1600-1799 solved 1/2 (50.0%)\n"));
+        assert!(text.contains("info string theme fork solved 1/2 (50.0%)\n"));
+    }
+}
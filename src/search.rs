@@ -16,18 +16,27 @@ NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FO
 DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT
 OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
 */
-use std::time::Instant;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
 
 use crate::{
-    board::{BoardState, MoveRep},
+    board::{BoardState, Move, MoveRep, PieceType},
     eval::{eval, score, DRAW, WIN},
     generate::generate,
+    tablebase::TB_WIN,
     tables::Tables,
     tt::ZobKeys,
+    types::Color,
 };
 
 /// Does a 'perft' function from the given boardstate
-pub fn perft(board: &mut BoardState, depth: usize, zob_keys: &ZobKeys) {
+pub fn perft(
+    board: &mut BoardState,
+    depth: usize,
+    zob_keys: &ZobKeys,
+    out: &mut impl Write,
+) -> io::Result<()> {
     let tables = Tables::new();
     let top_moves = generate(board, &tables);
     let mut total_node_count = 0;
@@ -37,9 +46,9 @@ pub fn perft(board: &mut BoardState, depth: usize, zob_keys: &ZobKeys) {
         total_node_count += lower_node_count;
         board.unmake(&lower_move, zob_keys);
 
-        println!("{} {lower_node_count}", lower_move.to_string());
+        writeln!(out, "{} {lower_node_count}", board.move_to_uci(&lower_move))?;
     }
-    println!("\n{total_node_count}");
+    writeln!(out, "\n{total_node_count}")
 }
 
 /// Child function of perft
@@ -65,26 +74,180 @@ pub fn perft_search(
     node_count
 }
 
-// Prototype search
+// Longest iterative deepening goes, for searches without a depth limit
+pub const MAX_DEPTH: usize = 64;
+
+/// Decides when a search has to stop. It is shared with the thread reading commands, which can stop
+/// the search or give it a new time limit while it runs
+#[derive(Debug)]
+pub struct SearchClock {
+    start: Instant,
+    // Milliseconds after start, or u64::MAX without a limit
+    limit: AtomicU64,
+    stopped: AtomicBool,
+    // Held searches keep their answer to themselves until released, as in pondering
+    held: AtomicBool,
+    nodes: AtomicU64,
+    // Nodes the search may visit, or u64::MAX without a limit
+    node_limit: AtomicU64,
+}
+
+impl SearchClock {
+    pub fn new(limit: Option<u128>) -> SearchClock {
+        SearchClock {
+            start: Instant::now(),
+            limit: AtomicU64::new(limit.map_or(u64::MAX, |l| l as u64)),
+            stopped: AtomicBool::new(false),
+            held: AtomicBool::new(false),
+            nodes: AtomicU64::new(0),
+            node_limit: AtomicU64::new(u64::MAX),
+        }
+    }
+
+    /// A clock without a limit, which holds the result until it is stopped or released
+    pub fn held() -> SearchClock {
+        let clock = SearchClock::new(None);
+        clock.held.store(true, Ordering::Relaxed);
+        clock
+    }
+
+    /// Limits the search to a number of nodes, as well as the time limit. When set more than once
+    /// the lowest limit is kept
+    pub fn set_node_limit(&self, limit: u64) {
+        self.node_limit.fetch_min(limit, Ordering::Relaxed);
+    }
+
+    pub fn count_node(&self) {
+        self.nodes.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn nodes(&self) -> u64 {
+        self.nodes.load(Ordering::Relaxed)
+    }
+
+    pub fn expired(&self) -> bool {
+        self.stopped.load(Ordering::Relaxed)
+            || self.nodes() > self.node_limit.load(Ordering::Relaxed)
+            || self.start.elapsed().as_millis() > self.limit.load(Ordering::Relaxed) as u128
+    }
+
+    pub fn elapsed_ms(&self) -> u128 {
+        self.start.elapsed().as_millis()
+    }
+
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_held(&self) -> bool {
+        self.held.load(Ordering::Relaxed) && !self.stopped.load(Ordering::Relaxed)
+    }
+
+    /// Lets a held search finish, with a time limit counting from now
+    pub fn release(&self, limit: Option<u128>) {
+        if let Some(limit) = limit {
+            let from_start = self.start.elapsed().as_millis() + limit;
+            self.limit.store(from_start as u64, Ordering::Relaxed);
+        }
+        self.held.store(false, Ordering::Relaxed);
+    }
+
+    /// Blocks until the clock is no longer held
+    pub fn wait(&self) {
+        while self.is_held() {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+}
+
+/// What a search found: its score for the side to move, and the line it expects, starting with the
+/// move to play
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchResult {
+    pub score: isize,
+    pub pv: Vec<MoveRep>,
+}
+
+impl SearchResult {
+    pub fn best_move(&self) -> MoveRep {
+        self.pv[0]
+    }
+
+    /// The score in uci form, counting mates in moves instead of centipawns
+    pub fn uci_score(&self, depth: usize) -> String {
+        match mate_in(self.score, depth) {
+            Some(moves) => format!("mate {moves}"),
+            None => format!("cp {}", self.score),
+        }
+    }
+}
+
+/// Moves to a mate found by a search of the depth, negative when being mated. Tablebase wins and
+/// losses count as mates too
+pub fn mate_in(score: isize, depth: usize) -> Option<isize> {
+    let plies = match score.abs() {
+        // Mates are scored by the depth left when they were found
+        s if s >= WIN => depth as isize + 1 - s / WIN,
+        // Tablebase scores by the plies to mate take away the depth left where they were probed
+        s if s > TB_WIN - u8::MAX as isize => depth.min(MAX_DEPTH) as isize - (s - TB_WIN),
+        _ => return None,
+    };
+    let moves = (plies.max(1) + 1) / 2;
+    Some(moves * score.signum())
+}
+
+/// How far iterative deepening goes, and how many lines it looks for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SearchOptions {
+    pub max_depth: usize,
+    pub multipv: usize,
+}
+
+impl Default for SearchOptions {
+    fn default() -> SearchOptions {
+        SearchOptions {
+            max_depth: MAX_DEPTH,
+            multipv: 1,
+        }
+    }
+}
+
+// Prototype search. Moves in `excluded` are left out, which is how later multipv lines are found
 pub fn negamax(
     board: &mut BoardState,
     tables: &Tables,
     zob_keys: &ZobKeys,
     depth: usize,
-    timer: Option<Instant>,
-    duration: Option<u128>,
-) -> MoveRep {
+    clock: &SearchClock,
+    excluded: &[Move],
+) -> SearchResult {
+    // With a table for the position, play its best move straight away
+    if let Some(tablebases) = board.tablebases.clone().filter(|_| excluded.is_empty()) {
+        if let Some(mv) = tablebases.best_move(board, tables, zob_keys) {
+            let score = tablebases.probe(board).map_or(DRAW, |v| v.score(depth));
+            return SearchResult {
+                score,
+                pv: vec![mv],
+            };
+        }
+    }
+
     let mut moves = generate(board, tables);
+    moves.retain(|mv| !excluded.contains(&Move::from_rep(mv, board)));
 
     moves.sort_by_key(|b| std::cmp::Reverse(score(b, board)));
 
     // If all moves result in draw, none will be picked, so set the bestmove in the event that no moved is picked
-    let mut best_move = moves[0];
+    let mut best = SearchResult {
+        score: isize::MIN,
+        pv: vec![moves[0]],
+    };
     let mut alpha = isize::MIN;
     let beta = isize::MAX;
     let mut node_count = 0;
     for mv in &moves {
         board.make(mv, zob_keys);
+        let mut child_pv = Vec::new();
         let score = negamax_child(
             board,
             tables,
@@ -92,21 +255,21 @@ pub fn negamax(
             beta.saturating_neg(),
             alpha.saturating_neg(),
             depth - 1,
-            timer,
-            duration,
+            clock,
             &mut node_count,
+            &mut child_pv,
         )
         .saturating_neg();
         board.unmake(mv, zob_keys);
         if score > alpha {
             alpha = score;
-            if alpha >= beta {
-                return *mv;
-            }
-            best_move = *mv;
+            best.score = score;
+            best.pv.clear();
+            best.pv.push(*mv);
+            best.pv.extend(child_pv);
         }
     }
-    best_move
+    best
 }
 
 fn negamax_child(
@@ -116,10 +279,19 @@ fn negamax_child(
     mut alpha: isize,
     beta: isize,
     depth: usize,
-    timer: Option<Instant>,
-    duration: Option<u128>,
+    clock: &SearchClock,
     node_count: &mut usize,
+    pv: &mut Vec<MoveRep>,
 ) -> isize {
+    clock.count_node();
+    // Nobody can win a dead position, so there is no need to search it. Going back to an earlier
+    // position makes no progress, so it is scored as a draw too
+    if board.insufficient_material() || board.is_repetition() {
+        return DRAW;
+    }
+    if let Some(value) = board.tablebases.as_ref().and_then(|t| t.probe(board)) {
+        return value.score(depth);
+    }
     let mut moves = generate(board, tables);
 
     moves.sort_by_key(|b| std::cmp::Reverse(score(b, board)));
@@ -127,7 +299,7 @@ fn negamax_child(
         match board.white_to_move {
             true => {
                 let black_attack_mask = board.black_attack_mask(tables);
-                if black_attack_mask & board.white_king == 0 {
+                if (black_attack_mask & board.pieces(Color::White, PieceType::King)).is_empty() {
                     return DRAW;
                 } else {
                     return -WIN * (depth + 1) as isize;
@@ -135,7 +307,7 @@ fn negamax_child(
             }
             false => {
                 let white_attack_mask = board.white_attack_mask(tables);
-                if white_attack_mask & board.black_king == 0 {
+                if (white_attack_mask & board.pieces(Color::Black, PieceType::King)).is_empty() {
                     return DRAW;
                 } else {
                     return -WIN * (depth + 1) as isize;
@@ -147,25 +319,15 @@ fn negamax_child(
         *node_count += 1;
         // TODO Investigate delta pruning. The fact that depth limits greater than 2 dont really improve preformance suggests
         // that this is not really a great approach
-        return quiescence(
-            board,
-            tables,
-            zob_keys,
-            alpha,
-            beta,
-            10,
-            timer,
-            duration,
-            moves.len(),
-        );
+        return quiescence(board, tables, zob_keys, alpha, beta, 10, clock, moves.len());
     }
+    let mut child_pv = Vec::new();
     for mv in &moves {
-        if let (Some(t), Some(d)) = (timer, duration) {
-            if t.elapsed().as_millis() > d {
-                break;
-            }
+        if clock.expired() {
+            break;
         }
         board.make(mv, zob_keys);
+        child_pv.clear();
         let score = negamax_child(
             board,
             tables,
@@ -173,9 +335,9 @@ fn negamax_child(
             beta.saturating_neg(),
             alpha.saturating_neg(),
             depth - 1,
-            timer,
-            duration,
+            clock,
             node_count,
+            &mut child_pv,
         )
         .saturating_neg();
         board.unmake(mv, zob_keys);
@@ -185,47 +347,77 @@ fn negamax_child(
         }
         if score > alpha {
             alpha = score;
+            pv.clear();
+            pv.push(*mv);
+            pv.extend_from_slice(&child_pv);
         }
     }
     alpha
 }
 
-/// Preforms a search using iterative deepening
+/// Preforms a search using iterative deepening, until the clock runs out or the depth limit is done.
+/// Each iteration finds the best `multipv` lines, best first, and passes them to `report`.
+/// Only `root_moves` are searched at the root, or every legal move when it is empty
 pub fn id_search(
     board: &mut BoardState,
     tables: &Tables,
     zob_keys: &ZobKeys,
-    timer: Option<Instant>,
-    duration: Option<u128>,
-) -> MoveRep {
-    let mut current_depth = 1;
-    let mut best_move = negamax(board, tables, zob_keys, 1, None, None);
-
-    loop {
-        current_depth += 1;
-        let possible_best = negamax(board, tables, zob_keys, current_depth, timer, duration);
-        if !timer_check(timer, duration) {
-            best_move = possible_best;
-        } else {
+    clock: &SearchClock,
+    options: SearchOptions,
+    root_moves: &[MoveRep],
+    report: &mut impl FnMut(usize, &[SearchResult]),
+) -> Vec<SearchResult> {
+    // Moves left out of the root are excluded from the start, as MultiPV excludes found lines
+    let (allowed, left_out): (Vec<MoveRep>, Vec<MoveRep>) = generate(board, tables)
+        .into_iter()
+        .partition(|mv| root_moves.is_empty() || root_moves.contains(mv));
+    let left_out: Vec<Move> = left_out
+        .iter()
+        .map(|mv| Move::from_rep(mv, board))
+        .collect();
+    let lines = options.multipv.clamp(1, allowed.len().max(1));
+    let iteration = |board: &mut BoardState, depth: usize, clock: &SearchClock| {
+        let mut results: Vec<SearchResult> = Vec::with_capacity(lines);
+        let mut excluded = left_out.clone();
+        for _ in 0..lines {
+            let result = negamax(board, tables, zob_keys, depth, clock, &excluded);
+            excluded.push(Move::from_rep(&result.best_move(), board));
+            results.push(result);
+        }
+        results
+    };
+
+    // The first iteration always finishes, so there is a move to play
+    let mut best = iteration(board, 1, &SearchClock::new(None));
+    report(1, &best);
+
+    for current_depth in 2..=options.max_depth {
+        let possible_best = iteration(board, current_depth, clock);
+        if clock.expired() {
             break;
         }
+        best = possible_best;
+        report(current_depth, &best);
     }
 
-    best_move
+    best
 }
 
 /// Preform the quiescence search
-fn quiescence(
+pub fn quiescence(
     board: &mut BoardState,
     tables: &Tables,
     zob_keys: &ZobKeys,
     mut alpha: isize,
     beta: isize,
     depth: usize,
-    timer: Option<Instant>,
-    duration: Option<u128>,
+    clock: &SearchClock,
     last_number_moves: usize,
 ) -> isize {
+    clock.count_node();
+    if board.insufficient_material() {
+        return DRAW;
+    }
     let mut moves = generate(board, tables);
     moves.sort_by_key(|b| std::cmp::Reverse(score(b, board)));
     let number_moves = moves.len();
@@ -244,11 +436,11 @@ fn quiescence(
     }
 
     for mv in &moves {
-        if timer_check(timer, duration) {
+        if clock.expired() {
             break;
         }
         // TODO Make a diffrent move generation function which only produces captures
-        if mv.ending_square & board.occupancy() == 0 {
+        if !board.occupancy().contains(mv.ending_square) {
             // Skip non captures
             continue;
         }
@@ -260,8 +452,7 @@ fn quiescence(
             beta.saturating_neg(),
             alpha.saturating_neg(),
             depth - 1,
-            timer,
-            duration,
+            clock,
             number_moves,
         )
         .saturating_neg();
@@ -280,18 +471,13 @@ fn quiescence(
     best_value
 }
 
-pub fn timer_check(timer: Option<Instant>, duration: Option<u128>) -> bool {
-    match (timer, duration) {
-        (Some(t), Some(d)) => t.elapsed().as_millis() > d,
-        _ => false,
-    }
-}
-
 #[cfg(test)]
 mod tests {
-    use crate::board::PieceType;
+    use rand_core::{RngCore, SeedableRng};
+    use rand_xorshift::XorShiftRng;
 
     use super::*;
+    use crate::types::{Bitboard, Piece, Square};
 
     #[test]
     fn depth_zero() {
@@ -672,6 +858,61 @@ mod tests {
         assert_eq!(node_count, 164075551);
     }
 
+    // Chess960 positions with their perft counts to depth 4, written with Shredder-FEN castling
+    const CHESS960_PERFT: [(&str, [usize; 4]); 7] = [
+        (
+            "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+            [21, 528, 12189, 326672],
+        ),
+        (
+            "2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9",
+            [21, 807, 18002, 667366],
+        ),
+        (
+            "b1q1rrkb/pppppppp/3nn3/8/P7/1PPP4/4PPPP/BQNNRKRB w GE - 1 9",
+            [20, 479, 10471, 273318],
+        ),
+        (
+            "qbbnnrkr/2pp2pp/p7/1p2pp2/8/P3PP2/1PPP1KPP/QBBNNR1R w hf - 0 9",
+            [22, 593, 13440, 382958],
+        ),
+        (
+            "1nbbnrkr/p1p1ppp1/3p4/1p3P1p/3Pq2P/8/PPP1P1P1/QNBBNRKR w HFhf - 0 9",
+            [28, 1120, 31058, 1171749],
+        ),
+        (
+            "qnbnr1kr/ppp1b1pp/4p3/3p1p2/8/2NPP3/PPP1BPPP/QNB1R1KR w HEhe - 1 9",
+            [29, 899, 26578, 824055],
+        ),
+        (
+            "q1bnrkr1/ppppp2p/2n2p2/4b1p1/2NP4/8/PPP1PPPP/QNB1RRKB w ge - 1 9",
+            [30, 860, 24566, 732757],
+        ),
+    ];
+
+    fn chess960_perft(max_depth: usize) {
+        let tables = Tables::new();
+        let zob_keys = ZobKeys::new();
+        for (fen, counts) in CHESS960_PERFT {
+            let mut board = BoardState::state_from_string_fen(fen.to_string());
+            for depth in 1..=max_depth {
+                let node_count = perft_search(&mut board, &tables, &zob_keys, depth);
+                assert_eq!(node_count, counts[depth - 1], "{fen} at depth {depth}");
+            }
+        }
+    }
+
+    #[test]
+    fn chess960_shallow() {
+        chess960_perft(3);
+    }
+
+    #[ignore = "Takes a while"]
+    #[test]
+    fn chess960_deep() {
+        chess960_perft(4);
+    }
+
     #[test]
     fn illegal_pawn_move() {
         let board = BoardState::state_from_string_fen(
@@ -679,8 +920,8 @@ mod tests {
         );
         let tables = Tables::new();
         let move1 = MoveRep::new(
-            1 << Tables::E5,
-            1 << Tables::F3,
+            Square::E5,
+            Square::F3,
             None,
             PieceType::Pawn,
             Some(PieceType::Pawn),
@@ -691,4 +932,196 @@ mod tests {
         }
         assert!(!moves.contains(&move1));
     }
+
+    #[test]
+    fn excluded_root_moves() {
+        let mut board =
+            BoardState::state_from_string_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1".to_string());
+        let tables = Tables::new();
+        let zob_keys = ZobKeys::new();
+        let clock = SearchClock::new(None);
+        let best = negamax(&mut board, &tables, &zob_keys, 2, &clock, &[]);
+        assert_eq!(best.best_move().to_string(), "a1a8");
+        assert_eq!(best.uci_score(2), "mate 1");
+
+        let excluded = [Move::from_rep(&best.best_move(), &board)];
+        let second = negamax(&mut board, &tables, &zob_keys, 2, &clock, &excluded);
+        assert_ne!(second.best_move(), best.best_move());
+        assert!(second.score < best.score);
+    }
+
+    #[test]
+    fn tablebase_mate_in() {
+        use crate::tablebase::TbValue;
+        // Probed at the root, the plies to mate are the table's own
+        assert_eq!(mate_in(TbValue::Win(5).score(4), 4), Some(3));
+        assert_eq!(mate_in(TbValue::Loss(4).score(4), 4), Some(-2));
+        // Probed a ply down, the move to get there counts too
+        assert_eq!(mate_in(-TbValue::Loss(4).score(2), 3), Some(3));
+        assert_eq!(mate_in(-TbValue::Win(3).score(2), 3), Some(-2));
+        // Mates the search finds itself still rank above tablebase wins
+        assert!(WIN > TbValue::Win(1).score(MAX_DEPTH));
+        assert_eq!(mate_in(TbValue::Draw.score(4), 4), None);
+        assert_eq!(mate_in(900, 4), None);
+    }
+
+    #[test]
+    fn dead_position_is_draw() {
+        let mut board =
+            BoardState::state_from_string_fen("8/8/4k3/8/8/2K5/8/2B5 w - - 0 1".to_string());
+        let tables = Tables::new();
+        let zob_keys = ZobKeys::new();
+        let score = quiescence(
+            &mut board,
+            &tables,
+            &zob_keys,
+            -WIN,
+            WIN,
+            10,
+            &SearchClock::new(None),
+            0,
+        );
+        assert_eq!(score, DRAW);
+    }
+
+    #[test]
+    fn repetition_is_draw() {
+        let tables = Tables::new();
+        let zob_keys = ZobKeys::new();
+        // White is a queen up, but going back to where the game was makes no progress
+        let mut board =
+            BoardState::state_from_string_fen("4k3/8/8/8/8/8/8/3QK3 w - - 0 1".to_string());
+        for mv in ["e1f2", "e8f8", "f2e1", "f8e8"] {
+            board
+                .apply_string_move(mv.to_string(), &tables, &zob_keys)
+                .unwrap();
+        }
+        let score = negamax_child(
+            &mut board,
+            &tables,
+            &zob_keys,
+            -WIN,
+            WIN,
+            3,
+            &SearchClock::new(None),
+            &mut 0,
+            &mut Vec::new(),
+        );
+        assert_eq!(score, DRAW);
+    }
+
+    #[test]
+    fn mop_up_mates_with_queen() {
+        // Too shallow to see the mate from the start, so the mop-up terms have to drive the
+        // defending king to the edge and bring the other king up to help
+        let tables = Tables::new();
+        let zob_keys = ZobKeys::new();
+        let board = BoardState::state_from_string_fen("8/8/8/3k4/8/8/8/K5Q1 w - - 0 1".to_string());
+        assert!(mates_within_fifty_moves(board, &tables, &zob_keys));
+    }
+
+    // Places a king pair and the given white pieces on random squares, with white to move and
+    // black not in check. Bishops are put on opposite colors
+    fn random_ending(rng: &mut XorShiftRng, pieces: &[PieceType], tables: &Tables) -> BoardState {
+        loop {
+            let mut board =
+                BoardState::state_from_string_fen("8/8/8/8/8/8/8/8 w - - 0 1".to_string());
+            let mut occupied = Bitboard::EMPTY;
+            let mut light_bishop = rng.next_u32().is_multiple_of(2);
+            let mut random_square = |allowed: Bitboard| loop {
+                let square = Square::new((rng.next_u32() % 64) as usize);
+                if (allowed & !occupied).contains(square) {
+                    occupied |= square.bit();
+                    return square;
+                }
+            };
+            let white_king = random_square(!Bitboard::EMPTY);
+            let black_king = random_square(!tables.king_attacks[white_king]);
+            board.put_piece(Piece::new(Color::White, PieceType::King), white_king);
+            board.put_piece(Piece::new(Color::Black, PieceType::King), black_king);
+            for piece in pieces {
+                let allowed = match piece {
+                    PieceType::Bishop => {
+                        light_bishop = !light_bishop;
+                        match light_bishop {
+                            false => Tables::LIGHT_SQUARES,
+                            true => !Tables::LIGHT_SQUARES,
+                        }
+                    }
+                    PieceType::Queen | PieceType::Rook | PieceType::Knight => !Bitboard::EMPTY,
+                    _ => unreachable!(),
+                };
+                let square = random_square(allowed);
+                board.put_piece(Piece::new(Color::White, *piece), square);
+            }
+            if board.black_in_check(tables) {
+                continue;
+            }
+            board.hash = ZobKeys::new().generate_hash(&board);
+            board.set_eval_params(board.eval_params.clone());
+            return board;
+        }
+    }
+
+    // Plays the ending out, returning if black was mated within the 50 move rule. White searches
+    // 4 plies deep, and black defends with 2
+    fn mates_within_fifty_moves(
+        mut board: BoardState,
+        tables: &Tables,
+        zob_keys: &ZobKeys,
+    ) -> bool {
+        for _ in 0..100 {
+            let moves = generate(&board, tables);
+            if moves.is_empty() {
+                return !board.white_to_move && board.black_in_check(tables);
+            }
+            if board.insufficient_material() {
+                return false;
+            }
+            let depth = if board.white_to_move { 4 } else { 2 };
+            let mv = negamax(
+                &mut board,
+                tables,
+                zob_keys,
+                depth,
+                &SearchClock::new(None),
+                &[],
+            )
+            .best_move();
+            board.make(&mv, zob_keys);
+        }
+        generate(&board, tables).is_empty() && board.black_in_check(tables)
+    }
+
+    fn mop_up_harness(pieces: &[PieceType], games: usize) {
+        let tables = Tables::new();
+        let zob_keys = ZobKeys::new();
+        let mut rng = XorShiftRng::seed_from_u64(31);
+        for _ in 0..games {
+            let board = random_ending(&mut rng, pieces, &tables);
+            let start = board.clone();
+            if !mates_within_fifty_moves(board, &tables, &zob_keys) {
+                print!("{}", start.pretty_board());
+                panic!("Failed to mate with {pieces:?}");
+            }
+        }
+    }
+
+    #[ignore = "Takes a while"]
+    #[test]
+    fn mop_up_queen() {
+        mop_up_harness(&[PieceType::Queen], 20);
+    }
+
+    #[ignore = "Takes a while"]
+    #[test]
+    fn mop_up_rook() {
+        mop_up_harness(&[PieceType::Rook], 20);
+    }
+
+    #[ignore = "Takes a while"]
+    #[test]
+    fn mop_up_bishops() {
+        mop_up_harness(&[PieceType::Bishop, PieceType::Bishop], 20);
+    }
 }
@@ -0,0 +1,427 @@
+/*
+Copyright 2025 Ethan Thummel
+
+Permission is hereby granted, free of charge, to any person obtaining a copy of this software and
+associated documentation files (the "Software"), to deal in the Software without restriction,
+including without limitation the rights to use, copy, modify, merge, publish, distribute,
+sublicense, and/or sell copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all copies or substantial
+portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT
+NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT
+OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+*/
+use std::sync::Arc;
+use std::time::Instant;
+
+use rand_core::{RngCore, SeedableRng};
+use rand_xorshift::XorShiftRng;
+
+use crate::{
+    board::BoardState,
+    eval::EvalParams,
+    generate::generate,
+    pgn::{Annotation, PgnWriter},
+    search::{id_search, SearchClock, SearchOptions, SearchResult},
+    tables::Tables,
+    tt::ZobKeys,
+};
+
+pub const MAX_LEVEL: u8 = 20;
+// The range of UCI_Elo, spread evenly over the skill levels
+pub const MIN_ELO: u32 = 800;
+pub const MAX_ELO: u32 = 2400;
+// Lines a weakened search looks at to pick its move from
+const CANDIDATES: usize = 4;
+
+/// How strongly the engine plays, from level 0 up to full strength at `MAX_LEVEL`. Weaker levels
+/// search shallower and fewer nodes, and play one of the best few moves instead of the best one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Strength {
+    level: u8,
+}
+
+impl Default for Strength {
+    fn default() -> Strength {
+        Strength { level: MAX_LEVEL }
+    }
+}
+
+impl Strength {
+    pub fn new(level: u8) -> Strength {
+        Strength {
+            level: level.min(MAX_LEVEL),
+        }
+    }
+
+    /// The level playing closest to the given rating
+    pub fn from_elo(elo: u32) -> Strength {
+        let elo = elo.clamp(MIN_ELO, MAX_ELO);
+        let level = (elo - MIN_ELO) * MAX_LEVEL as u32 / (MAX_ELO - MIN_ELO);
+        Strength::new(level as u8)
+    }
+
+    pub fn level(&self) -> u8 {
+        self.level
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.level == MAX_LEVEL
+    }
+
+    pub fn max_depth(&self) -> Option<usize> {
+        (!self.is_full()).then(|| 1 + self.level as usize / 3)
+    }
+
+    pub fn node_limit(&self) -> Option<u64> {
+        (!self.is_full()).then(|| 250 << (self.level / 2))
+    }
+
+    /// Lines the search has to find for the move to be picked from
+    pub fn multipv(&self) -> usize {
+        match self.is_full() {
+            true => 1,
+            false => CANDIDATES,
+        }
+    }
+
+    /// How far apart in centipawns moves can be and still be played about as often
+    pub fn temperature(&self) -> f64 {
+        10.0 * (MAX_LEVEL - self.level) as f64
+    }
+
+    /// Picks the line to play from the best lines of a search. Each line is played with a weight
+    /// falling off exponentially with how much worse it is than the best
+    pub fn choose<'a>(
+        &self,
+        lines: &'a [SearchResult],
+        rng: &mut impl RngCore,
+    ) -> &'a SearchResult {
+        let lines = &lines[..lines.len().min(self.multipv())];
+        if lines.len() < 2 {
+            return &lines[0];
+        }
+        let best = lines[0].score;
+        let weights: Vec<f64> = lines
+            .iter()
+            .map(|line| (line.score.saturating_sub(best) as f64 / self.temperature()).exp())
+            .collect();
+        let mut pick = rng.next_u64() as f64 / u64::MAX as f64 * weights.iter().sum::<f64>();
+        for (line, weight) in lines.iter().zip(&weights) {
+            if pick < *weight {
+                return line;
+            }
+            pick -= weight;
+        }
+        &lines[0]
+    }
+
+    /// Weakens a search to this level: caps its depth and the clock's nodes, and asks for enough
+    /// lines to pick the move from
+    pub fn limit(&self, options: SearchOptions, clock: &SearchClock) -> SearchOptions {
+        if let Some(limit) = self.node_limit() {
+            clock.set_node_limit(limit);
+        }
+        SearchOptions {
+            max_depth: options
+                .max_depth
+                .min(self.max_depth().unwrap_or(usize::MAX)),
+            multipv: options.multipv.max(self.multipv()),
+        }
+    }
+}
+
+// Nodes full strength gets per move when calibrating, as it has no limit of its own
+const CALIBRATION_NODES: u64 = 500_000;
+// Random moves at the start of each game, so the games differ
+const OPENING_PLIES: usize = 4;
+// Games still going after this are called a draw
+const MAX_PLIES: usize = 400;
+
+/// Plays a game from the starting position, returning the result for white (1.0 for a win, 0.5 for
+/// a draw and 0.0 for a loss) and the game with the engine's view of each searched move
+pub fn play_game<'a>(
+    white: Strength,
+    black: Strength,
+    params: &Arc<EvalParams>,
+    tables: &'a Tables,
+    zob_keys: &'a ZobKeys,
+    rng: &mut impl RngCore,
+) -> (f64, PgnWriter<'a>) {
+    let mut board = BoardState::starting_state();
+    board.set_eval_params(params.clone());
+    let mut pgn = PgnWriter::new(&board, tables, zob_keys);
+    pgn.set_tag("White", &format!("Level {}", white.level()));
+    pgn.set_tag("Black", &format!("Level {}", black.level()));
+
+    let result = 'game: {
+        for ply in 0..MAX_PLIES {
+            let moves = generate(&board, tables);
+            if moves.is_empty() {
+                let in_check = match board.white_to_move {
+                    true => board.white_in_check(tables),
+                    false => board.black_in_check(tables),
+                };
+                break 'game match (in_check, board.white_to_move) {
+                    (false, _) => 0.5,
+                    (true, true) => 0.0,
+                    (true, false) => 1.0,
+                };
+            }
+            if board.insufficient_material()
+                || board.is_repetition()
+                || board.reversable_move_counter >= 100
+            {
+                break 'game 0.5;
+            }
+
+            if ply < OPENING_PLIES {
+                let mv = moves[rng.next_u32() as usize % moves.len()];
+                pgn.push(mv);
+                board.make(&mv, zob_keys);
+                continue;
+            }
+            let strength = if board.white_to_move { white } else { black };
+            let clock = SearchClock::new(None);
+            clock.set_node_limit(CALIBRATION_NODES);
+            let options = strength.limit(SearchOptions::default(), &clock);
+            let started = Instant::now();
+            let mut depth = 0;
+            let lines = id_search(
+                &mut board,
+                tables,
+                zob_keys,
+                &clock,
+                options,
+                &[],
+                &mut |d, _| depth = d,
+            );
+            let line = strength.choose(&lines, rng);
+            pgn.push(line.best_move());
+            pgn.annotate(&Annotation {
+                score: line.score,
+                depth,
+                time: started.elapsed(),
+            });
+            // Show the line a weakened level passed over
+            if line != &lines[0] {
+                pgn.variation(&lines[0].pv).unwrap();
+            }
+            board.make(&line.best_move(), zob_keys);
+        }
+        0.5
+    };
+    pgn.set_result(match result {
+        1.0 => "1-0",
+        0.0 => "0-1",
+        _ => "1/2-1/2",
+    });
+    (result, pgn)
+}
+
+/// Plays two levels against each other, alternating colors. Returns the score of the first level
+/// and the games as pgn
+pub fn play_match(
+    first: Strength,
+    second: Strength,
+    games: usize,
+    params: &Arc<EvalParams>,
+    tables: &Tables,
+    zob_keys: &ZobKeys,
+    rng: &mut impl RngCore,
+) -> (f64, String) {
+    let mut score = 0.0;
+    let mut pgn = String::new();
+    for game in 0..games {
+        let (result, mut written) = match game % 2 {
+            0 => play_game(first, second, params, tables, zob_keys, rng),
+            _ => {
+                let (result, written) = play_game(second, first, params, tables, zob_keys, rng);
+                (1.0 - result, written)
+            }
+        };
+        score += result;
+        written.set_tag("Event", "Calibration");
+        written.set_tag("Round", &(game + 1).to_string());
+        pgn.push_str(&written.to_string());
+    }
+    (score, pgn)
+}
+
+/// Calibration harness: plays each level against the next one up, to check that every level is
+/// stronger than the one below it
+pub fn run(args: &[String], params: &Arc<EvalParams>) -> Result<(), String> {
+    let games = match args.first() {
+        Some(g) => match g.parse::<usize>() {
+            Ok(g) if g > 0 => g,
+            _ => return Err(format!("Error parsing games per match \"{g}\"")),
+        },
+        None => {
+            return Err("Usage: calibrate <games per match> [levels] [--pgn <file>]".to_string())
+        }
+    };
+    let mut levels = Vec::new();
+    let mut pgn_path = None;
+    let mut rest = args[1..].iter();
+    while let Some(level) = rest.next() {
+        if level == "--pgn" {
+            match rest.next() {
+                Some(path) => pgn_path = Some(path),
+                None => return Err("Missing file after --pgn".to_string()),
+            }
+            continue;
+        }
+        match level.parse::<u8>() {
+            Ok(l) if l <= MAX_LEVEL => levels.push(Strength::new(l)),
+            _ => return Err(format!("Error parsing level \"{level}\"")),
+        }
+    }
+    if levels.is_empty() {
+        levels = (0..=MAX_LEVEL).step_by(5).map(Strength::new).collect();
+    }
+    levels.sort();
+    levels.dedup();
+
+    let tables = Tables::new();
+    let zob_keys = ZobKeys::new();
+    let mut rng = XorShiftRng::seed_from_u64(0);
+    let mut ordered = true;
+    let mut pgn = String::new();
+    for pair in levels.windows(2) {
+        let (score, games_pgn) = play_match(
+            pair[1], pair[0], games, params, &tables, &zob_keys, &mut rng,
+        );
+        pgn.push_str(&games_pgn);
+        println!(
+            "info string level {} scored {score}/{games} against level {}",
+            pair[1].level(),
+            pair[0].level()
+        );
+        ordered &= score >= games as f64 / 2.0;
+    }
+    match ordered {
+        true => println!("info string every level beat the one below it"),
+        false => println!("info string some levels did not beat the one below them"),
+    }
+    if let Some(path) = pgn_path {
+        if let Err(e) = std::fs::write(path, pgn) {
+            return Err(format!("Could not write \"{path}\": {e}"));
+        }
+        println!("info string wrote the games to {path}");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::WIN;
+
+    fn lines(scores: &[isize]) -> Vec<SearchResult> {
+        let moves = generate(&BoardState::starting_state(), &Tables::new());
+        scores
+            .iter()
+            .zip(moves)
+            .map(|(&score, mv)| SearchResult {
+                score,
+                pv: vec![mv],
+            })
+            .collect()
+    }
+
+    #[test]
+    fn elo_levels() {
+        assert_eq!(Strength::from_elo(0), Strength::new(0));
+        assert_eq!(Strength::from_elo(MAX_ELO), Strength::default());
+        assert_eq!(Strength::from_elo(1600).level(), 10);
+        assert!(Strength::from_elo(1200) < Strength::from_elo(2000));
+        assert_eq!(Strength::new(30), Strength::default());
+    }
+
+    #[test]
+    fn limits_grow_with_level() {
+        for level in 1..MAX_LEVEL {
+            let (weaker, stronger) = (Strength::new(level - 1), Strength::new(level));
+            assert!(weaker.max_depth() <= stronger.max_depth());
+            assert!(weaker.node_limit() <= stronger.node_limit());
+            assert!(weaker.temperature() > stronger.temperature());
+        }
+        assert_eq!(Strength::default().max_depth(), None);
+        assert_eq!(Strength::default().node_limit(), None);
+    }
+
+    #[test]
+    fn choose_by_temperature() {
+        let mut rng = XorShiftRng::seed_from_u64(0);
+        let lines = lines(&[50, 30, -200, -WIN]);
+        let mut counts = [0; 4];
+        for _ in 0..1000 {
+            let pick = Strength::new(0).choose(&lines, &mut rng);
+            counts[lines.iter().position(|l| l == pick).unwrap()] += 1;
+        }
+        // Close moves are played about as often, much worse ones less often and losing ones never
+        assert!(counts[0] > counts[1] && counts[1] > 300, "{counts:?}");
+        assert!(counts[2] > 0 && counts[2] < counts[1] / 2, "{counts:?}");
+        assert_eq!(counts[3], 0);
+
+        // Full strength always plays the best move, and stronger levels stray less
+        for _ in 0..100 {
+            assert_eq!(Strength::default().choose(&lines, &mut rng), &lines[0]);
+        }
+        let strays = (0..1000)
+            .filter(|_| Strength::new(18).choose(&lines, &mut rng) != &lines[0])
+            .count();
+        assert!(strays < counts[1] + counts[2], "{strays}");
+    }
+
+    #[test]
+    fn limited_search() {
+        let tables = Tables::new();
+        let zob_keys = ZobKeys::new();
+        let mut board = BoardState::starting_state();
+        let search = |board: &mut BoardState, strength: Strength| {
+            let clock = SearchClock::new(None);
+            let options = strength.limit(SearchOptions::default(), &clock);
+            let mut depths = Vec::new();
+            id_search(
+                board,
+                &tables,
+                &zob_keys,
+                &clock,
+                options,
+                &[],
+                &mut |depth, lines| depths.push((depth, lines.len())),
+            );
+            (depths, clock.nodes())
+        };
+
+        // The first iteration is not counted, as it always finishes
+        assert_eq!(
+            search(&mut board, Strength::new(0)),
+            (vec![(1, CANDIDATES)], 0)
+        );
+
+        let limit = Strength::new(6).node_limit().unwrap();
+        let (depths, nodes) = search(&mut board, Strength::new(6));
+        assert!(depths.len() <= 3);
+        assert!(nodes > limit && nodes < limit + limit / 10, "{nodes}");
+    }
+
+    // Slow in debug builds: cargo test --release -- --ignored
+    #[test]
+    #[ignore]
+    fn levels_ordered() {
+        let tables = Tables::new();
+        let zob_keys = ZobKeys::new();
+        let mut rng = XorShiftRng::seed_from_u64(1);
+        let (weak, strong) = (Strength::new(0), Strength::new(12));
+        let params = Arc::new(EvalParams::default());
+        let (score, _) = play_match(strong, weak, 10, &params, &tables, &zob_keys, &mut rng);
+        assert!(score > 7.0, "{score}");
+    }
+}
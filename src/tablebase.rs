@@ -0,0 +1,1004 @@
+/*
+Copyright 2025 Ethan Thummel
+
+Permission is hereby granted, free of charge, to any person obtaining a copy of this software and
+associated documentation files (the "Software"), to deal in the Software without restriction,
+including without limitation the rights to use, copy, modify, merge, publish, distribute,
+sublicense, and/or sell copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all copies or substantial
+portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT
+NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT
+OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+*/
+use std::collections::HashMap;
+#[cfg(test)]
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::board::{BoardState, MoveRep, PieceType};
+use crate::eval::{DRAW, WIN};
+use crate::generate::generate;
+use crate::search::MAX_DEPTH;
+use crate::tables::Tables;
+use crate::tt::ZobKeys;
+use crate::types::{Bitboard, Color, Piece, Square};
+
+// Table files start with this, followed by the signature and the number of entries
+const MAGIC: &[u8; 6] = b"NUTTTB";
+
+// Largest number of pieces (kings included) a table can have
+pub const MAX_PIECES: usize = 4;
+
+/// Tablebase scores are at most this, plus the depth left, leaving them below every mate score
+pub const TB_WIN: isize = WIN - 1 - MAX_DEPTH as isize;
+
+/// The result of a position for the side to move, with the number of plies to mate
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TbValue {
+    Win(u8),
+    Loss(u8),
+    Draw,
+}
+
+impl TbValue {
+    // Wins always take an odd number of plies and losses an even number, so one byte can hold both
+    fn encode(self) -> u8 {
+        match self {
+            TbValue::Draw => 0,
+            TbValue::Win(plies) => plies,
+            TbValue::Loss(plies) => plies + 2,
+        }
+    }
+
+    fn decode(byte: u8) -> TbValue {
+        match byte {
+            0 => TbValue::Draw,
+            b if b % 2 == 1 => TbValue::Win(b),
+            b => TbValue::Loss(b - 2),
+        }
+    }
+
+    /// The value as a search score with the given depth left. Tablebase wins rank below mates the
+    /// search finds itself, and like them count the depth left so the plies from the root can be
+    /// recovered by `mate_in`
+    pub fn score(self, depth: usize) -> isize {
+        let depth = depth.min(MAX_DEPTH) as isize;
+        match self {
+            TbValue::Draw => DRAW,
+            TbValue::Win(plies) => TB_WIN - plies as isize + depth,
+            TbValue::Loss(plies) => -(TB_WIN - plies as isize + depth),
+        }
+    }
+}
+
+/// The material in a table, such as KRKP. The white pieces are always the stronger side, and
+/// positions with the colors the other way around are flipped before probing
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Signature {
+    white: Vec<PieceType>,
+    black: Vec<PieceType>,
+}
+
+// Pieces are listed from most to least valuable
+fn piece_order(piece: PieceType) -> usize {
+    match piece {
+        PieceType::Queen => 0,
+        PieceType::Rook => 1,
+        PieceType::Bishop => 2,
+        PieceType::Knight => 3,
+        PieceType::Pawn => 4,
+        PieceType::King => 5,
+    }
+}
+
+fn piece_worth(piece: PieceType) -> usize {
+    match piece {
+        PieceType::Queen => 9,
+        PieceType::Rook => 5,
+        PieceType::Bishop | PieceType::Knight => 3,
+        PieceType::Pawn => 1,
+        PieceType::King => 0,
+    }
+}
+
+impl Signature {
+    /// Parses a signature such as KQK or KRKP
+    pub fn parse(name: &str) -> Result<Signature, String> {
+        let mut sides = name.split('K');
+        if sides.next() != Some("") {
+            return Err(format!("Signature \"{name}\" must start with a king"));
+        }
+        let (white, black) = match (sides.next(), sides.next(), sides.next()) {
+            (Some(w), Some(b), None) => (w, b),
+            _ => return Err(format!("Signature \"{name}\" must have exactly two kings")),
+        };
+        let parse_side = |side: &str| -> Result<Vec<PieceType>, String> {
+            side.chars()
+                .map(|c| match c {
+                    'Q' => Ok(PieceType::Queen),
+                    'R' => Ok(PieceType::Rook),
+                    'B' => Ok(PieceType::Bishop),
+                    'N' => Ok(PieceType::Knight),
+                    'P' => Ok(PieceType::Pawn),
+                    _ => Err(format!("Unknown piece \"{c}\" in signature \"{name}\"")),
+                })
+                .collect()
+        };
+        let signature = Signature::new(parse_side(white)?, parse_side(black)?).0;
+        if signature.piece_count() > MAX_PIECES {
+            return Err(format!(
+                "Signature \"{name}\" has more than {MAX_PIECES} pieces"
+            ));
+        }
+        Ok(signature)
+    }
+
+    // Sorts the pieces, and puts the stronger side first. Also returns if the sides were swapped
+    fn new(mut white: Vec<PieceType>, mut black: Vec<PieceType>) -> (Signature, bool) {
+        white.sort_by_key(|p| piece_order(*p));
+        black.sort_by_key(|p| piece_order(*p));
+        let key = |side: &Vec<PieceType>| {
+            let worth: usize = side.iter().map(|p| piece_worth(*p)).sum();
+            let order: Vec<usize> = side.iter().map(|p| 5 - piece_order(*p)).collect();
+            (worth, side.len(), order)
+        };
+        if key(&black) > key(&white) {
+            (
+                Signature {
+                    white: black,
+                    black: white,
+                },
+                true,
+            )
+        } else {
+            (Signature { white, black }, false)
+        }
+    }
+
+    /// Gets the signature of a board, and if the board has to be flipped to match it
+    pub fn of_board(board: &BoardState) -> (Signature, bool) {
+        let side = |bitboards: [(Bitboard, PieceType); 5]| {
+            bitboards
+                .iter()
+                .flat_map(|(bb, piece)| std::iter::repeat_n(*piece, bb.count() as usize))
+                .collect::<Vec<_>>()
+        };
+        let white = side([
+            (
+                board.pieces(Color::White, PieceType::Queen),
+                PieceType::Queen,
+            ),
+            (board.pieces(Color::White, PieceType::Rook), PieceType::Rook),
+            (
+                board.pieces(Color::White, PieceType::Bishop),
+                PieceType::Bishop,
+            ),
+            (
+                board.pieces(Color::White, PieceType::Knight),
+                PieceType::Knight,
+            ),
+            (board.pieces(Color::White, PieceType::Pawn), PieceType::Pawn),
+        ]);
+        let black = side([
+            (
+                board.pieces(Color::Black, PieceType::Queen),
+                PieceType::Queen,
+            ),
+            (board.pieces(Color::Black, PieceType::Rook), PieceType::Rook),
+            (
+                board.pieces(Color::Black, PieceType::Bishop),
+                PieceType::Bishop,
+            ),
+            (
+                board.pieces(Color::Black, PieceType::Knight),
+                PieceType::Knight,
+            ),
+            (board.pieces(Color::Black, PieceType::Pawn), PieceType::Pawn),
+        ]);
+        Signature::new(white, black)
+    }
+
+    pub fn name(&self) -> String {
+        let mut name = String::from("K");
+        name.extend(self.white.iter().map(|p| p.letter()));
+        name.push('K');
+        name.extend(self.black.iter().map(|p| p.letter()));
+        name
+    }
+
+    pub fn piece_count(&self) -> usize {
+        2 + self.white.len() + self.black.len()
+    }
+
+    /// Number of entries. The strong king is kept on the a-d files, and the other pieces can be anywhere
+    pub fn size(&self) -> usize {
+        2 * 32 * 64usize.pow(self.piece_count() as u32 - 1)
+    }
+
+    /// Material which can never mate, which does not need a table
+    pub fn insufficient(&self) -> bool {
+        let pieces = self.white.iter().chain(self.black.iter());
+        let mut minors = 0;
+        for piece in pieces {
+            match piece {
+                PieceType::Bishop | PieceType::Knight => minors += 1,
+                _ => return false,
+            }
+        }
+        minors <= 1
+    }
+
+    /// The tables captures and promotions from this one lead to
+    pub fn dependencies(&self) -> Vec<Signature> {
+        let mut dependencies: Vec<Signature> = Vec::new();
+        let mut add = |white: Vec<PieceType>, black: Vec<PieceType>| {
+            let signature = Signature::new(white, black).0;
+            if !signature.insufficient() && !dependencies.contains(&signature) {
+                dependencies.push(signature);
+            }
+        };
+        for i in 0..self.white.len() {
+            let mut white = self.white.clone();
+            let piece = white.remove(i);
+            add(white.clone(), self.black.clone());
+            if piece == PieceType::Pawn {
+                for promoted in [
+                    PieceType::Queen,
+                    PieceType::Rook,
+                    PieceType::Bishop,
+                    PieceType::Knight,
+                ] {
+                    let mut white = white.clone();
+                    white.push(promoted);
+                    add(white, self.black.clone());
+                }
+            }
+        }
+        for i in 0..self.black.len() {
+            let mut black = self.black.clone();
+            let piece = black.remove(i);
+            add(self.white.clone(), black.clone());
+            if piece == PieceType::Pawn {
+                for promoted in [
+                    PieceType::Queen,
+                    PieceType::Rook,
+                    PieceType::Bishop,
+                    PieceType::Knight,
+                ] {
+                    let mut black = black.clone();
+                    black.push(promoted);
+                    add(self.white.clone(), black);
+                }
+            }
+        }
+        dependencies
+    }
+
+    // Each piece in table order: the strong king, the strong pieces, the weak king, the weak pieces.
+    // The bool is true for the strong side
+    fn layout(&self) -> Vec<(PieceType, bool)> {
+        let mut layout = vec![(PieceType::King, true)];
+        layout.extend(self.white.iter().map(|p| (*p, true)));
+        layout.push((PieceType::King, false));
+        layout.extend(self.black.iter().map(|p| (*p, false)));
+        layout
+    }
+
+    /// Gets the index of a board in the table
+    pub fn index(&self, board: &BoardState, flip: bool) -> usize {
+        let layout = self.layout();
+        let mut squares = Vec::with_capacity(layout.len());
+        let mut i = 0;
+        while i < layout.len() {
+            let (piece, strong) = layout[i];
+            for square in board.pieces(Color::from_white(strong != flip), piece) {
+                if squares.len() == layout.len() || layout[squares.len()] != (piece, strong) {
+                    break;
+                }
+                let square = square.index();
+                squares.push(if flip { square ^ 56 } else { square });
+            }
+            i = squares.len();
+        }
+
+        // Mirror so that the strong king is on the a-d files
+        if squares[0] % 8 < 4 {
+            for square in squares.iter_mut() {
+                *square ^= 7;
+            }
+        }
+        // Identical pieces are kept in ascending order
+        let mut start = 0;
+        while start < layout.len() {
+            let mut end = start + 1;
+            while end < layout.len() && layout[end] == layout[start] {
+                end += 1;
+            }
+            squares[start..end].sort_unstable();
+            start = end;
+        }
+
+        let strong_to_move = board.white_to_move != flip;
+        let king = squares[0] / 8 * 4 + squares[0] % 8 - 4;
+        let mut index = (!strong_to_move as usize) * 32 + king;
+        for square in &squares[1..] {
+            index = index * 64 + square;
+        }
+        index
+    }
+
+    // The inverse of index, giving if the strong side is to move and the squares
+    fn decode(&self, mut index: usize) -> (bool, Vec<usize>) {
+        let count = self.piece_count();
+        let mut squares = vec![0; count];
+        for i in (1..count).rev() {
+            squares[i] = index % 64;
+            index /= 64;
+        }
+        let king = index % 32;
+        squares[0] = king / 4 * 8 + king % 4 + 4;
+        (index / 32 == 0, squares)
+    }
+
+    // Puts the position on the board, with the strong side as white. Returns false if two pieces
+    // share a square
+    fn set_up(&self, board: &mut BoardState, strong_to_move: bool, squares: &[usize]) -> bool {
+        board.clear_pieces();
+        board.white_to_move = strong_to_move;
+        for ((piece, white), square) in self.layout().into_iter().zip(squares) {
+            let square = Square::new(*square);
+            if board.get_piece_and_color(square).is_some() {
+                return false;
+            }
+            board.put_piece(Piece::new(Color::from_white(white), piece), square);
+        }
+        true
+    }
+
+    // Checks that the position set up is legal and stored at this index
+    fn legal(&self, board: &BoardState, tables: &Tables, index: usize) -> bool {
+        if !((board.pieces(Color::White, PieceType::Pawn)
+            | board.pieces(Color::Black, PieceType::Pawn))
+            & (Tables::RANK_1 | Tables::RANK_8))
+            .is_empty()
+        {
+            return false;
+        }
+        let other_in_check = match board.white_to_move {
+            true => board.black_in_check(tables),
+            false => board.white_in_check(tables),
+        };
+        !other_in_check && self.index(board, false) == index
+    }
+}
+
+/// A finished table, holding a `TbValue` byte for every index
+#[derive(Debug, PartialEq, Eq)]
+pub struct Table {
+    signature: Signature,
+    values: Vec<u8>,
+}
+
+impl Table {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let name = self.signature.name();
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(name.len() as u8);
+        bytes.extend(name.bytes());
+        bytes.extend((self.values.len() as u32).to_le_bytes());
+        bytes.extend(pack(&self.values));
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Table, String> {
+        if bytes.len() < 7 || &bytes[0..6] != MAGIC {
+            return Err("Not a nuttchess tablebase file".to_string());
+        }
+        let name_end = 7 + bytes[6] as usize;
+        let header_end = name_end + 4;
+        if bytes.len() < header_end {
+            return Err("Tablebase file is truncated".to_string());
+        }
+        let name = String::from_utf8_lossy(&bytes[7..name_end]);
+        let signature = Signature::parse(&name)?;
+        let count = u32::from_le_bytes(bytes[name_end..header_end].try_into().unwrap()) as usize;
+        if count != signature.size() {
+            return Err(format!(
+                "Table {name} has {count} entries, expected {}",
+                signature.size()
+            ));
+        }
+        let values = unpack(&bytes[header_end..], count)?;
+        Ok(Table { signature, values })
+    }
+}
+
+// Run length encoding. A control byte below 128 is followed by that many plus one literal bytes,
+// otherwise the next byte is repeated control - 126 times
+fn pack(values: &[u8]) -> Vec<u8> {
+    let mut packed = Vec::new();
+    let mut i = 0;
+    while i < values.len() {
+        let mut run = 1;
+        while i + run < values.len() && values[i + run] == values[i] && run < 129 {
+            run += 1;
+        }
+        if run >= 2 {
+            packed.push((run + 126) as u8);
+            packed.push(values[i]);
+            i += run;
+            continue;
+        }
+        let start = i;
+        while i < values.len()
+            && i - start < 128
+            && !(i + 1 < values.len() && values[i + 1] == values[i])
+        {
+            i += 1;
+        }
+        packed.push((i - start - 1) as u8);
+        packed.extend(&values[start..i]);
+    }
+    packed
+}
+
+fn unpack(packed: &[u8], count: usize) -> Result<Vec<u8>, String> {
+    let mut values = Vec::with_capacity(count);
+    let mut i = 0;
+    while i < packed.len() {
+        let control = packed[i] as usize;
+        i += 1;
+        if control < 128 {
+            let end = i + control + 1;
+            if end > packed.len() {
+                return Err("Tablebase data is truncated".to_string());
+            }
+            values.extend(&packed[i..end]);
+            i = end;
+        } else {
+            let Some(value) = packed.get(i) else {
+                return Err("Tablebase data is truncated".to_string());
+            };
+            values.extend(std::iter::repeat_n(*value, control - 126));
+            i += 1;
+        }
+    }
+    if values.len() != count {
+        return Err(format!(
+            "Tablebase data has {} entries, expected {count}",
+            values.len()
+        ));
+    }
+    Ok(values)
+}
+
+// Tables do not know about en passant, so positions where it can be played are not probed
+fn en_passant_possible(board: &BoardState) -> bool {
+    let Some(target) = board.en_passant_target else {
+        return false;
+    };
+    let (pushed, pawns) = match board.white_to_move {
+        true => (target.south(), board.pieces(Color::White, PieceType::Pawn)),
+        false => (target.north(), board.pieces(Color::Black, PieceType::Pawn)),
+    };
+    !((pushed.bit().east() | pushed.bit().west()) & pawns).is_empty()
+}
+
+/// All of the loaded tables
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Tablebases {
+    tables: HashMap<String, Table>,
+}
+
+impl Tablebases {
+    /// Loads every .tb file in a directory
+    pub fn load_dir(path: &str) -> Result<Tablebases, String> {
+        let mut tablebases = Tablebases::default();
+        let entries =
+            std::fs::read_dir(path).map_err(|e| format!("Could not read \"{path}\": {e}"))?;
+        for entry in entries {
+            let file = entry
+                .map_err(|e| format!("Could not read \"{path}\": {e}"))?
+                .path();
+            if file.extension().is_some_and(|e| e == "tb") {
+                let bytes = std::fs::read(&file)
+                    .map_err(|e| format!("Could not read \"{}\": {e}", file.display()))?;
+                let table =
+                    Table::from_bytes(&bytes).map_err(|e| format!("{}: {e}", file.display()))?;
+                tablebases.insert(table);
+            }
+        }
+        Ok(tablebases)
+    }
+
+    pub fn insert(&mut self, table: Table) {
+        self.tables.insert(table.signature.name(), table);
+    }
+
+    pub fn len(&self) -> usize {
+        self.tables.len()
+    }
+
+    /// Looks up a position. Returns None if there is no table for it, or it has castling or en passant
+    pub fn probe(&self, board: &BoardState) -> Option<TbValue> {
+        if board.occupancy().count() as usize > MAX_PIECES
+            || board.pieces(Color::White, PieceType::King).count() != 1
+            || board.pieces(Color::Black, PieceType::King).count() != 1
+            || en_passant_possible(board)
+            || board.white_queenside_castle_rights
+            || board.white_kingside_castle_rights
+            || board.black_queenside_castle_rights
+            || board.black_kingside_castle_rights
+        {
+            return None;
+        }
+        let (signature, flip) = Signature::of_board(board);
+        if signature.insufficient() {
+            return Some(TbValue::Draw);
+        }
+        let table = self.tables.get(&signature.name())?;
+        Some(TbValue::decode(table.values[signature.index(board, flip)]))
+    }
+
+    /// Picks the move which wins the fastest, holds the draw, or loses the slowest
+    pub fn best_move(
+        &self,
+        board: &mut BoardState,
+        tables: &Tables,
+        zob_keys: &ZobKeys,
+    ) -> Option<MoveRep> {
+        self.probe(board)?;
+        let mut best: Option<(isize, MoveRep)> = None;
+        for mv in generate(board, tables) {
+            board.make(&mv, zob_keys);
+            let value = self.probe(board);
+            board.unmake(&mv, zob_keys);
+            // A move into material without a table can not be judged, so leave the position to the search
+            let score = -value?.score(0);
+            if best.is_none_or(|(s, _)| score > s) {
+                best = Some((score, mv));
+            }
+        }
+        best.map(|(_, mv)| mv)
+    }
+}
+
+// Yields the legal positions one move before the given one, made by the side which is not to move.
+// Captures and promotions can not be undone within a table, and en passant is not considered
+fn unmoves(
+    signature: &Signature,
+    board: &mut BoardState,
+    tables: &Tables,
+    strong_to_move: bool,
+    squares: &[usize],
+    predecessors: &mut Vec<usize>,
+) {
+    predecessors.clear();
+    let occupancy = squares
+        .iter()
+        .fold(Bitboard::EMPTY, |mask, &s| mask | Square::new(s).bit());
+    let mover_strong = !strong_to_move;
+    let mut previous = squares.to_vec();
+    for (i, (piece, strong)) in signature.layout().into_iter().enumerate() {
+        if strong != mover_strong {
+            continue;
+        }
+        let square = Square::new(squares[i]);
+        let origins = match piece {
+            PieceType::King => tables.king_attacks[square],
+            PieceType::Knight => tables.knight_attacks[square],
+            PieceType::Bishop => tables.get_bishop_attack(square, occupancy),
+            PieceType::Rook => tables.get_rook_attack(square, occupancy),
+            PieceType::Queen => {
+                tables.get_bishop_attack(square, occupancy)
+                    | tables.get_rook_attack(square, occupancy)
+            }
+            PieceType::Pawn => {
+                // The strong side is white, so its pawns come from lower squares
+                let (single, double, start_rank, double_rank) = match strong {
+                    true => (
+                        square.bit() >> 8,
+                        square.bit() >> 16,
+                        Tables::RANK_2,
+                        Tables::RANK_4,
+                    ),
+                    false => (
+                        square.bit() << 8,
+                        square.bit() << 16,
+                        Tables::RANK_7,
+                        Tables::RANK_5,
+                    ),
+                };
+                let mut origins = single & !(Tables::RANK_1 | Tables::RANK_8);
+                if !(square.bit() & double_rank).is_empty() && (single & occupancy).is_empty() {
+                    origins |= double & start_rank;
+                }
+                origins
+            }
+        } & !occupancy;
+        for origin in origins {
+            previous[i] = origin.index();
+            if !signature.set_up(board, mover_strong, &previous) {
+                continue;
+            }
+            let other_in_check = match mover_strong {
+                true => board.black_in_check(tables),
+                false => board.white_in_check(tables),
+            };
+            if !other_in_check {
+                predecessors.push(signature.index(board, false));
+            }
+        }
+        previous[i] = square.index();
+    }
+}
+
+/// Builds a table by retrograde analysis. Tables reached by captures and promotions must already be
+/// in `known`
+pub fn generate_table(
+    signature: &Signature,
+    known: &Tablebases,
+    tables: &Tables,
+    zob_keys: &ZobKeys,
+) -> Result<Table, String> {
+    // Positions wait in a bucket for the ply they are decided at. Odd plies are wins for the side to
+    // move, even plies are losses
+    const UNDECIDED: u8 = u8::MAX;
+    let size = signature.size();
+    let mut values = vec![0u8; size];
+    let mut resolved = vec![false; size];
+    let mut counts = vec![0u8; size];
+    let mut loss_floor = vec![UNDECIDED; size];
+    let mut buckets: Vec<Vec<u32>> = vec![Vec::new(); 254];
+
+    let mut board = BoardState::empty_state();
+
+    // Score the mates, and the moves which leave the table
+    for index in 0..size {
+        let (strong_to_move, squares) = signature.decode(index);
+        if !signature.set_up(&mut board, strong_to_move, &squares)
+            || !signature.legal(&board, tables, index)
+        {
+            continue;
+        }
+        let moves = generate(&board, tables);
+        if moves.is_empty() {
+            let in_check = match board.white_to_move {
+                true => board.white_in_check(tables),
+                false => board.black_in_check(tables),
+            };
+            if in_check {
+                buckets[0].push(index as u32);
+            }
+            continue;
+        }
+
+        let mut fastest_win: Option<usize> = None;
+        let mut can_lose = true;
+        let mut floor = 0;
+        let mut count = 0;
+        for mv in &moves {
+            if mv.attacked_type.is_none() && mv.promotion.is_none() {
+                count += 1;
+                continue;
+            }
+            board.make(mv, zob_keys);
+            let value = known.probe(&board);
+            board.unmake(mv, zob_keys);
+            match value {
+                Some(TbValue::Loss(plies)) => {
+                    let win = plies as usize + 1;
+                    fastest_win = Some(fastest_win.map_or(win, |w| w.min(win)));
+                    can_lose = false;
+                }
+                Some(TbValue::Win(plies)) => floor = floor.max(plies as usize + 1),
+                Some(TbValue::Draw) => can_lose = false,
+                None => return Err(format!("Missing a table needed by {}", signature.name())),
+            }
+        }
+        counts[index] = count;
+        if can_lose {
+            loss_floor[index] = floor as u8;
+        }
+        if let Some(win) = fastest_win {
+            buckets[win].push(index as u32);
+        } else if count == 0 && can_lose {
+            buckets[floor].push(index as u32);
+        }
+    }
+
+    // Walk backwards from the decided positions, one ply at a time
+    let mut predecessors = Vec::new();
+    for ply in 0..buckets.len() {
+        let bucket = std::mem::take(&mut buckets[ply]);
+        let win = ply % 2 == 1;
+        for index in bucket {
+            let index = index as usize;
+            if resolved[index] {
+                continue;
+            }
+            resolved[index] = true;
+            values[index] = match win {
+                true => TbValue::Win(ply as u8),
+                false => TbValue::Loss(ply as u8),
+            }
+            .encode();
+            if ply + 1 >= buckets.len() {
+                continue;
+            }
+
+            let (strong_to_move, squares) = signature.decode(index);
+            unmoves(
+                signature,
+                &mut board,
+                tables,
+                strong_to_move,
+                &squares,
+                &mut predecessors,
+            );
+            for &previous in &predecessors {
+                if resolved[previous] {
+                    continue;
+                }
+                if !win {
+                    // Moving into a lost position wins
+                    buckets[ply + 1].push(previous as u32);
+                } else {
+                    // Lost once every move leads to a win for the opponent
+                    counts[previous] -= 1;
+                    if counts[previous] == 0 && loss_floor[previous] != UNDECIDED {
+                        let loss = (ply + 1).max(loss_floor[previous] as usize);
+                        if loss < buckets.len() {
+                            buckets[loss].push(previous as u32);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(Table {
+        signature: signature.clone(),
+        values,
+    })
+}
+
+// Generates a table and everything it depends on, skipping tables which are already known
+fn generate_with_dependencies(
+    signature: &Signature,
+    tablebases: &mut Tablebases,
+    directory: &str,
+    tables: &Tables,
+    zob_keys: &ZobKeys,
+) -> Result<(), String> {
+    if signature.insufficient() || tablebases.tables.contains_key(&signature.name()) {
+        return Ok(());
+    }
+    for dependency in signature.dependencies() {
+        generate_with_dependencies(&dependency, tablebases, directory, tables, zob_keys)?;
+    }
+
+    let start = Instant::now();
+    let table = generate_table(signature, tablebases, tables, zob_keys)?;
+    let longest = table
+        .values
+        .iter()
+        .filter(|v| **v % 2 == 1)
+        .max()
+        .copied()
+        .unwrap_or(0);
+    let path = format!("{directory}/{}.tb", signature.name());
+    std::fs::write(&path, table.to_bytes())
+        .map_err(|e| format!("Could not write \"{path}\": {e}"))?;
+    println!(
+        "info string generated {} in {:.1}s, longest win {longest} plies",
+        signature.name(),
+        start.elapsed().as_secs_f64()
+    );
+    tablebases.insert(table);
+    Ok(())
+}
+
+/// Entry point for the tbgen command
+pub fn run(args: &[String]) -> Result<(), String> {
+    let Some((directory, names)) = args.split_first().filter(|(_, n)| !n.is_empty()) else {
+        return Err("Usage: tbgen <output directory> <signature>...".to_string());
+    };
+    let signatures = names
+        .iter()
+        .map(|n| Signature::parse(n))
+        .collect::<Result<Vec<_>, _>>()?;
+    std::fs::create_dir_all(directory)
+        .map_err(|e| format!("Could not create \"{directory}\": {e}"))?;
+
+    let tables = Tables::new();
+    let zob_keys = ZobKeys::new();
+    let mut tablebases = Tablebases::load_dir(directory)?;
+    for signature in &signatures {
+        generate_with_dependencies(signature, &mut tablebases, directory, &tables, &zob_keys)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::{negamax, SearchClock};
+
+    fn board(fen: &str) -> BoardState {
+        BoardState::state_from_string_fen(fen.to_string())
+    }
+
+    fn generate_kqk(tables: &Tables, zob_keys: &ZobKeys) -> Tablebases {
+        let mut tablebases = Tablebases::default();
+        let signature = Signature::parse("KQK").unwrap();
+        let table = generate_table(&signature, &tablebases, tables, zob_keys).unwrap();
+        tablebases.insert(table);
+        tablebases
+    }
+
+    fn longest_win(tablebases: &Tablebases, name: &str) -> u8 {
+        let values = &tablebases.tables[name].values;
+        values
+            .iter()
+            .filter(|v| **v % 2 == 1)
+            .max()
+            .copied()
+            .unwrap()
+    }
+
+    #[test]
+    fn signature_parse() {
+        assert_eq!(Signature::parse("KRKP").unwrap().name(), "KRKP");
+        // The stronger side is always white
+        assert_eq!(Signature::parse("KPKR").unwrap().name(), "KRKP");
+        assert_eq!(Signature::parse("KKNB").unwrap().name(), "KBNK");
+        assert_eq!(Signature::parse("KQK").unwrap().size(), 2 * 32 * 64 * 64);
+        assert!(Signature::parse("QK").is_err());
+        assert!(Signature::parse("KQ").is_err());
+        assert!(Signature::parse("KXK").is_err());
+        assert!(Signature::parse("KQRKR").is_err());
+        assert!(Signature::parse("KBK").unwrap().insufficient());
+        assert!(!Signature::parse("KNKN").unwrap().insufficient());
+    }
+
+    #[test]
+    fn signature_dependencies() {
+        let names: Vec<String> = Signature::parse("KRKP")
+            .unwrap()
+            .dependencies()
+            .iter()
+            .map(|s| s.name())
+            .collect();
+        for name in ["KRK", "KPK", "KQKR", "KRKR", "KRKB", "KRKN"] {
+            assert!(names.contains(&name.to_string()), "{name} missing");
+        }
+        assert_eq!(names.len(), 6);
+    }
+
+    #[test]
+    fn pack_round_trip() {
+        let mut values = vec![0u8; 300];
+        values.extend((0..=255u8).cycle().take(400));
+        values.extend([7, 7, 1, 7, 7, 7, 2]);
+        let packed = pack(&values);
+        assert!(packed.len() < values.len());
+        assert_eq!(unpack(&packed, values.len()).unwrap(), values);
+        assert!(unpack(&packed, values.len() + 1).is_err());
+        assert!(unpack(&packed[..packed.len() - 1], values.len()).is_err());
+    }
+
+    #[test]
+    fn kqk_table() {
+        let tables = Tables::new();
+        let zob_keys = ZobKeys::new();
+        let tablebases = generate_kqk(&tables, &zob_keys);
+
+        // The longest queen mate takes ten moves
+        assert_eq!(longest_win(&tablebases, "KQK"), 19);
+
+        let probe = |fen: &str| tablebases.probe(&board(fen));
+        assert_eq!(
+            probe("k7/8/1K6/8/8/8/8/6Q1 w - - 0 1"),
+            Some(TbValue::Win(1))
+        );
+        assert_eq!(
+            probe("K7/8/1k6/8/8/8/8/6q1 b - - 0 1"),
+            Some(TbValue::Win(1))
+        );
+        assert_eq!(
+            probe("k6Q/8/1K6/8/8/8/8/8 b - - 0 1"),
+            Some(TbValue::Loss(0))
+        );
+        assert_eq!(probe("k7/2Q5/1K6/8/8/8/8/8 b - - 0 1"), Some(TbValue::Draw));
+        // The lone king can take an undefended queen
+        assert_eq!(probe("8/8/8/3k4/3Q4/8/8/K7 b - - 0 1"), Some(TbValue::Draw));
+        assert_eq!(probe("4k3/8/8/8/8/8/8/R3K3 w Q - 0 1"), None);
+        assert_eq!(probe("4k3/8/8/8/8/8/8/R3K3 w - - 0 1"), None);
+
+        // Mirrored positions agree
+        let value = probe("8/8/8/2k5/8/8/8/K2Q4 w - - 0 1");
+        assert!(matches!(value, Some(TbValue::Win(_))));
+        assert_eq!(probe("8/8/8/5k2/8/8/8/4Q2K w - - 0 1"), value);
+    }
+
+    #[test]
+    fn table_round_trip() {
+        let tables = Tables::new();
+        let zob_keys = ZobKeys::new();
+        let tablebases = generate_kqk(&tables, &zob_keys);
+        let table = &tablebases.tables["KQK"];
+        let bytes = table.to_bytes();
+        assert_eq!(&Table::from_bytes(&bytes).unwrap(), table);
+        assert!(Table::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(Table::from_bytes(b"NUTTNNUE").is_err());
+    }
+
+    #[test]
+    fn best_move_mates() {
+        let tables = Tables::new();
+        let zob_keys = ZobKeys::new();
+        let tablebases = Arc::new(generate_kqk(&tables, &zob_keys));
+
+        let mut board = board("8/8/8/2k5/8/8/8/K2Q4 w - - 0 1");
+        board.set_tablebases(Some(tablebases.clone()));
+        let mut plies = 0;
+        while !generate(&board, &tables).is_empty() {
+            let value = tablebases.probe(&board).unwrap();
+            // Every move gets one ply closer to mate
+            let mv = negamax(
+                &mut board,
+                &tables,
+                &zob_keys,
+                1,
+                &SearchClock::new(None),
+                &[],
+            )
+            .best_move();
+            board.make(&mv, &zob_keys);
+            let next = tablebases.probe(&board).unwrap();
+            match (value, next) {
+                (TbValue::Win(p), TbValue::Loss(n)) | (TbValue::Loss(p), TbValue::Win(n)) => {
+                    assert_eq!(n + 1, p)
+                }
+                _ => panic!("{value:?} then {next:?}"),
+            }
+            plies += 1;
+        }
+        assert!(board.black_in_check(&tables));
+        assert!(plies <= 19);
+    }
+
+    #[test]
+    #[ignore = "Takes a while"]
+    fn longest_wins() {
+        let tables = Tables::new();
+        let zob_keys = ZobKeys::new();
+        let mut tablebases = Tablebases::default();
+        for name in ["KQK", "KRK", "KPK"] {
+            let signature = Signature::parse(name).unwrap();
+            let table = generate_table(&signature, &tablebases, &tables, &zob_keys).unwrap();
+            tablebases.insert(table);
+        }
+        assert_eq!(longest_win(&tablebases, "KRK"), 31);
+        assert_eq!(longest_win(&tablebases, "KPK"), 55);
+        let probe = |fen: &str| tablebases.probe(&board(fen));
+        // The king in front of its pawn wins, but not once the pawn is on the seventh
+        assert!(matches!(
+            probe("4k3/8/4K3/4P3/8/8/8/8 w - - 0 1"),
+            Some(TbValue::Win(_))
+        ));
+        assert_eq!(
+            probe("4k3/4P3/4K3/8/8/8/8/8 b - - 0 1"),
+            Some(TbValue::Draw)
+        );
+        // A pawn on the rook file draws when the defender reaches the corner
+        assert_eq!(probe("k7/8/8/8/P7/8/8/K7 w - - 0 1"), Some(TbValue::Draw));
+    }
+}
@@ -20,6 +20,7 @@ use rand_core::{RngCore, SeedableRng};
 use rand_xorshift::XorShiftRng;
 
 use crate::board::{BoardState, PieceType};
+use crate::types::{Color, Piece, Square};
 
 pub struct ZobKeys {
     // Piece order:
@@ -34,19 +35,6 @@ pub struct ZobKeys {
 }
 
 impl ZobKeys {
-    pub const WHITE_PAWN_INDEX: usize = 0;
-    pub const WHITE_KNIGHT_INDEX: usize = 1;
-    pub const WHITE_BISHOP_INDEX: usize = 2;
-    pub const WHITE_ROOK_INDEX: usize = 3;
-    pub const WHITE_QUEEN_INDEX: usize = 4;
-    pub const WHITE_KING_INDEX: usize = 5;
-    pub const BLACK_PAWN_INDEX: usize = 6;
-    pub const BLACK_KNIGHT_INDEX: usize = 7;
-    pub const BLACK_BISHOP_INDEX: usize = 8;
-    pub const BLACK_ROOK_INDEX: usize = 9;
-    pub const BLACK_QUEEN_INDEX: usize = 10;
-    pub const BLACK_KING_INDEX: usize = 11;
-
     pub const WHITE_KINGSIDE_INDEX: usize = 0;
     pub const WHITE_QUEENSIDE_INDEX: usize = 1;
     pub const BLACK_KINGSIDE_INDEX: usize = 2;
@@ -88,20 +76,7 @@ impl ZobKeys {
 
     /// Match the piece and side to move to the index
     pub fn match_to_index(piece: PieceType, white_to_move: bool) -> usize {
-        match (piece, white_to_move) {
-            (PieceType::Pawn, true) => ZobKeys::WHITE_PAWN_INDEX,
-            (PieceType::Knight, true) => ZobKeys::WHITE_KNIGHT_INDEX,
-            (PieceType::Bishop, true) => ZobKeys::WHITE_BISHOP_INDEX,
-            (PieceType::Rook, true) => ZobKeys::WHITE_ROOK_INDEX,
-            (PieceType::Queen, true) => ZobKeys::WHITE_QUEEN_INDEX,
-            (PieceType::King, true) => ZobKeys::WHITE_KING_INDEX,
-            (PieceType::Pawn, false) => ZobKeys::BLACK_PAWN_INDEX,
-            (PieceType::Knight, false) => ZobKeys::BLACK_KNIGHT_INDEX,
-            (PieceType::Bishop, false) => ZobKeys::BLACK_BISHOP_INDEX,
-            (PieceType::Rook, false) => ZobKeys::BLACK_ROOK_INDEX,
-            (PieceType::Queen, false) => ZobKeys::BLACK_QUEEN_INDEX,
-            (PieceType::King, false) => ZobKeys::BLACK_KING_INDEX,
-        }
+        Piece::new(Color::from_white(white_to_move), piece).index()
     }
 
     /// Initially generate a hash from a board state
@@ -126,27 +101,13 @@ impl ZobKeys {
         }
         // Piece keys
         for index in 0..64 {
-            if let Some(piece) = board.get_piece_and_color(1 << index) {
-                let piece_type_offset = match piece {
-                    (PieceType::Pawn, true) => 0,
-                    (PieceType::Knight, true) => 1,
-                    (PieceType::Bishop, true) => 2,
-                    (PieceType::Rook, true) => 3,
-                    (PieceType::Queen, true) => 4,
-                    (PieceType::King, true) => 5,
-                    (PieceType::Pawn, false) => 6,
-                    (PieceType::Knight, false) => 7,
-                    (PieceType::Bishop, false) => 8,
-                    (PieceType::Rook, false) => 9,
-                    (PieceType::Queen, false) => 10,
-                    (PieceType::King, false) => 11,
-                };
-                hash ^= self.piece_keys[piece_type_offset][index];
+            if let Some(piece) = board.get_piece_and_color(Square::new(index)) {
+                hash ^= self.piece_keys[piece.index()][index];
             }
         }
         // En passant square
-        if board.en_passant_target & 0xff0000ff0000 != 0 {
-            hash ^= self.enpassant_keys[board.en_passant_target.trailing_zeros() as usize];
+        if let Some(target) = board.en_passant_target {
+            hash ^= self.enpassant_keys[target];
         }
         hash
     }
@@ -221,13 +182,7 @@ mod tests {
         hash_test(
             "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
             "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1",
-            MoveRep::new(
-                1 << Tables::E2,
-                1 << Tables::E4,
-                None,
-                PieceType::Pawn,
-                None,
-            ),
+            MoveRep::new(Square::E2, Square::E4, None, PieceType::Pawn, None),
         );
     }
 
@@ -236,13 +191,7 @@ mod tests {
         hash_test(
             "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
             "rnbqkbnr/pppppppp/8/8/8/5N2/PPPPPPPP/RNBQKB1R b KQkq - 0 1",
-            MoveRep::new(
-                1 << Tables::G1,
-                1 << Tables::F3,
-                None,
-                PieceType::Knight,
-                None,
-            ),
+            MoveRep::new(Square::G1, Square::F3, None, PieceType::Knight, None),
         );
     }
 
@@ -251,13 +200,7 @@ mod tests {
         hash_test(
             "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 1",
             "rnbqkbnr/pppp1ppp/8/1B2p3/4P3/8/PPPP1PPP/RNBQK1NR b KQkq - 0 1",
-            MoveRep::new(
-                1 << Tables::F1,
-                1 << Tables::B5,
-                None,
-                PieceType::Bishop,
-                None,
-            ),
+            MoveRep::new(Square::F1, Square::B5, None, PieceType::Bishop, None),
         );
     }
 
@@ -266,13 +209,7 @@ mod tests {
         hash_test(
             "rnbqkbnr/pppp1ppp/8/4p3/6P1/5P2/PPPPP2P/RNBQKBNR b KQkq - 0 1",
             "rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 0 1",
-            MoveRep::new(
-                1 << Tables::D8,
-                1 << Tables::H4,
-                None,
-                PieceType::Queen,
-                None,
-            ),
+            MoveRep::new(Square::D8, Square::H4, None, PieceType::Queen, None),
         );
     }
 
@@ -282,8 +219,8 @@ mod tests {
             "rnb1kbnr/pppPpppp/8/8/8/8/PPP1PPPP/RNBQKBNR w KQkq - 0 1",
             "rnbQkbnr/ppp1pppp/8/8/8/8/PPP1PPPP/RNBQKBNR b KQkq - 0 1",
             MoveRep::new(
-                1 << Tables::D7,
-                1 << Tables::D8,
+                Square::D7,
+                Square::D8,
                 Some(Promotion::Queen),
                 PieceType::Pawn,
                 None,
@@ -297,8 +234,8 @@ mod tests {
             "rnbqkbnr/pppp1ppp/8/4p3/3P4/8/PPP1PPPP/RNBQKBNR w KQkq - 0 1",
             "rnbqkbnr/pppp1ppp/8/4P3/8/8/PPP1PPPP/RNBQKBNR b KQkq - 0 1",
             MoveRep::new(
-                1 << Tables::D4,
-                1 << Tables::E5,
+                Square::D4,
+                Square::E5,
                 None,
                 PieceType::Pawn,
                 Some(PieceType::Pawn),
@@ -311,13 +248,7 @@ mod tests {
         hash_test(
             "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
             "rnbqkbnr/pppppppp/8/8/5P2/8/PPPPP1PP/RNBQKBNR b KQkq f3 0 1",
-            MoveRep::new(
-                1 << Tables::F2,
-                1 << Tables::F4,
-                None,
-                PieceType::Pawn,
-                None,
-            ),
+            MoveRep::new(Square::F2, Square::F4, None, PieceType::Pawn, None),
         );
     }
 
@@ -326,13 +257,7 @@ mod tests {
         hash_test(
             "rnbqkbnr/pppppp2/8/6pp/8/6PP/PPPPPP2/RNBQKBNR w KQkq g6 0 1",
             "rnbqkbnr/pppppp2/8/6pp/8/5PPP/PPPPP3/RNBQKBNR b KQkq - 0 1",
-            MoveRep::new(
-                1 << Tables::F2,
-                1 << Tables::F3,
-                None,
-                PieceType::Pawn,
-                None,
-            ),
+            MoveRep::new(Square::F2, Square::F3, None, PieceType::Pawn, None),
         );
     }
 
@@ -341,13 +266,7 @@ mod tests {
         hash_test(
             "rnbqkbnr/pppppp2/6p1/7p/8/6PP/PPPPPP2/RNBQKBNR w KQkq - 0 1",
             "rnbqkbnr/pppppp2/6p1/7p/8/5PPP/PPPPP3/RNBQKBNR b KQkq - 0 1",
-            MoveRep::new(
-                1 << Tables::F2,
-                1 << Tables::F3,
-                None,
-                PieceType::Pawn,
-                None,
-            ),
+            MoveRep::new(Square::F2, Square::F3, None, PieceType::Pawn, None),
         );
     }
 
@@ -356,13 +275,7 @@ mod tests {
         hash_test(
             "rnbqkbnr/pppppp2/8/6pp/8/6PP/PPPPPP2/RNBQKBNR w KQkq g6 0 1",
             "rnbqkbnr/pppppp2/8/6pp/5P2/6PP/PPPPP3/RNBQKBNR b KQkq f3 0 1",
-            MoveRep::new(
-                1 << Tables::F2,
-                1 << Tables::F4,
-                None,
-                PieceType::Pawn,
-                None,
-            ),
+            MoveRep::new(Square::F2, Square::F4, None, PieceType::Pawn, None),
         );
     }
 
@@ -372,8 +285,8 @@ mod tests {
             "rnbqkbnr/pppppp2/7p/6pP/8/8/PPPPPPP1/RNBQKBNR w KQkq g6 0 1",
             "rnbqkbnr/pppppp2/6Pp/8/8/8/PPPPPPP1/RNBQKBNR b KQkq - 0 1",
             MoveRep::new(
-                1 << Tables::H5,
-                1 << Tables::G6,
+                Square::H5,
+                Square::G6,
                 None,
                 PieceType::Pawn,
                 Some(PieceType::Pawn),
@@ -385,13 +298,7 @@ mod tests {
         hash_test(
             "rnbqkbnr/pppppp2/7p/6pP/8/8/PPPPPPP1/RNBQKBNR w KQkq g6 0 1",
             "rnbqkbnr/pppppp2/7p/6pP/8/4P3/PPPP1PP1/RNBQKBNR b KQkq - 0 1",
-            MoveRep::new(
-                1 << Tables::E2,
-                1 << Tables::E3,
-                None,
-                PieceType::Pawn,
-                None,
-            ),
+            MoveRep::new(Square::E2, Square::E3, None, PieceType::Pawn, None),
         );
     }
     #[test]
@@ -400,8 +307,8 @@ mod tests {
             "rnbqkbnr/ppp1pppp/8/2Pp4/8/8/PP1PPPPP/RNBQKBNR w KQkq d6 0 1",
             "rnbqkbnr/ppp1pppp/3P4/8/8/8/PP1PPPPP/RNBQKBNR b KQkq - 0 1",
             MoveRep::new(
-                1 << Tables::C5,
-                1 << Tables::D6,
+                Square::C5,
+                Square::D6,
                 None,
                 PieceType::Pawn,
                 Some(PieceType::Pawn),
@@ -413,13 +320,7 @@ mod tests {
         hash_test(
             "rnbqkbnr/ppp1pppp/8/2Pp4/8/8/PP1PPPPP/RNBQKBNR w KQkq d6 0 1",
             "rnbqkbnr/ppp1pppp/2P5/3p4/8/8/PP1PPPPP/RNBQKBNR b KQkq - 0 1",
-            MoveRep::new(
-                1 << Tables::C5,
-                1 << Tables::C6,
-                None,
-                PieceType::Pawn,
-                None,
-            ),
+            MoveRep::new(Square::C5, Square::C6, None, PieceType::Pawn, None),
         );
     }
 
@@ -429,8 +330,8 @@ mod tests {
             "rnbqk2r/pppppppp/8/8/3P4/8/PPP1PPPP/RNBQKBNR b KQkq - 0 1",
             "rnbq1rk1/pppppppp/8/8/3P4/8/PPP1PPPP/RNBQKBNR w KQ - 0 1",
             MoveRep::new(
-                1 << Tables::E8,
-                1 << Tables::G8,
+                Square::E8,
+                Square::G8,
                 Some(Promotion::Castle),
                 PieceType::King,
                 None,
@@ -444,8 +345,8 @@ mod tests {
             "r3kbnr/ppp1pppp/3q4/3p4/8/8/PPPPPPPP/RNBQKBNR b KQkq - 0 1",
             "2kr1bnr/ppp1pppp/3q4/3p4/8/8/PPPPPPPP/RNBQKBNR w KQ - 0 1",
             MoveRep::new(
-                1 << Tables::E8,
-                1 << Tables::C8,
+                Square::E8,
+                Square::C8,
                 Some(Promotion::Castle),
                 PieceType::King,
                 None,
@@ -459,8 +360,8 @@ mod tests {
             "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQK2R w KQkq - 0 1",
             "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQ1RK1 b kq - 0 1",
             MoveRep::new(
-                1 << Tables::E1,
-                1 << Tables::G1,
+                Square::E1,
+                Square::G1,
                 Some(Promotion::Castle),
                 PieceType::King,
                 None,
@@ -474,8 +375,8 @@ mod tests {
             "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/R3KBNR w KQkq - 0 1",
             "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/2KR1BNR b kq - 0 1",
             MoveRep::new(
-                1 << Tables::E1,
-                1 << Tables::C1,
+                Square::E1,
+                Square::C1,
                 Some(Promotion::Castle),
                 PieceType::King,
                 None,
@@ -0,0 +1,427 @@
+/*
+Copyright 2025 Ethan Thummel
+
+Permission is hereby granted, free of charge, to any person obtaining a copy of this software and
+associated documentation files (the "Software"), to deal in the Software without restriction,
+including without limitation the rights to use, copy, modify, merge, publish, distribute,
+sublicense, and/or sell copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all copies or substantial
+portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT
+NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT
+OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+*/
+use std::fs::File;
+use std::io::BufReader;
+use std::sync::Arc;
+
+use crate::{
+    board::BoardState,
+    eval::{eval, EvalParams},
+    generate::generate,
+    pgn::PgnReader,
+    search::{quiescence, SearchClock},
+    tables::Tables,
+    tt::ZobKeys,
+};
+
+/// A quiet position used for tuning, along with the result of the game it came from
+pub struct TuningPosition {
+    pub board: BoardState,
+    // Result from whites point of view. 1.0 is a white win, 0.5 a draw and 0.0 a black win
+    pub result: f64,
+    pub number_moves: usize,
+    pub last_number_moves: usize,
+}
+
+/// Parse a labeled position. The line holds a fen string (the move counters may be left out) and
+/// the game result in one of the common forms: `1-0`, `0-1`, `1/2-1/2`, `[1.0]`, `[0.5]` or `[0.0]`,
+/// optionally quoted as in an EPD `c9` opcode
+pub fn parse_position(line: &str) -> Result<(BoardState, f64), String> {
+    let tokens: Vec<&str> = line
+        .split(|c: char| c.is_whitespace() || c == ';')
+        .filter(|token| !token.is_empty())
+        .collect();
+    if tokens.len() < 4 {
+        return Err(format!("Not enough tokens in \"{line}\""));
+    }
+
+    // Fill in the move counters if they are missing
+    let mut fen_tokens = tokens[..4].to_vec();
+    let mut rest = 4;
+    for default in ["0", "1"] {
+        match tokens.get(rest) {
+            Some(t) if t.parse::<u16>().is_ok() => {
+                fen_tokens.push(t);
+                rest += 1;
+            }
+            _ => fen_tokens.push(default),
+        }
+    }
+    let board = BoardState::state_from_fen(fen_tokens.into_iter())?;
+
+    // Search the rest of the line for the result
+    for token in &tokens[rest..] {
+        let token = token.trim_matches(|c| c == '"' || c == '[' || c == ']');
+        let result = match token {
+            "1-0" | "1.0" | "1" => 1.0,
+            "0-1" | "0.0" | "0" => 0.0,
+            "1/2-1/2" | "0.5" => 0.5,
+            _ => continue,
+        };
+        return Ok((board, result));
+    }
+    Err(format!("No game result found in \"{line}\""))
+}
+
+/// Follow the quiescence search from the board down to the quiet position it evaluates
+pub fn quiet_position(
+    mut board: BoardState,
+    result: f64,
+    tables: &Tables,
+    zob_keys: &ZobKeys,
+) -> TuningPosition {
+    // Like the main search, the mobility of the first position is scored against itself
+    let mut last_number_moves = generate(&board, tables).len();
+    let mut depth = 10;
+    loop {
+        let moves = generate(&board, tables);
+        let mut best_score = eval(&board, tables, moves.len(), last_number_moves);
+        let mut best_move = None;
+        if depth > 0 {
+            for mv in &moves {
+                // Only captures are looked at in quiescence
+                if !board.occupancy().contains(mv.ending_square) {
+                    continue;
+                }
+                board.make(mv, zob_keys);
+                let score = quiescence(
+                    &mut board,
+                    tables,
+                    zob_keys,
+                    isize::MIN,
+                    isize::MAX,
+                    depth - 1,
+                    &SearchClock::new(None),
+                    moves.len(),
+                )
+                .saturating_neg();
+                board.unmake(mv, zob_keys);
+                if score > best_score {
+                    best_score = score;
+                    best_move = Some(*mv);
+                }
+            }
+        }
+        match best_move {
+            Some(mv) => {
+                board.make(&mv, zob_keys);
+                last_number_moves = moves.len();
+                depth -= 1;
+            }
+            None => {
+                return TuningPosition {
+                    board,
+                    result,
+                    number_moves: moves.len(),
+                    last_number_moves,
+                }
+            }
+        }
+    }
+}
+
+/// Load and resolve every labeled position in the file. A pgn file gives every position of its
+/// finished games, labeled with the game result
+pub fn load_positions(
+    path: &str,
+    tables: &Tables,
+    zob_keys: &ZobKeys,
+) -> Result<Vec<TuningPosition>, String> {
+    if path.ends_with(".pgn") {
+        return load_pgn_positions(path, tables, zob_keys);
+    }
+    let text = match std::fs::read_to_string(path) {
+        Ok(t) => t,
+        Err(e) => return Err(format!("Could not read \"{path}\": {e}")),
+    };
+    let mut positions = Vec::new();
+    for (line_number, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match parse_position(line) {
+            Ok((board, result)) => {
+                positions.push(quiet_position(board, result, tables, zob_keys));
+            }
+            Err(e) => return Err(format!("Line {}: {e}", line_number + 1)),
+        }
+    }
+    Ok(positions)
+}
+
+fn load_pgn_positions(
+    path: &str,
+    tables: &Tables,
+    zob_keys: &ZobKeys,
+) -> Result<Vec<TuningPosition>, String> {
+    let file = match File::open(path) {
+        Ok(f) => f,
+        Err(e) => return Err(format!("Could not read \"{path}\": {e}")),
+    };
+    let mut positions = Vec::new();
+    for game in PgnReader::new(BufReader::new(file), tables, zob_keys) {
+        let game = game?;
+        let result = match game.result.as_str() {
+            "1-0" => 1.0,
+            "0-1" => 0.0,
+            "1/2-1/2" => 0.5,
+            _ => continue,
+        };
+        for board in game.positions(zob_keys) {
+            positions.push(quiet_position(board, result, tables, zob_keys));
+        }
+    }
+    Ok(positions)
+}
+
+/// Map a centipawn score to an expected game result
+pub fn sigmoid(score: f64, k: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-k * score / 400.0))
+}
+
+/// Mean squared error between the game results and the evaluation with the given parameters
+pub fn error(
+    positions: &mut [TuningPosition],
+    params: &Arc<EvalParams>,
+    tables: &Tables,
+    k: f64,
+) -> f64 {
+    let mut total = 0.0;
+    for position in positions.iter_mut() {
+        position.board.set_eval_params(params.clone());
+        let mut score = eval(
+            &position.board,
+            tables,
+            position.number_moves,
+            position.last_number_moves,
+        ) as f64;
+        // The evaluation is relative to the side to move, the results are relative to white
+        if !position.board.white_to_move {
+            score = -score;
+        }
+        total += (position.result - sigmoid(score, k)).powi(2);
+    }
+    total / positions.len().max(1) as f64
+}
+
+/// Find the scaling constant which best fits the current evaluation to the results
+pub fn find_k(positions: &mut [TuningPosition], params: &Arc<EvalParams>, tables: &Tables) -> f64 {
+    let mut best_k = 1.0;
+    let mut best_error = error(positions, params, tables, best_k);
+    let mut step = 0.5;
+    // Narrow in on the best value, one decimal place at a time
+    while step > 0.001 {
+        let mut improved = true;
+        while improved {
+            improved = false;
+            for k in [best_k - step, best_k + step] {
+                if k <= 0.0 {
+                    continue;
+                }
+                let e = error(positions, params, tables, k);
+                if e < best_error {
+                    best_error = e;
+                    best_k = k;
+                    improved = true;
+                }
+            }
+        }
+        step /= 10.0;
+    }
+    best_k
+}
+
+/// Every single weight the tuner adjusts. The king value is left out since both sides always have
+/// one, as are the pawn table squares a pawn can never stand on
+fn tunable_weights() -> Vec<(&'static str, usize)> {
+    let mut weights = Vec::new();
+    for name in EvalParams::NAMES {
+        match name {
+            "king" => {}
+            "queen" | "rook" | "bishop" | "knight" | "pawn" => weights.push((name, 0)),
+            "pawn_table" => weights.extend((8..56).map(|index| (name, index))),
+            _ => weights.extend((0..64).map(|index| (name, index))),
+        }
+    }
+    weights
+}
+
+/// Texel style local search over the evaluation weights
+pub fn tune(
+    positions: &mut [TuningPosition],
+    tables: &Tables,
+    mut params: EvalParams,
+    k: f64,
+    max_iterations: usize,
+) -> EvalParams {
+    let weights = tunable_weights();
+    let mut best_error = error(positions, &Arc::new(params.clone()), tables, k);
+    println!("info string initial error {best_error:.6}");
+
+    // Start with bigger steps, and refine them once they stop helping
+    let mut step = 8;
+    for iteration in 1..=max_iterations {
+        let mut improved = false;
+        for (name, index) in &weights {
+            for delta in [step, -step] {
+                let mut candidate = params.clone();
+                candidate.field_mut(name).unwrap()[*index] += delta;
+                let candidate = Arc::new(candidate);
+                let e = error(positions, &candidate, tables, k);
+                if e < best_error {
+                    best_error = e;
+                    params = (*candidate).clone();
+                    improved = true;
+                    break;
+                }
+            }
+        }
+        println!("info string iteration {iteration} step {step} error {best_error:.6}");
+        if !improved {
+            if step == 1 {
+                break;
+            }
+            step /= 2;
+        }
+    }
+    params
+}
+
+/// Entry point for `nuttchess tune <labeled positions> <output file> [max iterations]`. Tuning
+/// starts from the given parameters, so an earlier run can be carried on
+pub fn run(args: &[String], params: &EvalParams) -> Result<(), String> {
+    let (data_path, output_path) = match (args.first(), args.get(1)) {
+        (Some(d), Some(o)) => (d, o),
+        _ => {
+            return Err(
+                "Usage: tune <labeled positions or pgn> <output file> [max iterations]".to_string(),
+            )
+        }
+    };
+    let max_iterations = match args.get(2) {
+        Some(i) => match i.parse::<usize>() {
+            Ok(i) => i,
+            Err(_) => return Err(format!("Error parsing max iterations \"{i}\"")),
+        },
+        None => 1000,
+    };
+
+    let tables = Tables::new();
+    let zob_keys = ZobKeys::new();
+    let mut positions = load_positions(data_path, &tables, &zob_keys)?;
+    println!("info string loaded {} positions", positions.len());
+
+    let params = params.clone();
+    let k = find_k(&mut positions, &Arc::new(params.clone()), &tables);
+    println!("info string using k {k:.3}");
+    let tuned = tune(&mut positions, &tables, params, k, max_iterations);
+
+    // The parameter file can be loaded with the EvalFile option or --eval-file, and the rust file can
+    // be pasted over the constants in eval.rs
+    tuned.save(output_path)?;
+    let rust_path = format!("{output_path}.rs");
+    if let Err(e) = std::fs::write(&rust_path, tuned.to_rust()) {
+        return Err(format!("Could not write \"{rust_path}\": {e}"));
+    }
+    println!("info string wrote {output_path} and {rust_path}");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::PieceType;
+    use crate::types::{Color, Square};
+
+    #[test]
+    fn parse_bracket_result() {
+        let (board, result) = parse_position("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1 [1.0]").unwrap();
+        assert_eq!(result, 1.0);
+        assert_eq!(
+            board.pieces(Color::White, PieceType::Pawn),
+            Square::E2.bit()
+        );
+    }
+
+    #[test]
+    fn parse_epd_result() {
+        let (board, result) = parse_position("4k3/8/8/8/8/8/8/q3K3 w - - c9 \"0-1\";").unwrap();
+        assert_eq!(result, 0.0);
+        assert_eq!(board.full_move_counter, 1);
+
+        let (_, result) = parse_position("4k3/8/8/8/8/8/8/4K3 b - - 3 40 1/2-1/2").unwrap();
+        assert_eq!(result, 0.5);
+    }
+
+    #[test]
+    fn parse_missing_result() {
+        assert!(parse_position("4k3/8/8/8/8/8/8/4K3 w - - 0 1").is_err());
+        assert!(parse_position("4k3/8/8").is_err());
+    }
+
+    #[test]
+    fn sigmoid_midpoint() {
+        assert_eq!(sigmoid(0.0, 1.0), 0.5);
+        assert!(sigmoid(400.0, 1.0) > 0.9);
+        assert!(sigmoid(-400.0, 1.0) < 0.1);
+    }
+
+    #[test]
+    fn quiet_position_takes_hanging_queen() {
+        let tables = Tables::new();
+        let zob_keys = ZobKeys::new();
+        let board =
+            BoardState::state_from_string_fen("4k3/8/8/3q4/4P3/8/8/4K3 w - - 0 1".to_string());
+        let position = quiet_position(board, 1.0, &tables, &zob_keys);
+        assert!(position
+            .board
+            .pieces(Color::Black, PieceType::Queen)
+            .is_empty());
+        assert_eq!(
+            position.board.pieces(Color::White, PieceType::Pawn),
+            Square::D5.bit()
+        );
+    }
+
+    #[test]
+    fn tune_lowers_overvalued_knight() {
+        // Games where an extra knight only ever draws, so the default knight value is too high
+        let tables = Tables::new();
+        let zob_keys = ZobKeys::new();
+        let mut positions: Vec<TuningPosition> = [
+            "4k3/pppp4/8/8/8/8/PPPP4/1N2K3 w - - 0 1 [0.5]",
+            "1n2k3/pppp4/8/8/8/8/PPPP4/4K3 w - - 0 1 [0.5]",
+            "4k3/4pppp/8/8/8/8/4PPPP/4K1N1 b - - 0 1 [0.5]",
+            "4k1n1/4pppp/8/8/8/8/4PPPP/4K3 b - - 0 1 [0.5]",
+        ]
+        .iter()
+        .map(|line| {
+            let (board, result) = parse_position(line).unwrap();
+            quiet_position(board, result, &tables, &zob_keys)
+        })
+        .collect();
+
+        let params = EvalParams::default();
+        let before = error(&mut positions, &Arc::new(params.clone()), &tables, 1.0);
+        let tuned = tune(&mut positions, &tables, params.clone(), 1.0, 3);
+        assert!(tuned.knight < params.knight, "{}", tuned.knight);
+        let after = error(&mut positions, &Arc::new(tuned), &tables, 1.0);
+        assert!(after < before, "{after} {before}");
+    }
+}
@@ -0,0 +1,486 @@
+/*
+Copyright 2025 Ethan Thummel
+
+Permission is hereby granted, free of charge, to any person obtaining a copy of this software and
+associated documentation files (the "Software"), to deal in the Software without restriction,
+including without limitation the rights to use, copy, modify, merge, publish, distribute,
+sublicense, and/or sell copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all copies or substantial
+portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT
+NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT
+OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+*/
+use crate::board::PieceType;
+use std::fmt;
+use std::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, Index, IndexMut, Not, Shl, Shr};
+
+/// A square on the board, indexed like the bitboards with H1 as 0 and A8 as 63
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Square(u8);
+
+impl Square {
+    // rank 1
+    pub const A1: Square = Square(7);
+    pub const B1: Square = Square(6);
+    pub const C1: Square = Square(5);
+    pub const D1: Square = Square(4);
+    pub const E1: Square = Square(3);
+    pub const F1: Square = Square(2);
+    pub const G1: Square = Square(1);
+    pub const H1: Square = Square(0);
+    // rank 2
+    pub const A2: Square = Square(15);
+    pub const B2: Square = Square(14);
+    pub const C2: Square = Square(13);
+    pub const D2: Square = Square(12);
+    pub const E2: Square = Square(11);
+    pub const F2: Square = Square(10);
+    pub const G2: Square = Square(9);
+    pub const H2: Square = Square(8);
+    // rank 3
+    pub const A3: Square = Square(23);
+    pub const B3: Square = Square(22);
+    pub const C3: Square = Square(21);
+    pub const D3: Square = Square(20);
+    pub const E3: Square = Square(19);
+    pub const F3: Square = Square(18);
+    pub const G3: Square = Square(17);
+    pub const H3: Square = Square(16);
+    // rank 4
+    pub const A4: Square = Square(31);
+    pub const B4: Square = Square(30);
+    pub const C4: Square = Square(29);
+    pub const D4: Square = Square(28);
+    pub const E4: Square = Square(27);
+    pub const F4: Square = Square(26);
+    pub const G4: Square = Square(25);
+    pub const H4: Square = Square(24);
+    // rank 5
+    pub const A5: Square = Square(39);
+    pub const B5: Square = Square(38);
+    pub const C5: Square = Square(37);
+    pub const D5: Square = Square(36);
+    pub const E5: Square = Square(35);
+    pub const F5: Square = Square(34);
+    pub const G5: Square = Square(33);
+    pub const H5: Square = Square(32);
+    // rank 6
+    pub const A6: Square = Square(47);
+    pub const B6: Square = Square(46);
+    pub const C6: Square = Square(45);
+    pub const D6: Square = Square(44);
+    pub const E6: Square = Square(43);
+    pub const F6: Square = Square(42);
+    pub const G6: Square = Square(41);
+    pub const H6: Square = Square(40);
+    // rank 7
+    pub const A7: Square = Square(55);
+    pub const B7: Square = Square(54);
+    pub const C7: Square = Square(53);
+    pub const D7: Square = Square(52);
+    pub const E7: Square = Square(51);
+    pub const F7: Square = Square(50);
+    pub const G7: Square = Square(49);
+    pub const H7: Square = Square(48);
+    // rank 8
+    pub const A8: Square = Square(63);
+    pub const B8: Square = Square(62);
+    pub const C8: Square = Square(61);
+    pub const D8: Square = Square(60);
+    pub const E8: Square = Square(59);
+    pub const F8: Square = Square(58);
+    pub const G8: Square = Square(57);
+    pub const H8: Square = Square(56);
+
+    /// The square for an index from 0 to 63
+    #[inline]
+    pub const fn new(index: usize) -> Square {
+        debug_assert!(index < 64);
+        Square(index as u8)
+    }
+
+    /// The lowest square of a non empty set, for sets known to hold one square such as a king
+    #[inline]
+    pub const fn from_bit(mask: Bitboard) -> Square {
+        debug_assert!(mask.0 != 0);
+        Square(mask.0.trailing_zeros() as u8)
+    }
+
+    /// The square for a file and rank, both counted from 0 starting at a1
+    pub const fn from_file_rank(file: u8, rank: u8) -> Square {
+        debug_assert!(file < 8 && rank < 8);
+        Square(rank * 8 + 7 - file)
+    }
+
+    #[inline]
+    pub const fn index(self) -> usize {
+        self.0 as usize
+    }
+
+    /// The set holding only this square
+    #[inline]
+    pub const fn bit(self) -> Bitboard {
+        Bitboard(1 << self.0)
+    }
+
+    /// The file from 0 (a) to 7 (h)
+    pub const fn file(self) -> u8 {
+        7 - self.0 % 8
+    }
+
+    /// The rank from 0 (1) to 7 (8)
+    pub const fn rank(self) -> u8 {
+        self.0 / 8
+    }
+
+    /// The square one rank up, towards the eighth rank
+    pub const fn north(self) -> Square {
+        debug_assert!(self.0 < 56);
+        Square(self.0 + 8)
+    }
+
+    /// The square one rank down, towards the first rank
+    pub const fn south(self) -> Square {
+        debug_assert!(self.0 >= 8);
+        Square(self.0 - 8)
+    }
+
+    /// The same square seen from the other side of the board
+    pub const fn flip(self) -> Square {
+        Square(self.0 ^ 56)
+    }
+}
+
+impl fmt::Display for Square {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", (b'a' + self.file()) as char, self.rank() + 1)
+    }
+}
+
+impl<T> Index<Square> for [T; 64] {
+    type Output = T;
+
+    #[inline]
+    fn index(&self, square: Square) -> &T {
+        &self[square.index()]
+    }
+}
+
+impl<T> IndexMut<Square> for [T; 64] {
+    #[inline]
+    fn index_mut(&mut self, square: Square) -> &mut T {
+        &mut self[square.index()]
+    }
+}
+
+/// A set of squares. Iterating it yields the squares from H1 up to A8
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Bitboard(pub u64);
+
+impl Bitboard {
+    pub const EMPTY: Bitboard = Bitboard(0);
+    const FILE_A: u64 = 0x8080808080808080;
+    const FILE_H: u64 = 0x101010101010101;
+
+    #[inline]
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    #[inline]
+    pub const fn count(self) -> u32 {
+        self.0.count_ones()
+    }
+
+    #[inline]
+    pub const fn contains(self, square: Square) -> bool {
+        self.0 & square.bit().0 != 0
+    }
+
+    /// The lowest square in the set
+    #[inline]
+    pub const fn lsb(self) -> Option<Square> {
+        if self.0 == 0 {
+            None
+        } else {
+            Some(Square::from_bit(self))
+        }
+    }
+
+    /// Remove and return the lowest square in the set
+    #[inline]
+    pub fn pop_lsb(&mut self) -> Option<Square> {
+        let square = self.lsb()?;
+        self.0 &= self.0 - 1;
+        Some(square)
+    }
+
+    /// Every square moved one rank up, towards the eighth rank
+    #[inline]
+    pub const fn north(self) -> Bitboard {
+        Bitboard(self.0 << 8)
+    }
+
+    /// Every square moved one rank down, towards the first rank
+    #[inline]
+    pub const fn south(self) -> Bitboard {
+        Bitboard(self.0 >> 8)
+    }
+
+    /// Every square moved one file towards the h file, dropping the ones that fall off the board
+    #[inline]
+    pub const fn east(self) -> Bitboard {
+        Bitboard((self.0 >> 1) & !Bitboard::FILE_A)
+    }
+
+    /// Every square moved one file towards the a file, dropping the ones that fall off the board
+    #[inline]
+    pub const fn west(self) -> Bitboard {
+        Bitboard((self.0 << 1) & !Bitboard::FILE_H)
+    }
+}
+
+impl Iterator for Bitboard {
+    type Item = Square;
+
+    #[inline]
+    fn next(&mut self) -> Option<Square> {
+        self.pop_lsb()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let count = self.count() as usize;
+        (count, Some(count))
+    }
+}
+
+impl fmt::Display for Bitboard {
+    /// The board as ones and zeros from the eighth rank down, with the files underneath
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for rank in (0..8).rev() {
+            write!(f, "{}  ", rank + 1)?;
+            for file in 0..8 {
+                let bit = self.contains(Square::from_file_rank(file, rank)) as u8;
+                write!(f, " {bit}")?;
+            }
+            writeln!(f)?;
+        }
+        write!(f, "\n    a b c d e f g h")
+    }
+}
+
+impl Not for Bitboard {
+    type Output = Bitboard;
+
+    #[inline]
+    fn not(self) -> Bitboard {
+        Bitboard(!self.0)
+    }
+}
+
+impl Shl<u32> for Bitboard {
+    type Output = Bitboard;
+
+    #[inline]
+    fn shl(self, rhs: u32) -> Bitboard {
+        Bitboard(self.0 << rhs)
+    }
+}
+
+impl Shr<u32> for Bitboard {
+    type Output = Bitboard;
+
+    #[inline]
+    fn shr(self, rhs: u32) -> Bitboard {
+        Bitboard(self.0 >> rhs)
+    }
+}
+
+macro_rules! bitboard_op {
+    ($trait:ident, $method:ident, $assign_trait:ident, $assign_method:ident, $op:tt) => {
+        impl $trait for Bitboard {
+            type Output = Bitboard;
+
+            #[inline]
+            fn $method(self, rhs: Bitboard) -> Bitboard {
+                Bitboard(self.0 $op rhs.0)
+            }
+        }
+
+        impl $assign_trait for Bitboard {
+            #[inline]
+            fn $assign_method(&mut self, rhs: Bitboard) {
+                self.0 = self.0 $op rhs.0;
+            }
+        }
+    };
+}
+
+bitboard_op!(BitAnd, bitand, BitAndAssign, bitand_assign, &);
+bitboard_op!(BitOr, bitor, BitOrAssign, bitor_assign, |);
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Color {
+    White,
+    Black,
+}
+
+impl Color {
+    pub const fn from_white(white: bool) -> Color {
+        if white {
+            Color::White
+        } else {
+            Color::Black
+        }
+    }
+
+    #[inline]
+    pub const fn is_white(self) -> bool {
+        matches!(self, Color::White)
+    }
+
+    /// 0 for white and 1 for black
+    #[inline]
+    pub const fn index(self) -> usize {
+        self as usize
+    }
+}
+
+impl Not for Color {
+    type Output = Color;
+
+    #[inline]
+    fn not(self) -> Color {
+        match self {
+            Color::White => Color::Black,
+            Color::Black => Color::White,
+        }
+    }
+}
+
+/// A colored piece
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Piece {
+    pub color: Color,
+    pub kind: PieceType,
+}
+
+impl Piece {
+    pub const fn new(color: Color, kind: PieceType) -> Piece {
+        Piece { color, kind }
+    }
+
+    /// Index from 0 to 11, white pawn to black king, in the order of ZobKeys::piece_keys
+    #[inline]
+    pub const fn index(self) -> usize {
+        self.color.index() * 6 + self.kind as usize
+    }
+
+    /// The fen letter, upper case for white
+    pub fn letter(self) -> char {
+        match self.color {
+            Color::White => self.kind.letter(),
+            Color::Black => self.kind.letter().to_ascii_lowercase(),
+        }
+    }
+
+    pub fn from_letter(letter: char) -> Option<Piece> {
+        let kind = match letter.to_ascii_uppercase() {
+            'P' => PieceType::Pawn,
+            'N' => PieceType::Knight,
+            'B' => PieceType::Bishop,
+            'R' => PieceType::Rook,
+            'Q' => PieceType::Queen,
+            'K' => PieceType::King,
+            _ => return None,
+        };
+        let color = Color::from_white(letter.is_ascii_uppercase());
+        Some(Piece { color, kind })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn square_layout() {
+        assert_eq!(Square::H1.index(), 0);
+        assert_eq!(Square::A1.index(), 7);
+        assert_eq!(Square::A8.index(), 63);
+        assert_eq!(Square::E4.bit(), Bitboard(1 << 27));
+        assert_eq!((Square::E4.file(), Square::E4.rank()), (4, 3));
+        assert_eq!(Square::from_file_rank(4, 3), Square::E4);
+        assert_eq!(Square::from_bit(Square::C6.bit()), Square::C6);
+        assert_eq!(
+            (Square::E4.north(), Square::E4.south()),
+            (Square::E5, Square::E3)
+        );
+        assert_eq!(Square::E2.flip(), Square::E7);
+        assert_eq!(Square::new(63), Square::A8);
+    }
+
+    #[test]
+    fn square_strings() {
+        assert_eq!(Square::G1.to_string(), "g1");
+        assert_eq!(Square::A8.to_string(), "a8");
+        assert_eq!(Square::H1.to_string(), "h1");
+    }
+
+    #[test]
+    fn bitboard_iterates_squares() {
+        let bb = Square::A1.bit() | Square::H1.bit() | Square::D5.bit();
+        assert_eq!(bb.count(), 3);
+        assert_eq!(
+            bb.collect::<Vec<_>>(),
+            vec![Square::H1, Square::A1, Square::D5]
+        );
+        assert_eq!(Bitboard::EMPTY.lsb(), None);
+    }
+
+    #[test]
+    fn bitboard_pop_lsb() {
+        let mut bb = Bitboard(3);
+        assert_eq!(bb.pop_lsb(), Some(Square::H1));
+        assert_eq!(bb, Bitboard(2));
+    }
+
+    #[test]
+    fn bitboard_shifts() {
+        let a_file = Bitboard(0x8080808080808080);
+        let h_file = Bitboard(0x101010101010101);
+        assert!(a_file.west().is_empty());
+        assert!(h_file.east().is_empty());
+        assert_eq!(a_file.east(), Bitboard(0x4040404040404040));
+        assert_eq!(h_file.west(), Bitboard(0x202020202020202));
+        assert_eq!(Square::E4.bit().north(), Square::E5.bit());
+        assert_eq!(Square::E4.bit().south(), Square::E3.bit());
+        assert_eq!(Bitboard(0xff) << 56, Bitboard(0xff00000000000000));
+    }
+
+    #[test]
+    fn bitboard_display() {
+        let text = Square::A8.bit().to_string();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], "8   1 0 0 0 0 0 0 0");
+        assert_eq!(lines[7], "1   0 0 0 0 0 0 0 0");
+        assert_eq!(lines[9], "    a b c d e f g h");
+    }
+
+    #[test]
+    fn piece_indices() {
+        assert_eq!(Piece::new(Color::White, PieceType::Pawn).index(), 0);
+        assert_eq!(Piece::new(Color::Black, PieceType::King).index(), 11);
+        assert_eq!(!Color::White, Color::Black);
+        for letter in "PNBRQKpnbrqk".chars() {
+            assert_eq!(Piece::from_letter(letter).unwrap().letter(), letter);
+        }
+        assert_eq!(Piece::from_letter('x'), None);
+    }
+}
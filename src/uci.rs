@@ -0,0 +1,390 @@
+/*
+Copyright 2025 Ethan Thummel
+
+Permission is hereby granted, free of charge, to any person obtaining a copy of this software and
+associated documentation files (the "Software"), to deal in the Software without restriction,
+including without limitation the rights to use, copy, modify, merge, publish, distribute,
+sublicense, and/or sell copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all copies or substantial
+portions of the Software.
+
+THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT
+NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A PARTICULAR PURPOSE AND
+NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM,
+DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT
+OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+*/
+use std::fmt;
+
+/// A command sent to the engine by the gui
+#[derive(Debug, PartialEq, Eq)]
+pub enum UciCommand {
+    Uci,
+    IsReady,
+    UciNewGame,
+    SetOption {
+        name: String,
+        value: String,
+    },
+    Position {
+        fen: Option<String>,
+        moves: Vec<String>,
+    },
+    Go(GoOptions),
+    Perft(usize),
+    PonderHit,
+    Stop,
+    Quit,
+    // Not part of uci, but handy when debugging
+    Print,
+    Fen,
+    Eval,
+    SaveEval(String),
+}
+
+/// The limits given to a go command
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct GoOptions {
+    pub wtime: Option<u64>,
+    pub btime: Option<u64>,
+    pub winc: Option<u64>,
+    pub binc: Option<u64>,
+    pub movestogo: Option<u64>,
+    pub depth: Option<usize>,
+    pub movetime: Option<u64>,
+    pub infinite: bool,
+    pub ponder: bool,
+    // Searches stop after `nodes`, go no deeper than a mate in `mate` moves, and only try
+    // `searchmoves` at the root
+    pub nodes: Option<u64>,
+    pub mate: Option<u64>,
+    pub searchmoves: Vec<String>,
+}
+
+/// Why a line could not be understood
+#[derive(Debug, PartialEq, Eq)]
+pub enum UciError {
+    Empty,
+    UnknownCommand(String),
+    MissingValue(&'static str),
+    InvalidValue {
+        name: &'static str,
+        value: String,
+    },
+    Unexpected {
+        command: &'static str,
+        token: String,
+    },
+}
+
+impl fmt::Display for UciError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UciError::Empty => write!(f, "Empty command"),
+            UciError::UnknownCommand(c) => write!(f, "Unknown command {c}"),
+            UciError::MissingValue(name) => write!(f, "Expected a value after {name}"),
+            UciError::InvalidValue { name, value } => {
+                write!(f, "Invalid value \"{value}\" for {name}")
+            }
+            UciError::Unexpected { command, token } => {
+                write!(f, "Unexpected token \"{token}\" in {command}")
+            }
+        }
+    }
+}
+
+// Reads the number following a keyword
+fn number<'a, T: std::str::FromStr>(
+    tokens: &mut impl Iterator<Item = &'a str>,
+    name: &'static str,
+) -> Result<T, UciError> {
+    let value = tokens.next().ok_or(UciError::MissingValue(name))?;
+    value.parse().map_err(|_| UciError::InvalidValue {
+        name,
+        value: value.to_string(),
+    })
+}
+
+impl UciCommand {
+    /// Parses one line of input
+    pub fn parse(line: &str) -> Result<UciCommand, UciError> {
+        let mut tokens = line.split_whitespace();
+        let command = match tokens.next().ok_or(UciError::Empty)? {
+            "uci" => UciCommand::Uci,
+            "isready" => UciCommand::IsReady,
+            "ucinewgame" => UciCommand::UciNewGame,
+            "setoption" => Self::parse_setoption(&mut tokens)?,
+            "position" => Self::parse_position(&mut tokens)?,
+            "go" => Self::parse_go(&mut tokens)?,
+            "ponderhit" => UciCommand::PonderHit,
+            "stop" => UciCommand::Stop,
+            "quit" => UciCommand::Quit,
+            "print" => UciCommand::Print,
+            "fen" => UciCommand::Fen,
+            "eval" => UciCommand::Eval,
+            "saveeval" => {
+                let path = tokens.by_ref().collect::<Vec<_>>().join(" ");
+                if path.is_empty() {
+                    return Err(UciError::MissingValue("saveeval"));
+                }
+                UciCommand::SaveEval(path)
+            }
+            c => return Err(UciError::UnknownCommand(c.to_string())),
+        };
+        Ok(command)
+    }
+
+    // setoption name <id> [value <x>]
+    fn parse_setoption<'a>(
+        tokens: &mut impl Iterator<Item = &'a str>,
+    ) -> Result<UciCommand, UciError> {
+        match tokens.next() {
+            Some("name") => {}
+            Some(t) => {
+                return Err(UciError::Unexpected {
+                    command: "setoption",
+                    token: t.to_string(),
+                })
+            }
+            None => return Err(UciError::MissingValue("setoption")),
+        }
+        let name = tokens
+            .by_ref()
+            .take_while(|t| *t != "value")
+            .collect::<Vec<_>>()
+            .join(" ");
+        if name.is_empty() {
+            return Err(UciError::MissingValue("name"));
+        }
+        let value = tokens.collect::<Vec<_>>().join(" ");
+        Ok(UciCommand::SetOption { name, value })
+    }
+
+    // position [startpos | fen <fen>] [moves <move>...]
+    fn parse_position<'a>(
+        tokens: &mut impl Iterator<Item = &'a str>,
+    ) -> Result<UciCommand, UciError> {
+        let fen = match tokens.next() {
+            Some("startpos") => None,
+            Some("fen") => {
+                let fen = tokens
+                    .by_ref()
+                    .take_while(|t| *t != "moves")
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                if fen.is_empty() {
+                    return Err(UciError::MissingValue("fen"));
+                }
+                let moves = tokens.map(|t| t.to_string()).collect();
+                return Ok(UciCommand::Position {
+                    fen: Some(fen),
+                    moves,
+                });
+            }
+            Some(t) => {
+                return Err(UciError::Unexpected {
+                    command: "position",
+                    token: t.to_string(),
+                })
+            }
+            None => return Err(UciError::MissingValue("position")),
+        };
+        let moves = match tokens.next() {
+            Some("moves") => tokens.map(|t| t.to_string()).collect(),
+            Some(t) => {
+                return Err(UciError::Unexpected {
+                    command: "position",
+                    token: t.to_string(),
+                })
+            }
+            None => Vec::new(),
+        };
+        Ok(UciCommand::Position { fen, moves })
+    }
+
+    // Unknown tokens are skipped rather than refusing the go, since the gui still wants a bestmove
+    fn parse_go<'a>(tokens: &mut impl Iterator<Item = &'a str>) -> Result<UciCommand, UciError> {
+        let mut options = GoOptions::default();
+        // searchmoves takes every move up to the next keyword
+        let mut reading_moves = false;
+        while let Some(token) = tokens.next() {
+            match token {
+                "perft" => return Ok(UciCommand::Perft(number(tokens, "perft")?)),
+                "wtime" => options.wtime = Some(number(tokens, "wtime")?),
+                "btime" => options.btime = Some(number(tokens, "btime")?),
+                "winc" => options.winc = Some(number(tokens, "winc")?),
+                "binc" => options.binc = Some(number(tokens, "binc")?),
+                "movestogo" => options.movestogo = Some(number(tokens, "movestogo")?),
+                "depth" => options.depth = Some(number(tokens, "depth")?),
+                "movetime" => options.movetime = Some(number(tokens, "movetime")?),
+                "infinite" => options.infinite = true,
+                "ponder" => options.ponder = true,
+                "nodes" => options.nodes = Some(number(tokens, "nodes")?),
+                "mate" => options.mate = Some(number(tokens, "mate")?),
+                "searchmoves" => {
+                    reading_moves = true;
+                    continue;
+                }
+                t if reading_moves => {
+                    options.searchmoves.push(t.to_string());
+                    continue;
+                }
+                _ => {}
+            }
+            reading_moves = false;
+        }
+        Ok(UciCommand::Go(options))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_simple() {
+        assert_eq!(UciCommand::parse("uci"), Ok(UciCommand::Uci));
+        assert_eq!(UciCommand::parse("  isready  "), Ok(UciCommand::IsReady));
+        assert_eq!(UciCommand::parse("quit"), Ok(UciCommand::Quit));
+        assert_eq!(UciCommand::parse("fen"), Ok(UciCommand::Fen));
+        assert_eq!(UciCommand::parse(""), Err(UciError::Empty));
+        assert_eq!(
+            UciCommand::parse("fly"),
+            Err(UciError::UnknownCommand("fly".to_string()))
+        );
+    }
+
+    #[test]
+    fn parse_position() {
+        assert_eq!(
+            UciCommand::parse("position startpos"),
+            Ok(UciCommand::Position {
+                fen: None,
+                moves: vec![]
+            })
+        );
+        assert_eq!(
+            UciCommand::parse("position startpos moves e2e4 e7e5"),
+            Ok(UciCommand::Position {
+                fen: None,
+                moves: vec!["e2e4".to_string(), "e7e5".to_string()]
+            })
+        );
+        assert_eq!(
+            UciCommand::parse("position fen 8/8/8/8/8/8/8/K1k5 w - - 0 1 moves a1a2"),
+            Ok(UciCommand::Position {
+                fen: Some("8/8/8/8/8/8/8/K1k5 w - - 0 1".to_string()),
+                moves: vec!["a1a2".to_string()]
+            })
+        );
+        assert_eq!(
+            UciCommand::parse("position"),
+            Err(UciError::MissingValue("position"))
+        );
+        assert_eq!(
+            UciCommand::parse("position fen"),
+            Err(UciError::MissingValue("fen"))
+        );
+        assert_eq!(
+            UciCommand::parse("position startpos e2e4"),
+            Err(UciError::Unexpected {
+                command: "position",
+                token: "e2e4".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn parse_go() {
+        assert_eq!(
+            UciCommand::parse("go wtime 1000 btime 2000 winc 10 binc 20"),
+            Ok(UciCommand::Go(GoOptions {
+                wtime: Some(1000),
+                btime: Some(2000),
+                winc: Some(10),
+                binc: Some(20),
+                ..GoOptions::default()
+            }))
+        );
+        assert_eq!(
+            UciCommand::parse("go depth 5"),
+            Ok(UciCommand::Go(GoOptions {
+                depth: Some(5),
+                ..GoOptions::default()
+            }))
+        );
+        assert_eq!(UciCommand::parse("go perft 3"), Ok(UciCommand::Perft(3)));
+        assert_eq!(
+            UciCommand::parse("go ponder wtime 10 btime 20"),
+            Ok(UciCommand::Go(GoOptions {
+                wtime: Some(10),
+                btime: Some(20),
+                ponder: true,
+                ..GoOptions::default()
+            }))
+        );
+        assert_eq!(
+            UciCommand::parse("go nodes 10000 mate 3"),
+            Ok(UciCommand::Go(GoOptions {
+                nodes: Some(10000),
+                mate: Some(3),
+                ..GoOptions::default()
+            }))
+        );
+        assert_eq!(
+            UciCommand::parse("go searchmoves e2e4 d2d4 wtime 10 btime 20"),
+            Ok(UciCommand::Go(GoOptions {
+                wtime: Some(10),
+                btime: Some(20),
+                searchmoves: vec!["e2e4".to_string(), "d2d4".to_string()],
+                ..GoOptions::default()
+            }))
+        );
+        assert_eq!(
+            UciCommand::parse("go depth 3 sometime soon"),
+            Ok(UciCommand::Go(GoOptions {
+                depth: Some(3),
+                ..GoOptions::default()
+            }))
+        );
+        assert_eq!(UciCommand::parse("ponderhit"), Ok(UciCommand::PonderHit));
+        assert_eq!(
+            UciCommand::parse("go movetime"),
+            Err(UciError::MissingValue("movetime"))
+        );
+        assert_eq!(
+            UciCommand::parse("go movetime soon"),
+            Err(UciError::InvalidValue {
+                name: "movetime",
+                value: "soon".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn parse_setoption() {
+        assert_eq!(
+            UciCommand::parse("setoption name Eval File value /tmp/my eval.txt"),
+            Ok(UciCommand::SetOption {
+                name: "Eval File".to_string(),
+                value: "/tmp/my eval.txt".to_string()
+            })
+        );
+        assert_eq!(
+            UciCommand::parse("setoption name UseNNUE"),
+            Ok(UciCommand::SetOption {
+                name: "UseNNUE".to_string(),
+                value: String::new()
+            })
+        );
+        assert_eq!(
+            UciCommand::parse("setoption value 3"),
+            Err(UciError::Unexpected {
+                command: "setoption",
+                token: "value".to_string()
+            })
+        );
+    }
+}